@@ -0,0 +1,794 @@
+//! Alarm engine, monitoring telemetry against user-defined limits.
+//!
+//! Each [`AlarmRule`] watches a single field (or derived channel) and raises an
+//! alarm when its [`AlarmCondition`] is met. Rules are saved with the layout,
+//! while the runtime state of the alarms (active, acknowledged, shelved) and the
+//! history of the alarm events live in an [`AlarmEngine`] stored in the egui
+//! context, accessible from anywhere with [`AlarmEngineExt::alarms`].
+
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use egui::{Color32, Context, mutex::Mutex};
+use jiff::Zoned;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter};
+
+use crate::{derived::TelemetryField, mavlink::TimedMessage};
+
+/// Maximum number of events kept in the history.
+const MAX_HISTORY_LEN: usize = 10_000;
+
+/// Definition of an alarm, as saved in the layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlarmRule {
+    /// Unique name of the alarm
+    pub name: String,
+    /// Field monitored by the alarm, the rule is ignored until one is chosen
+    pub field: Option<TelemetryField>,
+    pub condition: AlarmCondition,
+    pub severity: Severity,
+    pub enabled: bool,
+}
+
+impl Default for AlarmRule {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            field: None,
+            condition: AlarmCondition::default(),
+            severity: Severity::Warning,
+            enabled: true,
+        }
+    }
+}
+
+/// Condition that raises an alarm.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Display, EnumIter)]
+pub enum AlarmCondition {
+    /// Value outside of `[low, high]`. The alarm clears once the value is back
+    /// inside the range by at least `hysteresis`.
+    #[strum(to_string = "Threshold")]
+    Threshold {
+        low: Option<f64>,
+        high: Option<f64>,
+        hysteresis: f64,
+    },
+    /// Absolute rate of change greater than `max_rate` per second. The alarm
+    /// clears once the rate is lower than `max_rate - hysteresis`.
+    #[strum(to_string = "Rate of change")]
+    RateOfChange { max_rate: f64, hysteresis: f64 },
+    /// No new value received for more than `timeout` seconds.
+    #[strum(to_string = "Stale data")]
+    Stale { timeout: f64 },
+    /// Enum field equal to the given entry (or numeric value equal to the raw
+    /// value of the entry, written as `ENUM.ENTRY`).
+    #[strum(to_string = "Enum equals")]
+    EnumEquals { entry: String },
+}
+
+impl Default for AlarmCondition {
+    fn default() -> Self {
+        Self::Threshold {
+            low: None,
+            high: None,
+            hysteresis: 0.0,
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    Display,
+    EnumIter,
+)]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn color(&self) -> Color32 {
+        match self {
+            Severity::Info => Color32::from_rgb(0x3b, 0x82, 0xf6),
+            Severity::Warning => Color32::from_rgb(0xf5, 0x9e, 0x0b),
+            Severity::Critical => Color32::from_rgb(0xdc, 0x26, 0x26),
+        }
+    }
+}
+
+/// Value read from a message for an alarm rule.
+#[derive(Debug, Clone, PartialEq)]
+enum Reading {
+    Numeric(f64),
+    Enum(String),
+}
+
+impl Reading {
+    fn read(field: &TelemetryField, message: &TimedMessage) -> Option<Self> {
+        match field {
+            TelemetryField::Mavlink(f) if f.msg_id() != message.id() => None,
+            TelemetryField::Mavlink(f) if f.field().enumtype.is_some() => f
+                .extract_as_enum_str(&message.message)
+                .ok()
+                .map(Reading::Enum),
+            _ => field.extract_from_message(message).map(Reading::Numeric),
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Reading::Numeric(value) => Some(*value),
+            Reading::Enum(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Reading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reading::Numeric(value) => write!(f, "{value:.3}"),
+            Reading::Enum(entry) => write!(f, "{entry}"),
+        }
+    }
+}
+
+/// Runtime state of an alarm.
+#[derive(Debug, Clone)]
+pub struct AlarmState {
+    /// Whether the alarm condition is currently met
+    pub active: bool,
+    /// Whether the operator acknowledged the last activation of the alarm
+    pub acknowledged: bool,
+    /// Annunciation of the alarm is suppressed until this instant
+    pub shelved_until: Option<Instant>,
+    /// Time of the last activation
+    pub raised_at: Option<Zoned>,
+    /// Last value received, formatted for display
+    pub last_value: Option<String>,
+    last_update: Instant,
+    previous: Option<(Instant, f64)>,
+}
+
+impl AlarmState {
+    fn new() -> Self {
+        Self {
+            active: false,
+            acknowledged: true,
+            shelved_until: None,
+            raised_at: None,
+            last_value: None,
+            last_update: Instant::now(),
+            previous: None,
+        }
+    }
+
+    pub fn is_shelved(&self) -> bool {
+        self.shelved_until.is_some_and(|t| t > Instant::now())
+    }
+
+    /// An alarm is annunciated while it is active or not yet acknowledged,
+    /// unless shelved.
+    pub fn is_annunciated(&self) -> bool {
+        (self.active || !self.acknowledged) && !self.is_shelved()
+    }
+
+    pub fn status(&self) -> &'static str {
+        match (self.is_shelved(), self.active, self.acknowledged) {
+            (true, _, _) => "Shelved",
+            (false, true, false) => "Active",
+            (false, true, true) => "Active (ack)",
+            (false, false, false) => "Cleared (unack)",
+            (false, false, true) => "Normal",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, EnumIter)]
+pub enum AlarmEventKind {
+    Raised,
+    Cleared,
+    Acknowledged,
+    Shelved,
+    Unshelved,
+    #[strum(to_string = "Connection lost")]
+    ConnectionLost,
+    #[strum(to_string = "Connection restored")]
+    ConnectionRestored,
+    #[strum(to_string = "NACK")]
+    Nack,
+    /// A critical command was armed and waits for confirmation
+    Armed,
+    /// A critical command was confirmed, cancelled or timed out
+    Disarmed,
+    /// A checklist item was signed off by the operator
+    #[strum(to_string = "Signed off")]
+    SignedOff,
+}
+
+impl AlarmEventKind {
+    /// Whether the event needs to be acknowledged by the operator.
+    fn needs_acknowledgement(&self) -> bool {
+        matches!(self, Self::Raised | Self::ConnectionLost | Self::Nack)
+    }
+}
+
+/// Entry of the event history, either related to an alarm rule or reported
+/// by other parts of the application (connection drops, NACKs, armed commands,
+/// checklist sign-offs).
+#[derive(Debug, Clone)]
+pub struct AlarmEvent {
+    pub time: Zoned,
+    /// Name of the alarm rule, or of the component that reported the event
+    pub source: String,
+    pub field: String,
+    pub value: Option<String>,
+    pub severity: Severity,
+    pub kind: AlarmEventKind,
+    pub acknowledged: bool,
+}
+
+impl AlarmEvent {
+    pub fn new(kind: AlarmEventKind, severity: Severity, source: impl Into<String>) -> Self {
+        Self {
+            time: Zoned::now(),
+            source: source.into(),
+            field: String::new(),
+            value: None,
+            severity,
+            kind,
+            acknowledged: !kind.needs_acknowledgement(),
+        }
+    }
+
+    pub fn with_value(mut self, value: impl Into<String>) -> Self {
+        self.value = Some(value.into());
+        self
+    }
+
+    fn from_rule(rule: &AlarmRule, state: &AlarmState, kind: AlarmEventKind) -> Self {
+        Self {
+            field: rule.field.as_ref().map(|f| f.name()).unwrap_or_default(),
+            value: state.last_value.clone(),
+            ..Self::new(kind, rule.severity, &rule.name)
+        }
+    }
+}
+
+/// Evaluates the alarm rules on incoming messages and keeps track of the
+/// alarms state and history.
+#[derive(Debug, Default)]
+pub struct AlarmEngine {
+    rules: Vec<AlarmRule>,
+    states: Vec<AlarmState>,
+    history: VecDeque<AlarmEvent>,
+}
+
+impl AlarmEngine {
+    /// Updates the rules if they changed. The state of an alarm is kept by
+    /// the rule name, so editing its condition or severity does not raise it
+    /// again; the previous reading is dropped if the monitored field changed.
+    pub fn sync(&mut self, rules: &[AlarmRule]) {
+        if self.rules == rules {
+            return;
+        }
+        let mut previous: Vec<_> = std::mem::take(&mut self.rules)
+            .into_iter()
+            .zip(std::mem::take(&mut self.states))
+            .collect();
+        self.states = rules
+            .iter()
+            .map(|rule| {
+                let Some(i) = previous.iter().position(|(r, _)| r.name == rule.name) else {
+                    return AlarmState::new();
+                };
+                let (old, mut state) = previous.remove(i);
+                if old.field != rule.field {
+                    state.previous = None;
+                    state.last_value = None;
+                    state.last_update = Instant::now();
+                }
+                state
+            })
+            .collect();
+        self.rules = rules.to_vec();
+
+        // rules no longer evaluated would keep their alarm raised forever
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            if !rule.enabled || rule.field.is_none() {
+                transition(&mut self.history, rule, state, false);
+            }
+        }
+    }
+
+    /// Evaluates the rules monitoring the fields of the given messages.
+    pub fn process<'a>(&mut self, messages: impl IntoIterator<Item = &'a TimedMessage>) {
+        for message in messages {
+            for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+                let Some(field) = rule.field.as_ref().filter(|_| rule.enabled) else {
+                    continue;
+                };
+                let Some(reading) = Reading::read(field, message) else {
+                    continue;
+                };
+                let active = evaluate(&rule.condition, state, &reading, message.time);
+                state.last_value = Some(reading.to_string());
+                state.last_update = message.time;
+                if let Some(value) = reading.as_f64() {
+                    state.previous = Some((message.time, value));
+                }
+                if let Some(active) = active {
+                    transition(&mut self.history, rule, state, active);
+                }
+            }
+        }
+    }
+
+    /// Checks the stale data rules and the expired shelves. Returns whether
+    /// any rule depends on time, and thus needs to be checked periodically.
+    pub fn check_timeouts(&mut self) -> bool {
+        let now = Instant::now();
+        let mut time_dependent = false;
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            if state.shelved_until.is_some_and(|t| t <= now) {
+                state.shelved_until = None;
+                push_event(&mut self.history, rule, state, AlarmEventKind::Unshelved);
+            }
+            time_dependent |= state.shelved_until.is_some();
+
+            match rule.condition {
+                AlarmCondition::Stale { timeout } if rule.enabled && rule.field.is_some() => {
+                    time_dependent = true;
+                    let stale = now.duration_since(state.last_update).as_secs_f64() > timeout;
+                    transition(&mut self.history, rule, state, stale);
+                }
+                _ => {}
+            }
+        }
+        time_dependent
+    }
+
+    pub fn acknowledge(&mut self, index: usize) {
+        match (self.rules.get(index), self.states.get_mut(index)) {
+            (Some(rule), Some(state)) if !state.acknowledged => {
+                state.acknowledged = true;
+                for event in self.history.iter_mut() {
+                    if event.kind == AlarmEventKind::Raised && event.source == rule.name {
+                        event.acknowledged = true;
+                    }
+                }
+                push_event(&mut self.history, rule, state, AlarmEventKind::Acknowledged);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn acknowledge_all(&mut self) {
+        for i in 0..self.rules.len() {
+            self.acknowledge(i);
+        }
+    }
+
+    /// Acknowledges an event of the history, and the alarm that raised it.
+    pub fn acknowledge_event(&mut self, index: usize) {
+        let Some(event) = self.history.get_mut(index) else {
+            return;
+        };
+        event.acknowledged = true;
+        if event.kind == AlarmEventKind::Raised {
+            let source = event.source.clone();
+            if let Some(i) = self.rules.iter().position(|r| r.name == source) {
+                self.acknowledge(i);
+            }
+        }
+    }
+
+    /// Records an event reported by other parts of the application.
+    pub fn record(&mut self, event: AlarmEvent) {
+        log_event(&mut self.history, event);
+    }
+
+    /// Suppresses the annunciation of an alarm for the given duration.
+    pub fn shelve(&mut self, index: usize, duration: Duration) {
+        if let (Some(rule), Some(state)) = (self.rules.get(index), self.states.get_mut(index)) {
+            state.shelved_until = Some(Instant::now() + duration);
+            push_event(&mut self.history, rule, state, AlarmEventKind::Shelved);
+        }
+    }
+
+    pub fn unshelve(&mut self, index: usize) {
+        match (self.rules.get(index), self.states.get_mut(index)) {
+            (Some(rule), Some(state)) if state.shelved_until.is_some() => {
+                state.shelved_until = None;
+                push_event(&mut self.history, rule, state, AlarmEventKind::Unshelved);
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns all the alarms with their current state.
+    pub fn alarms(&self) -> impl Iterator<Item = (usize, &AlarmRule, &AlarmState)> {
+        self.rules
+            .iter()
+            .zip(&self.states)
+            .enumerate()
+            .map(|(i, (rule, state))| (i, rule, state))
+    }
+
+    /// Returns the alarms currently annunciated, most severe first.
+    pub fn annunciated(&self) -> Vec<(usize, &AlarmRule, &AlarmState)> {
+        let mut alarms: Vec<_> = self
+            .alarms()
+            .filter(|(_, _, state)| state.is_annunciated())
+            .collect();
+        alarms.sort_by_key(|(_, rule, _)| std::cmp::Reverse(rule.severity));
+        alarms
+    }
+
+    pub fn history(&self) -> &VecDeque<AlarmEvent> {
+        &self.history
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+}
+
+/// Returns whether the condition is met after the new reading, or `None` if it
+/// cannot be determined from it.
+fn evaluate(
+    condition: &AlarmCondition,
+    state: &AlarmState,
+    reading: &Reading,
+    time: Instant,
+) -> Option<bool> {
+    match condition {
+        AlarmCondition::Threshold {
+            low,
+            high,
+            hysteresis,
+        } => {
+            let value = reading.as_f64()?;
+            // once active, the range is shrunk by the hysteresis
+            let margin = if state.active { *hysteresis } else { 0.0 };
+            let too_high = high.is_some_and(|h| value > h - margin);
+            let too_low = low.is_some_and(|l| value < l + margin);
+            Some(too_high || too_low)
+        }
+        AlarmCondition::RateOfChange {
+            max_rate,
+            hysteresis,
+        } => {
+            let value = reading.as_f64()?;
+            let (t0, v0) = state.previous?;
+            let dt = time.checked_duration_since(t0)?.as_secs_f64();
+            if dt <= 0.0 {
+                return None;
+            }
+            let rate = ((value - v0) / dt).abs();
+            let margin = if state.active { *hysteresis } else { 0.0 };
+            Some(rate > max_rate - margin)
+        }
+        // any reading means fresh data
+        AlarmCondition::Stale { .. } => Some(false),
+        AlarmCondition::EnumEquals { entry } => match reading {
+            Reading::Enum(name) => Some(name == entry),
+            Reading::Numeric(value) => {
+                // numeric values are compared to an entry written as ENUM.ENTRY
+                let (enum_name, entry) = entry.split_once('.')?;
+                let raw = crate::mavlink::reflection::MAVLINK_PROFILE
+                    .get_enum_entry_value(enum_name, entry)?;
+                Some(*value == raw as f64)
+            }
+        },
+    }
+}
+
+fn transition(
+    history: &mut VecDeque<AlarmEvent>,
+    rule: &AlarmRule,
+    state: &mut AlarmState,
+    active: bool,
+) {
+    if active == state.active {
+        return;
+    }
+    // shelving only suppresses the annunciation, the events are still logged
+    state.active = active;
+    if active {
+        state.acknowledged = false;
+        state.raised_at = Some(Zoned::now());
+        push_event(history, rule, state, AlarmEventKind::Raised);
+    } else {
+        push_event(history, rule, state, AlarmEventKind::Cleared);
+    }
+}
+
+fn push_event(
+    history: &mut VecDeque<AlarmEvent>,
+    rule: &AlarmRule,
+    state: &AlarmState,
+    kind: AlarmEventKind,
+) {
+    log_event(history, AlarmEvent::from_rule(rule, state, kind));
+}
+
+fn log_event(history: &mut VecDeque<AlarmEvent>, event: AlarmEvent) {
+    if history.len() >= MAX_HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(event);
+}
+
+/// Extension trait to access the [`AlarmEngine`] stored in the egui context.
+pub trait AlarmEngineExt {
+    fn alarms(&self) -> Arc<Mutex<AlarmEngine>>;
+}
+
+impl AlarmEngineExt for Context {
+    fn alarms(&self) -> Arc<Mutex<AlarmEngine>> {
+        self.memory_mut(|w| {
+            if let Some(arc) = w.data.get_temp("alarm_engine".into()) {
+                arc
+            } else {
+                let engine = Arc::new(Mutex::new(AlarmEngine::default()));
+                w.data.insert_temp("alarm_engine".into(), engine.clone());
+                engine
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::derived::DerivedField;
+
+    /// Enabled rule monitoring a derived channel.
+    fn rule_named(name: &str) -> AlarmRule {
+        AlarmRule {
+            name: name.to_owned(),
+            field: Some(TelemetryField::Derived(DerivedField::new("x"))),
+            ..Default::default()
+        }
+    }
+
+    fn threshold(low: Option<f64>, high: Option<f64>, hysteresis: f64) -> AlarmCondition {
+        AlarmCondition::Threshold {
+            low,
+            high,
+            hysteresis,
+        }
+    }
+
+    #[test]
+    fn threshold_with_hysteresis() {
+        let condition = threshold(Some(-1.0), Some(10.0), 2.0);
+        let mut state = AlarmState::new();
+        let now = Instant::now();
+        let eval = |value: f64, state: &mut AlarmState| {
+            let active = evaluate(&condition, state, &Reading::Numeric(value), now);
+            state.active = active == Some(true);
+            state.active
+        };
+
+        assert!(!eval(9.0, &mut state));
+        assert!(eval(10.5, &mut state));
+        // still active inside the hysteresis band
+        assert!(eval(8.5, &mut state));
+        assert!(!eval(7.5, &mut state));
+        assert!(eval(-1.5, &mut state));
+        assert!(eval(0.5, &mut state));
+        assert!(!eval(1.5, &mut state));
+    }
+
+    #[test]
+    fn threshold_ignores_enum_readings() {
+        let condition = threshold(None, Some(1.0), 0.0);
+        let state = AlarmState::new();
+        let reading = Reading::Enum("OPEN".to_owned());
+        assert_eq!(evaluate(&condition, &state, &reading, Instant::now()), None);
+    }
+
+    #[test]
+    fn rate_of_change() {
+        let condition = AlarmCondition::RateOfChange {
+            max_rate: 10.0,
+            hysteresis: 5.0,
+        };
+        let mut state = AlarmState::new();
+        let t0 = Instant::now();
+        let t1 = t0 + Duration::from_millis(100);
+
+        // no previous sample, nothing to compare
+        assert_eq!(
+            evaluate(&condition, &state, &Reading::Numeric(0.0), t0),
+            None
+        );
+
+        state.previous = Some((t0, 0.0));
+        assert_eq!(
+            evaluate(&condition, &state, &Reading::Numeric(0.5), t1),
+            Some(false)
+        );
+        assert_eq!(
+            evaluate(&condition, &state, &Reading::Numeric(-2.0), t1),
+            Some(true)
+        );
+        state.active = true;
+        assert_eq!(
+            evaluate(&condition, &state, &Reading::Numeric(0.6), t1),
+            Some(true)
+        );
+        assert_eq!(
+            evaluate(&condition, &state, &Reading::Numeric(0.4), t1),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn raise_acknowledge_and_clear() {
+        let rule = rule_named("test");
+        let mut engine = AlarmEngine::default();
+        engine.sync(std::slice::from_ref(&rule));
+
+        transition(&mut engine.history, &rule, &mut engine.states[0], true);
+        assert_eq!(engine.annunciated().len(), 1);
+        assert_eq!(engine.states[0].status(), "Active");
+
+        assert!(!engine.history()[0].acknowledged);
+        engine.acknowledge(0);
+        assert_eq!(engine.states[0].status(), "Active (ack)");
+        assert!(engine.history()[0].acknowledged);
+
+        transition(&mut engine.history, &rule, &mut engine.states[0], false);
+        assert!(engine.annunciated().is_empty());
+
+        let kinds: Vec<_> = engine.history().iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                AlarmEventKind::Raised,
+                AlarmEventKind::Acknowledged,
+                AlarmEventKind::Cleared
+            ]
+        );
+    }
+
+    #[test]
+    fn state_is_kept_by_rule_name() {
+        let rule = rule_named("test");
+        let other = rule_named("other");
+        let mut engine = AlarmEngine::default();
+        engine.sync(&[rule.clone(), other.clone()]);
+        transition(&mut engine.history, &rule, &mut engine.states[0], true);
+
+        // edited and moved after another rule, still the same alarm
+        let edited = AlarmRule {
+            condition: threshold(None, Some(1.0), 0.0),
+            severity: Severity::Critical,
+            ..rule.clone()
+        };
+        engine.sync(&[other.clone(), edited.clone()]);
+        assert!(!engine.states[0].active);
+        assert!(engine.states[1].active);
+        assert!(!engine.states[1].acknowledged);
+        assert_eq!(engine.history().len(), 1);
+
+        // renamed, a new alarm
+        let renamed = AlarmRule {
+            name: "renamed".to_owned(),
+            ..edited
+        };
+        engine.sync(&[other, renamed]);
+        assert!(!engine.states[1].active);
+    }
+
+    #[test]
+    fn disabling_an_active_rule_clears_it() {
+        let rule = rule_named("test");
+        let mut engine = AlarmEngine::default();
+        engine.sync(std::slice::from_ref(&rule));
+        transition(&mut engine.history, &rule, &mut engine.states[0], true);
+        assert_eq!(engine.annunciated().len(), 1);
+
+        engine.sync(&[AlarmRule {
+            enabled: false,
+            ..rule
+        }]);
+        assert!(!engine.states[0].active);
+        // still annunciated until the activation is acknowledged
+        assert_eq!(engine.states[0].status(), "Cleared (unack)");
+        engine.acknowledge(0);
+        assert!(engine.annunciated().is_empty());
+
+        let kinds: Vec<_> = engine.history().iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                AlarmEventKind::Raised,
+                AlarmEventKind::Cleared,
+                AlarmEventKind::Acknowledged
+            ]
+        );
+    }
+
+    #[test]
+    fn alarms_raised_while_shelved_are_logged() {
+        let rule = rule_named("test");
+        let mut engine = AlarmEngine::default();
+        engine.sync(std::slice::from_ref(&rule));
+        engine.shelve(0, Duration::from_secs(60));
+
+        transition(&mut engine.history, &rule, &mut engine.states[0], true);
+        assert!(!engine.states[0].acknowledged);
+        assert!(engine.states[0].raised_at.is_some());
+        assert!(engine.annunciated().is_empty());
+
+        // annunciated once unshelved, until acknowledged
+        engine.unshelve(0);
+        assert_eq!(engine.annunciated().len(), 1);
+
+        let kinds: Vec<_> = engine.history().iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                AlarmEventKind::Shelved,
+                AlarmEventKind::Raised,
+                AlarmEventKind::Unshelved
+            ]
+        );
+    }
+
+    #[test]
+    fn acknowledge_reported_events() {
+        let mut engine = AlarmEngine::default();
+        engine.record(AlarmEvent::new(
+            AlarmEventKind::Nack,
+            Severity::Warning,
+            "cmd",
+        ));
+        engine.record(AlarmEvent::new(
+            AlarmEventKind::ConnectionRestored,
+            Severity::Info,
+            "link",
+        ));
+        assert!(!engine.history()[0].acknowledged);
+        assert!(engine.history()[1].acknowledged);
+
+        engine.acknowledge_event(0);
+        assert!(engine.history()[0].acknowledged);
+    }
+
+    #[test]
+    fn oldest_events_are_dropped() {
+        let mut engine = AlarmEngine::default();
+        for i in 0..MAX_HISTORY_LEN + 2 {
+            engine.record(AlarmEvent::new(
+                AlarmEventKind::Nack,
+                Severity::Warning,
+                i.to_string(),
+            ));
+        }
+        assert_eq!(engine.history().len(), MAX_HISTORY_LEN);
+        assert_eq!(
+            engine.history().front().map(|e| e.source.as_str()),
+            Some("2")
+        );
+    }
+}
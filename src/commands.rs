@@ -0,0 +1,213 @@
+//! Library of named command definitions.
+//!
+//! A [`CommandDefinition`] holds everything needed to send a telecommand: the
+//! message with its default field values, the fields that the operator can
+//! change before sending, the target system and whether the command is
+//! critical. The library is saved in its own file, so it is shared by all the
+//! layouts, and it is kept in a global instance: command panes and command
+//! switch entries bound to a definition by name pick up its changes, checking
+//! the library [revision](CommandLibrary::revision).
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use egui::{TextEdit, Ui, mutex::RwLock};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::mavlink::reflection::MessageMap;
+
+/// Name of the file holding the library, in the app storage directory.
+pub const LIBRARY_FILE: &str = "commands.json";
+
+/// Maximum number of fields editable before sending, one for each of the
+/// number keys selecting them in the command switch.
+pub const MAX_EDITABLE_FIELDS: usize = 9;
+
+/// Global library of the command definitions.
+pub static COMMAND_LIBRARY: LazyLock<RwLock<CommandLibrary>> =
+    LazyLock::new(|| RwLock::new(CommandLibrary::default()));
+
+/// Definition of a command, as saved in the library.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandDefinition {
+    /// Name used to refer to the command from panes and the command switch
+    pub name: String,
+    pub system_id: u8,
+    /// Message to send, holding the default field values
+    pub message: Option<MessageMap>,
+    /// Fields the operator can change before sending, the others are locked.
+    /// At most [`MAX_EDITABLE_FIELDS`].
+    #[serde(default)]
+    pub editable_fields: BTreeSet<usize>,
+    #[serde(default)]
+    pub criticality: Criticality,
+}
+
+impl CommandDefinition {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            system_id: 1,
+            message: None,
+            editable_fields: BTreeSet::new(),
+            criticality: Criticality::default(),
+        }
+    }
+}
+
+/// Whether a command is critical, and how it has to be confirmed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Criticality {
+    /// Whether the command has to be armed and confirmed before being sent
+    pub critical: bool,
+    /// Text to type to enable the confirmation, not required if empty
+    pub confirmation_text: String,
+}
+
+impl Criticality {
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+        ui.checkbox(&mut self.critical, "Critical command")
+            .on_hover_text("The command has to be armed and then confirmed before being sent");
+        ui.add_enabled_ui(self.critical, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Confirmation text:");
+                ui.add(
+                    TextEdit::singleline(&mut self.confirmation_text)
+                        .hint_text("none")
+                        .desired_width(100.0),
+                );
+            })
+            .response
+            .on_hover_text(
+                "Text the operator has to type to confirm, leave empty to not require it",
+            );
+        });
+    }
+}
+
+/// Command definitions, with the file they are saved to.
+#[derive(Debug, Default)]
+pub struct CommandLibrary {
+    commands: Vec<CommandDefinition>,
+    /// Incremented at every change, to let bound commands know they are stale
+    revision: u64,
+    path: Option<PathBuf>,
+}
+
+impl CommandLibrary {
+    /// Loads the library from `path`, starting empty if the file does not
+    /// exist yet. Changes are saved to the same file.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let commands = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Invalid command library at {:?}: {}", path, e);
+                Vec::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                warn!("Unable to read the command library at {:?}: {}", path, e);
+                Vec::new()
+            }
+        };
+        info!("Loaded {} commands from {:?}", commands.len(), path);
+        Self {
+            commands,
+            revision: 1,
+            path: Some(path.to_owned()),
+        }
+    }
+
+    pub fn commands(&self) -> &[CommandDefinition] {
+        &self.commands
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandDefinition> {
+        self.commands.iter().find(|c| c.name == name)
+    }
+
+    /// Criticality of a message sent to a system, taken from the definitions
+    /// sending the same message to the same system: a command that has to be
+    /// confirmed in the library has to be confirmed wherever it is sent from.
+    pub fn criticality_of(&self, system_id: u8, message_id: u32) -> Criticality {
+        self.commands
+            .iter()
+            .filter(|c| c.system_id == system_id && c.criticality.critical)
+            .find(|c| {
+                c.message
+                    .as_ref()
+                    .is_some_and(|m| m.message_id() == message_id)
+            })
+            .map(|c| c.criticality.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Replaces the definitions if they changed, saving them to file.
+    pub fn update(&mut self, commands: Vec<CommandDefinition>) -> anyhow::Result<()> {
+        if self.commands == commands {
+            return Ok(());
+        }
+        self.commands = commands;
+        self.revision += 1;
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(path, serde_json::to_string_pretty(&self.commands)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mavlink::{
+        GET_VALVE_INFO_TC_DATA, MavMessage, MessageData, WIGGLE_SERVO_TC_DATA,
+        reflection::MapConvertible,
+    };
+
+    fn definition(system_id: u8, message: MavMessage, critical: bool) -> CommandDefinition {
+        CommandDefinition {
+            system_id,
+            message: Some(message.as_map()),
+            criticality: Criticality {
+                critical,
+                confirmation_text: "FIRE".to_owned(),
+            },
+            ..CommandDefinition::new("test")
+        }
+    }
+
+    #[test]
+    fn criticality_is_taken_from_the_critical_definitions() {
+        let wiggle = MavMessage::WIGGLE_SERVO_TC(WIGGLE_SERVO_TC_DATA::default());
+        let info = MavMessage::GET_VALVE_INFO_TC(GET_VALVE_INFO_TC_DATA::default());
+        let library = CommandLibrary {
+            commands: vec![definition(1, info, false), definition(2, wiggle, true)],
+            ..Default::default()
+        };
+        let wiggle_id = WIGGLE_SERVO_TC_DATA::ID;
+        assert!(library.criticality_of(2, wiggle_id).critical);
+        assert_eq!(
+            library.criticality_of(2, wiggle_id).confirmation_text,
+            "FIRE"
+        );
+        // same message to another system
+        assert!(!library.criticality_of(1, wiggle_id).critical);
+        assert!(
+            !library
+                .criticality_of(1, GET_VALVE_INFO_TC_DATA::ID)
+                .critical
+        );
+    }
+}
@@ -64,9 +64,9 @@ impl MessageTransceiver for EthernetTransceiver {
     /// Waits for a message over Ethernet, blocking until a valid message arrives.
     #[profiling::function]
     fn wait_for_message(&self) -> Result<TimedMessage, MessageReadError> {
-        let (_, msg) = self.incoming_conn.recv()?;
+        let (header, msg) = self.incoming_conn.recv()?;
         debug!("Received message: {:?}", &msg);
-        Ok(TimedMessage::just_received(msg))
+        Ok(TimedMessage::just_received(header, msg))
     }
 
     /// Transmits a message using the UDP socket.
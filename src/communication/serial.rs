@@ -123,9 +123,9 @@ impl MessageTransceiver for SerialTransceiver {
     /// Blocks until a valid message is received from the serial port.
     #[profiling::function]
     fn wait_for_message(&self) -> Result<TimedMessage, MessageReadError> {
-        let (_, msg) = self.mav_connection.recv()?;
+        let (header, msg) = self.mav_connection.recv()?;
         debug!("Received message: {:?}", &msg);
-        Ok(TimedMessage::just_received(msg))
+        Ok(TimedMessage::just_received(header, msg))
     }
 
     /// Transmits a message via the serial connection.
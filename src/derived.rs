@@ -0,0 +1,131 @@
+//! Derived channels, i.e. quantities computed from telemetry fields.
+//!
+//! Each [`DerivedChannel`] is a named expression (see [`expression`]) over
+//! fields of the MAVLink profile and over previously defined channels. The
+//! definitions are saved with the layout, while the compiled [`DerivedRegistry`]
+//! is kept in a global instance shared by the message broker, which evaluates
+//! the channels as messages arrive, and by the panes, which read the results
+//! attached to every [`TimedMessage`].
+
+pub mod condition;
+pub mod expression;
+mod registry;
+
+use std::sync::{Arc, LazyLock};
+
+use egui::mutex::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mavlink::{TimedMessage, reflection::IndexedField},
+    utils::units::UnitOfMeasure,
+};
+
+pub use registry::DerivedRegistry;
+
+/// Global registry of the derived channels defined in the current layout.
+pub static DERIVED_CHANNELS: LazyLock<RwLock<DerivedRegistry>> =
+    LazyLock::new(|| RwLock::new(DerivedRegistry::default()));
+
+/// Definition of a derived channel, as saved in the layout.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DerivedChannel {
+    /// Name used to refer to the channel in panes and other expressions
+    pub name: String,
+    /// Source of the expression computing the channel value
+    pub expression: String,
+    /// Unit of measure of the result, if any
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+/// Value of a derived channel computed from a received message.
+#[derive(Debug, Clone)]
+pub struct DerivedSample {
+    pub name: Arc<str>,
+    pub value: f64,
+}
+
+/// Reference to a derived channel, usable in panes in place of a telemetry field.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DerivedField {
+    pub name: String,
+}
+
+impl DerivedField {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+
+    pub fn unit(&self) -> UnitOfMeasure {
+        let registry = DERIVED_CHANNELS.read();
+        UnitOfMeasure::from(registry.get(&self.name).and_then(|c| c.unit.as_ref()))
+    }
+
+    /// Returns the ids of the messages that trigger an update of the channel.
+    pub fn msg_ids(&self) -> Vec<u32> {
+        DERIVED_CHANNELS.read().trigger_ids(&self.name)
+    }
+
+    /// Returns the value of the channel computed from `message`, if any.
+    pub fn extract_from_message(&self, message: &TimedMessage) -> Option<f64> {
+        message
+            .derived
+            .iter()
+            .find(|sample| *sample.name == *self.name)
+            .map(|sample| sample.value)
+    }
+}
+
+/// Either a telemetry field or a derived channel.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TelemetryField {
+    Mavlink(IndexedField),
+    Derived(DerivedField),
+}
+
+impl TelemetryField {
+    pub fn name(&self) -> String {
+        match self {
+            TelemetryField::Mavlink(field) => field.field().name.clone(),
+            TelemetryField::Derived(field) => field.name.clone(),
+        }
+    }
+
+    pub fn unit(&self) -> UnitOfMeasure {
+        match self {
+            TelemetryField::Mavlink(field) => UnitOfMeasure::from(field.field().unit.as_ref()),
+            TelemetryField::Derived(field) => field.unit(),
+        }
+    }
+
+    pub fn msg_ids(&self) -> Vec<u32> {
+        match self {
+            TelemetryField::Mavlink(field) => vec![field.msg_id()],
+            TelemetryField::Derived(field) => field.msg_ids(),
+        }
+    }
+
+    pub fn extract_from_message(&self, message: &TimedMessage) -> Option<f64> {
+        match self {
+            TelemetryField::Mavlink(field) if field.msg_id() == message.id() => {
+                field.extract_as_f64(&message.message).ok()
+            }
+            TelemetryField::Mavlink(_) => None,
+            TelemetryField::Derived(field) => field.extract_from_message(message),
+        }
+    }
+}
+
+impl From<IndexedField> for TelemetryField {
+    fn from(field: IndexedField) -> Self {
+        Self::Mavlink(field)
+    }
+}
+
+impl From<DerivedField> for TelemetryField {
+    fn from(field: DerivedField) -> Self {
+        Self::Derived(field)
+    }
+}
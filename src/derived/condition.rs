@@ -0,0 +1,124 @@
+//! Conditions on the telemetry, i.e. expressions read as true or false.
+//!
+//! A [`Condition`] is written with the syntax of the derived channels (see
+//! [`expression`](super::expression)), e.g. `GSE_TM.ox_tank_top_pressure < 2`,
+//! and is met when it evaluates to a value other than zero. Conditions are
+//! evaluated over the [`LatestValues`] received by their owner.
+
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+    APP_START_TIMESTAMP_ORIGIN,
+    mavlink::{
+        TimedMessage,
+        reflection::{IndexedField, MAVLINK_PROFILE},
+    },
+};
+
+use super::{
+    DERIVED_CHANNELS,
+    expression::{Expression, ExpressionError, Scope},
+};
+
+#[derive(Debug, Clone)]
+pub struct Condition {
+    source: String,
+    expression: Expression,
+}
+
+impl Condition {
+    pub fn parse(source: &str, derived_names: &[&str]) -> Result<Self, ExpressionError> {
+        Ok(Self {
+            source: source.trim().to_owned(),
+            expression: Expression::parse(source, derived_names)?,
+        })
+    }
+
+    /// Parses the condition, reading the derived channels currently defined.
+    pub fn parse_with_derived(source: &str) -> Result<Self, ExpressionError> {
+        let derived = DERIVED_CHANNELS.read();
+        let derived_names: Vec<&str> = derived.valid_channels().map(|c| c.name.as_str()).collect();
+        Self::parse(source, &derived_names)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the condition is met, or `None` if its inputs are not known yet.
+    pub fn is_met(&mut self, values: &LatestValues) -> Option<bool> {
+        let t = APP_START_TIMESTAMP_ORIGIN.elapsed().as_secs_f64();
+        self.expression
+            .eval(values, t)
+            .map(|v| v != 0.0 && !v.is_nan())
+    }
+
+    /// Ids of the messages read by the condition, directly or through the
+    /// derived channels.
+    pub fn input_message_ids(&self) -> Vec<u32> {
+        let derived = DERIVED_CHANNELS.read();
+        let fields = self
+            .expression
+            .input_fields()
+            .into_iter()
+            .map(|f| f.msg_id());
+        let channels = self
+            .expression
+            .input_derived()
+            .into_iter()
+            .flat_map(|name| derived.trigger_ids(&name));
+        let mut ids: Vec<u32> = fields.chain(channels).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+/// Ids of the messages read by any of the conditions, without duplicates.
+pub fn input_message_ids<'a>(conditions: impl IntoIterator<Item = &'a Condition>) -> Vec<u32> {
+    let mut ids: Vec<u32> = conditions
+        .into_iter()
+        .flat_map(Condition::input_message_ids)
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Latest values of the fields and derived channels received.
+#[derive(Debug, Clone, Default)]
+pub struct LatestValues {
+    fields: HashMap<IndexedField, f64>,
+    derived: HashMap<String, f64>,
+}
+
+impl LatestValues {
+    pub fn update(&mut self, message: &TimedMessage) {
+        if let Some(fields) = MAVLINK_PROFILE.get_fields(message.id()) {
+            for field in fields {
+                if let Ok(value) = field.extract_as_f64(&message.message) {
+                    self.fields.insert(field, value);
+                }
+            }
+        }
+        for sample in &message.derived {
+            self.derived.insert(sample.name.to_string(), sample.value);
+        }
+    }
+}
+
+impl Scope for LatestValues {
+    fn field_value(&self, field: &IndexedField) -> Option<f64> {
+        self.fields.get(field).copied()
+    }
+
+    fn derived_value(&self, name: &str) -> Option<f64> {
+        self.derived.get(name).copied()
+    }
+}
@@ -0,0 +1,1028 @@
+//! Expression engine used by derived channels.
+//!
+//! Expressions are parsed once into an [`Expression`] tree and then evaluated
+//! every time one of their inputs changes. The syntax is a small arithmetic
+//! language over telemetry fields:
+//!
+//! - numbers (`1`, `2.5`, `1e-3`) and the constants `pi`, `e` and `g0`;
+//! - fields, written as `MESSAGE.field` (e.g. `GSE_TM.ox_tank_top_pressure`);
+//! - other derived channels, referenced by name;
+//! - enum entries of the MAVLink profile, written as `ENUM.ENTRY`, which
+//!   evaluate to their raw value;
+//! - the operators `+ - * / % ^`, comparisons (`== != < <= > >=`) and logic
+//!   (`&& || !`), where comparisons and logic yield `1` or `0`;
+//! - math functions (see [`Function`]) and stateful filters (see [`Filter`]).
+
+use std::{collections::VecDeque, fmt::Display, iter::Peekable, str::CharIndices};
+
+use thiserror::Error;
+
+use crate::{
+    mavlink::reflection::{FieldLike, IndexedField, MAVLINK_PROFILE},
+    utils::units::UnitOfMeasure,
+};
+
+/// Errors that can occur while compiling an expression.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ExpressionError {
+    #[error("Unexpected character '{0}' at position {1}")]
+    UnexpectedChar(char, usize),
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("Unexpected token '{0}'")]
+    UnexpectedToken(String),
+    #[error("Unknown identifier '{0}'")]
+    UnknownIdentifier(String),
+    #[error("Unknown field '{0}'")]
+    UnknownField(String),
+    #[error("Unknown function '{0}'")]
+    UnknownFunction(String),
+    #[error("Function '{0}' expects {1} argument(s)")]
+    WrongArity(String, &'static str),
+    #[error("Argument {1} of '{0}' must be a constant")]
+    NotConstant(String, usize),
+    #[error("The window of '{0}' can be at most {1} samples")]
+    WindowTooLarge(String, usize),
+    #[error("Cannot convert from '{0}' to '{1}'")]
+    IncompatibleUnits(String, String),
+    #[error("Invalid channel name '{0}'")]
+    InvalidName(String),
+    #[error("Channel '{0}' is defined more than once")]
+    DuplicateName(String),
+}
+
+/// Source of the current values of the expression inputs.
+pub trait Scope {
+    /// Returns the latest value of a telemetry field, if known.
+    fn field_value(&self, field: &IndexedField) -> Option<f64>;
+
+    /// Returns the latest value of a derived channel, if known.
+    fn derived_value(&self, name: &str) -> Option<f64>;
+}
+
+/// A parsed expression, ready to be evaluated.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    /// Parses an expression. `derived_names` lists the derived channels that
+    /// can be referenced by the expression.
+    pub fn parse(source: &str, derived_names: &[&str]) -> Result<Self, ExpressionError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            derived_names,
+        };
+        let root = parser.expression(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(ExpressionError::UnexpectedToken(token.to_string()));
+        }
+        Ok(Self { root })
+    }
+
+    /// Evaluates the expression at time `t` (in seconds). Returns `None` if any
+    /// of the inputs is not available yet.
+    pub fn eval(&mut self, scope: &impl Scope, t: f64) -> Option<f64> {
+        self.root.eval(scope, t)
+    }
+
+    /// Returns all the telemetry fields read by the expression.
+    pub fn input_fields(&self) -> Vec<IndexedField> {
+        let mut fields = Vec::new();
+        self.root.visit(&mut |node| match node {
+            Node::Field(field) if !fields.contains(field) => fields.push(field.clone()),
+            _ => {}
+        });
+        fields
+    }
+
+    /// Returns the names of all the derived channels read by the expression.
+    pub fn input_derived(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.root.visit(&mut |node| match node {
+            Node::Derived(name) if !names.contains(name) => names.push(name.clone()),
+            _ => {}
+        });
+        names
+    }
+}
+
+fn is_true(value: f64) -> bool {
+    value != 0.0 && !value.is_nan()
+}
+
+fn from_bool(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+// ┌────────────────────────┐
+// │          AST           │
+// └────────────────────────┘
+
+#[derive(Debug, Clone)]
+enum Node {
+    Const(f64),
+    Field(IndexedField),
+    Derived(String),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+    Filter(Filter, Box<Node>),
+}
+
+impl Node {
+    fn eval(&mut self, scope: &impl Scope, t: f64) -> Option<f64> {
+        match self {
+            Node::Const(value) => Some(*value),
+            Node::Field(field) => scope.field_value(field),
+            Node::Derived(name) => scope.derived_value(name),
+            Node::Unary(op, arg) => {
+                let value = arg.eval(scope, t)?;
+                Some(match op {
+                    UnaryOp::Neg => -value,
+                    UnaryOp::Not => from_bool(!is_true(value)),
+                })
+            }
+            Node::Binary(op, lhs, rhs) => {
+                // evaluate both sides to keep the filters state consistent
+                let lhs = lhs.eval(scope, t);
+                let rhs = rhs.eval(scope, t);
+                Some(op.apply(lhs?, rhs?))
+            }
+            Node::Call(function, args) => {
+                // evaluate all the arguments to keep the filters state consistent
+                let values: Vec<_> = args.iter_mut().map(|arg| arg.eval(scope, t)).collect();
+                let values = values.into_iter().collect::<Option<Vec<_>>>()?;
+                Some(function.apply(&values))
+            }
+            Node::Filter(filter, arg) => {
+                let value = arg.eval(scope, t)?;
+                filter.apply(value, t)
+            }
+        }
+    }
+
+    fn visit(&self, f: &mut impl FnMut(&Node)) {
+        f(self);
+        match self {
+            Node::Unary(_, arg) | Node::Filter(_, arg) => arg.visit(f),
+            Node::Binary(_, lhs, rhs) => {
+                lhs.visit(f);
+                rhs.visit(f);
+            }
+            Node::Call(_, args) => args.iter().for_each(|arg| arg.visit(f)),
+            Node::Const(_) | Node::Field(_) | Node::Derived(_) => {}
+        }
+    }
+
+    fn as_const(&self) -> Option<f64> {
+        match self {
+            Node::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+impl BinaryOp {
+    /// Binding power of the operator, higher binds tighter.
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::Ne => 3,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 4,
+            BinaryOp::Add | BinaryOp::Sub => 5,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 6,
+            BinaryOp::Pow => 8,
+        }
+    }
+
+    fn is_right_associative(&self) -> bool {
+        matches!(self, BinaryOp::Pow)
+    }
+
+    fn apply(&self, lhs: f64, rhs: f64) -> f64 {
+        match self {
+            BinaryOp::Or => from_bool(is_true(lhs) || is_true(rhs)),
+            BinaryOp::And => from_bool(is_true(lhs) && is_true(rhs)),
+            BinaryOp::Eq => from_bool(lhs == rhs),
+            BinaryOp::Ne => from_bool(lhs != rhs),
+            BinaryOp::Lt => from_bool(lhs < rhs),
+            BinaryOp::Le => from_bool(lhs <= rhs),
+            BinaryOp::Gt => from_bool(lhs > rhs),
+            BinaryOp::Ge => from_bool(lhs >= rhs),
+            BinaryOp::Add => lhs + rhs,
+            BinaryOp::Sub => lhs - rhs,
+            BinaryOp::Mul => lhs * rhs,
+            BinaryOp::Div => lhs / rhs,
+            BinaryOp::Rem => lhs % rhs,
+            BinaryOp::Pow => lhs.powf(rhs),
+        }
+    }
+}
+
+/// Stateless functions available in expressions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Pow,
+    Floor,
+    Ceil,
+    Round,
+    Sign,
+    /// Euclidean norm of all the arguments
+    Norm,
+    Min,
+    Max,
+    Clamp,
+    /// `if(cond, a, b)`
+    If,
+    /// Degrees to radians
+    Rad,
+    /// Radians to degrees
+    Deg,
+    /// Linear unit conversion, resolved at parse time: `value * scale + offset`
+    Convert {
+        scale: f64,
+        offset: f64,
+    },
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "abs" => Function::Abs,
+            "sqrt" => Function::Sqrt,
+            "exp" => Function::Exp,
+            "ln" => Function::Ln,
+            "log10" => Function::Log10,
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "tan" => Function::Tan,
+            "asin" => Function::Asin,
+            "acos" => Function::Acos,
+            "atan" => Function::Atan,
+            "atan2" => Function::Atan2,
+            "pow" => Function::Pow,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "round" => Function::Round,
+            "sign" => Function::Sign,
+            "norm" => Function::Norm,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "clamp" => Function::Clamp,
+            "if" => Function::If,
+            "rad" => Function::Rad,
+            "deg" => Function::Deg,
+            _ => return None,
+        })
+    }
+
+    /// Checks the number of arguments, returning a description of the
+    /// expected arity if it does not match.
+    fn check_arity(&self, n: usize) -> Result<(), &'static str> {
+        let (ok, expected) = match self {
+            Function::Atan2 | Function::Pow => (n == 2, "2"),
+            Function::Clamp | Function::If => (n == 3, "3"),
+            Function::Norm | Function::Min | Function::Max => (n >= 1, "at least 1"),
+            _ => (n == 1, "1"),
+        };
+        if ok { Ok(()) } else { Err(expected) }
+    }
+
+    fn apply(&self, args: &[f64]) -> f64 {
+        match self {
+            Function::Abs => args[0].abs(),
+            Function::Sqrt => args[0].sqrt(),
+            Function::Exp => args[0].exp(),
+            Function::Ln => args[0].ln(),
+            Function::Log10 => args[0].log10(),
+            Function::Sin => args[0].sin(),
+            Function::Cos => args[0].cos(),
+            Function::Tan => args[0].tan(),
+            Function::Asin => args[0].asin(),
+            Function::Acos => args[0].acos(),
+            Function::Atan => args[0].atan(),
+            Function::Atan2 => args[0].atan2(args[1]),
+            Function::Pow => args[0].powf(args[1]),
+            Function::Floor => args[0].floor(),
+            Function::Ceil => args[0].ceil(),
+            Function::Round => args[0].round(),
+            Function::Sign => {
+                if args[0] == 0.0 {
+                    0.0
+                } else {
+                    args[0].signum()
+                }
+            }
+            Function::Norm => args.iter().map(|v| v * v).sum::<f64>().sqrt(),
+            Function::Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+            Function::Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            // clamp() panics on NaN bounds
+            Function::Clamp if args[1].is_nan() || args[2].is_nan() => f64::NAN,
+            Function::Clamp => args[0].clamp(args[1].min(args[2]), args[2].max(args[1])),
+            Function::If => {
+                if is_true(args[0]) {
+                    args[1]
+                } else {
+                    args[2]
+                }
+            }
+            Function::Rad => args[0].to_radians(),
+            Function::Deg => args[0].to_degrees(),
+            Function::Convert { scale, offset } => args[0] * scale + offset,
+        }
+    }
+}
+
+/// Largest window of the moving average, bounding the samples kept per filter.
+const MAX_AVERAGE_SIZE: usize = 10_000;
+
+/// Stateful filters available in expressions.
+///
+/// Filters keep memory of the previous samples, so each occurrence in an
+/// expression has its own independent state.
+#[derive(Debug, Clone)]
+enum Filter {
+    /// `avg(x, n)`: moving average over the last `n` finite samples
+    MovingAverage { size: usize, window: VecDeque<f64> },
+    /// `ddt(x)`: time derivative, per second
+    Derivative { last: Option<(f64, f64)> },
+    /// `integ(x)`: time integral (trapezoidal), in value·seconds
+    Integral { last: Option<(f64, f64)>, acc: f64 },
+    /// `lowpass(x, tau)`: first order low-pass filter with time constant `tau` seconds
+    LowPass { tau: f64, last: Option<(f64, f64)> },
+}
+
+impl Filter {
+    fn apply(&mut self, value: f64, t: f64) -> Option<f64> {
+        match self {
+            Filter::MovingAverage { size, window } => {
+                // a single NaN would otherwise poison the whole window
+                if value.is_finite() {
+                    window.push_back(value);
+                    if window.len() > *size {
+                        window.pop_front();
+                    }
+                }
+                // summing the window every time avoids the drift of a running sum
+                (!window.is_empty()).then(|| window.iter().sum::<f64>() / window.len() as f64)
+            }
+            Filter::Derivative { last } => {
+                let res = match *last {
+                    Some((t0, v0)) if t > t0 => Some((value - v0) / (t - t0)),
+                    // keep the previous sample if time did not advance
+                    Some(_) => return None,
+                    None => None,
+                };
+                *last = Some((t, value));
+                res
+            }
+            Filter::Integral { last, acc } => {
+                match *last {
+                    Some((t0, v0)) if t > t0 => *acc += (value + v0) / 2.0 * (t - t0),
+                    _ => {}
+                }
+                *last = Some((t, value));
+                Some(*acc)
+            }
+            Filter::LowPass { tau, last } => {
+                let filtered = match *last {
+                    Some((t0, y0)) if t > t0 => {
+                        let alpha = (t - t0) / (*tau + (t - t0));
+                        y0 + alpha * (value - y0)
+                    }
+                    Some((_, y0)) => y0,
+                    None => value,
+                };
+                *last = Some((t, filtered));
+                Some(filtered)
+            }
+        }
+    }
+}
+
+// ┌────────────────────────┐
+// │         LEXER          │
+// └────────────────────────┘
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Str(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{n}"),
+            Token::Ident(s) => write!(f, "{s}"),
+            Token::Str(s) => write!(f, "\"{s}\""),
+            Token::Op(op) => write!(f, "{op}"),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+const OPERATORS: [&str; 18] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "+", "-", "*", "/", "%", "^", "=", "&", "|",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<CharIndices> = source.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut literal = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                let exponent_sign = (c == '+' || c == '-') && literal.ends_with(['e', 'E']);
+                if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign {
+                    literal.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let value = literal
+                .parse()
+                .map_err(|_| ExpressionError::UnexpectedToken(literal.clone()))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' || c == '.' {
+                    ident.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Ident(ident));
+        } else if c == '"' {
+            chars.next();
+            let mut literal = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, c)) => literal.push(c),
+                    None => return Err(ExpressionError::UnexpectedEnd),
+                }
+            }
+            tokens.push(Token::Str(literal));
+        } else if c == '(' {
+            chars.next();
+            tokens.push(Token::LParen);
+        } else if c == ')' {
+            chars.next();
+            tokens.push(Token::RParen);
+        } else if c == ',' {
+            chars.next();
+            tokens.push(Token::Comma);
+        } else {
+            let rest = &source[pos..];
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or(ExpressionError::UnexpectedChar(c, pos))?;
+            for _ in 0..op.len() {
+                chars.next();
+            }
+            // allow single `=`, `&` and `|` as shorthands
+            let op = match *op {
+                "=" => "==",
+                "&" => "&&",
+                "|" => "||",
+                op => op,
+            };
+            tokens.push(Token::Op(op));
+        }
+    }
+    Ok(tokens)
+}
+
+// ┌────────────────────────┐
+// │         PARSER         │
+// └────────────────────────┘
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    derived_names: &'a [&'a str],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ExpressionError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(ExpressionError::UnexpectedToken(token.to_string())),
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+
+    /// Precedence climbing over binary operators.
+    fn expression(&mut self, min_precedence: u8) -> Result<Node, ExpressionError> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let Some(op) = binary_op(op) else { break };
+            let precedence = op.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            self.next();
+            let next_min = if op.is_right_associative() {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let rhs = self.expression(next_min)?;
+            lhs = fold_constants(Node::Binary(op, Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        match self.peek() {
+            Some(Token::Op("-")) => {
+                self.next();
+                // unary minus binds looser than `^`, so `-2^2` is `-(2^2)`
+                let arg = self.expression(BinaryOp::Pow.precedence())?;
+                Ok(fold_constants(Node::Unary(UnaryOp::Neg, Box::new(arg))))
+            }
+            Some(Token::Op("+")) => {
+                self.next();
+                self.unary()
+            }
+            Some(Token::Op("!")) => {
+                self.next();
+                let arg = self.unary()?;
+                Ok(fold_constants(Node::Unary(UnaryOp::Not, Box::new(arg))))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Node::Const(value)),
+            Some(Token::LParen) => {
+                let node = self.expression(0)?;
+                self.expect(Token::RParen)?;
+                Ok(node)
+            }
+            Some(Token::Ident(name)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.next();
+                    self.call(name)
+                } else {
+                    self.identifier(name)
+                }
+            }
+            Some(token) => Err(ExpressionError::UnexpectedToken(token.to_string())),
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+
+    /// Parses the arguments of a function call, the opening parenthesis has
+    /// already been consumed.
+    fn arguments(&mut self) -> Result<Vec<Argument>, ExpressionError> {
+        let mut args = Vec::new();
+        if self.peek() == Some(&Token::RParen) {
+            self.next();
+            return Ok(args);
+        }
+        loop {
+            if let Some(Token::Str(literal)) = self.peek() {
+                let literal = literal.clone();
+                self.next();
+                args.push(Argument::Str(literal));
+            } else {
+                args.push(Argument::Expr(self.expression(0)?));
+            }
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RParen) => break,
+                Some(token) => return Err(ExpressionError::UnexpectedToken(token.to_string())),
+                None => return Err(ExpressionError::UnexpectedEnd),
+            }
+        }
+        Ok(args)
+    }
+
+    fn call(&mut self, name: String) -> Result<Node, ExpressionError> {
+        let args = self.arguments()?;
+
+        // unit conversions take string arguments, resolved right away
+        if name == "convert" {
+            return match <[Argument; 3]>::try_from(args) {
+                Ok(
+                    [
+                        Argument::Expr(value),
+                        Argument::Str(from),
+                        Argument::Str(to),
+                    ],
+                ) => {
+                    let (scale, offset) = UnitOfMeasure::from(Some(&from))
+                        .conversion_to(&UnitOfMeasure::from(Some(&to)))
+                        .ok_or(ExpressionError::IncompatibleUnits(from, to))?;
+                    Ok(fold_constants(Node::Call(
+                        Function::Convert { scale, offset },
+                        vec![value],
+                    )))
+                }
+                _ => Err(ExpressionError::WrongArity(
+                    name,
+                    "1 value and 2 unit strings",
+                )),
+            };
+        }
+
+        let mut args = args
+            .into_iter()
+            .map(|arg| match arg {
+                Argument::Expr(node) => Ok(node),
+                Argument::Str(literal) => {
+                    Err(ExpressionError::UnexpectedToken(format!("\"{literal}\"")))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(filter) = self.filter(&name, &mut args)? {
+            return Ok(filter);
+        }
+
+        let function =
+            Function::from_name(&name).ok_or(ExpressionError::UnknownFunction(name.clone()))?;
+        function
+            .check_arity(args.len())
+            .map_err(|expected| ExpressionError::WrongArity(name, expected))?;
+        Ok(fold_constants(Node::Call(function, args)))
+    }
+
+    /// Builds a filter node if `name` is a filter, `None` otherwise.
+    fn filter(&self, name: &str, args: &mut Vec<Node>) -> Result<Option<Node>, ExpressionError> {
+        let arity = match name {
+            "avg" | "lowpass" => 2,
+            "ddt" | "integ" => 1,
+            _ => return Ok(None),
+        };
+        if args.len() != arity {
+            let expected = if arity == 1 { "1" } else { "2" };
+            return Err(ExpressionError::WrongArity(name.to_string(), expected));
+        }
+        let filter = match name {
+            "avg" => {
+                let size = const_arg(name, args, 1)?.max(1.0);
+                if size > MAX_AVERAGE_SIZE as f64 {
+                    return Err(ExpressionError::WindowTooLarge(
+                        name.to_string(),
+                        MAX_AVERAGE_SIZE,
+                    ));
+                }
+                Filter::MovingAverage {
+                    size: size as usize,
+                    window: VecDeque::new(),
+                }
+            }
+            "lowpass" => Filter::LowPass {
+                tau: const_arg(name, args, 1)?.max(0.0),
+                last: None,
+            },
+            "ddt" => Filter::Derivative { last: None },
+            _ => Filter::Integral {
+                last: None,
+                acc: 0.0,
+            },
+        };
+        let arg = args.swap_remove(0);
+        Ok(Some(Node::Filter(filter, Box::new(arg))))
+    }
+
+    fn identifier(&self, name: String) -> Result<Node, ExpressionError> {
+        if self.derived_names.contains(&name.as_str()) {
+            return Ok(Node::Derived(name));
+        }
+        match name.as_str() {
+            "pi" => return Ok(Node::Const(std::f64::consts::PI)),
+            "e" => return Ok(Node::Const(std::f64::consts::E)),
+            "g0" => return Ok(Node::Const(9.80665)),
+            "true" => return Ok(Node::Const(1.0)),
+            "false" => return Ok(Node::Const(0.0)),
+            _ => {}
+        }
+        let Some((prefix, suffix)) = name.split_once('.') else {
+            return Err(ExpressionError::UnknownIdentifier(name));
+        };
+        if let Some(msg) = MAVLINK_PROFILE.get_msg(prefix) {
+            let field = suffix
+                .to_mav_field(msg.id, &MAVLINK_PROFILE)
+                .map_err(|_| ExpressionError::UnknownField(name.clone()))?;
+            return Ok(Node::Field(field));
+        }
+        MAVLINK_PROFILE
+            .get_enum_entry_value(prefix, suffix)
+            .map(|value| Node::Const(value as f64))
+            .ok_or(ExpressionError::UnknownField(name))
+    }
+}
+
+enum Argument {
+    Expr(Node),
+    Str(String),
+}
+
+fn binary_op(op: &str) -> Option<BinaryOp> {
+    Some(match op {
+        "||" => BinaryOp::Or,
+        "&&" => BinaryOp::And,
+        "==" => BinaryOp::Eq,
+        "!=" => BinaryOp::Ne,
+        "<" => BinaryOp::Lt,
+        "<=" => BinaryOp::Le,
+        ">" => BinaryOp::Gt,
+        ">=" => BinaryOp::Ge,
+        "+" => BinaryOp::Add,
+        "-" => BinaryOp::Sub,
+        "*" => BinaryOp::Mul,
+        "/" => BinaryOp::Div,
+        "%" => BinaryOp::Rem,
+        "^" => BinaryOp::Pow,
+        _ => return None,
+    })
+}
+
+fn const_arg(name: &str, args: &[Node], index: usize) -> Result<f64, ExpressionError> {
+    args.get(index)
+        .and_then(Node::as_const)
+        .ok_or(ExpressionError::NotConstant(name.to_string(), index + 1))
+}
+
+/// Collapses nodes whose arguments are all constants into a single constant.
+fn fold_constants(node: Node) -> Node {
+    match &node {
+        Node::Unary(op, arg) => match (op, arg.as_const()) {
+            (UnaryOp::Neg, Some(value)) => Node::Const(-value),
+            (UnaryOp::Not, Some(value)) => Node::Const(from_bool(!is_true(value))),
+            _ => node,
+        },
+        Node::Binary(op, lhs, rhs) => match (lhs.as_const(), rhs.as_const()) {
+            (Some(lhs), Some(rhs)) => Node::Const(op.apply(lhs, rhs)),
+            _ => node,
+        },
+        Node::Call(function, args) => {
+            match args.iter().map(Node::as_const).collect::<Option<Vec<_>>>() {
+                Some(values) => Node::Const(function.apply(&values)),
+                None => node,
+            }
+        }
+        _ => node,
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Values of the derived channels, and of the fields by name.
+    struct TestScope(HashMap<&'static str, f64>);
+
+    impl Scope for TestScope {
+        fn field_value(&self, field: &IndexedField) -> Option<f64> {
+            self.0.get(field.name()).copied()
+        }
+
+        fn derived_value(&self, name: &str) -> Option<f64> {
+            self.0.get(name).copied()
+        }
+    }
+
+    fn eval(source: &str) -> Option<f64> {
+        let scope = TestScope(HashMap::from([("a", 3.0), ("b", 4.0)]));
+        Expression::parse(source, &["a", "b", "c"])
+            .unwrap()
+            .eval(&scope, 0.0)
+    }
+
+    #[test]
+    fn test_arithmetic_precedence() {
+        assert_eq!(eval("1 + 2 * 3"), Some(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Some(9.0));
+        assert_eq!(eval("2 ^ 3 ^ 2"), Some(512.0));
+        assert_eq!(eval("-2 ^ 2"), Some(-4.0));
+        assert_eq!(eval("10 % 4 - 1"), Some(1.0));
+        assert_eq!(eval("1.5e3 / 1e-1"), Some(15000.0));
+    }
+
+    #[test]
+    fn test_logic_and_comparisons() {
+        assert_eq!(eval("a < b && b == 4"), Some(1.0));
+        assert_eq!(eval("a > b || !(a = 3)"), Some(0.0));
+        assert_eq!(eval("if(a >= 3, 10, 20)"), Some(10.0));
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(eval("norm(a, b)"), Some(5.0));
+        assert_eq!(eval("max(a, b, 2)"), Some(4.0));
+        assert_eq!(eval("clamp(a, 0, 1)"), Some(1.0));
+        assert_eq!(eval("deg(pi)"), Some(180.0));
+    }
+
+    #[test]
+    fn test_clamp_with_nan_bounds() {
+        assert!(eval("clamp(a, 0/0, 1)").unwrap().is_nan());
+        assert!(eval("clamp(a, 0/0, 0/0)").unwrap().is_nan());
+        assert!(eval("clamp(a, b, 0/0)").unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_missing_input() {
+        assert_eq!(eval("a + c"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            Expression::parse("1 +", &[]),
+            Err(ExpressionError::UnexpectedEnd)
+        ));
+        assert!(matches!(
+            Expression::parse("foo(1)", &[]),
+            Err(ExpressionError::UnknownFunction(_))
+        ));
+        assert!(matches!(
+            Expression::parse("atan2(1)", &[]),
+            Err(ExpressionError::WrongArity(_, _))
+        ));
+        assert!(matches!(
+            Expression::parse("avg(1, a)", &["a"]),
+            Err(ExpressionError::NotConstant(_, 2))
+        ));
+        assert!(matches!(
+            Expression::parse("avg(a)", &["a"]),
+            Err(ExpressionError::WrongArity(_, "2"))
+        ));
+        assert!(matches!(
+            Expression::parse("avg(a, 1e9)", &["a"]),
+            Err(ExpressionError::WindowTooLarge(_, MAX_AVERAGE_SIZE))
+        ));
+        assert!(matches!(
+            Expression::parse("GSE_TM.not_a_field", &[]),
+            Err(ExpressionError::UnknownField(_))
+        ));
+        assert!(matches!(
+            Expression::parse("1 $ 2", &[]),
+            Err(ExpressionError::UnexpectedChar('$', 2))
+        ));
+    }
+
+    #[test]
+    fn test_enum_entries() {
+        let msg_id = MAVLINK_PROFILE.get_msg("WIGGLE_SERVO_TC").unwrap().id;
+        let servo_id = "servo_id".to_mav_field(msg_id, &MAVLINK_PROFILE).unwrap();
+        let enum_name = servo_id.field().enumtype.clone().unwrap();
+        let raw = MAVLINK_PROFILE
+            .get_enum_entry_value(&enum_name, "MAIN_VALVE")
+            .unwrap();
+        assert_eq!(eval(&format!("{enum_name}.MAIN_VALVE")), Some(raw as f64));
+        // entries are qualified by their enum, as names can repeat across enums
+        assert!(matches!(
+            Expression::parse("MAIN_VALVE", &[]),
+            Err(ExpressionError::UnknownIdentifier(_))
+        ));
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut scope = TestScope(HashMap::new());
+        let mut avg = Expression::parse("avg(a, 2)", &["a"]).unwrap();
+        let mut ddt = Expression::parse("ddt(a)", &["a"]).unwrap();
+        let mut integ = Expression::parse("integ(a)", &["a"]).unwrap();
+
+        let samples = [(0.0, 1.0), (1.0, 3.0), (2.0, 7.0)];
+        let mut results = Vec::new();
+        for (t, a) in samples {
+            scope.0.insert("a", a);
+            results.push((
+                avg.eval(&scope, t),
+                ddt.eval(&scope, t),
+                integ.eval(&scope, t),
+            ));
+        }
+        assert_eq!(results[0], (Some(1.0), None, Some(0.0)));
+        assert_eq!(results[1], (Some(2.0), Some(2.0), Some(2.0)));
+        assert_eq!(results[2], (Some(5.0), Some(4.0), Some(7.0)));
+    }
+
+    #[test]
+    fn test_average_skips_non_finite_samples() {
+        let mut scope = TestScope(HashMap::new());
+        let mut avg = Expression::parse("avg(a, 2)", &["a"]).unwrap();
+        let samples = [f64::NAN, 1.0, f64::NAN, 3.0, f64::INFINITY, 5.0];
+        let mut results = Vec::new();
+        for (t, a) in samples.into_iter().enumerate() {
+            scope.0.insert("a", a);
+            results.push(avg.eval(&scope, t as f64));
+        }
+        assert_eq!(
+            results,
+            vec![None, Some(1.0), Some(1.0), Some(2.0), Some(2.0), Some(4.0)]
+        );
+    }
+
+    #[test]
+    fn test_filters_in_calls_see_every_sample() {
+        let mut scope = TestScope(HashMap::new());
+        let mut expr = Expression::parse("max(b, ddt(a))", &["a", "b"]).unwrap();
+        scope.0.insert("a", 1.0);
+        assert_eq!(expr.eval(&scope, 0.0), None);
+        // the derivative was fed also while b was missing
+        scope.0.insert("a", 3.0);
+        scope.0.insert("b", -10.0);
+        assert_eq!(expr.eval(&scope, 1.0), Some(2.0));
+    }
+
+    #[test]
+    fn test_inputs() {
+        let expr = Expression::parse("a + avg(b, 3) * a", &["a", "b"]).unwrap();
+        assert_eq!(expr.input_derived(), vec!["a".to_string(), "b".to_string()]);
+        assert!(expr.input_fields().is_empty());
+    }
+
+    #[test]
+    fn test_field_inputs() {
+        let mut expr = Expression::parse(
+            "GSE_TM.ox_tank_top_pressure * 2 + a + GSE_TM.ox_tank_top_pressure",
+            &["a"],
+        )
+        .unwrap();
+        let fields = expr.input_fields();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].name(), "ox_tank_top_pressure");
+        assert_eq!(expr.input_derived(), vec!["a".to_string()]);
+
+        let scope = TestScope(HashMap::from([("ox_tank_top_pressure", 1.5), ("a", 1.0)]));
+        assert_eq!(expr.eval(&scope, 0.0), Some(5.5));
+        assert_eq!(expr.eval(&TestScope(HashMap::new()), 1.0), None);
+    }
+}
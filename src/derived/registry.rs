@@ -0,0 +1,177 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    APP_START_TIMESTAMP_ORIGIN,
+    mavlink::{TimedMessage, reflection::IndexedField},
+};
+
+use super::{
+    DerivedChannel, DerivedSample,
+    expression::{Expression, ExpressionError, Scope},
+};
+
+/// Compiled set of derived channels, evaluated on every incoming message.
+#[derive(Debug, Default)]
+pub struct DerivedRegistry {
+    /// Definitions the registry was compiled from, used to detect changes
+    definitions: Vec<DerivedChannel>,
+    channels: Vec<CompiledChannel>,
+    /// Telemetry fields read by at least one channel, grouped by message id
+    inputs: HashMap<u32, Vec<IndexedField>>,
+    cache: ValueCache,
+}
+
+#[derive(Debug)]
+struct CompiledChannel {
+    name: Arc<str>,
+    expression: Result<Expression, ExpressionError>,
+    /// Message ids that trigger a new evaluation of the channel
+    trigger_ids: HashSet<u32>,
+}
+
+impl DerivedRegistry {
+    /// Recompiles the channels if the definitions changed since the last call.
+    pub fn sync(&mut self, definitions: &[DerivedChannel]) {
+        if self.definitions != definitions {
+            self.compile(definitions);
+        }
+    }
+
+    fn compile(&mut self, definitions: &[DerivedChannel]) {
+        self.definitions = definitions.to_vec();
+        self.channels.clear();
+        self.inputs.clear();
+        self.cache = ValueCache::default();
+
+        for (i, definition) in definitions.iter().enumerate() {
+            // channels can only reference the ones defined before them
+            let previous: Vec<&str> = definitions[..i].iter().map(|d| d.name.as_str()).collect();
+            let expression = validate_name(&definition.name, &previous)
+                .and_then(|_| Expression::parse(&definition.expression, &previous));
+
+            let mut trigger_ids = HashSet::new();
+            if let Ok(expression) = &expression {
+                for field in expression.input_fields() {
+                    trigger_ids.insert(field.msg_id());
+                    let inputs = self.inputs.entry(field.msg_id()).or_default();
+                    if !inputs.contains(&field) {
+                        inputs.push(field);
+                    }
+                }
+                for name in expression.input_derived() {
+                    if let Some(channel) = self.channels.iter().find(|c| *c.name == *name) {
+                        trigger_ids.extend(channel.trigger_ids.iter().copied());
+                    }
+                }
+            }
+
+            self.channels.push(CompiledChannel {
+                name: Arc::from(definition.name.as_str()),
+                expression,
+                trigger_ids,
+            });
+        }
+    }
+
+    /// Evaluates all the channels triggered by `message`, appending the
+    /// results to its derived samples.
+    pub fn process(&mut self, message: &mut TimedMessage) {
+        let msg_id = message.id();
+        let Some(inputs) = self.inputs.get(&msg_id) else {
+            return;
+        };
+        for field in inputs {
+            if let Ok(value) = field.extract_as_f64(&message.message) {
+                self.cache.fields.insert(field.clone(), value);
+            }
+        }
+
+        let t = message
+            .time
+            .saturating_duration_since(*APP_START_TIMESTAMP_ORIGIN)
+            .as_secs_f64();
+        for channel in &mut self.channels {
+            if !channel.trigger_ids.contains(&msg_id) {
+                continue;
+            }
+            let Ok(expression) = &mut channel.expression else {
+                continue;
+            };
+            if let Some(value) = expression.eval(&self.cache, t) {
+                self.cache.derived.insert(channel.name.clone(), value);
+                message.derived.push(DerivedSample {
+                    name: channel.name.clone(),
+                    value,
+                });
+            }
+        }
+    }
+
+    /// Returns the compilation error of the channel at `index`, if any.
+    pub fn error(&self, index: usize) -> Option<&ExpressionError> {
+        self.channels
+            .get(index)
+            .and_then(|c| c.expression.as_ref().err())
+    }
+
+    /// Returns the definitions of all the channels that compiled successfully.
+    pub fn valid_channels(&self) -> impl Iterator<Item = &DerivedChannel> {
+        self.definitions
+            .iter()
+            .zip(&self.channels)
+            .filter(|(_, c)| c.expression.is_ok())
+            .map(|(d, _)| d)
+    }
+
+    /// Returns the definition of a channel by name.
+    pub fn get(&self, name: &str) -> Option<&DerivedChannel> {
+        self.definitions.iter().find(|d| d.name == name)
+    }
+
+    /// Returns the message ids that trigger the evaluation of a channel, sorted.
+    pub fn trigger_ids(&self, name: &str) -> Vec<u32> {
+        let mut ids: Vec<u32> = self
+            .channels
+            .iter()
+            .find(|c| *c.name == *name)
+            .map(|c| c.trigger_ids.iter().copied().collect())
+            .unwrap_or_default();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// Latest known value of every expression input.
+#[derive(Debug, Default)]
+struct ValueCache {
+    fields: HashMap<IndexedField, f64>,
+    derived: HashMap<Arc<str>, f64>,
+}
+
+impl Scope for ValueCache {
+    fn field_value(&self, field: &IndexedField) -> Option<f64> {
+        self.fields.get(field).copied()
+    }
+
+    fn derived_value(&self, name: &str) -> Option<f64> {
+        self.derived.get(name).copied()
+    }
+}
+
+fn validate_name(name: &str, previous: &[&str]) -> Result<(), ExpressionError> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if !valid {
+        Err(ExpressionError::InvalidName(name.to_string()))
+    } else if previous.contains(&name) {
+        Err(ExpressionError::DuplicateName(name.to_string()))
+    } else {
+        Ok(())
+    }
+}
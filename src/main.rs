@@ -5,10 +5,14 @@
 #[cfg(feature = "conrig")]
 mod cli;
 
+mod alarms;
+mod commands;
 mod communication;
+mod derived;
 mod error;
 mod mavlink;
 mod message_broker;
+mod tc_log;
 mod ui;
 mod utils;
 
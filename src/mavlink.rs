@@ -8,6 +8,12 @@ pub mod reflection;
 
 use std::time::Instant;
 
+use jiff::{Unit, Zoned};
+
+use crate::{derived::DerivedSample, error::ErrInstrument};
+
+use reflection::{FieldLookup, MessageMap};
+
 // Re-export from the mavlink crate
 pub use skyward_mavlink::{
     mavlink::*, orion::*,
@@ -23,16 +29,22 @@ pub const DEFAULT_SEND_ETHERNET_PORT: u16 = 42070;
 pub struct TimedMessage {
     /// The underlying mavlink message
     pub message: MavMessage,
+    /// System that sent the message
+    pub system_id: u8,
     /// The time instant at which the message was received
     pub time: Instant,
+    /// Values of the derived channels computed from this message
+    pub derived: Vec<DerivedSample>,
 }
 
 impl TimedMessage {
     /// Create a new `TimedMessage` instance with the given message and the current time
-    pub fn just_received(message: MavMessage) -> Self {
+    pub fn just_received(header: MavHeader, message: MavMessage) -> Self {
         Self {
             message,
+            system_id: header.system_id,
             time: Instant::now(),
+            derived: Vec::new(),
         }
     }
 
@@ -40,3 +52,15 @@ impl TimedMessage {
         self.message.message_id()
     }
 }
+
+/// Sets the `timestamp` field of a message about to be sent, if it has one, to
+/// the current time in nanoseconds.
+pub fn stamp_current_time(map: &mut MessageMap) {
+    if let Some(tm) = map.get_mut_field("timestamp") {
+        *tm = Zoned::now()
+            .round(Unit::Nanosecond)
+            .log_unwrap()
+            .timestamp()
+            .as_nanosecond() as u64;
+    }
+}
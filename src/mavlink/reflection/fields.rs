@@ -243,6 +243,8 @@ impl PartialEq for IndexedField {
     }
 }
 
+impl Eq for IndexedField {}
+
 impl serde::Serialize for IndexedField {
     /// Serializes the `IndexedField` by its field index and message ID.
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
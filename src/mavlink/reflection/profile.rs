@@ -74,6 +74,25 @@ impl ReflectionContext {
         self.mavlink_profile.enums.get(enum_name)
     }
 
+    /// Get the raw value of an entry of an enumeration, by their names.
+    ///
+    /// Entries without an explicit value are assigned their position in the enumeration.
+    ///
+    /// # Arguments
+    /// * `enum_name` - The name of the enumeration.
+    /// * `entry_name` - The name of the enumeration entry.
+    ///
+    /// # Returns
+    /// * `Some(u32)` if the enumeration has an entry with that name, otherwise `None`.
+    pub fn get_enum_entry_value(&'static self, enum_name: &str, entry_name: &str) -> Option<u32> {
+        self.get_enum(enum_name)?
+            .entries
+            .iter()
+            .enumerate()
+            .find(|(_, entry)| entry.name == entry_name)
+            .map(|(i, entry)| entry.value.unwrap_or(i as u32))
+    }
+
     /// Get all fields for a message by its identifier.
     ///
     /// # Arguments
@@ -16,13 +16,20 @@ use std::{sync::Arc, time::Duration};
 
 use tracing::error;
 
-use crate::mavlink::{MavFrame, MavHeader, MavMessage, MavlinkVersion, TimedMessage};
+use crate::{
+    alarms::{AlarmEngineExt, AlarmEvent, AlarmEventKind, Severity},
+    derived::DERIVED_CHANNELS,
+    mavlink::{MavFrame, MavHeader, MavMessage, MavlinkVersion, TimedMessage},
+    tc_log::{TcLogExt, TcSource},
+};
 pub use connection::ConnectionConfig;
 use connection::ConnectionHandler;
 pub use message_bundle::MessageBundle;
 
 const RECEPTION_QUEUE_INTERVAL: Duration = Duration::from_secs(3);
 const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/// Time without receptions after which the telemetry link is considered lost
+const LINK_LOSS_TIMEOUT: Duration = Duration::from_secs(3);
 
 /// The MessageBroker struct contains the state of the message broker.
 ///
@@ -35,6 +42,9 @@ pub struct MessageBroker {
     last_receptions: Arc<Mutex<ReceptionQueue>>,
     /// Connection to the Mavlink listener
     connection: ConnectionHandler,
+    /// Whether the telemetry link was lost, i.e. no message was received for
+    /// a while or the connection reported an error
+    link_lost: bool,
     /// Egui context
     ctx: egui::Context,
 }
@@ -47,6 +57,7 @@ impl MessageBroker {
             // TODO: make this configurable
             last_receptions: Arc::new(Mutex::new(ReceptionQueue::new(RECEPTION_QUEUE_INTERVAL))),
             connection: ConnectionHandler::new(RECONNECT_INTERVAL),
+            link_lost: false,
             ctx,
         }
     }
@@ -90,11 +101,20 @@ impl MessageBroker {
     #[profiling::function]
     pub fn process_incoming_messages(&mut self, bundle: &mut MessageBundle) {
         // process messages only if the connection is open
-        if let Some(connection) = self.connection.connection.read().as_ref() {
+        let received = self
+            .connection
+            .connection
+            .read()
+            .as_ref()
+            .map(|connection| connection.retrieve_messages());
+        if let Some(received) = received {
             // check for communication errors, and log them
-            match connection.retrieve_messages() {
+            match received {
                 Ok(messages) => {
-                    for message in messages {
+                    let mut derived_channels = DERIVED_CHANNELS.write();
+                    for mut message in messages {
+                        // Compute the derived channels before dispatching the message
+                        derived_channels.process(&mut message);
                         bundle.insert(message.clone());
 
                         // Update the last reception time
@@ -108,28 +128,92 @@ impl MessageBroker {
                 Err(e) => {
                     error!("Error while receiving messages: {:?}", e);
                     // TODO: user error handling, until them silently close the connection
+                    self.set_link_lost(true, Some(format!("{e:?}")));
+                    return;
                 }
             }
         }
+        self.check_link();
     }
 
-    /// Processes outgoing messages.
+    /// Records the loss and the recovery of the telemetry link in the event
+    /// log. The link is lost if no message is received for a while.
+    fn check_link(&mut self) {
+        if !self.is_connected() {
+            // Closing the connection is not a link loss
+            self.link_lost = false;
+            return;
+        }
+        match self.time_since_last_reception() {
+            Some(elapsed) if elapsed > LINK_LOSS_TIMEOUT => {
+                let detail = format!("no messages for {:.0} s", elapsed.as_secs_f64());
+                self.set_link_lost(true, Some(detail));
+            }
+            Some(_) => self.set_link_lost(false, None),
+            None => {}
+        }
+        // Keep checking while waiting for messages
+        self.ctx.request_repaint_after(LINK_LOSS_TIMEOUT);
+    }
+
+    fn set_link_lost(&mut self, lost: bool, detail: Option<String>) {
+        if lost == self.link_lost {
+            return;
+        }
+        self.link_lost = lost;
+        let event = if lost {
+            AlarmEvent::new(
+                AlarmEventKind::ConnectionLost,
+                Severity::Critical,
+                "Connection",
+            )
+        } else {
+            AlarmEvent::new(
+                AlarmEventKind::ConnectionRestored,
+                Severity::Info,
+                "Connection",
+            )
+        };
+        let event = match detail {
+            Some(detail) => event.with_value(detail),
+            None => event,
+        };
+        self.ctx.alarms().lock().record(event);
+    }
+
+    /// Processes outgoing messages sent by `source`, recording them in the
+    /// telecommand log.
     ///
     /// **WARNING**: This methods blocks the UI, thus a detailed profiling is needed.
     // FIXME
     #[profiling::function]
-    pub fn process_outgoing_messages(&mut self, messages: Vec<(MavHeader, MavMessage)>) {
-        if let Some(connection) = self.connection.connection.read().as_ref() {
-            for (header, msg) in messages {
-                let frame = MavFrame {
-                    header,
-                    msg,
-                    protocol_version: MavlinkVersion::V1,
-                };
-                if let Err(e) = connection.send_message(frame) {
-                    error!("Error while transmitting message: {:?}", e);
+    pub fn process_outgoing_messages(
+        &mut self,
+        source: TcSource,
+        messages: Vec<(MavHeader, MavMessage)>,
+    ) {
+        if messages.is_empty() {
+            return;
+        }
+        let tc_log = self.ctx.tc_log();
+        let mut tc_log = tc_log.lock();
+        let connection = self.connection.connection.read();
+        for (header, msg) in messages {
+            let error = match connection.as_ref() {
+                Some(connection) => {
+                    let frame = MavFrame {
+                        header,
+                        msg: msg.clone(),
+                        protocol_version: MavlinkVersion::V1,
+                    };
+                    connection.send_message(frame).err().map(|e| {
+                        error!("Error while transmitting message: {:?}", e);
+                        format!("{e:?}")
+                    })
                 }
-            }
+                None => Some("not connected".to_owned()),
+            };
+            tc_log.record(source.clone(), header, msg, error);
         }
     }
 
@@ -23,6 +23,11 @@ impl MessageBundle {
             .collect()
     }
 
+    /// Returns an iterator over all the messages contained in the bundle.
+    pub fn iter(&self) -> impl Iterator<Item = &TimedMessage> {
+        self.storage.iter()
+    }
+
     /// Inserts a new message into the bundle.
     pub fn insert(&mut self, message: TimedMessage) {
         self.storage.push(message);
@@ -0,0 +1,566 @@
+//! Log of the telecommands sent during the session.
+//!
+//! Every frame sent through the message broker is recorded in the [`TcLog`],
+//! together with the pane that originated it (see [`TcSource`]), and matched with the ACK, NACK
+//! or WACK sent back by the receiver through its sequence number (see
+//! [`PendingReply`]). The outcome of the commands whose originator waits for
+//! it is then delivered back to the originator (see [`TcDelivery`]). The log
+//! lives in the egui context, so it survives layout switches, and it is
+//! accessible from anywhere with [`TcLogExt::tc_log`].
+
+mod correlation;
+
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use egui::{Color32, Context, mutex::Mutex};
+use jiff::Zoned;
+
+use crate::{
+    mavlink::{MavHeader, MavMessage, Message, TimedMessage, reflection::MAVLINK_PROFILE},
+    utils::id::PaneId,
+};
+
+pub use correlation::{
+    DEFAULT_REPLY_TIMEOUT, PendingReply, Reply, ReplyKind, ReplyTimeout, ReplyTracker,
+};
+
+/// Maximum number of commands kept in the log.
+const MAX_LOG_LEN: usize = 10_000;
+
+/// Originator of a command: a pane of the layout, or a window outside of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TcSource {
+    /// Pane that sent the command, telling apart panes with the same label
+    pub pane: Option<PaneId>,
+    /// Name shown to the operator, e.g. the text of the button of a command
+    pub label: String,
+}
+
+impl From<&str> for TcSource {
+    fn from(label: &str) -> Self {
+        Self {
+            pane: None,
+            label: label.to_owned(),
+        }
+    }
+}
+
+/// A command sent to the connected system.
+#[derive(Debug, Clone)]
+pub struct TcRecord {
+    /// Identifies the record in the log, increasing in the order of sending
+    pub id: u64,
+    /// Pane or window that sent the command
+    pub source: TcSource,
+    pub header: MavHeader,
+    pub message: MavMessage,
+    pub sent_at: Zoned,
+    pub reply: TcReply,
+    sent_instant: Instant,
+    /// Time waited for the reply, as long as the originator waits for it
+    timeout: Duration,
+    /// The command as tracked for its originator, if it waits for the reply
+    pending: Option<PendingReply>,
+}
+
+impl TcRecord {
+    pub fn name(&self) -> String {
+        MAVLINK_PROFILE
+            .get_msg(self.message.message_id())
+            .map(|m| m.name.clone())
+            .unwrap_or_else(|| self.message.message_name().to_owned())
+    }
+
+    /// Fields of the command, decoded as `(name, value)` pairs.
+    pub fn fields(&self) -> Vec<(String, String)> {
+        MAVLINK_PROFILE
+            .get_fields(self.message.message_id())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|field| {
+                let value = if field.field().enumtype.is_some() {
+                    field.extract_as_enum_str(&self.message)
+                } else {
+                    field.extract_as_string(&self.message)
+                };
+                (field.field().name.clone(), value.unwrap_or_else(|e| e))
+            })
+            .collect()
+    }
+
+    /// Whether the reply identifies this command.
+    fn is_replied_by(&self, reply: &Reply) -> bool {
+        reply.acknowledges(&self.header, &self.message)
+    }
+}
+
+/// Outcome of a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TcReply {
+    Pending,
+    /// The command could not be sent, with the reason
+    NotSent(String),
+    Ack {
+        latency: Duration,
+    },
+    Nack {
+        latency: Duration,
+        err_id: u16,
+        error: Option<String>,
+    },
+    Wack {
+        latency: Duration,
+        err_id: u16,
+        error: Option<String>,
+    },
+    Timeout,
+}
+
+impl TcReply {
+    pub fn color(&self) -> Color32 {
+        match self {
+            Self::Pending => Color32::GRAY,
+            Self::Ack { .. } => Color32::GREEN,
+            Self::Wack { .. } => Color32::ORANGE,
+            Self::NotSent(_) | Self::Nack { .. } | Self::Timeout => Color32::RED,
+        }
+    }
+
+    pub fn latency(&self) -> Option<Duration> {
+        match self {
+            Self::Ack { latency } | Self::Nack { latency, .. } | Self::Wack { latency, .. } => {
+                Some(*latency)
+            }
+            _ => None,
+        }
+    }
+
+    /// Reply of the receiver, if any.
+    pub fn kind(&self) -> Option<ReplyKind> {
+        match self {
+            Self::Ack { .. } => Some(ReplyKind::Ack),
+            Self::Nack { err_id, .. } => Some(ReplyKind::Nack { err_id: *err_id }),
+            Self::Wack { err_id, .. } => Some(ReplyKind::Wack { err_id: *err_id }),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TcReply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pending => write!(f, "Waiting"),
+            Self::NotSent(reason) => write!(f, "Not sent: {reason}"),
+            Self::Ack { .. } => write!(f, "ACK"),
+            Self::Nack { err_id, error, .. } | Self::Wack { err_id, error, .. } => {
+                let kind = if matches!(self, Self::Nack { .. }) {
+                    "NACK"
+                } else {
+                    "WACK"
+                };
+                match error {
+                    Some(error) => write!(f, "{kind}: {error} ({err_id})"),
+                    None => write!(f, "{kind}: error {err_id}"),
+                }
+            }
+            Self::Timeout => write!(f, "Timeout"),
+        }
+    }
+}
+
+/// Outcome of a command, to deliver to the pane or window that sent it.
+#[derive(Debug, Clone)]
+pub struct TcDelivery {
+    pub source: TcSource,
+    /// The command, as returned to the originator by [`PendingReply::send`]
+    pub pending: PendingReply,
+    pub reply: TcReply,
+}
+
+/// Commands sent during the session, with their replies.
+#[derive(Debug)]
+pub struct TcLog {
+    records: VecDeque<TcRecord>,
+    next_id: u64,
+    /// Commands the originators wait a reply for
+    tracker: Arc<Mutex<ReplyTracker>>,
+    /// Outcomes not yet delivered to the originators
+    deliveries: Vec<TcDelivery>,
+}
+
+impl Default for TcLog {
+    fn default() -> Self {
+        Self::with_tracker(correlation::REPLY_TRACKER.clone())
+    }
+}
+
+impl TcLog {
+    /// Creates a log delivering the outcome of the commands of `tracker`.
+    pub fn with_tracker(tracker: Arc<Mutex<ReplyTracker>>) -> Self {
+        Self {
+            records: VecDeque::new(),
+            next_id: 0,
+            tracker,
+            deliveries: Vec::new(),
+        }
+    }
+
+    /// Records a command sent by `source`. If the command could not be sent,
+    /// `error` holds the reason.
+    pub fn record(
+        &mut self,
+        source: impl Into<TcSource>,
+        header: MavHeader,
+        message: MavMessage,
+        error: Option<String>,
+    ) {
+        if self.records.len() >= MAX_LOG_LEN {
+            self.records.pop_front();
+        }
+        let pending = self.tracker.lock().pending(&header, &message);
+        let mut record = TcRecord {
+            id: self.next_id,
+            source: source.into(),
+            header,
+            message,
+            sent_at: Zoned::now(),
+            reply: TcReply::Pending,
+            sent_instant: Instant::now(),
+            timeout: pending.map_or(DEFAULT_REPLY_TIMEOUT, |p| p.timeout()),
+            pending,
+        };
+        self.next_id += 1;
+        if let Some(error) = error {
+            let reply = TcReply::NotSent(error);
+            resolve(&self.tracker, &mut self.deliveries, &mut record, reply);
+        }
+        self.records.push_back(record);
+    }
+
+    /// Matches the ACK, NACK and WACK among the given messages with the
+    /// command they acknowledge. Replies arriving after the timeout still
+    /// update the outcome of the command.
+    pub fn process_replies<'a>(&mut self, messages: impl IntoIterator<Item = &'a TimedMessage>) {
+        for message in messages {
+            let Some(reply) = Reply::parse(message) else {
+                continue;
+            };
+            let Some(record) = self
+                .records
+                .iter()
+                .position(|r| r.reply == TcReply::Pending && r.is_replied_by(&reply))
+                .or_else(|| {
+                    self.records
+                        .iter()
+                        .rposition(|r| r.reply == TcReply::Timeout && r.is_replied_by(&reply))
+                })
+                .map(|i| &mut self.records[i])
+            else {
+                continue;
+            };
+            let latency = message.time.saturating_duration_since(record.sent_instant);
+            let outcome = match reply.kind {
+                ReplyKind::Ack => TcReply::Ack { latency },
+                ReplyKind::Nack { err_id } => TcReply::Nack {
+                    latency,
+                    err_id,
+                    error: decode_error(&message.message),
+                },
+                ReplyKind::Wack { err_id } => TcReply::Wack {
+                    latency,
+                    err_id,
+                    error: decode_error(&message.message),
+                },
+            };
+            resolve(&self.tracker, &mut self.deliveries, record, outcome);
+        }
+    }
+
+    /// Marks as timed out the commands waiting for longer than their timeout,
+    /// the one of the [`PendingReply`] of the originator if it waits for the
+    /// reply. Returns whether some command is still waiting for a reply.
+    pub fn check_timeouts(&mut self) -> bool {
+        let mut waiting = false;
+        for record in self
+            .records
+            .iter_mut()
+            .filter(|r| r.reply == TcReply::Pending)
+        {
+            if record.sent_instant.elapsed() > record.timeout {
+                resolve(
+                    &self.tracker,
+                    &mut self.deliveries,
+                    record,
+                    TcReply::Timeout,
+                );
+            } else {
+                waiting = true;
+            }
+        }
+        waiting
+    }
+
+    /// Takes the outcomes to deliver to the originators of the commands, in
+    /// the order they were received.
+    pub fn take_deliveries(&mut self) -> Vec<TcDelivery> {
+        std::mem::take(&mut self.deliveries)
+    }
+
+    pub fn records(&self) -> &VecDeque<TcRecord> {
+        &self.records
+    }
+
+    /// The record with the given id, if still in the log.
+    pub fn get(&self, id: u64) -> Option<&TcRecord> {
+        self.records
+            .binary_search_by_key(&id, |r| r.id)
+            .ok()
+            .and_then(|i| self.records.get(i))
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
+
+/// Sets the outcome of the command, delivering it to the originator if it
+/// waits for it.
+fn resolve(
+    tracker: &Mutex<ReplyTracker>,
+    deliveries: &mut Vec<TcDelivery>,
+    record: &mut TcRecord,
+    reply: TcReply,
+) {
+    if let Some(pending) = record.pending {
+        tracker.lock().forget(&pending);
+        deliveries.push(TcDelivery {
+            source: record.source.clone(),
+            pending,
+            reply: reply.clone(),
+        });
+    }
+    record.reply = reply;
+}
+
+/// Decodes the error code of a NACK or WACK, if it is an enum entry.
+fn decode_error(reply: &MavMessage) -> Option<String> {
+    MAVLINK_PROFILE
+        .get_fields(reply.message_id())?
+        .into_iter()
+        .find(|f| f.field().name == "err_id" && f.field().enumtype.is_some())?
+        .extract_as_enum_str(reply)
+        .ok()
+}
+
+pub trait TcLogExt {
+    fn tc_log(&self) -> Arc<Mutex<TcLog>>;
+}
+
+impl TcLogExt for Context {
+    fn tc_log(&self) -> Arc<Mutex<TcLog>> {
+        self.memory_mut(|w| {
+            if let Some(arc) = w.data.get_temp("tc_log".into()) {
+                arc
+            } else {
+                let log = Arc::new(Mutex::new(TcLog::default()));
+                w.data.insert_temp("tc_log".into(), log.clone());
+                log
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mavlink::{ACK_TM_DATA, MessageData, NACK_TM_DATA, WIGGLE_SERVO_TC_DATA};
+
+    fn wiggle() -> MavMessage {
+        MavMessage::WIGGLE_SERVO_TC(WIGGLE_SERVO_TC_DATA::default())
+    }
+
+    fn reply(message: MavMessage) -> TimedMessage {
+        TimedMessage::just_received(MavHeader::default(), message)
+    }
+
+    /// A log with a tracker of its own, not shared with the other tests.
+    fn new_log() -> (TcLog, Arc<Mutex<ReplyTracker>>) {
+        let tracker = Arc::new(Mutex::new(ReplyTracker::default()));
+        (TcLog::with_tracker(tracker.clone()), tracker)
+    }
+
+    #[test]
+    fn replies_match_the_oldest_pending_command() {
+        let (mut log, _) = new_log();
+        log.record("test", MavHeader::default(), wiggle(), None);
+        log.record("test", MavHeader::default(), wiggle(), None);
+
+        let recv_msgid = WIGGLE_SERVO_TC_DATA::ID as u8;
+        log.process_replies([&reply(MavMessage::ACK_TM(ACK_TM_DATA {
+            recv_msgid,
+            ..Default::default()
+        }))]);
+        assert!(matches!(log.records()[0].reply, TcReply::Ack { .. }));
+        assert_eq!(log.records()[1].reply, TcReply::Pending);
+
+        log.process_replies([&reply(MavMessage::NACK_TM(NACK_TM_DATA {
+            recv_msgid,
+            err_id: 4,
+            ..Default::default()
+        }))]);
+        assert!(matches!(
+            log.records()[1].reply,
+            TcReply::Nack { err_id: 4, .. }
+        ));
+    }
+
+    #[test]
+    fn unrelated_replies_are_ignored() {
+        let (mut log, _) = new_log();
+        log.record("test", MavHeader::default(), wiggle(), None);
+        log.record(
+            "test",
+            MavHeader::default(),
+            wiggle(),
+            Some("offline".into()),
+        );
+
+        log.process_replies([&reply(MavMessage::ACK_TM(ACK_TM_DATA {
+            recv_msgid: WIGGLE_SERVO_TC_DATA::ID as u8 + 1,
+            ..Default::default()
+        }))]);
+        assert_eq!(log.records()[0].reply, TcReply::Pending);
+        assert_eq!(
+            log.records()[1].reply,
+            TcReply::NotSent("offline".to_owned())
+        );
+    }
+
+    #[test]
+    fn replies_match_the_sequence_number() {
+        let (mut log, tracker) = new_log();
+        let mut headers = [MavHeader::default(); 2];
+        let mut pending = Vec::new();
+        for header in headers.iter_mut() {
+            pending.push(
+                tracker
+                    .lock()
+                    .send(header, &wiggle(), ReplyTimeout::default()),
+            );
+            log.record("test", *header, wiggle(), None);
+        }
+
+        log.process_replies([&reply(MavMessage::ACK_TM(ACK_TM_DATA {
+            recv_msgid: WIGGLE_SERVO_TC_DATA::ID as u8,
+            seq_ack: headers[1].sequence,
+            ..Default::default()
+        }))]);
+        assert_eq!(log.records()[0].reply, TcReply::Pending);
+        assert!(matches!(log.records()[1].reply, TcReply::Ack { .. }));
+        assert_eq!(tracker.lock().pending(&headers[1], &wiggle()), None);
+
+        // a late reply still updates the outcome
+        log.records[0].reply = TcReply::Timeout;
+        log.process_replies([&reply(MavMessage::ACK_TM(ACK_TM_DATA {
+            recv_msgid: WIGGLE_SERVO_TC_DATA::ID as u8,
+            seq_ack: headers[0].sequence,
+            ..Default::default()
+        }))]);
+        assert!(matches!(log.records()[0].reply, TcReply::Ack { .. }));
+
+        let delivered: Vec<_> = log.take_deliveries().iter().map(|d| d.pending).collect();
+        assert_eq!(delivered, vec![pending[1], pending[0]]);
+    }
+
+    #[test]
+    fn outcomes_are_delivered_to_the_originator() {
+        let (mut log, tracker) = new_log();
+        let source = TcSource {
+            pane: Some(PaneId::new(3)),
+            label: "Command: Ignition".to_owned(),
+        };
+        let mut replied = MavHeader::default();
+        let timeout = ReplyTimeout::default();
+        let pending = tracker.lock().send(&mut replied, &wiggle(), timeout);
+        log.record(source.clone(), replied, wiggle(), None);
+        let mut lost = MavHeader::default();
+        let timed_out = tracker
+            .lock()
+            .send(&mut lost, &wiggle(), ReplyTimeout(Duration::ZERO));
+        log.record(source.clone(), lost, wiggle(), None);
+        let mut offline = MavHeader::default();
+        let not_sent = tracker.lock().send(&mut offline, &wiggle(), timeout);
+        log.record(source.clone(), offline, wiggle(), Some("offline".into()));
+        // not waited for by its originator
+        let untracked = MavHeader {
+            sequence: 200,
+            ..Default::default()
+        };
+        log.record("test", untracked, wiggle(), None);
+
+        // the same reply from another system is not delivered
+        let nack = NACK_TM_DATA {
+            recv_msgid: WIGGLE_SERVO_TC_DATA::ID as u8,
+            seq_ack: replied.sequence,
+            err_id: 4,
+            ..Default::default()
+        };
+        let other_system = MavHeader {
+            system_id: 9,
+            ..Default::default()
+        };
+        let from_other =
+            TimedMessage::just_received(other_system, MavMessage::NACK_TM(nack.clone()));
+        log.process_replies([&from_other]);
+        std::thread::sleep(Duration::from_millis(1));
+        log.check_timeouts();
+        log.process_replies([&reply(MavMessage::NACK_TM(nack))]);
+
+        let deliveries = log.take_deliveries();
+        let outcomes: Vec<_> = deliveries
+            .iter()
+            .map(|d| (d.pending, d.reply.clone()))
+            .collect();
+        assert_eq!(outcomes.len(), 3);
+        assert_eq!(
+            outcomes[0],
+            (not_sent, TcReply::NotSent("offline".to_owned()))
+        );
+        assert_eq!(outcomes[1], (timed_out, TcReply::Timeout));
+        assert_eq!(outcomes[2].0, pending);
+        assert_eq!(outcomes[2].1.kind(), Some(ReplyKind::Nack { err_id: 4 }));
+        assert!(deliveries.iter().all(|d| d.source == source));
+        assert!(log.take_deliveries().is_empty());
+    }
+
+    #[test]
+    fn records_keep_the_originating_pane() {
+        let (mut log, _) = new_log();
+        let source = TcSource {
+            pane: Some(PaneId::new(7)),
+            label: "Command: Ignition".to_owned(),
+        };
+        log.record(source.clone(), MavHeader::default(), wiggle(), None);
+        log.record("Command Switch", MavHeader::default(), wiggle(), None);
+        assert_eq!(log.records()[0].source, source);
+        assert_eq!(log.records()[1].source.pane, None);
+        assert_eq!(log.records()[1].source.label, "Command Switch");
+    }
+
+    #[test]
+    fn records_keep_their_id_when_the_oldest_are_dropped() {
+        let (mut log, _) = new_log();
+        for _ in 0..MAX_LOG_LEN + 2 {
+            log.record("test", MavHeader::default(), wiggle(), None);
+        }
+        assert_eq!(log.records().len(), MAX_LOG_LEN);
+        assert_eq!(log.records().front().map(|r| r.id), Some(2));
+        assert!(log.get(1).is_none());
+        assert_eq!(log.get(7).map(|r| r.id), Some(7));
+        assert!(log.get(MAX_LOG_LEN as u64 + 2).is_none());
+    }
+}
@@ -0,0 +1,341 @@
+//! Correlation of the telecommands with their replies.
+//!
+//! Every command is stamped with a sequence number when it is sent, and the
+//! receiver echoes it in the `seq_ack` field of its ACK, NACK or WACK together
+//! with the id of the acknowledged message. Matching both, and the system the
+//! reply comes from, tells apart the outstanding commands of the same type,
+//! e.g. the same command sent to two different valves. Originators keep the
+//! [`PendingReply`] of every command they are waiting for, and the
+//! [`TcLog`](super::TcLog) delivers to them its reply or timeout.
+//!
+//! Sequence numbers are a single byte, so they wrap around every 256 commands.
+//! The [`ReplyTracker`] keeps the commands waiting for a reply until it
+//! arrives or they time out, and a new command skips the sequence numbers
+//! still outstanding for its system and message.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
+
+use egui::{DragValue, Response, Ui, Widget, mutex::Mutex};
+use serde::{Deserialize, Serialize};
+
+use crate::mavlink::{
+    ACK_TM_DATA, MavHeader, MavMessage, Message, NACK_TM_DATA, TimedMessage, WACK_TM_DATA,
+};
+
+/// Time waited for a reply, unless configured otherwise.
+pub const DEFAULT_REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Tracker of the commands sent by all the originators, so that outstanding
+/// commands never share a sequence number.
+pub(super) static REPLY_TRACKER: LazyLock<Arc<Mutex<ReplyTracker>>> =
+    LazyLock::new(|| Arc::new(Mutex::new(ReplyTracker::default())));
+
+/// System, message id and sequence number of an outstanding command.
+type ReplyKey = (u8, u32, u8);
+
+fn reply_key(header: &MavHeader, message: &MavMessage) -> ReplyKey {
+    (header.system_id, message.message_id(), header.sequence)
+}
+
+/// Sequence numbers handed out to the commands, and the commands still
+/// waiting for a reply.
+#[derive(Debug, Default)]
+pub struct ReplyTracker {
+    next_sequence: u8,
+    outstanding: BTreeMap<ReplyKey, PendingReply>,
+}
+
+impl ReplyTracker {
+    /// Stamps the header of the command about to be sent, and starts waiting
+    /// for its reply for up to `timeout`.
+    pub fn send(
+        &mut self,
+        header: &mut MavHeader,
+        message: &MavMessage,
+        timeout: ReplyTimeout,
+    ) -> PendingReply {
+        // drop the commands whose originators stopped waiting, also if the log
+        // never saw them
+        self.outstanding.retain(|_, pending| !pending.is_expired());
+        self.stamp(header);
+        // past 256 outstanding commands one of them is replaced
+        for _ in 0..u8::MAX {
+            if !self.outstanding.contains_key(&reply_key(header, message)) {
+                break;
+            }
+            self.stamp(header);
+        }
+        let pending = PendingReply {
+            system_id: header.system_id,
+            msg_id: message.message_id(),
+            sequence: header.sequence,
+            sent_at: Instant::now(),
+            timeout: timeout.0,
+        };
+        self.outstanding.insert(reply_key(header, message), pending);
+        pending
+    }
+
+    /// The command sent with the given header, if its originator waits for
+    /// the reply.
+    pub fn pending(&self, header: &MavHeader, message: &MavMessage) -> Option<PendingReply> {
+        self.outstanding.get(&reply_key(header, message)).copied()
+    }
+
+    /// Stops tracking the command, once replied or timed out, freeing its
+    /// sequence number.
+    pub fn forget(&mut self, pending: &PendingReply) {
+        self.outstanding.remove(&pending.key());
+    }
+
+    fn stamp(&mut self, header: &mut MavHeader) {
+        header.sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+    }
+}
+
+/// Outcome reported by the receiver of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyKind {
+    Ack,
+    Nack { err_id: u16 },
+    Wack { err_id: u16 },
+}
+
+impl ReplyKind {
+    /// Whether the command was not accepted.
+    pub fn is_rejection(&self) -> bool {
+        !matches!(self, Self::Ack)
+    }
+}
+
+impl std::fmt::Display for ReplyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ack => write!(f, "ACK"),
+            Self::Nack { err_id } => write!(f, "NACK (error {err_id})"),
+            Self::Wack { err_id } => write!(f, "WACK (error {err_id})"),
+        }
+    }
+}
+
+/// ACK, NACK or WACK received from the system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reply {
+    /// System that sent the reply
+    pub system_id: u8,
+    /// Id of the acknowledged message
+    pub recv_msgid: u8,
+    /// Sequence number of the acknowledged message
+    pub seq_ack: u8,
+    pub kind: ReplyKind,
+}
+
+impl Reply {
+    /// Parses the message, if it is a reply to a command.
+    pub fn parse(message: &TimedMessage) -> Option<Self> {
+        let (recv_msgid, seq_ack, kind) = match &message.message {
+            MavMessage::ACK_TM(ACK_TM_DATA {
+                recv_msgid,
+                seq_ack,
+                ..
+            }) => (*recv_msgid, *seq_ack, ReplyKind::Ack),
+            MavMessage::NACK_TM(NACK_TM_DATA {
+                recv_msgid,
+                seq_ack,
+                err_id,
+                ..
+            }) => (*recv_msgid, *seq_ack, ReplyKind::Nack { err_id: *err_id }),
+            MavMessage::WACK_TM(WACK_TM_DATA {
+                recv_msgid,
+                seq_ack,
+                err_id,
+                ..
+            }) => (*recv_msgid, *seq_ack, ReplyKind::Wack { err_id: *err_id }),
+            _ => return None,
+        };
+        Some(Self {
+            system_id: message.system_id,
+            recv_msgid,
+            seq_ack,
+            kind,
+        })
+    }
+
+    /// Whether this is the reply to the command sent with the given header.
+    pub fn acknowledges(&self, header: &MavHeader, message: &MavMessage) -> bool {
+        self.system_id == header.system_id
+            && self.recv_msgid == message.message_id() as u8
+            && self.seq_ack == header.sequence
+    }
+}
+
+/// Command waiting for its reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingReply {
+    system_id: u8,
+    msg_id: u32,
+    sequence: u8,
+    sent_at: Instant,
+    timeout: Duration,
+}
+
+impl PendingReply {
+    /// Stamps the header of the command about to be sent, and starts waiting
+    /// for its reply for up to `timeout`. The reply or the timeout is then
+    /// delivered to the originator of the command.
+    pub fn send(header: &mut MavHeader, message: &MavMessage, timeout: ReplyTimeout) -> Self {
+        REPLY_TRACKER.lock().send(header, message, timeout)
+    }
+
+    fn key(&self) -> ReplyKey {
+        (self.system_id, self.msg_id, self.sequence)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.sent_at.elapsed()
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.elapsed() > self.timeout
+    }
+}
+
+/// Time to wait for the reply to a command before considering it lost.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReplyTimeout(pub Duration);
+
+impl Default for ReplyTimeout {
+    fn default() -> Self {
+        Self(DEFAULT_REPLY_TIMEOUT)
+    }
+}
+
+impl Widget for &mut ReplyTimeout {
+    fn ui(self, ui: &mut Ui) -> Response {
+        ui.horizontal(|ui| {
+            let label = ui.label("Reply timeout:");
+            let mut secs = self.0.as_secs_f32();
+            let response = DragValue::new(&mut secs)
+                .speed(0.1)
+                .range(0.5..=60.0)
+                .fixed_decimals(1)
+                .suffix(" s")
+                .ui(ui)
+                .labelled_by(label.id)
+                .on_hover_text("Time to wait for the ACK, NACK or WACK of a command");
+            self.0 = Duration::from_secs_f32(secs);
+            response
+        })
+        .inner
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mavlink::{GET_VALVE_INFO_TC_DATA, MessageData, WIGGLE_SERVO_TC_DATA};
+
+    fn wiggle() -> MavMessage {
+        MavMessage::WIGGLE_SERVO_TC(WIGGLE_SERVO_TC_DATA::default())
+    }
+
+    fn reply_from(system_id: u8, message: MavMessage) -> Reply {
+        let header = MavHeader {
+            system_id,
+            ..Default::default()
+        };
+        Reply::parse(&TimedMessage::just_received(header, message)).unwrap()
+    }
+
+    #[test]
+    fn replies_are_told_apart_by_sequence_and_system() {
+        let mut tracker = ReplyTracker::default();
+        let timeout = ReplyTimeout::default();
+        let mut first = MavHeader::default();
+        tracker.send(&mut first, &wiggle(), timeout);
+        let mut second = MavHeader::default();
+        tracker.send(&mut second, &wiggle(), timeout);
+        assert_ne!(first.sequence, second.sequence);
+
+        let nack = reply_from(
+            0,
+            MavMessage::NACK_TM(NACK_TM_DATA {
+                recv_msgid: WIGGLE_SERVO_TC_DATA::ID as u8,
+                seq_ack: second.sequence,
+                err_id: 2,
+                ..Default::default()
+            }),
+        );
+        assert!(!nack.acknowledges(&first, &wiggle()));
+        assert!(nack.acknowledges(&second, &wiggle()));
+        assert_eq!(nack.kind, ReplyKind::Nack { err_id: 2 });
+
+        // the same reply from another system
+        let other_system = MavHeader {
+            system_id: 1,
+            ..second
+        };
+        assert!(!nack.acknowledges(&other_system, &wiggle()));
+
+        // replies to other messages are ignored
+        let ack = reply_from(
+            0,
+            MavMessage::ACK_TM(ACK_TM_DATA {
+                recv_msgid: WIGGLE_SERVO_TC_DATA::ID as u8 + 1,
+                seq_ack: first.sequence,
+                ..Default::default()
+            }),
+        );
+        assert!(!ack.acknowledges(&first, &wiggle()));
+    }
+
+    #[test]
+    fn commands_are_tracked_until_forgotten() {
+        let mut tracker = ReplyTracker::default();
+        let message = MavMessage::GET_VALVE_INFO_TC(GET_VALVE_INFO_TC_DATA::default());
+        let mut header = MavHeader::default();
+        let timeout = ReplyTimeout(Duration::from_secs(7));
+        let pending = tracker.send(&mut header, &message, timeout);
+        assert!(!pending.is_expired());
+        assert_eq!(pending.timeout(), timeout.0);
+        assert_eq!(tracker.pending(&header, &wiggle()), None);
+        assert_eq!(tracker.pending(&header, &message), Some(pending));
+        tracker.forget(&pending);
+        assert_eq!(tracker.pending(&header, &message), None);
+
+        let expired = tracker.send(&mut header, &message, ReplyTimeout(Duration::ZERO));
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(expired.is_expired());
+    }
+
+    #[test]
+    fn outstanding_sequences_are_skipped_on_wrap_around() {
+        let mut tracker = ReplyTracker::default();
+        let mut header = MavHeader::default();
+        let timeout = ReplyTimeout(Duration::from_secs(60));
+        let first = tracker.send(&mut header, &wiggle(), timeout);
+        let first_header = header;
+        for _ in 0..u8::MAX {
+            let pending = tracker.send(&mut header, &wiggle(), timeout);
+            assert_ne!(pending.sequence, first.sequence);
+        }
+        assert_eq!(tracker.pending(&first_header, &wiggle()), Some(first));
+
+        // the sequence is free again once the command is replied, and it is
+        // the only one
+        tracker.forget(&first);
+        let reused = tracker.send(&mut header, &wiggle(), timeout);
+        assert_eq!(reused.sequence, first.sequence);
+    }
+}
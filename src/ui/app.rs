@@ -2,11 +2,6 @@ use eframe::CreationContext;
 use egui::{Button, Key, Modifiers, Sides, Stroke};
 use egui_tiles::{Behavior, Container, Linear, LinearDir, Tile, TileId, Tiles, Tree};
 use serde::{Deserialize, Serialize};
-#[cfg(feature = "conrig")]
-use skyward_mavlink::{
-    mavlink::MessageData,
-    orion::{ACK_TM_DATA, NACK_TM_DATA},
-};
 use std::{
     fs,
     path::{Path, PathBuf},
@@ -16,9 +11,12 @@ use tracing::{debug, error, trace, warn};
 
 use crate::{
     APP_NAME,
+    alarms::{AlarmEngineExt, AlarmRule},
+    commands::{COMMAND_LIBRARY, CommandLibrary, LIBRARY_FILE},
+    derived::{DERIVED_CHANNELS, DerivedChannel},
     error::ErrInstrument,
-    mavlink::{MavHeader, MavMessage},
     message_broker::{ConnectionConfig, MessageBroker, MessageBundle},
+    tc_log::{TcLogExt, TcSource},
     ui::shortcuts::ShortcutHandlerExt,
     utils::id::PaneId,
 };
@@ -30,9 +28,14 @@ use super::{
     persistency::LayoutManager,
     utils::maximized_pane_ui,
     widget_gallery::WidgetGallery,
-    widgets::ReceptionLed,
-    windows::{ConnectionsWindow, LayoutManagerWindow},
+    widgets::{AlarmBanner, ReceptionLed},
+    windows::{
+        AlarmRulesWindow, CommandLibraryWindow, ConnectionsWindow, DerivedChannelsWindow,
+        LayoutManagerWindow,
+    },
 };
+#[cfg(feature = "conrig")]
+use crate::alarms::{AlarmEvent, AlarmEventKind, Severity};
 
 static LAYOUTS_DIR: &str = "layouts";
 
@@ -49,13 +52,16 @@ pub struct App {
     widget_gallery: WidgetGallery,
     sources_window: ConnectionsWindow,
     layout_manager_window: LayoutManagerWindow,
+    derived_channels_window: DerivedChannelsWindow,
+    alarm_rules_window: AlarmRulesWindow,
+    command_library_window: CommandLibraryWindow,
 }
 
 // An app must implement the `App` trait to define how the ui is built
 impl eframe::App for App {
     // The update function is called each time the UI needs repainting!
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.process_incoming_messages();
+        self.process_incoming_messages(ctx);
 
         // Get the id of the hovered pane, in order to apply actions to it
         let hovered_pane = self.behavior.tile_id_hovered;
@@ -212,6 +218,17 @@ impl eframe::App for App {
             < Duration::from_millis(100);
         let reception_frequency = self.message_broker.reception_frequency();
 
+        // Show a banner at the top of the screen while any alarm is annunciated
+        {
+            let engine = ctx.alarms();
+            let mut engine = engine.lock();
+            if !engine.annunciated().is_empty() {
+                egui::TopBottomPanel::top("alarm_banner")
+                    .frame(egui::Frame::NONE)
+                    .show(ctx, |ui| ui.add(AlarmBanner::new(&mut engine)));
+            }
+        }
+
         // Show a panel at the bottom of the screen with few global controls
         egui::TopBottomPanel::bottom("bottom_control").show(ctx, |ui| {
             // Horizontal belt of controls
@@ -251,6 +268,45 @@ impl eframe::App for App {
                                 .toggle_open_state(&self.layout_manager);
                         }
 
+                        // Derived channels button
+                        if ui
+                            .add(
+                                Button::new("Derived 🧮")
+                                    .stroke(Stroke::NONE)
+                                    .corner_radius(0),
+                            )
+                            .on_hover_text("Open the Derived Channels editor")
+                            .clicked()
+                        {
+                            self.derived_channels_window.toggle_open_state();
+                        }
+
+                        // Alarm rules button
+                        if ui
+                            .add(
+                                Button::new("Alarms 🔔")
+                                    .stroke(Stroke::NONE)
+                                    .corner_radius(0),
+                            )
+                            .on_hover_text("Open the Alarm Rules editor")
+                            .clicked()
+                        {
+                            self.alarm_rules_window.toggle_open_state();
+                        }
+
+                        // Command library button
+                        if ui
+                            .add(
+                                Button::new("Library 📚")
+                                    .stroke(Stroke::NONE)
+                                    .corner_radius(0),
+                            )
+                            .on_hover_text("Open the Command Library editor")
+                            .clicked()
+                        {
+                            self.command_library_window.toggle_open_state();
+                        }
+
                         #[cfg(feature = "conrig")]
                         {
                             // Command Shortcuts button
@@ -323,6 +379,11 @@ impl eframe::App for App {
 
         self.layout_manager_window
             .show(ctx, &mut self.layout_manager, &mut self.state);
+        self.derived_channels_window
+            .show(ctx, &mut self.state.derived_channels);
+        self.alarm_rules_window
+            .show(ctx, &mut self.state.alarm_rules);
+        self.command_library_window.show(ctx);
         if let Some(action) = self.widget_gallery.show(ctx) {
             debug!("Widget gallery returned action {action:?}");
             self.behavior.action = Some(action);
@@ -383,6 +444,11 @@ impl App {
                 });
         }
 
+        // Load the command library, shared by all the layouts
+        if let Some(dir) = eframe::storage_dir(APP_NAME) {
+            *COMMAND_LIBRARY.write() = CommandLibrary::load(dir.join(LIBRARY_FILE));
+        }
+
         let mut message_broker = MessageBroker::new(ctx.egui_ctx.clone());
 
         // Start connection if configured
@@ -400,16 +466,36 @@ impl App {
             message_bundle: MessageBundle::default(),
             sources_window: ConnectionsWindow::default(),
             layout_manager_window: LayoutManagerWindow::default(),
+            derived_channels_window: DerivedChannelsWindow::default(),
+            alarm_rules_window: AlarmRulesWindow::default(),
+            command_library_window: CommandLibraryWindow::default(),
         }
     }
 
     /// Retrieves new messages from the message broker and dispatches them to the panes.
     #[profiling::function]
-    fn process_incoming_messages(&mut self) {
+    fn process_incoming_messages(&mut self, ctx: &egui::Context) {
         let start = Instant::now();
 
+        // Keep the derived channels and the alarms in sync with the current layout
+        DERIVED_CHANNELS.write().sync(&self.state.derived_channels);
+        let alarms = ctx.alarms();
+        {
+            let mut alarms = alarms.lock();
+            alarms.sync(&self.state.alarm_rules);
+            if alarms.check_timeouts() {
+                ctx.request_repaint_after(Duration::from_secs(1));
+            }
+        }
+        let tc_log = ctx.tc_log();
+        if tc_log.lock().check_timeouts() {
+            ctx.request_repaint_after(Duration::from_millis(500));
+        }
+
         self.message_broker
             .process_incoming_messages(&mut self.message_bundle);
+        tc_log.lock().process_replies(self.message_bundle.iter());
+        self.deliver_replies(ctx);
 
         // Skip updating the panes if there are no messages
         let count = self.message_bundle.count();
@@ -417,6 +503,8 @@ impl App {
             return;
         }
 
+        alarms.lock().process(self.message_bundle.iter());
+
         debug!(
             "Receiving {count} messages from message broker took {:?}",
             start.elapsed()
@@ -441,40 +529,69 @@ impl App {
             start.elapsed()
         );
 
-        // Handle acknowledgements in the command switch window
-        #[cfg(feature = "conrig")]
-        self.state.command_switch_window.handle_acknowledgements(
-            self.message_bundle
-                .get(&[ACK_TM_DATA::ID, NACK_TM_DATA::ID])
-                .iter()
-                .map(|m| &m.message)
-                .collect(),
-        );
-
         // Reset the message bundle after processing
         self.message_bundle.reset();
     }
 
+    /// Delivers the outcome of the commands, once replied or timed out, to the
+    /// panes and windows that sent them.
+    fn deliver_replies(&mut self, ctx: &egui::Context) {
+        let deliveries = ctx.tc_log().lock().take_deliveries();
+        for delivery in deliveries {
+            let Some(pane_id) = delivery.source.pane else {
+                // commands sent outside of the layout come from the command switch
+                #[cfg(feature = "conrig")]
+                {
+                    let rejected = self
+                        .state
+                        .command_switch_window
+                        .handle_reply(&delivery.pending, &delivery.reply);
+                    if let Some((name, reply)) = rejected {
+                        ctx.alarms().lock().record(
+                            AlarmEvent::new(AlarmEventKind::Nack, Severity::Warning, name)
+                                .with_value(reply.to_string()),
+                        );
+                    }
+                }
+                continue;
+            };
+            let pane =
+                self.state
+                    .panes_tree
+                    .tiles
+                    .iter_mut()
+                    .find_map(|(tile_id, tile)| match tile {
+                        Tile::Pane(pane) if PaneId::new(tile_id) == pane_id => Some(pane),
+                        _ => None,
+                    });
+            if let Some(pane) = pane {
+                pane.handle_reply(&delivery.pending, &delivery.reply);
+            }
+        }
+    }
+
     /// Sends outgoing messages from the panes to the message broker.
     #[profiling::function]
     fn process_outgoing_messages(&mut self) {
-        let mut outgoing: Vec<(MavHeader, MavMessage)> = self
-            .state
-            .panes_tree
-            .tiles
-            .iter_mut()
-            .filter_map(|(_, tile)| {
-                if let Tile::Pane(pane) = tile {
-                    Some(pane.drain_outgoing_messages())
-                } else {
-                    None
-                }
-            })
-            .flatten()
-            .collect();
+        for (tile_id, tile) in self.state.panes_tree.tiles.iter_mut() {
+            let Tile::Pane(pane) = tile else { continue };
+            let outgoing = pane.drain_outgoing_messages();
+            if outgoing.is_empty() {
+                continue;
+            }
+            // the tile identifies the pane, also when restored from a layout
+            let source = TcSource {
+                pane: Some(PaneId::new(tile_id)),
+                label: pane.source_name(),
+            };
+            self.message_broker
+                .process_outgoing_messages(source, outgoing);
+        }
         #[cfg(feature = "conrig")]
-        outgoing.extend(self.state.command_switch_window.consume_messages_to_send());
-        self.message_broker.process_outgoing_messages(outgoing);
+        self.message_broker.process_outgoing_messages(
+            "Command Switch".into(),
+            self.state.command_switch_window.consume_messages_to_send(),
+        );
     }
 }
 
@@ -488,6 +605,10 @@ pub struct AppConfig {
 pub struct AppState {
     pub panes_tree: Tree<Pane>,
     pub next_pane_id: PaneId,
+    #[serde(default)]
+    pub derived_channels: Vec<DerivedChannel>,
+    #[serde(default)]
+    pub alarm_rules: Vec<AlarmRule>,
     #[cfg(feature = "conrig")]
     pub command_switch_window: CommandSwitchWindow,
 }
@@ -502,6 +623,8 @@ impl Default for AppState {
         Self {
             panes_tree,
             next_pane_id,
+            derived_channels: Vec::new(),
+            alarm_rules: Vec::new(),
             #[cfg(feature = "conrig")]
             command_switch_window: CommandSwitchWindow::default(),
         }
@@ -1,18 +1,28 @@
+mod alarms;
+mod checklist;
 mod command;
 mod default;
 mod messages_viewer;
 mod pid_drawing_tool;
 mod plot;
+mod script;
+mod state_machine;
+mod tc_log;
+mod timeline;
+mod timer;
+mod trajectory;
 mod valve_control;
 
 use egui::Ui;
 use enum_dispatch::enum_dispatch;
 use serde::{Deserialize, Serialize};
 use skyward_mavlink::mavlink::MavHeader;
+use strum::EnumMessage;
 use strum_macros::{self, EnumIter, EnumMessage};
 
 use crate::{
     mavlink::{MavMessage, TimedMessage},
+    tc_log::{PendingReply, TcReply},
     utils::id::PaneId,
 };
 
@@ -27,6 +37,16 @@ impl Pane {
     pub fn boxed(pane: PaneKind) -> Box<Self> {
         Box::new(Self { pane })
     }
+
+    /// Name of the pane as the source of the commands it sends: its kind,
+    /// followed by its label if any, e.g. `Command: Ignition`.
+    pub fn source_name(&self) -> String {
+        let kind = self.pane.get_message().unwrap_or("Pane");
+        match self.source_label() {
+            Some(label) => format!("{kind}: {label}"),
+            None => kind.to_owned(),
+        }
+    }
 }
 
 #[enum_dispatch(PaneKind)]
@@ -53,6 +73,16 @@ pub trait PaneBehavior {
         Vec::new()
     }
 
+    /// Handles the outcome of a command sent by the pane while waiting for its
+    /// reply, delivered once the reply arrives or the command times out.
+    fn handle_reply(&mut self, _pending: &PendingReply, _reply: &TcReply) {}
+
+    /// Returns the label telling the pane apart from the others of its kind as
+    /// the source of the commands it sends, if any.
+    fn source_label(&self) -> Option<String> {
+        None
+    }
+
     /// Initializes the pane with the given pane ID. This is called when the pane is inserted into the layout.
     fn init(&mut self, _pane_id: PaneId) {}
 }
@@ -78,6 +108,14 @@ impl PaneBehavior for Pane {
         self.pane.drain_outgoing_messages()
     }
 
+    fn handle_reply(&mut self, pending: &PendingReply, reply: &TcReply) {
+        self.pane.handle_reply(pending, reply);
+    }
+
+    fn source_label(&self) -> Option<String> {
+        self.pane.source_label()
+    }
+
     fn init(&mut self, pane_id: PaneId) {
         self.pane.init(pane_id);
     }
@@ -98,11 +136,35 @@ pub enum PaneKind {
     #[strum(message = "Plot 2D")]
     Plot2D(plot::Plot2DPane),
 
+    #[strum(message = "Trajectory 3D")]
+    Trajectory3D(trajectory::Trajectory3DPane),
+
     #[strum(message = "Pid")]
     Pid(pid_drawing_tool::PidPane),
 
     #[strum(message = "Valve Control")]
     ValveControl(valve_control::ValveControlPane),
+
+    #[strum(message = "Alarms")]
+    Alarms(alarms::AlarmsPane),
+
+    #[strum(message = "State Timeline")]
+    Timeline(timeline::TimelinePane),
+
+    #[strum(message = "State Machine")]
+    StateMachine(state_machine::StateMachinePane),
+
+    #[strum(message = "Telecommand Log")]
+    TcLog(tc_log::TcLogPane),
+
+    #[strum(message = "Script")]
+    Script(script::ScriptPane),
+
+    #[strum(message = "Checklist")]
+    Checklist(checklist::ChecklistPane),
+
+    #[strum(message = "Timer")]
+    Timer(timer::TimerPane),
 }
 
 impl Default for PaneKind {
@@ -0,0 +1,329 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    time::Duration,
+};
+
+use egui::{Color32, ComboBox, Grid, RichText, Ui};
+use egui_extras::{Column, TableBuilder};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{
+    alarms::{AlarmEngine, AlarmEngineExt, AlarmEvent, AlarmEventKind, AlarmState, Severity},
+    ui::panes::{PaneBehavior, PaneResponse},
+};
+
+/// Durations offered when shelving an alarm, in minutes.
+const SHELVE_MINUTES: [u64; 3] = [5, 15, 60];
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct AlarmsPane {
+    /// Whether the alarms in normal state are listed too
+    show_normal: bool,
+    /// Filters and ordering of the event log
+    #[serde(default)]
+    log: EventLogSettings,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+struct EventLogSettings {
+    /// Only events whose source or field contain this text are shown
+    search: String,
+    min_severity: Severity,
+    hidden_kinds: HashSet<AlarmEventKind>,
+    unacknowledged_only: bool,
+    sort_by: EventColumn,
+    /// Sort in ascending order, by default the newest events come first
+    ascending: bool,
+}
+
+impl EventLogSettings {
+    fn matches(&self, event: &AlarmEvent) -> bool {
+        let search = self.search.to_lowercase();
+        event.severity >= self.min_severity
+            && !self.hidden_kinds.contains(&event.kind)
+            && (!self.unacknowledged_only || !event.acknowledged)
+            && (search.is_empty()
+                || event.source.to_lowercase().contains(&search)
+                || event.field.to_lowercase().contains(&search))
+    }
+
+    /// Returns the indices of the events to show, in display order.
+    fn visible_events(&self, history: &VecDeque<AlarmEvent>) -> Vec<usize> {
+        let mut indices: Vec<usize> = history
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| self.matches(event))
+            .map(|(i, _)| i)
+            .collect();
+        // The history is already ordered by time, and the sort is stable
+        match self.sort_by {
+            EventColumn::Time => {}
+            EventColumn::Severity => indices.sort_by_key(|&i| history[i].severity),
+            EventColumn::Source => {
+                indices.sort_by(|&a, &b| history[a].source.cmp(&history[b].source))
+            }
+            EventColumn::Field => indices.sort_by(|&a, &b| history[a].field.cmp(&history[b].field)),
+            EventColumn::Event => indices.sort_by_key(|&i| history[i].kind.to_string()),
+        }
+        if !self.ascending {
+            indices.reverse();
+        }
+        indices
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+enum EventColumn {
+    #[default]
+    Time,
+    Severity,
+    Source,
+    Field,
+    Event,
+}
+
+impl PaneBehavior for AlarmsPane {
+    #[profiling::function]
+    fn ui(&mut self, ui: &mut Ui) -> PaneResponse {
+        let mut pane_response = PaneResponse::default();
+
+        let engine = ui.ctx().alarms();
+        let res = ui
+            .scope(|ui| {
+                let mut engine = engine.lock();
+                ui.horizontal(|ui| {
+                    ui.heading("Alarms");
+                    ui.checkbox(&mut self.show_normal, "Show normal");
+                });
+                ui.separator();
+                alarms_table(ui, &mut engine, self.show_normal);
+                ui.separator();
+                ui.heading("Events");
+                event_log_filters(ui, &mut self.log, &mut engine);
+                event_log_table(ui, &mut self.log, &mut engine);
+            })
+            .response;
+
+        if res.interact(egui::Sense::click_and_drag()).dragged() {
+            pane_response.set_drag_started();
+        }
+
+        pane_response
+    }
+}
+
+fn alarms_table(ui: &mut Ui, engine: &mut AlarmEngine, show_normal: bool) {
+    let mut to_ack = None;
+    let mut to_shelve = None;
+    let mut to_unshelve = None;
+
+    let alarms: Vec<_> = engine
+        .alarms()
+        .filter(|(_, _, state)| show_normal || state.is_annunciated() || state.is_shelved())
+        .collect();
+    if alarms.is_empty() {
+        ui.label("No active alarms");
+        return;
+    }
+
+    Grid::new(ui.auto_id_with("alarms_grid"))
+        .num_columns(7)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.strong("Severity");
+            ui.strong("Name");
+            ui.strong("Field");
+            ui.strong("Value");
+            ui.strong("Status");
+            ui.strong("Since");
+            ui.strong("Actions");
+            ui.end_row();
+
+            for (i, rule, state) in alarms {
+                ui.label(
+                    RichText::new(rule.severity.to_string())
+                        .strong()
+                        .color(rule.severity.color()),
+                );
+                ui.label(&rule.name);
+                ui.label(rule.field.as_ref().map(|f| f.name()).unwrap_or_default());
+                ui.label(state.last_value.as_deref().unwrap_or("N/A"));
+                ui.label(RichText::new(state.status()).color(status_color(state, ui)));
+                ui.label(
+                    state
+                        .raised_at
+                        .as_ref()
+                        .map(|t| t.strftime("%H:%M:%S").to_string())
+                        .unwrap_or_default(),
+                );
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!state.acknowledged, egui::Button::new("Ack"))
+                        .clicked()
+                    {
+                        to_ack = Some(i);
+                    }
+                    if state.is_shelved() {
+                        if ui.button("Unshelve").clicked() {
+                            to_unshelve = Some(i);
+                        }
+                    } else {
+                        ui.menu_button("Shelve", |ui| {
+                            for minutes in SHELVE_MINUTES {
+                                if ui.button(format!("{minutes} min")).clicked() {
+                                    to_shelve = Some((i, Duration::from_secs(minutes * 60)));
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
+                });
+                ui.end_row();
+            }
+        });
+
+    if let Some(i) = to_ack {
+        engine.acknowledge(i);
+    }
+    if let Some((i, duration)) = to_shelve {
+        engine.shelve(i, duration);
+    }
+    if let Some(i) = to_unshelve {
+        engine.unshelve(i);
+    }
+}
+
+fn event_log_filters(ui: &mut Ui, settings: &mut EventLogSettings, engine: &mut AlarmEngine) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Search:");
+        ui.add(egui::TextEdit::singleline(&mut settings.search).desired_width(120.0));
+        ComboBox::from_id_salt(ui.auto_id_with("min_severity"))
+            .selected_text(format!("≥ {}", settings.min_severity))
+            .show_ui(ui, |ui| {
+                for severity in Severity::iter() {
+                    ui.selectable_value(&mut settings.min_severity, severity, severity.to_string());
+                }
+            });
+        ui.menu_button("Events…", |ui| {
+            for kind in AlarmEventKind::iter() {
+                let mut visible = !settings.hidden_kinds.contains(&kind);
+                if ui.checkbox(&mut visible, kind.to_string()).changed() {
+                    if visible {
+                        settings.hidden_kinds.remove(&kind);
+                    } else {
+                        settings.hidden_kinds.insert(kind);
+                    }
+                }
+            }
+        });
+        ui.checkbox(&mut settings.unacknowledged_only, "Unacknowledged only");
+        if ui
+            .button("Clear")
+            .on_hover_text("Clear the event log")
+            .clicked()
+        {
+            engine.clear_history();
+        }
+    });
+}
+
+fn event_log_table(ui: &mut Ui, settings: &mut EventLogSettings, engine: &mut AlarmEngine) {
+    let visible = settings.visible_events(engine.history());
+    let mut to_ack = None;
+
+    let id = ui.id().with("alarm_event_log");
+    TableBuilder::new(ui)
+        .id_salt(id)
+        .striped(true)
+        .resizable(true)
+        .auto_shrink([false, true])
+        .column(Column::auto().at_least(90.0))
+        .column(Column::auto())
+        .column(Column::initial(120.0).clip(true))
+        .column(Column::initial(100.0).clip(true))
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::remainder())
+        .header(20.0, |mut header| {
+            for (label, column) in [
+                ("Time", Some(EventColumn::Time)),
+                ("Severity", Some(EventColumn::Severity)),
+                ("Source", Some(EventColumn::Source)),
+                ("Field", Some(EventColumn::Field)),
+                ("Value", None),
+                ("Event", Some(EventColumn::Event)),
+                ("Ack", None),
+            ] {
+                header.col(|ui| match column {
+                    Some(column) => sort_header(ui, label, column, settings),
+                    None => {
+                        ui.strong(label);
+                    }
+                });
+            }
+        })
+        .body(|body| {
+            body.rows(18.0, visible.len(), |mut row| {
+                let index = visible[row.index()];
+                let event = &engine.history()[index];
+                row.col(|ui| {
+                    ui.label(event.time.strftime("%H:%M:%S%.3f").to_string());
+                });
+                row.col(|ui| {
+                    ui.label(
+                        RichText::new(event.severity.to_string()).color(event.severity.color()),
+                    );
+                });
+                row.col(|ui| {
+                    ui.label(&event.source);
+                });
+                row.col(|ui| {
+                    ui.label(&event.field);
+                });
+                row.col(|ui| {
+                    ui.label(event.value.as_deref().unwrap_or_default());
+                });
+                row.col(|ui| {
+                    ui.label(event.kind.to_string());
+                });
+                row.col(|ui| {
+                    if event.acknowledged {
+                        ui.label("✔");
+                    } else if ui.small_button("Ack").clicked() {
+                        to_ack = Some(index);
+                    }
+                });
+            });
+        });
+
+    if let Some(index) = to_ack {
+        engine.acknowledge_event(index);
+    }
+}
+
+/// Column header that sorts the event log when clicked.
+fn sort_header(ui: &mut Ui, label: &str, column: EventColumn, settings: &mut EventLogSettings) {
+    let text = match (settings.sort_by == column, settings.ascending) {
+        (true, true) => format!("{label} ⏶"),
+        (true, false) => format!("{label} ⏷"),
+        (false, _) => label.to_owned(),
+    };
+    if ui.button(RichText::new(text).strong()).clicked() {
+        if settings.sort_by == column {
+            settings.ascending = !settings.ascending;
+        } else {
+            settings.sort_by = column;
+            settings.ascending = false;
+        }
+    }
+}
+
+fn status_color(state: &AlarmState, ui: &Ui) -> Color32 {
+    match (state.active, state.acknowledged) {
+        (true, false) => Color32::RED,
+        (true, true) | (false, false) => Color32::ORANGE,
+        (false, true) => ui.visuals().text_color(),
+    }
+}
@@ -0,0 +1,465 @@
+mod list;
+
+use std::{path::PathBuf, sync::Arc};
+
+use egui::{Button, Color32, Context, RichText, ScrollArea, TextEdit, Ui, mutex::Mutex};
+use egui_file::FileDialog;
+use jiff::Zoned;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    APP_NAME,
+    alarms::{AlarmEngineExt, AlarmEvent, AlarmEventKind, Severity},
+    commands::COMMAND_LIBRARY,
+    derived::condition::LatestValues,
+    error::ErrInstrument,
+    mavlink::{
+        MavHeader, MavMessage, TimedMessage, reflection::MapConvertible, stamp_current_time,
+    },
+    tc_log::{PendingReply, ReplyKind, ReplyTimeout, TcReply},
+    ui::{app::PaneResponse, widgets::CommandArming},
+    utils::id::PaneId,
+};
+
+use super::PaneBehavior;
+
+use list::{Checklist, ChecklistItem, ItemKind};
+
+/// Checklist read from a text file, whose items are signed off by the
+/// operator. Items can be verified by the telemetry or send a command of the
+/// library, and every sign-off is recorded in the event history and in the
+/// session log.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChecklistPane {
+    /// File the checklist is read from
+    path: Option<PathBuf>,
+    /// Name of the operator signing off the items
+    operator: String,
+
+    #[serde(skip)]
+    pane_id: Option<PaneId>,
+    /// Checklist read from the file, or the reason it could not be read
+    #[serde(skip)]
+    loaded: Option<Result<Checklist, String>>,
+    /// Progress of each item of the checklist, never restored from the layout
+    #[serde(skip)]
+    progress: Vec<ItemProgress>,
+    #[serde(skip)]
+    values: LatestValues,
+    /// Command item waiting for the confirmation to be sent, if critical
+    #[serde(skip)]
+    armed: Option<(usize, CommandArming)>,
+    #[serde(skip)]
+    to_send: Vec<(MavHeader, MavMessage)>,
+}
+
+impl PartialEq for ChecklistPane {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.operator == other.operator
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct ItemProgress {
+    sign_off: Option<SignOff>,
+    /// Last command sent by the item, with its outcome once delivered
+    command: Option<(PendingReply, Option<TcReply>)>,
+}
+
+#[derive(Clone, Debug)]
+struct SignOff {
+    operator: String,
+    time: Zoned,
+}
+
+impl PaneBehavior for ChecklistPane {
+    #[profiling::function]
+    fn ui(&mut self, ui: &mut Ui) -> PaneResponse {
+        let mut pane_response = PaneResponse::default();
+
+        if self.loaded.is_none() && self.path.is_some() {
+            self.load();
+        }
+
+        let res = ui
+            .scope(|ui| {
+                self.toolbar(ui);
+                ui.separator();
+                match &self.loaded {
+                    Some(Ok(_)) => self.items_ui(ui),
+                    Some(Err(e)) => {
+                        ui.label(RichText::new(e).color(Color32::RED));
+                    }
+                    None => {
+                        ui.label(RichText::new("Open a checklist to start").italics());
+                    }
+                }
+            })
+            .response;
+
+        if res.interact(egui::Sense::click_and_drag()).dragged() {
+            pane_response.set_drag_started();
+        }
+
+        self.show_file_dialog(ui.ctx());
+
+        pane_response
+    }
+
+    fn update(&mut self, messages: &[&TimedMessage]) {
+        for message in messages {
+            self.values.update(message);
+        }
+    }
+
+    fn get_message_subscriptions(&self) -> Box<dyn Iterator<Item = u32>> {
+        let Some(Ok(checklist)) = &self.loaded else {
+            return Box::new(None.into_iter());
+        };
+        Box::new(checklist.input_message_ids().into_iter())
+    }
+
+    fn drain_outgoing_messages(&mut self) -> Vec<(MavHeader, MavMessage)> {
+        self.to_send.drain(..).collect()
+    }
+
+    fn handle_reply(&mut self, pending: &PendingReply, reply: &TcReply) {
+        let commands = self.progress.iter_mut().filter_map(|p| p.command.as_mut());
+        for (_, outcome) in commands.filter(|(sent, _)| sent == pending) {
+            *outcome = Some(reply.clone());
+        }
+    }
+
+    fn source_label(&self) -> Option<String> {
+        self.path.is_some().then(|| self.name())
+    }
+
+    fn init(&mut self, pane_id: PaneId) {
+        self.pane_id = Some(pane_id);
+    }
+}
+
+impl ChecklistPane {
+    fn name(&self) -> String {
+        self.path
+            .as_ref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Checklist".to_owned())
+    }
+
+    /// Reads the checklist from the file, clearing the progress.
+    fn load(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        self.armed = None;
+        self.loaded = Some(
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("Unable to read {}: {}", path.display(), e))
+                .and_then(|text| Checklist::parse(&text).map_err(|e| e.to_string())),
+        );
+        let items = match &self.loaded {
+            Some(Ok(checklist)) => checklist.items.len(),
+            Some(Err(e)) => {
+                error!("Failed to load the checklist: {}", e);
+                0
+            }
+            None => 0,
+        };
+        self.progress = vec![ItemProgress::default(); items];
+    }
+
+    fn toolbar(&mut self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            if ui.button("📂 Open…").clicked() {
+                self.open_file_dialog(ui.ctx());
+            }
+            let signed_off = self.progress.iter().any(|p| p.sign_off.is_some());
+            if ui
+                .add_enabled(self.path.is_some(), Button::new("⟲ Reload"))
+                .on_hover_text("Read the checklist again from the file, clearing the sign-offs")
+                .clicked()
+            {
+                if signed_off {
+                    warn!("Checklist {} reloaded, sign-offs cleared", self.name());
+                }
+                self.load();
+            }
+            if let Some(path) = &self.path {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                ui.label(RichText::new(name).strong())
+                    .on_hover_text(path.display().to_string());
+            }
+            ui.separator();
+            let label = ui.label("Operator:");
+            ui.add(
+                TextEdit::singleline(&mut self.operator)
+                    .hint_text("name")
+                    .desired_width(100.0),
+            )
+            .labelled_by(label.id);
+        });
+    }
+
+    fn items_ui(&mut self, ui: &mut Ui) {
+        let Some(Ok(checklist)) = &mut self.loaded else {
+            return;
+        };
+
+        let total = checklist
+            .items
+            .iter()
+            .filter(|i| i.kind.needs_sign_off())
+            .count();
+        let done = self
+            .progress
+            .iter()
+            .filter(|p| p.sign_off.is_some())
+            .count();
+        ui.add(
+            egui::ProgressBar::new(done as f32 / total.max(1) as f32)
+                .text(format!("{done} / {total} signed off")),
+        );
+        if self.operator.trim().is_empty() {
+            ui.label(
+                RichText::new("Enter the operator name to sign off the items")
+                    .color(ui.visuals().warn_fg_color),
+            );
+        }
+
+        let mut actions = Vec::new();
+        ScrollArea::vertical()
+            .id_salt(ui.id().with("checklist_items"))
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                for (i, item) in checklist.items.iter_mut().enumerate() {
+                    let Some(progress) = self.progress.get(i) else {
+                        continue;
+                    };
+                    let armed = self.armed.as_mut().filter(|(a, _)| *a == i).map(|(_, a)| a);
+                    if let Some(action) =
+                        item_ui(ui, item, progress, &self.values, &self.operator, armed)
+                    {
+                        actions.push((i, action));
+                    }
+                }
+            });
+
+        for (i, action) in actions {
+            match action {
+                ItemAction::SignOff => self.sign_off(ui.ctx(), i),
+                ItemAction::Send => self.send_command(ui.ctx(), i, false),
+                ItemAction::Confirm => self.send_command(ui.ctx(), i, true),
+            }
+        }
+    }
+
+    fn item(&self, index: usize) -> Option<&ChecklistItem> {
+        match &self.loaded {
+            Some(Ok(checklist)) => checklist.items.get(index),
+            _ => None,
+        }
+    }
+
+    fn sign_off(&mut self, ctx: &Context, index: usize) {
+        let operator = self.operator.trim().to_owned();
+        let checklist = self.name();
+        let Some(item) = self.item(index) else {
+            return;
+        };
+        info!(
+            checklist = checklist.as_str(),
+            item = item.text.as_str(),
+            operator = operator.as_str(),
+            "Checklist item signed off"
+        );
+        let event = AlarmEvent {
+            field: item.text.clone(),
+            ..AlarmEvent::new(AlarmEventKind::SignedOff, Severity::Info, checklist)
+                .with_value(operator.clone())
+        };
+        ctx.alarms().lock().record(event);
+        if let Some(progress) = self.progress.get_mut(index) {
+            progress.sign_off = Some(SignOff {
+                operator,
+                time: Zoned::now(),
+            });
+        }
+    }
+
+    /// Sends the library command of the item, arming it first if critical,
+    /// unless `confirmed`.
+    fn send_command(&mut self, ctx: &Context, index: usize, confirmed: bool) {
+        let Some(ItemKind::Command(name)) = self.item(index).map(|i| &i.kind) else {
+            return;
+        };
+        let Some(definition) = COMMAND_LIBRARY.read().get(name).cloned() else {
+            warn!("Command {name} not found in the library");
+            return;
+        };
+        let Some(mut map) = definition.message else {
+            return;
+        };
+        if !confirmed {
+            let mut arming = CommandArming::default();
+            arming.criticality = definition.criticality;
+            if !arming.request(ctx, &definition.name) {
+                self.armed = Some((index, arming));
+                return;
+            }
+        }
+        self.armed = None;
+
+        info!("Sending {} from the checklist", definition.name);
+        stamp_current_time(&mut map);
+        let mut header = MavHeader {
+            system_id: definition.system_id,
+            ..Default::default()
+        };
+        let message = MavMessage::from_map(map).log_unwrap();
+        if let Some(progress) = self.progress.get_mut(index) {
+            let pending = PendingReply::send(&mut header, &message, ReplyTimeout::default());
+            progress.command = Some((pending, None));
+        }
+        self.to_send.push((header, message));
+    }
+
+    fn dialog_id(&self) -> egui::Id {
+        *(self.pane_id.unwrap_or(PaneId::from_str("checklist_pane")) / "load_file_dialog")
+    }
+
+    fn open_file_dialog(&self, ctx: &Context) {
+        // Create a new file dialog and store it in the memory
+        let initial_path = self.path.clone().or_else(|| eframe::storage_dir(APP_NAME));
+        let mut file_dialog = FileDialog::open_file(initial_path);
+        file_dialog.open();
+        let id = self.dialog_id();
+        ctx.memory_mut(|m| m.data.insert_temp(id, Arc::new(Mutex::new(file_dialog))));
+    }
+
+    fn show_file_dialog(&mut self, ctx: &Context) {
+        let id = self.dialog_id();
+        let Some(file_dialog) = ctx.memory_mut(|m| m.data.get_temp::<Arc<Mutex<FileDialog>>>(id))
+        else {
+            return;
+        };
+        let mut file_dialog = file_dialog.lock();
+        let selected = file_dialog.show(ctx).selected();
+        if let Some(path) = file_dialog.path().filter(|_| selected) {
+            self.path = Some(path.to_path_buf());
+            self.load();
+        }
+    }
+}
+
+enum ItemAction {
+    SignOff,
+    /// Send the command of the item
+    Send,
+    /// Send the armed command of the item
+    Confirm,
+}
+
+/// Shows an item with its status and controls, returning the action requested.
+fn item_ui(
+    ui: &mut Ui,
+    item: &mut ChecklistItem,
+    progress: &ItemProgress,
+    values: &LatestValues,
+    operator: &str,
+    armed: Option<&mut CommandArming>,
+) -> Option<ItemAction> {
+    if let ItemKind::Section = item.kind {
+        ui.add_space(4.0);
+        ui.label(RichText::new(&item.text).strong().size(15.0));
+        ui.separator();
+        return None;
+    }
+
+    let mut action = None;
+    // whether the item can be signed off, or the reason it cannot
+    let mut ready = Ok(());
+    ui.horizontal(|ui| {
+        match &progress.sign_off {
+            Some(sign_off) => {
+                ui.label(RichText::new("✔").color(Color32::GREEN))
+                    .on_hover_text(format!(
+                        "Signed off by {} at {}",
+                        sign_off.operator,
+                        sign_off.time.strftime("%H:%M:%S")
+                    ));
+            }
+            None => {
+                ui.label(RichText::new("☐").monospace());
+            }
+        }
+        ui.label(&item.text);
+
+        match &mut item.kind {
+            ItemKind::Section | ItemKind::Manual => {}
+            ItemKind::Verify(condition) => {
+                let met = condition.is_met(values);
+                let (text, color) = match met {
+                    Some(true) => ("met", Color32::GREEN),
+                    Some(false) => ("not met", Color32::RED),
+                    None => ("no data", ui.visuals().weak_text_color()),
+                };
+                ui.label(RichText::new(text).color(color))
+                    .on_hover_text(condition.source());
+                if met != Some(true) {
+                    ready = Err("The condition is not met");
+                }
+            }
+            ItemKind::Command(name) => {
+                let reply = progress.command.as_ref().map(|(_, reply)| reply);
+                let (text, color) = match reply {
+                    None => ("not sent".to_owned(), ui.visuals().weak_text_color()),
+                    Some(None) => ("waiting".to_owned(), ui.visuals().text_color()),
+                    Some(Some(reply)) => (reply.to_string(), reply.color()),
+                };
+                ui.label(RichText::new(text).color(color));
+                let acknowledged = reply.and_then(|r| r.as_ref()?.kind()) == Some(ReplyKind::Ack);
+                if !acknowledged {
+                    ready = Err("The command has not been acknowledged");
+                }
+                let in_library = COMMAND_LIBRARY.read().get(name).is_some();
+                if ui
+                    .add_enabled(in_library, Button::new("📤 Send"))
+                    .on_hover_text(format!("Send {name} from the command library"))
+                    .on_disabled_hover_text(format!("{name} is not in the command library"))
+                    .clicked()
+                {
+                    action = Some(ItemAction::Send);
+                }
+            }
+        }
+
+        if progress.sign_off.is_none() {
+            if operator.trim().is_empty() {
+                ready = Err("Enter the operator name first");
+            }
+            let response = ui.add_enabled(ready.is_ok(), Button::new("Sign off"));
+            let clicked = match ready {
+                Ok(()) => response.clicked(),
+                Err(reason) => {
+                    response.on_disabled_hover_text(reason);
+                    false
+                }
+            };
+            if clicked {
+                action = Some(ItemAction::SignOff);
+            }
+        }
+    });
+
+    let confirmed = match (&item.kind, armed) {
+        (ItemKind::Command(name), Some(arming)) => arming.show_armed(ui, name),
+        _ => false,
+    };
+    if confirmed {
+        action = Some(ItemAction::Confirm);
+    }
+    action
+}
@@ -0,0 +1,141 @@
+//! Text format of the checklists shown by the checklist pane.
+//!
+//! A checklist has one item per line, in the [line-based
+//! format](crate::utils::text) shared with the scripts:
+//!
+//! ```text
+//! # Pad operations
+//! section "Before filling"
+//! manual "Pad clear of personnel"
+//! verify "Oxidizer tank vented" GSE_TM.ox_tank_top_pressure < 1.5
+//! verify "Filling valve closed" GSE_TM.ox_filling_valve_state == 0
+//! command "Arm the igniter" arm_igniter
+//! ```
+//!
+//! - `section "TEXT"` starts a new group of items;
+//! - `manual "TEXT"` is checked by the operator;
+//! - `verify "TEXT" CONDITION` can be signed off only while the condition is
+//!   met, written with the syntax of the derived channels (see
+//!   [`condition`](crate::derived::condition));
+//! - `command "TEXT" NAME` sends the command of the library with the given
+//!   name, and can be signed off once acknowledged.
+
+use crate::{
+    derived::condition::{self, Condition},
+    utils::text::{LineError, parse_lines},
+};
+
+#[derive(Debug, Clone)]
+pub struct Checklist {
+    pub items: Vec<ChecklistItem>,
+}
+
+/// Item of a checklist, with the line it was read from.
+#[derive(Debug, Clone)]
+pub struct ChecklistItem {
+    pub line: usize,
+    pub text: String,
+    pub kind: ItemKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum ItemKind {
+    /// Title of the following items, not signed off
+    Section,
+    Manual,
+    /// Verified by a condition on the telemetry
+    Verify(Condition),
+    /// Name of the library command sent by the item
+    Command(String),
+}
+
+impl ItemKind {
+    pub fn needs_sign_off(&self) -> bool {
+        !matches!(self, Self::Section)
+    }
+}
+
+impl Checklist {
+    pub fn parse(text: &str) -> Result<Self, LineError> {
+        let items = parse_lines(text, parse_item)?
+            .into_iter()
+            .map(|(line, (text, kind))| ChecklistItem { line, text, kind })
+            .collect();
+        Ok(Self { items })
+    }
+
+    /// Ids of the messages read by the verified items.
+    pub fn input_message_ids(&self) -> Vec<u32> {
+        condition::input_message_ids(self.items.iter().filter_map(|item| match &item.kind {
+            ItemKind::Verify(condition) => Some(condition),
+            _ => None,
+        }))
+    }
+}
+
+/// Parses the text and the kind of an item.
+fn parse_item(line: &str) -> Result<(String, ItemKind), String> {
+    let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let (text, argument) = rest
+        .trim()
+        .strip_prefix('"')
+        .and_then(|rest| rest.split_once('"'))
+        .ok_or("Expected the text of the item in double quotes")?;
+    let argument = argument.trim();
+    let kind = match keyword {
+        "section" | "manual" if !argument.is_empty() => {
+            return Err(format!("Unexpected \"{argument}\" after the text"));
+        }
+        "section" => ItemKind::Section,
+        "manual" => ItemKind::Manual,
+        "verify" if argument.is_empty() => return Err("Missing the condition".to_owned()),
+        "verify" => {
+            ItemKind::Verify(Condition::parse_with_derived(argument).map_err(|e| e.to_string())?)
+        }
+        "command" if argument.is_empty() => return Err("Missing the command name".to_owned()),
+        "command" => ItemKind::Command(argument.to_owned()),
+        _ => return Err(format!("Unknown item \"{keyword}\"")),
+    };
+    Ok((text.trim().to_owned(), kind))
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_are_parsed() {
+        let items = Checklist::parse(
+            "# pad\n\
+             section \"Before filling\"\n\
+             \n\
+             manual \"Valve #2 closed\"  # walk around\n\
+             verify \"Always true\" 1 > 0\n\
+             command \"Vent\" open_vent",
+        )
+        .unwrap()
+        .items;
+        let lines: Vec<usize> = items.iter().map(|i| i.line).collect();
+        assert_eq!(lines, vec![2, 4, 5, 6]);
+        assert_eq!(items[1].text, "Valve #2 closed");
+        assert!(matches!(items[0].kind, ItemKind::Section));
+        assert!(matches!(&items[2].kind, ItemKind::Verify(c) if c.source() == "1 > 0"));
+        assert!(matches!(&items[3].kind, ItemKind::Command(name) if name == "open_vent"));
+    }
+
+    #[test]
+    fn malformed_items_are_rejected() {
+        for line in [
+            "verify \"no condition\"",
+            "command \"no name\"",
+            "manual \"extra\" argument",
+            "manual unquoted",
+            "check \"unknown\"",
+        ] {
+            assert!(parse_item(line).is_err(), "{line}");
+        }
+        let error = Checklist::parse("manual \"ok\"\nverify \"no condition\"").err();
+        assert_eq!(error.map(|e| e.line), Some(2));
+    }
+}
@@ -1,16 +1,28 @@
-use egui::{Button, DragValue, RichText, Sense, Ui};
-use jiff::{Unit, Zoned};
-use mavlink_bindgen::parser::MavType;
+use std::{
+    collections::BTreeSet,
+    time::{Duration, Instant},
+};
+
+use egui::{Button, Context, DragValue, RichText, Sense, Ui};
 use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
 
 use crate::{
+    commands::COMMAND_LIBRARY,
     error::ErrInstrument,
     mavlink::{
-        MavHeader, MavMessage, Message, TimedMessage,
-        reflection::{FieldLike, FieldLookup, MAVLINK_PROFILE, MapConvertible, MessageMap},
+        MavHeader, MavMessage, TimedMessage,
+        reflection::{FieldType, MAVLINK_PROFILE, MapConvertible, MessageMap},
+        stamp_current_time,
+    },
+    tc_log::{PendingReply, ReplyTimeout, TcReply},
+    ui::{
+        app::PaneResponse,
+        widgets::{
+            CommandArming, LibraryCommandSelector, field_editor, fields_editor, message_selector,
+            settable_fields,
+        },
     },
-    ui::app::PaneResponse,
 };
 
 use super::PaneBehavior;
@@ -22,10 +34,29 @@ pub struct CommandPane {
     text_size: f32,
     system_id: u8,
     show_only_tc: bool,
+    #[serde(default)]
+    periodic: PeriodicSettings,
+    #[serde(default)]
+    arming: CommandArming,
+    /// Name of the library command the pane is bound to, if any
+    #[serde(default)]
+    library_command: Option<String>,
 
     #[serde(skip)]
     settings_visible: bool,
-    // TODO handle message responses
+    /// Never restored from the layout, periodic sending is always started by the user
+    #[serde(skip)]
+    periodic_run: Option<PeriodicRun>,
+    /// Revision of the command library the bound command was copied from
+    #[serde(skip)]
+    library_revision: u64,
+    /// Fields of the bound command the operator can change, the others are
+    /// locked to the library definition
+    #[serde(skip)]
+    editable_fields: BTreeSet<usize>,
+    /// Last command sent, with its outcome once delivered
+    #[serde(skip)]
+    last_sent: Option<(PendingReply, Option<TcReply>)>,
     #[serde(skip)]
     commands_to_send: Vec<(MavHeader, MavMessage)>,
 }
@@ -38,7 +69,14 @@ impl Default for CommandPane {
             text_size: 16.0,
             system_id: 1, // Default system ID
             show_only_tc: true,
+            periodic: PeriodicSettings::default(),
+            arming: CommandArming::default(),
+            library_command: None,
             settings_visible: false,
+            periodic_run: None,
+            library_revision: 0,
+            editable_fields: BTreeSet::new(),
+            last_sent: None,
             commands_to_send: Vec::new(),
         }
     }
@@ -50,54 +88,140 @@ impl PartialEq for CommandPane {
             && self.text == other.text
             && self.text_size == other.text_size
             && self.show_only_tc == other.show_only_tc
+            && self.periodic == other.periodic
+            && self.arming == other.arming
+            && self.library_command == other.library_command
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+struct PeriodicSettings {
+    /// Whether clicking the button starts sending the command periodically
+    enabled: bool,
+    /// Period in seconds
+    period: f32,
+    limit: PeriodicLimit,
+}
+
+impl Default for PeriodicSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            period: 1.0,
+            limit: PeriodicLimit::Unlimited,
+        }
+    }
+}
+
+/// When to stop sending a command periodically.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+enum PeriodicLimit {
+    /// Until stopped by the user
+    Unlimited,
+    /// After the given number of sends
+    Repetitions(u32),
+    /// After the given number of seconds
+    Duration(f32),
+}
+
+impl PeriodicLimit {
+    fn is_reached(&self, run: &PeriodicRun) -> bool {
+        match *self {
+            Self::Unlimited => false,
+            Self::Repetitions(n) => run.count >= n,
+            Self::Duration(secs) => run.started.elapsed().as_secs_f32() >= secs,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PeriodicRun {
+    /// Context to repaint at the next send, which is due also when the pane
+    /// is not shown
+    ctx: Context,
+    started: Instant,
+    next_send: Instant,
+    /// Number of times the command was sent
+    count: u32,
+}
+
 impl PaneBehavior for CommandPane {
     #[profiling::function]
     fn ui(&mut self, ui: &mut Ui) -> PaneResponse {
         let mut response = PaneResponse::default();
+        self.sync_with_library();
 
         let parent = ui
             .scope(|ui| {
-                let btn_text = RichText::new(&self.text).size(self.text_size).strong();
-                let btn = Button::new(btn_text).sense(egui::Sense::click());
+                if self.arming.is_armed() {
+                    let confirmed = ui
+                        .vertical_centered(|ui| self.arming.show_armed(ui, &self.text))
+                        .inner;
+                    if confirmed {
+                        self.invoke(ui.ctx());
+                    }
+                    return;
+                }
+
+                let running = self.periodic_run.is_some();
+                let text = if running {
+                    format!("⏹ {}", self.text)
+                } else {
+                    self.text.clone()
+                };
+                let btn_text = RichText::new(text).size(self.text_size).strong();
+                let mut btn = Button::new(btn_text).sense(egui::Sense::click());
+                if running {
+                    btn = btn.fill(ui.visuals().selection.bg_fill);
+                }
 
                 // Clever way to add padding to the button
                 ui.allocate_rect(ui.max_rect(), Sense::click());
                 let btn_rect = ui.max_rect().shrink(2.0);
                 let btn_res = ui.put(btn_rect, btn);
+                let btn_res = if running {
+                    btn_res.on_hover_text("Sending periodically, click to stop")
+                } else {
+                    btn_res
+                };
 
                 // open the menu on right click on button
                 btn_res.context_menu(|ui| command_menu(ui, self));
                 if btn_res.clicked() {
                     info!("Command {} clicked", self.text);
-                    // send the message
-                    if let Some(message) = self.message.as_ref() {
-                        info!(
-                            "Sending {} message",
-                            MAVLINK_PROFILE
-                                .get_msg(message.message_id())
-                                .log_unwrap()
-                                .name
-                        );
-                        let mut map = message.clone();
-                        if let Some(tm) = map.get_mut_field("timestamp") {
-                            // set the timestamp to the current time
-                            *tm = Zoned::now()
-                                .round(Unit::Nanosecond)
-                                .log_unwrap()
-                                .timestamp()
-                                .as_nanosecond() as u64;
-                        }
-                        let header = MavHeader {
-                            system_id: self.system_id,
-                            ..Default::default()
-                        };
-                        let msg = MavMessage::from_map(map).log_unwrap();
-                        self.commands_to_send.push((header, msg));
+                    if running {
+                        self.stop_periodic();
+                    } else if self.arming.request(ui.ctx(), &self.text) {
+                        self.invoke(ui.ctx());
                     }
                 }
+
+                // show the outcome of the last command sent
+                if let Some((_, reply)) = &self.last_sent {
+                    let (text, color) = match reply {
+                        Some(reply) => (reply.to_string(), reply.color()),
+                        None => (TcReply::Pending.to_string(), TcReply::Pending.color()),
+                    };
+                    ui.painter().text(
+                        btn_rect.right_top() + egui::vec2(-4.0, 4.0),
+                        egui::Align2::RIGHT_TOP,
+                        text,
+                        egui::FontId::proportional(11.0),
+                        color,
+                    );
+                }
+
+                // show how many times the command was sent while running
+                if let Some(run) = &self.periodic_run {
+                    ui.painter().text(
+                        btn_rect.center_bottom() - egui::vec2(0.0, 4.0),
+                        egui::Align2::CENTER_BOTTOM,
+                        format!("sent {} × every {:.2} s", run.count, self.periodic.period),
+                        egui::FontId::proportional(11.0),
+                        ui.visuals().strong_text_color(),
+                    );
+                }
             })
             .response;
 
@@ -125,8 +249,170 @@ impl PaneBehavior for CommandPane {
     }
 
     fn drain_outgoing_messages(&mut self) -> Vec<(MavHeader, MavMessage)> {
+        // called at every frame, also when the pane is not shown
+        self.sync_with_library();
+        self.run_periodic();
         self.commands_to_send.drain(..).collect()
     }
+
+    fn handle_reply(&mut self, pending: &PendingReply, reply: &TcReply) {
+        let last_sent = self.last_sent.as_mut().filter(|(last, _)| last == pending);
+        if let Some((_, outcome)) = last_sent {
+            *outcome = Some(reply.clone());
+        }
+    }
+
+    fn source_label(&self) -> Option<String> {
+        Some(self.text.clone())
+    }
+}
+
+impl CommandPane {
+    /// Copies the definition of the bound library command, if it changed,
+    /// keeping the values the operator set in the editable fields. Commands
+    /// not bound still require the criticality the library sets for their
+    /// message.
+    fn sync_with_library(&mut self) {
+        let library = COMMAND_LIBRARY.read();
+        self.arming
+            .require_library(&library, self.system_id, self.message.as_ref());
+        let Some(name) = &self.library_command else {
+            return;
+        };
+        if library.revision() == self.library_revision {
+            return;
+        }
+        self.library_revision = library.revision();
+        match library.get(name) {
+            Some(definition) => {
+                let mut message = definition.message.clone();
+                if let (Some(message), Some(previous)) = (&mut message, &self.message) {
+                    copy_fields(previous, message, &definition.editable_fields);
+                }
+                self.message = message;
+                self.system_id = definition.system_id;
+                self.arming.criticality = definition.criticality.clone();
+                self.editable_fields = definition.editable_fields.clone();
+            }
+            None => {
+                warn!("Command {name} not found in the library");
+                self.message = None;
+                self.editable_fields.clear();
+            }
+        }
+    }
+
+    /// Whether the command is sent periodically. Critical commands are not,
+    /// as each send has to be armed and confirmed.
+    fn sends_periodically(&self) -> bool {
+        self.periodic.enabled && !self.arming.is_critical()
+    }
+
+    /// Sends the command, or starts sending it periodically if enabled
+    fn invoke(&mut self, ctx: &Context) {
+        if self.sends_periodically() {
+            self.start_periodic(ctx);
+        } else {
+            self.send_command();
+        }
+    }
+
+    /// Queues the message to be sent, with the timestamp set to the current time
+    fn send_command(&mut self) {
+        let Some(message) = self.message.as_ref() else {
+            return;
+        };
+        info!(
+            "Sending {} message",
+            MAVLINK_PROFILE
+                .get_msg(message.message_id())
+                .log_unwrap()
+                .name
+        );
+        let mut map = message.clone();
+        stamp_current_time(&mut map);
+        let mut header = MavHeader {
+            system_id: self.system_id,
+            ..Default::default()
+        };
+        let msg = MavMessage::from_map(map).log_unwrap();
+        let pending = PendingReply::send(&mut header, &msg, ReplyTimeout::default());
+        self.last_sent = Some((pending, None));
+        self.commands_to_send.push((header, msg));
+    }
+
+    fn start_periodic(&mut self, ctx: &Context) {
+        if self.message.is_none() {
+            return;
+        }
+        info!("Periodic sending of {} started", self.text);
+        let now = Instant::now();
+        self.periodic_run = Some(PeriodicRun {
+            ctx: ctx.clone(),
+            started: now,
+            next_send: now,
+            count: 0,
+        });
+    }
+
+    fn stop_periodic(&mut self) {
+        if let Some(run) = self.periodic_run.take() {
+            info!(
+                "Periodic sending of {} stopped after {} sends",
+                self.text, run.count
+            );
+        }
+    }
+
+    /// Sends the message if the period elapsed, and stops once the limit is reached
+    fn run_periodic(&mut self) {
+        if self.message.is_none() || !self.sends_periodically() {
+            self.stop_periodic();
+        }
+        let Some(run) = self.periodic_run.as_mut() else {
+            return;
+        };
+        let now = Instant::now();
+        let due = now >= run.next_send && !self.periodic.limit.is_reached(run);
+        if due {
+            let period = Duration::from_secs_f32(self.periodic.period);
+            run.count += 1;
+            run.next_send += period;
+            // do not try to catch up with the missed sends
+            if run.next_send < now {
+                run.next_send = now + period;
+            }
+        }
+        let finished = self.periodic.limit.is_reached(run);
+        if !finished {
+            run.ctx
+                .request_repaint_after(run.next_send.saturating_duration_since(now));
+        }
+
+        if due {
+            self.send_command();
+        }
+        if finished {
+            self.stop_periodic();
+        }
+    }
+}
+
+/// Copies the values of the given fields, if the messages are the same.
+fn copy_fields(from: &MessageMap, to: &mut MessageMap, fields: &BTreeSet<usize>) {
+    if from.message_id() != to.message_id() {
+        return;
+    }
+    let values: Vec<(usize, FieldType)> = fields
+        .iter()
+        .filter_map(|&i| from.field_map().get(i).map(|v| (i, FieldType::clone(v))))
+        .collect();
+    let mut field_map = to.field_map_mut();
+    for (i, value) in values {
+        if let Some(field) = field_map.get_mut(i) {
+            **field = value;
+        }
+    }
 }
 
 fn command_menu(ui: &mut Ui, pane: &mut CommandPane) {
@@ -136,6 +422,48 @@ fn command_menu(ui: &mut Ui, pane: &mut CommandPane) {
     }
 }
 
+fn periodic_settings(ui: &mut Ui, settings: &mut PeriodicSettings, critical: bool) {
+    ui.add_enabled(
+        !critical,
+        egui::Checkbox::new(&mut settings.enabled, "Send periodically"),
+    )
+    .on_hover_text("Clicking the button starts sending the command, click again to stop")
+    .on_disabled_hover_text("Critical commands have to be armed and confirmed at every send");
+    ui.add_enabled_ui(settings.enabled && !critical, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Period:");
+            ui.add(
+                DragValue::new(&mut settings.period)
+                    .range(0.05..=3600.0)
+                    .speed(0.05)
+                    .suffix(" s"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Stop:");
+            let limit = &mut settings.limit;
+            ui.selectable_value(limit, PeriodicLimit::Unlimited, "Never");
+            let is_count = matches!(limit, PeriodicLimit::Repetitions(_));
+            if ui.selectable_label(is_count, "Count").clicked() && !is_count {
+                *limit = PeriodicLimit::Repetitions(10);
+            }
+            let is_duration = matches!(limit, PeriodicLimit::Duration(_));
+            if ui.selectable_label(is_duration, "Duration").clicked() && !is_duration {
+                *limit = PeriodicLimit::Duration(60.0);
+            }
+        });
+        match &mut settings.limit {
+            PeriodicLimit::Unlimited => {}
+            PeriodicLimit::Repetitions(n) => {
+                ui.add(DragValue::new(n).range(1..=u32::MAX).suffix(" sends"));
+            }
+            PeriodicLimit::Duration(secs) => {
+                ui.add(DragValue::new(secs).range(0.1..=86400.0).suffix(" s"));
+            }
+        }
+    });
+}
+
 fn command_settings(ui: &mut Ui, pane: &mut CommandPane) {
     ui.set_max_width(200.0);
     ui.horizontal(|ui| {
@@ -149,6 +477,33 @@ fn command_settings(ui: &mut Ui, pane: &mut CommandPane) {
 
     ui.separator();
 
+    ui.horizontal(|ui| {
+        ui.label("Command:");
+        let selector =
+            LibraryCommandSelector::new(ui.id().with("library_command"), &mut pane.library_command);
+        if ui.add(selector).changed() {
+            // force the synchronization with the new definition
+            pane.library_revision = 0;
+        }
+    });
+
+    ui.separator();
+    periodic_settings(ui, &mut pane.periodic, pane.arming.is_critical());
+    ui.separator();
+
+    // the rest is defined in the library for bound commands, but the fields
+    // it leaves editable
+    if pane.library_command.is_some() {
+        ui.label(
+            RichText::new("Message, system ID and criticality are defined in the command library")
+                .italics(),
+        );
+        if let Some(message_map) = pane.message.as_mut() {
+            bound_fields_editor(ui, message_map, &pane.editable_fields);
+        }
+        return;
+    }
+
     // add a label for the system ID
     ui.horizontal(|ui| {
         let label = ui.label("System ID:");
@@ -157,150 +512,131 @@ fn command_settings(ui: &mut Ui, pane: &mut CommandPane) {
             .labelled_by(label.id);
     });
 
+    pane.arming.settings_ui(ui);
+    ui.separator();
+
     // add a checkbox for filtering sendable messages
     ui.checkbox(&mut pane.show_only_tc, "Show only TC messages");
+    message_selector(ui, &mut pane.message, pane.show_only_tc);
 
-    // Create a combo box for selecting the message kind
-    let mut message_id = pane.message.as_ref().map(|m| m.message_id());
-    let selected_text = message_id
-        .and_then(|id| MAVLINK_PROFILE.get_msg(id))
-        .map(|m| m.name.clone())
-        .unwrap_or("Select a Message".to_string());
-    egui::ComboBox::from_id_salt(ui.id().with("message_selector"))
-        .selected_text(selected_text)
-        .show_ui(ui, |ui| {
-            let mut msgs = MAVLINK_PROFILE.get_sorted_msgs();
-            if pane.show_only_tc {
-                msgs.retain(|m| m.name.ends_with("_TC"));
-            }
-            for msg in msgs {
-                ui.selectable_value(&mut message_id, Some(msg.id), &msg.name);
-            }
-        });
+    // For each field in the message, show an editor of its value
+    if let Some(message_map) = pane.message.as_mut() {
+        fields_editor(ui, message_map);
+    }
+}
 
-    // If the message id is changed, update the message
-    if pane
-        .message
-        .as_ref()
-        .is_none_or(|m| Some(m.message_id()) != message_id)
-    {
-        if let Some(id) = message_id {
-            pane.message = Some(
-                MavMessage::default_message_from_id(id)
-                    .log_unwrap()
-                    .as_map(),
-            );
-        } else {
-            pane.message = None;
+/// Shows an editor for each settable field of a bound command, the ones not
+/// editable in the library being locked.
+fn bound_fields_editor(ui: &mut Ui, message_map: &mut MessageMap, editable: &BTreeSet<usize>) {
+    let fields = settable_fields(message_map);
+    if fields.is_empty() {
+        return;
+    }
+    ui.group(|ui| {
+        for field in fields {
+            let enabled = editable.contains(&field.id());
+            ui.add_enabled_ui(enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", &field.field().name.to_uppercase()));
+                    field_editor(field, message_map, ui)
+                        .on_disabled_hover_text("Locked by the command library");
+                });
+            });
         }
+    });
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::{CommandDefinition, CommandLibrary, Criticality},
+        mavlink::WIGGLE_SERVO_TC_DATA,
+    };
+
+    fn periodic_pane(critical: bool) -> CommandPane {
+        let mut pane = CommandPane {
+            message: Some(MavMessage::WIGGLE_SERVO_TC(WIGGLE_SERVO_TC_DATA::default()).as_map()),
+            ..Default::default()
+        };
+        pane.periodic.enabled = true;
+        pane.arming.criticality.critical = critical;
+        pane
     }
 
-    // For each field in the message, show a text box with the field name and value,
-    // and update the MessageMap based on the content of these text fields.
-    if let Some(message_map) = pane.message.as_mut() {
-        let mut settable_fields = (0..message_map.field_map().len())
-            .map(|f| {
-                f.to_mav_field(message_map.message_id(), &MAVLINK_PROFILE)
-                    .log_unwrap()
-            })
-            .collect::<Vec<_>>();
+    #[test]
+    fn critical_commands_are_sent_once() {
+        let ctx = Context::default();
+        let mut pane = periodic_pane(true);
+        pane.invoke(&ctx);
+        assert!(pane.periodic_run.is_none());
+        assert_eq!(pane.drain_outgoing_messages().len(), 1);
+        assert!(pane.drain_outgoing_messages().is_empty());
+    }
 
-        // filter out the fields that are not settable
-        settable_fields.retain(|f| {
-            // skip the timestamp field
-            f.field().name.to_lowercase() != "timestamp"
-        });
+    #[test]
+    fn unbound_commands_critical_in_the_library_are_armed() {
+        let ctx = Context::default();
+        let mut pane = periodic_pane(false);
+        let mut library = CommandLibrary::default();
+        let definition = CommandDefinition {
+            system_id: pane.system_id,
+            message: pane.message.clone(),
+            criticality: Criticality {
+                critical: true,
+                confirmation_text: String::new(),
+            },
+            ..CommandDefinition::new("wiggle")
+        };
+        library.update(vec![definition]).unwrap();
+        pane.arming
+            .require_library(&library, pane.system_id, pane.message.as_ref());
 
-        if !settable_fields.is_empty() {
-            ui.group(|ui| {
-                for field in settable_fields {
-                    ui.horizontal(|ui| {
-                        ui.label(format!("{}:", &field.field().name.to_uppercase()));
-
-                        // show the combo box for enum types
-                        if let Some(enum_type) = &field.field().enumtype {
-                            let enum_info = MAVLINK_PROFILE.get_enum(enum_type).log_unwrap();
-                            // TODO handle enum advanced options
-                            macro_rules! variant_selector_for {
-                                ($kind:ty) => {{
-                                    let variant_ix: &mut $kind =
-                                        message_map.get_mut_field(field).log_unwrap();
-                                    let selected_text =
-                                        enum_info.entries[*variant_ix as usize].name.clone();
-                                    egui::ComboBox::from_id_salt(ui.id().with("field_selector"))
-                                        .selected_text(selected_text)
-                                        .show_ui(ui, |ui| {
-                                            for (index, variant) in
-                                                enum_info.entries.iter().enumerate()
-                                            {
-                                                ui.selectable_value(
-                                                    variant_ix,
-                                                    index as $kind,
-                                                    &variant.name,
-                                                );
-                                            }
-                                        });
-                                }};
-                            }
-                            match field.field().mavtype {
-                                MavType::UInt8 => variant_selector_for!(u8),
-                                MavType::UInt16 => variant_selector_for!(u16),
-                                MavType::UInt32 => variant_selector_for!(u32),
-                                MavType::UInt64 => variant_selector_for!(u64),
-                                _ => {
-                                    // TODO handle other enum types
-                                    warn!(
-                                        "Enum type {} is not supported for field {}",
-                                        enum_type,
-                                        field.field().name
-                                    );
-                                }
-                            }
-                        } else {
-                            // show the drag value for numeric types and text box for char types
-                            macro_rules! drag_value_with_range {
-                                ($_type:ty, $min:expr, $max:expr) => {{
-                                    let value: &mut $_type =
-                                        message_map.get_mut_field(field).log_unwrap();
-                                    ui.add(egui::DragValue::new(value).range($min..=$max));
-                                }};
-                            }
-
-                            match field.field().mavtype {
-                                MavType::UInt8MavlinkVersion | MavType::UInt8 => {
-                                    drag_value_with_range!(u8, 0, u8::MAX)
-                                }
-                                MavType::UInt16 => drag_value_with_range!(u16, 0, u16::MAX),
-                                MavType::UInt32 => drag_value_with_range!(u32, 0, u32::MAX),
-                                MavType::UInt64 => drag_value_with_range!(u64, 0, u64::MAX),
-                                MavType::Int8 => drag_value_with_range!(i8, i8::MIN, i8::MAX),
-                                MavType::Int16 => drag_value_with_range!(i16, i16::MIN, i16::MAX),
-                                MavType::Int32 => drag_value_with_range!(i32, i32::MIN, i32::MAX),
-                                MavType::Int64 => drag_value_with_range!(i64, i64::MIN, i64::MAX),
-                                MavType::Float => drag_value_with_range!(f32, f32::MIN, f32::MAX),
-                                MavType::Double => drag_value_with_range!(f64, f64::MIN, f64::MAX),
-                                MavType::Char => {
-                                    let value: &mut char =
-                                        message_map.get_mut_field(field).log_unwrap();
-                                    let mut buffer = value.to_string();
-                                    ui.add(
-                                        egui::TextEdit::singleline(&mut buffer)
-                                            .hint_text("char")
-                                            .char_limit(1),
-                                    );
-                                    if let Some(c) = buffer.chars().next() {
-                                        *value = c;
-                                    } else {
-                                        warn!("Invalid char input: {}", buffer);
-                                        // TODO handle invalid char input (USER ERROR)
-                                    }
-                                }
-                                MavType::Array(_, _) => warn!("Array types are not supported yet"), // TODO handle array types
-                            }
-                        }
-                    });
-                }
-            });
-        }
+        assert!(!pane.sends_periodically());
+        assert!(!pane.arming.request(&ctx, &pane.text));
+        assert!(pane.arming.is_armed());
+
+        // the same message to another system is not critical
+        let mut other = periodic_pane(false);
+        other.system_id += 1;
+        other
+            .arming
+            .require_library(&library, other.system_id, other.message.as_ref());
+        assert!(other.arming.request(&ctx, &other.text));
+    }
+
+    #[test]
+    fn commands_are_tracked_and_get_their_reply() {
+        let ctx = Context::default();
+        let mut pane = periodic_pane(false);
+        pane.periodic.enabled = false;
+        pane.invoke(&ctx);
+        let (pending, reply) = pane.last_sent.clone().unwrap();
+        assert_eq!(reply, None);
+
+        // outcomes of other commands are ignored
+        let other = PendingReply::send(
+            &mut MavHeader::default(),
+            &pane.drain_outgoing_messages()[0].1,
+            ReplyTimeout::default(),
+        );
+        pane.handle_reply(&other, &TcReply::Timeout);
+        assert_eq!(pane.last_sent, Some((pending, None)));
+        pane.handle_reply(&pending, &TcReply::Timeout);
+        assert_eq!(pane.last_sent, Some((pending, Some(TcReply::Timeout))));
+    }
+
+    #[test]
+    fn periodic_sending_stops_if_the_command_becomes_critical() {
+        let ctx = Context::default();
+        let mut pane = periodic_pane(false);
+        pane.invoke(&ctx);
+        assert!(pane.periodic_run.is_some());
+        assert_eq!(pane.drain_outgoing_messages().len(), 1);
+
+        pane.arming.criticality.critical = true;
+        assert!(pane.drain_outgoing_messages().is_empty());
+        assert!(pane.periodic_run.is_none());
     }
 }
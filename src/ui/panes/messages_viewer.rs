@@ -5,12 +5,17 @@ use mavlink_bindgen::parser::MavType;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    derived::{DERIVED_CHANNELS, DerivedField},
     error::ErrInstrument,
     mavlink::{
         MavMessage, MessageData, ROCKET_FLIGHT_TM_DATA, TimedMessage,
         reflection::{IndexedField, MAVLINK_PROFILE},
     },
-    ui::panes::{PaneBehavior, PaneResponse},
+    ui::{
+        panes::{PaneBehavior, PaneResponse},
+        widgets::UnitSelector,
+    },
+    utils::units::UnitOfMeasure,
 };
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
@@ -18,10 +23,17 @@ pub struct MessagesViewerPane {
     // == PANE RELATED ==
     selected_message: Option<u32>,
     selected_fields: HashSet<usize>,
+    #[serde(default)]
+    selected_derived: HashSet<String>,
+    /// Display units of the fields, if different from the native ones
+    #[serde(default)]
+    display_units: HashMap<usize, String>,
 
     // == TEMP VALUES ==
     #[serde(skip)]
     field_map: HashMap<usize, Option<String>>,
+    #[serde(skip)]
+    derived_map: HashMap<String, f64>,
 
     // == UI RELATED ==
     #[serde(skip)]
@@ -92,14 +104,70 @@ impl PaneBehavior for MessagesViewerPane {
                                     for field in fields {
                                         let mut selected =
                                             self.selected_fields.contains(&field.id());
-                                        let response: Response =
-                                            ui.checkbox(&mut selected, field.field().name.clone());
-                                        if response.clicked() {
-                                            if selected {
-                                                self.selected_fields.insert(field.id());
-                                            } else {
-                                                self.selected_fields.remove(&field.id());
+                                        ui.horizontal(|ui| {
+                                            let response: Response = ui.checkbox(
+                                                &mut selected,
+                                                field.field().name.clone(),
+                                            );
+                                            if response.clicked() {
+                                                if selected {
+                                                    self.selected_fields.insert(field.id());
+                                                } else {
+                                                    self.selected_fields.remove(&field.id());
+                                                }
                                             }
+
+                                            // Allow to choose the display unit of selected fields
+                                            let native =
+                                                UnitOfMeasure::from(field.field().unit.as_ref());
+                                            if selected && native.compatible_units().len() > 1 {
+                                                let mut choice =
+                                                    self.display_units.get(&field.id()).cloned();
+                                                let unit_selector = UnitSelector::new(
+                                                    ("display_unit", field.id()),
+                                                    &native,
+                                                    &mut choice,
+                                                );
+                                                if ui.add(unit_selector).changed() {
+                                                    match choice {
+                                                        Some(unit) => {
+                                                            self.display_units
+                                                                .insert(field.id(), unit);
+                                                        }
+                                                        None => {
+                                                            self.display_units.remove(&field.id());
+                                                        }
+                                                    }
+                                                    // wait for the next message to show the converted value
+                                                    self.field_map.remove(&field.id());
+                                                }
+                                            }
+                                        });
+                                    }
+                                }
+
+                                // derived channels computed on the selected message
+                                let derived_names = {
+                                    let registry = DERIVED_CHANNELS.read();
+                                    registry
+                                        .valid_channels()
+                                        .filter(|c| {
+                                            registry.trigger_ids(&c.name).contains(selected_msg)
+                                        })
+                                        .map(|c| c.name.clone())
+                                        .collect::<Vec<_>>()
+                                };
+                                if !derived_names.is_empty() {
+                                    ui.separator();
+                                    ui.label("Derived Channels:");
+                                }
+                                for name in derived_names {
+                                    let mut selected = self.selected_derived.contains(&name);
+                                    if ui.checkbox(&mut selected, &name).clicked() {
+                                        if selected {
+                                            self.selected_derived.insert(name);
+                                        } else {
+                                            self.selected_derived.remove(&name);
                                         }
                                     }
                                 }
@@ -137,6 +205,22 @@ impl PaneBehavior for MessagesViewerPane {
                                             }
                                         }
                                     }
+                                    let mut derived_names =
+                                        self.selected_derived.iter().collect::<Vec<_>>();
+                                    derived_names.sort();
+                                    for name in derived_names {
+                                        let value = self
+                                            .derived_map
+                                            .get(name)
+                                            .map(|v| {
+                                                let unit = DerivedField::new(name).unit();
+                                                format!("{v:.5} {unit}")
+                                            })
+                                            .unwrap_or("N/A".to_string());
+                                        ui.label(name);
+                                        ui.label(value);
+                                        ui.end_row();
+                                    }
                                 } else {
                                     ui.label("No message selected");
                                 }
@@ -175,12 +259,19 @@ impl PaneBehavior for MessagesViewerPane {
                 if let Some(fields) = MAVLINK_PROFILE.get_fields(*selected_msg) {
                     for field in fields {
                         if self.selected_fields.contains(&field.id()) {
-                            let value = field.format(&msg.message);
+                            let display_unit = self.display_units.get(&field.id());
+                            let value = field.format_with_unit(&msg.message, display_unit);
                             self.field_map.insert(field.id(), Some(value));
                         }
                     }
                 }
             }
+            for sample in &msg.derived {
+                if self.selected_derived.contains(&*sample.name) {
+                    self.derived_map
+                        .insert(sample.name.to_string(), sample.value);
+                }
+            }
         }
     }
 
@@ -195,6 +286,10 @@ impl PaneBehavior for MessagesViewerPane {
 
 trait MessageViewerFormatter {
     fn format(&self, msg: &MavMessage) -> String;
+
+    /// Formats the value followed by its unit, converting it to `display_unit`
+    /// if given and compatible.
+    fn format_with_unit(&self, msg: &MavMessage, display_unit: Option<&String>) -> String;
 }
 
 impl MessageViewerFormatter for IndexedField {
@@ -220,4 +315,25 @@ impl MessageViewerFormatter for IndexedField {
             }
         }
     }
+
+    fn format_with_unit(&self, msg: &MavMessage, display_unit: Option<&String>) -> String {
+        if self.field().enumtype.is_some() {
+            return self.format(msg);
+        }
+        let native = UnitOfMeasure::from(self.field().unit.as_ref());
+        let unit = native.display_unit(display_unit.map(String::as_str));
+        if unit != native {
+            if let Some(value) = self
+                .extract_as_f64(msg)
+                .ok()
+                .and_then(|v| native.convert(v, &unit))
+            {
+                return format!("{value:.5} {unit}");
+            }
+        }
+        match native {
+            UnitOfMeasure::Adimensional => self.format(msg),
+            _ => format!("{} {native}", self.format(msg)),
+        }
+    }
 }
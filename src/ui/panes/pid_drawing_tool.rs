@@ -1,6 +1,6 @@
 mod connections;
 mod elements;
-mod grid;
+pub(super) mod grid;
 mod pid_data;
 mod symbols;
 
@@ -218,7 +218,7 @@ impl PaneBehavior for PidPane {
     fn update(&mut self, messages: &[&TimedMessage]) {
         for msg in messages {
             for element in self.elements.values_mut() {
-                element.update(&msg.message, &self.message_subscription_ids[..]);
+                element.update(msg, &self.message_subscription_ids[..]);
             }
         }
     }
@@ -289,31 +289,7 @@ impl PidPane {
     }
 
     fn draw_grid(&self, ui: &Ui, theme: Theme) {
-        let painter = ui.painter();
-        let window_rect = ui.max_rect();
-        let dot_color = PidPane::dots_color(theme);
-
-        let offset_x = (self.grid.zero_pos.x % self.grid.size()) as i32;
-        let offset_y = (self.grid.zero_pos.y % self.grid.size()) as i32;
-
-        let start_x =
-            (window_rect.min.x / self.grid.size()) as i32 * self.grid.size() as i32 + offset_x;
-        let end_x = (window_rect.max.x / self.grid.size() + 2.0) as i32 * self.grid.size() as i32
-            + offset_x;
-        let start_y =
-            (window_rect.min.y / self.grid.size()) as i32 * self.grid.size() as i32 + offset_y;
-        let end_y = (window_rect.max.y / self.grid.size() + 2.0) as i32 * self.grid.size() as i32
-            + offset_y;
-
-        for x in (start_x..end_x).step_by(self.grid.size() as usize) {
-            for y in (start_y..end_y).step_by(self.grid.size() as usize) {
-                let rect = egui::Rect::from_min_size(
-                    egui::Pos2::new(x as f32, y as f32),
-                    egui::Vec2::new(2.0, 2.0),
-                );
-                painter.rect_filled(rect, 0.0, dot_color);
-            }
-        }
+        self.grid.draw(ui, PidPane::dots_color(theme));
     }
 
     fn draw_connections(&self, ui: &Ui, theme: Theme) {
@@ -4,7 +4,7 @@ use egui::{Theme, Ui};
 use glam::{Mat2, Vec2};
 use serde::{Deserialize, Serialize};
 
-use crate::mavlink::MavMessage;
+use crate::mavlink::TimedMessage;
 
 use super::{
     grid::GridInfo,
@@ -125,7 +125,7 @@ impl Element {
         self.symbol.subscriptions_ui(ui, msgs);
     }
 
-    pub fn update(&mut self, message: &MavMessage, subscribed_msg_ids: &[u32]) {
+    pub fn update(&mut self, message: &TimedMessage, subscribed_msg_ids: &[u32]) {
         self.symbol.update(message, subscribed_msg_ids);
     }
 
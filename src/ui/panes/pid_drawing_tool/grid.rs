@@ -1,5 +1,6 @@
 use core::f32;
 
+use egui::{Color32, Ui};
 use glam::Vec2;
 use serde::{Deserialize, Serialize};
 
@@ -57,4 +58,28 @@ impl GridInfo {
     pub fn screen_to_grid(&self, p_s: Vec2) -> Vec2 {
         (p_s - self.zero_pos) / self.size
     }
+
+    /// Draws the grid dots over the whole area of the ui
+    pub fn draw(&self, ui: &Ui, dot_color: Color32) {
+        let painter = ui.painter();
+        let window_rect = ui.max_rect();
+
+        let offset_x = (self.zero_pos.x % self.size()) as i32;
+        let offset_y = (self.zero_pos.y % self.size()) as i32;
+
+        let start_x = (window_rect.min.x / self.size()) as i32 * self.size() as i32 + offset_x;
+        let end_x = (window_rect.max.x / self.size() + 2.0) as i32 * self.size() as i32 + offset_x;
+        let start_y = (window_rect.min.y / self.size()) as i32 * self.size() as i32 + offset_y;
+        let end_y = (window_rect.max.y / self.size() + 2.0) as i32 * self.size() as i32 + offset_y;
+
+        for x in (start_x..end_x).step_by(self.size() as usize) {
+            for y in (start_y..end_y).step_by(self.size() as usize) {
+                let rect = egui::Rect::from_min_size(
+                    egui::Pos2::new(x as f32, y as f32),
+                    egui::Vec2::new(2.0, 2.0),
+                );
+                painter.rect_filled(rect, 0.0, dot_color);
+            }
+        }
+    }
 }
@@ -12,7 +12,7 @@ use icons::Icon;
 use label::Label;
 use value_display::ValueDisplay;
 
-use crate::mavlink::MavMessage;
+use crate::mavlink::TimedMessage;
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, EnumIter, Display, Debug)]
 #[enum_dispatch]
@@ -35,7 +35,7 @@ pub trait SymbolBehavior {
     fn reset_subscriptions(&mut self);
 
     /// Updates the symbol based on the received message.
-    fn update(&mut self, message: &MavMessage, subscribed_msg_ids: &[u32]);
+    fn update(&mut self, message: &TimedMessage, subscribed_msg_ids: &[u32]);
 
     /// Renders the symbol on the UI.
     fn paint(&mut self, ui: &mut Ui, theme: Theme, pos: Vec2, size: f32, rotation: f32);
@@ -7,7 +7,7 @@ use glam::Vec2;
 use motor_valve::MotorValve;
 use serde::{Deserialize, Serialize};
 
-use crate::{mavlink::MavMessage, ui::utils::glam_to_egui};
+use crate::{mavlink::TimedMessage, ui::utils::glam_to_egui};
 
 use super::SymbolBehavior;
 
@@ -153,7 +153,7 @@ impl Display for Icon {
 }
 
 impl SymbolBehavior for Icon {
-    fn update(&mut self, message: &MavMessage, subscribed_msg_ids: &[u32]) {
+    fn update(&mut self, message: &TimedMessage, subscribed_msg_ids: &[u32]) {
         if let Icon::MotorValve(state) = self {
             state.update(message, subscribed_msg_ids)
         }
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use crate::{
     error::ErrInstrument,
     mavlink::reflection::MAVLINK_PROFILE,
-    mavlink::{MavMessage, Message, reflection::IndexedField},
+    mavlink::{TimedMessage, reflection::IndexedField},
     ui::cache::ChangeTracker,
 };
 
@@ -79,7 +79,7 @@ impl MotorValve {
         }
     }
 
-    pub fn update(&mut self, msg: &MavMessage, subscribed_msg_ids: &[u32]) {
+    pub fn update(&mut self, msg: &TimedMessage, subscribed_msg_ids: &[u32]) {
         // Reset field if msg_id has changed
         if let Some(inner_field) = &self.subscribed_field {
             if !subscribed_msg_ids.contains(&inner_field.msg_id()) {
@@ -88,8 +88,8 @@ impl MotorValve {
         }
 
         if let Some(field) = &self.subscribed_field {
-            if field.msg_id() == msg.message_id() {
-                let value = field.extract_as_u8(msg).log_unwrap();
+            if field.msg_id() == msg.id() {
+                let value = field.extract_as_u8(&msg.message).log_unwrap();
                 match &mut self.variant {
                     MotorValveVariant::TwoWay(two_way_internal) => {
                         two_way_internal.last_value = match value {
@@ -6,7 +6,7 @@ use egui::{
 use glam::Vec2;
 
 use crate::{
-    mavlink::MavMessage,
+    mavlink::TimedMessage,
     ui::utils::{egui_to_glam, glam_to_egui},
 };
 
@@ -34,7 +34,7 @@ impl Default for Label {
 }
 
 impl SymbolBehavior for Label {
-    fn update(&mut self, _message: &MavMessage, _subscribed_msg_ids: &[u32]) {}
+    fn update(&mut self, _message: &TimedMessage, _subscribed_msg_ids: &[u32]) {}
 
     fn reset_subscriptions(&mut self) {}
 
@@ -6,13 +6,16 @@ use egui::{
 use glam::Vec2;
 
 use crate::{
+    derived::{DERIVED_CHANNELS, DerivedField, TelemetryField},
     error::ErrInstrument,
+    mavlink::TimedMessage,
     mavlink::reflection::MAVLINK_PROFILE,
-    mavlink::{MavMessage, Message, reflection::IndexedField},
     ui::{
         cache::ChangeTracker,
         utils::{egui_to_glam, glam_to_egui},
+        widgets::UnitSelector,
     },
+    utils::units::{Quantity, UnitOfMeasure},
 };
 
 use super::SymbolBehavior;
@@ -21,9 +24,12 @@ const FONT_SIZE: f32 = 2.0;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ValueDisplay {
-    subscribed_field: Option<IndexedField>,
+    subscribed_field: Option<TelemetryField>,
     size: Vec2,
     color: DisplayColor,
+    /// Unit used to display the value, if different from the native one
+    #[serde(default)]
+    display_unit: Option<String>,
 
     #[serde(skip)]
     last_value: Option<f32>,
@@ -38,23 +44,23 @@ impl Default for ValueDisplay {
             last_value: Some(0.0),
             size: Vec2::new(FONT_SIZE * 0.6 * 4.0, FONT_SIZE),
             color: DisplayColor::Default,
+            display_unit: None,
             is_subs_window_visible: false,
         }
     }
 }
 
 impl SymbolBehavior for ValueDisplay {
-    fn update(&mut self, message: &MavMessage, subscribed_msg_ids: &[u32]) {
+    fn update(&mut self, message: &TimedMessage, subscribed_msg_ids: &[u32]) {
         // Reset field if msg_id has changed
-        if let Some(inner_field) = &self.subscribed_field {
+        if let Some(TelemetryField::Mavlink(inner_field)) = &self.subscribed_field {
             if !subscribed_msg_ids.contains(&inner_field.msg_id()) {
                 self.subscribed_field = None;
             }
         }
 
         if let Some(subscribed_field) = &self.subscribed_field {
-            if message.message_id() == subscribed_field.msg_id() {
-                let value = subscribed_field.extract_as_f64(message).log_unwrap();
+            if let Some(value) = subscribed_field.extract_from_message(message) {
                 self.last_value = Some(value as f32);
             }
         }
@@ -74,13 +80,19 @@ impl SymbolBehavior for ValueDisplay {
             DisplayColor::Custom(color32) => color32,
         };
 
-        let unit = self
+        let native_unit = self
             .subscribed_field
             .as_ref()
-            .and_then(|f| f.field().unit.as_deref())
-            .unwrap_or("");
+            .map(|f| f.unit())
+            .unwrap_or(UnitOfMeasure::Adimensional);
+        let unit = native_unit.display_unit(self.display_unit.as_deref());
         let text = match self.last_value {
-            Some(value) => format!("{value:.5} {unit}"),
+            Some(value) => {
+                let value = native_unit
+                    .convert(value as f64, &unit)
+                    .unwrap_or(value as f64);
+                format!("{value:.5} {unit}")
+            }
             None => "N/A".to_string(),
         };
         let text_rect = painter.text(
@@ -112,7 +124,13 @@ impl SymbolBehavior for ValueDisplay {
             .movable(true)
             .open(&mut self.is_subs_window_visible)
             .show(ui.ctx(), |ui| {
-                subscription_window(ui, mavlink_ids, &mut self.color, &mut self.subscribed_field)
+                subscription_window(
+                    ui,
+                    mavlink_ids,
+                    &mut self.color,
+                    &mut self.display_unit,
+                    &mut self.subscribed_field,
+                )
             });
         // reset last_value if the subscribed field has changed
         if change_tracker.has_changed(&self.subscribed_field) {
@@ -140,7 +158,8 @@ fn subscription_window(
     ui: &mut Ui,
     msg_ids: &[u32],
     color_mode: &mut DisplayColor,
-    field: &mut Option<IndexedField>,
+    display_unit: &mut Option<String>,
+    field: &mut Option<TelemetryField>,
 ) {
     // Color settings
     let mut checked = matches!(color_mode, DisplayColor::Custom(_));
@@ -165,7 +184,10 @@ fn subscription_window(
     ui.add_sized([250., 10.], egui::Separator::default());
 
     // Subscription settings
-    let mut current_msg_id = field.as_ref().map(|f| f.msg_id()).unwrap_or(msg_ids[0]);
+    let mut current_msg_id = field
+        .as_ref()
+        .and_then(|f| f.msg_ids().into_iter().find(|id| msg_ids.contains(id)))
+        .unwrap_or(msg_ids[0]);
 
     // extract the msg name from the id to show it in the combo box
     let msg_name = MAVLINK_PROFILE
@@ -191,10 +213,23 @@ fn subscription_window(
         *field = None;
     }
 
-    // Get all fields available for subscription
-    let fields = MAVLINK_PROFILE
+    // Get all fields available for subscription, including the derived
+    // channels computed on the selected message
+    let mut fields: Vec<TelemetryField> = MAVLINK_PROFILE
         .get_plottable_fields(current_msg_id)
-        .log_expect("Invalid message id");
+        .log_expect("Invalid message id")
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    {
+        let registry = DERIVED_CHANNELS.read();
+        fields.extend(
+            registry
+                .valid_channels()
+                .filter(|c| registry.trigger_ids(&c.name).contains(&current_msg_id))
+                .map(|c| DerivedField::new(&c.name).into()),
+        );
+    }
 
     // If no fields available for subscription
     if fields.is_empty() {
@@ -209,12 +244,25 @@ fn subscription_window(
     // Otherwise, select the first field available
     let field = field.get_or_insert(fields[0].to_owned());
     egui::ComboBox::from_label("field")
-        .selected_text(&field.field().name)
+        .selected_text(field.name())
         .show_ui(ui, |ui| {
             for msg in fields.iter() {
-                ui.selectable_value(field, msg.to_owned(), &msg.field().name);
+                ui.selectable_value(field, msg.to_owned(), msg.name());
             }
         });
+
+    // Display unit settings
+    let native_unit = field.unit();
+    if native_unit.compatible_units().len() > 1 {
+        ui.horizontal(|ui| {
+            ui.add(UnitSelector::new(
+                ui.auto_id_with("display_unit"),
+                &native_unit,
+                display_unit,
+            ));
+            ui.label("unit");
+        });
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -224,19 +272,16 @@ enum DisplayColor {
     Custom(Color32),
 }
 
-fn field_to_color(field: Option<&IndexedField>, visuals: &Visuals) -> Color32 {
-    let unit_str = field.and_then(|f| f.field().unit.as_deref());
-    unit_to_color(unit_str, visuals)
+fn field_to_color(field: Option<&TelemetryField>, visuals: &Visuals) -> Color32 {
+    let unit = field.map(|f| f.unit());
+    unit_to_color(unit.as_ref(), visuals)
 }
 
-fn unit_to_color(unit_str: Option<&str>, visuals: &Visuals) -> Color32 {
-    match unit_str {
-        Some(string) => match string {
-            "kg" => Color32::GREEN,
-            "Pa" | "Bar" => Color32::BLUE,
-            "deg" | "degC" => Color32::YELLOW,
-            _ => visuals.text_color(),
-        },
-        None => visuals.text_color(),
+fn unit_to_color(unit: Option<&UnitOfMeasure>, visuals: &Visuals) -> Color32 {
+    match unit.and_then(|u| u.quantity()) {
+        Some(Quantity::Mass) => Color32::GREEN,
+        Some(Quantity::Pressure) => Color32::BLUE,
+        Some(Quantity::Angle | Quantity::Temperature) => Color32::YELLOW,
+        _ => visuals.text_color(),
     }
 }
@@ -1,24 +1,60 @@
+mod annotations;
+mod axes;
+mod export;
 mod fields;
+mod series;
 mod source_window;
+mod spectrum;
+mod stats;
+mod xy;
 
 use super::PaneBehavior;
 use crate::{
-    error::ErrInstrument,
+    APP_NAME,
+    alarms::AlarmEngineExt,
     mavlink::{MessageData, ROCKET_FLIGHT_TM_DATA, TimedMessage},
+    tc_log::TcLogExt,
     ui::app::PaneResponse,
-    utils::units::UnitOfMeasure,
+    utils::{
+        id::PaneId,
+        timer::MISSION_T0,
+        units::{TimeUnits, UnitOfMeasure},
+    },
 };
-use egui::{Color32, Ui, Vec2, Vec2b};
-use egui_plot::{AxisHints, Corner, HPlacement, Legend, Line, PlotPoint, log_grid_spacer};
+use egui::{Color32, Context, Id, Rect, RichText, Sense, Ui, Vec2, Vec2b, mutex::Mutex, pos2};
+use egui_file::FileDialog;
+use egui_plot::{
+    AxisHints, Corner, HLine, HPlacement, Legend, Line, LineStyle, PlotBounds, PlotPoint,
+    PlotPoints, PlotUi, Points, VLine, log_grid_spacer,
+};
+use jiff::Timestamp;
 use serde::{self, Deserialize, Serialize};
 use std::{
+    fs::File,
     hash::{DefaultHasher, Hash, Hasher},
+    io::BufWriter,
     iter::zip,
+    ops::RangeInclusive,
+    path::Path,
+    sync::Arc,
     time::{Duration, Instant},
 };
+use tracing::error;
 
+use annotations::{
+    EventMarker, MarkerSettings, Reference, ReferenceKind, StateTransitions, alarm_markers,
+    command_markers,
+};
+use axes::{AxisScale, AxisSide, YAxisSettings, format_tick, nice_ticks};
+use export::{
+    CsvSeries, Snapshot, SnapshotAxis, SnapshotMarker, SnapshotReference, SnapshotSeries, write_csv,
+};
 use fields::{XPlotField, YPlotField};
-use source_window::sources_window;
+use series::TimeAwarePlotPoints;
+use source_window::{sources_window, spectrum_settings, xy_settings};
+use spectrum::{Spectrum, SpectrumScale, SpectrumSettings};
+use stats::SeriesStats;
+use xy::{COLOR_LEVELS, ColorBy, Colormap, XySettings, color_level, level_runs, value_range};
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct Plot2DPane {
@@ -26,10 +62,39 @@ pub struct Plot2DPane {
     // UI settings
     #[serde(skip)]
     line_data: Vec<TimeAwarePlotPoints>,
+    /// Value of the color field when each point of `line_data` was received,
+    /// if coloring the XY plot by a field
+    #[serde(skip)]
+    color_data: Vec<TimeAwarePlotPoints>,
+    /// Latest value of the color field
+    #[serde(skip)]
+    color_value: Option<f64>,
     #[serde(skip)]
     state_valid: bool,
     #[serde(skip)]
     settings_visible: bool,
+    /// Range of x shown in the last frame, `None` when following the data
+    #[serde(skip)]
+    view_range: Option<RangeInclusive<f64>>,
+    /// T0 the points were computed with, when showing the mission time
+    #[serde(skip)]
+    mission_t0: Option<Instant>,
+    /// Whether the view is frozen for inspection instead of following the
+    /// live data, points keep being buffered meanwhile for
+    /// [`PAUSED_LIFESPANS`] lifespans
+    #[serde(skip)]
+    paused: bool,
+    #[serde(skip)]
+    state_transitions: StateTransitions,
+    /// Last spectrum computed, with the settings it was computed with, kept
+    /// while paused
+    #[serde(skip)]
+    spectrum: Option<(SpectrumSettings, Spectrum)>,
+    #[serde(skip)]
+    pane_id: Option<PaneId>,
+    /// Export waiting for the file to be chosen
+    #[serde(skip)]
+    pending_export: Option<PendingExport>,
 }
 
 impl PartialEq for Plot2DPane {
@@ -42,81 +107,189 @@ impl PaneBehavior for Plot2DPane {
     #[profiling::function]
     fn ui(&mut self, ui: &mut Ui) -> PaneResponse {
         let mut response = PaneResponse::default();
+        if self.settings.y_axes.is_empty() {
+            self.settings.y_axes = default_y_axes();
+        }
         let data_settings_digest = self.settings.data_digest();
 
+        // recompute the points when T0 changes
+        if self.settings.x_field == XPlotField::MissionTime {
+            let t0 = *MISSION_T0.read();
+            if t0 != self.mission_t0 {
+                self.mission_t0 = t0;
+                self.state_valid = false;
+            }
+        }
+
         let ctrl_pressed = ui.input(|i| i.modifiers.ctrl);
 
+        // Some(true) to jump back to live, Some(false) to pause
+        let follow_request = ui
+            .horizontal(|ui| {
+                ui.selectable_value(&mut self.settings.mode, PlotMode::TimeSeries, "📈 Time");
+                ui.selectable_value(&mut self.settings.mode, PlotMode::Spectrum, "📊 Spectrum");
+                ui.selectable_value(&mut self.settings.mode, PlotMode::Xy, "🗺 XY");
+                if self.settings.mode == PlotMode::TimeSeries {
+                    ui.toggle_value(&mut self.settings.show_stats, "Σ Stats")
+                        .on_hover_text("Statistics of the series over the visible window");
+                }
+                ui.separator();
+                if self.paused {
+                    let jump = ui
+                        .small_button("⏭ Live")
+                        .on_hover_text("Jump to live, following the incoming data")
+                        .clicked();
+                    ui.label(RichText::new("Paused, drag and scroll to inspect").weak());
+                    jump.then_some(true)
+                } else {
+                    ui.small_button("⏸ Pause")
+                        .on_hover_text("Freeze the view to inspect the data")
+                        .clicked()
+                        .then_some(false)
+                }
+            })
+            .inner;
+
+        match self.settings.mode {
+            PlotMode::TimeSeries => {}
+            PlotMode::Spectrum => {
+                self.spectrum_ui(ui, &mut response, ctrl_pressed, follow_request);
+                self.settings_window(ui, data_settings_digest);
+                return response;
+            }
+            PlotMode::Xy => {
+                self.xy_ui(ui, &mut response, ctrl_pressed, follow_request);
+                self.settings_window(ui, data_settings_digest);
+                return response;
+            }
+        }
+
         let x_unit = self.settings.x_field.unit();
         let y_units = self
             .settings
             .y_fields
             .iter()
-            .map(|(field, _)| field.unit())
+            .map(|(field, line)| {
+                field
+                    .unit()
+                    .display_unit(self.settings.display_unit_of(line))
+            })
             .collect::<Vec<_>>();
-        // define y_unit as the common unit of the y_fields if they are all the same
-        let y_unit = y_units.iter().fold(y_units.first(), |acc, unit| {
-            if let Some(acc) = acc {
-                if acc == unit {
-                    return Some(acc);
-                }
-            }
-            None
-        });
         let x_name = self.settings.x_field.name();
+        // name the messages in the legend when plotting more than one
+        let multi_message = self.settings.y_msg_ids().len() > 1;
+        let series_names = self
+            .settings
+            .y_fields
+            .iter()
+            .map(|(field, _)| {
+                if multi_message {
+                    field.qualified_name()
+                } else {
+                    field.name()
+                }
+            })
+            .collect::<Vec<_>>();
+        let legend_labels = zip(&series_names, &self.line_data)
+            .map(|(name, points)| {
+                format!(
+                    "{}: {:.5}",
+                    name,
+                    points.last().map(|l| l.y).unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // decimate to the range shown in the last frame, or to the whole data
+        // when following it, so that new points can extend the auto bounds
+        let buckets = ui.available_width() as usize;
+        let lines = self
+            .line_data
+            .iter()
+            .map(|points| points.decimated(self.view_range.clone(), buckets))
+            .collect::<Vec<_>>();
+        // map the values of each axis to plot space
+        let normalized = self.settings.y_axes.len() > 1
+            || self.settings.y_axes.iter().any(|axis| axis.range.is_some());
+        let scales = self
+            .settings
+            .y_axes
+            .iter()
+            .enumerate()
+            .map(|(i, axis)| {
+                let values = zip(&self.settings.y_fields, &lines)
+                    .filter(|((_, line), _)| self.settings.axis_index(line) == i)
+                    .flat_map(|(_, points)| points.iter().map(|p| p.y));
+                AxisScale::fit(axis, normalized, values)
+            })
+            .collect::<Vec<_>>();
+        let series_scales = self
+            .settings
+            .y_fields
+            .iter()
+            .map(|(_, line)| scales[self.settings.axis_index(line)])
+            .collect::<Vec<_>>();
+
+        let references = self.series_references(ui.ctx());
+        let markers = self.event_markers(ui.ctx());
 
         let x_axis = match x_unit {
-            UnitOfMeasure::Time(ref time_unit) => {
-                AxisHints::new_x().label(&x_name).formatter(move |m, r| {
-                    let scaling_factor_to_nanos = time_unit.scale() * 1e9;
-                    let r_span_in_nanos = (r.end() - r.start()).abs() * scaling_factor_to_nanos;
-                    let m_in_nanos = m.value * scaling_factor_to_nanos;
-                    // all the following numbers are arbitrary
-                    // they are chosen based on common sense
-                    if r_span_in_nanos < 4e3 {
-                        format!("{m_in_nanos:.0}ns")
-                    } else if r_span_in_nanos < 4e6 {
-                        format!("{:.0}µs", m_in_nanos / 1e3)
-                    } else if r_span_in_nanos < 4e9 {
-                        format!("{:.0}ms", m_in_nanos / 1e6)
-                    } else if r_span_in_nanos < 24e10 {
-                        format!("{:.0}s", m_in_nanos / 1e9)
-                    } else if r_span_in_nanos < 144e11 {
-                        format!("{:.0}m{:.0}s", m_in_nanos / 60e9, (m_in_nanos % 60e9) / 1e9)
-                    } else if r_span_in_nanos < 3456e11 {
-                        format!(
-                            "{:.0}h{:.0}m",
-                            m_in_nanos / 3600e9,
-                            (m_in_nanos % 3600e9) / 60e9
-                        )
-                    } else {
-                        format!(
-                            "{:.0}d{:.0}h",
-                            m_in_nanos / 86400e9,
-                            (m_in_nanos % 86400e9) / 3600e9
-                        )
-                    }
-                })
-            }
+            // wall-clock times are shown as such rather than as durations
+            _ if self.settings.x_field == XPlotField::ReceiptUtc => AxisHints::new_x()
+                .label(&x_name)
+                .formatter(|m, _| format_utc_millis(m.value)),
+            UnitOfMeasure::Time(ref time_unit) => AxisHints::new_x()
+                .label(&x_name)
+                .formatter(move |m, r| format_time_tick(m.value, r.end() - r.start(), time_unit)),
             _ => AxisHints::new_x().label(&x_name),
         };
-        let y_axis = AxisHints::new_y().placement(HPlacement::Right);
+        let y_axes = zip(&self.settings.y_axes, &scales)
+            .enumerate()
+            .map(|(i, (axis, scale))| {
+                let units = zip(&self.settings.y_fields, &y_units)
+                    .filter(|((_, line), _)| self.settings.axis_index(line) == i)
+                    .map(|(_, unit)| unit);
+                let placement = match axis.side {
+                    AxisSide::Left => HPlacement::Left,
+                    AxisSide::Right => HPlacement::Right,
+                };
+                let hints = AxisHints::new_y()
+                    .placement(placement)
+                    .label(axis_label(units));
+                if normalized || axis.log_scale {
+                    let scale = *scale;
+                    hints.formatter(move |m, _| format_tick(scale.value_at(m.value)))
+                } else {
+                    hints
+                }
+            })
+            .collect::<Vec<_>>();
 
+        let x_is_utc = self.settings.x_field == XPlotField::ReceiptUtc;
         let cursor_formatter = |name: &str, value: &PlotPoint| {
-            let x_unit = format!(" [{x_unit}]");
-            let y_unit = y_unit
-                .as_ref()
-                .map(|unit| format!(" [{unit}]"))
-                .unwrap_or_default();
-            if name.is_empty() {
-                format!(
-                    "{}: {:.2}{}\ny: {:.2}{}",
-                    x_name, value.x, x_unit, value.y, y_unit
-                )
+            let x_value = if x_is_utc {
+                format_utc_millis(value.x)
             } else {
-                format!(
-                    "{}: {:.2}{}\n{}: {:.2}{}",
-                    x_name, value.x, x_unit, name, value.y, y_unit
-                )
+                format!("{:.2} [{x_unit}]", value.x)
+            };
+            // the series is found by its name, to map back its value
+            let series = legend_labels.iter().position(|label| label == name);
+            match series {
+                Some(i) => format!(
+                    "{}: {}\n{}: {:.2} [{}]",
+                    x_name,
+                    x_value,
+                    name,
+                    series_scales[i].value_at(value.y),
+                    y_units[i]
+                ),
+                None if scales.len() == 1 => format!(
+                    "{}: {}\ny: {:.2}",
+                    x_name,
+                    x_value,
+                    scales[0].value_at(value.y)
+                ),
+                None => format!("{x_name}: {x_value}"),
             }
         };
 
@@ -132,51 +305,203 @@ impl PaneBehavior for Plot2DPane {
                     // lines
                     .hidden_items(None),
             )
-            .label_formatter(cursor_formatter);
+            // dragging with ctrl moves the pane, not the view
+            .allow_drag(!ctrl_pressed);
+        if self.settings.show_stats {
+            // room for the header and a row per series
+            let row_height =
+                ui.text_style_height(&egui::TextStyle::Body) + ui.spacing().item_spacing.y;
+            let stats_height = row_height * (self.settings.y_fields.len() + 1) as f32;
+            plot = plot.height((ui.available_height() - stats_height).max(50.0));
+        }
+        // plots of the same group share the x range, and with it the pause
+        // state, and the cursor
+        let link_id = self.settings.link_group.map(link_group_id);
+        if let Some(id) = link_id {
+            plot = plot
+                .link_axis(id, Vec2b::new(true, false))
+                .link_cursor(id, Vec2b::new(true, false));
+        }
+        // the cursor shows the values of all the series while inspecting
+        plot = if self.paused {
+            plot.show_x(false).show_y(false)
+        } else {
+            plot.label_formatter(cursor_formatter)
+        };
 
         if self.settings.axes_visible {
-            plot = plot.custom_x_axes(vec![x_axis]).custom_y_axes(vec![y_axis]);
+            plot = plot.custom_x_axes(vec![x_axis]).custom_y_axes(y_axes);
         } else {
             plot = plot.show_axes(Vec2b::FALSE);
         }
 
-        plot.show(ui, |plot_ui| {
+        let plot_response = plot.show(ui, |plot_ui| {
             if plot_ui.response().dragged() && ctrl_pressed {
                 response.set_drag_started();
             }
 
-            for ((field, settings), TimeAwarePlotPoints { points, .. }) in
-                zip(&self.settings.y_fields, &self.line_data)
-            {
-                let legend_label = format!(
-                    "{}: {:.5}",
-                    field.name(),
-                    points.last().map(|l| l.y).unwrap_or_default()
+            self.paused = follow(plot_ui, follow_request);
+            self.view_range = (!plot_ui.auto_bounds().x).then(|| plot_ui.plot_bounds().range_x());
+
+            let plotted = zip(lines, &series_scales)
+                .map(|(points, scale)| {
+                    points
+                        .into_iter()
+                        .filter_map(|p| Some(PlotPoint::new(p.x, scale.position_of(p.y)?)))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            let mut export_kind = None;
+            plot_ui.response().context_menu(|ui| {
+                show_menu(
+                    ui,
+                    &mut self.settings_visible,
+                    &mut self.settings,
+                    Some(&mut export_kind),
+                )
+            });
+            // the exports are of what is shown when requested
+            let export = export_kind.map(|kind| match kind {
+                ExportKind::Csv => PendingExport::Csv(plot_ui.plot_bounds().range_x()),
+                ExportKind::Image => PendingExport::Image(Box::new(snapshot(
+                    &self.settings,
+                    plot_ui.plot_bounds(),
+                    plot_ui.response().rect.size(),
+                    &scales,
+                    &y_units,
+                    zip(zip(&series_names, &plotted), &references),
+                    &markers,
+                ))),
+            });
+
+            // references and markers are drawn below the series
+            for (series_references, scale) in zip(&references, &series_scales) {
+                for reference in series_references {
+                    let style = match reference.kind {
+                        ReferenceKind::Line { .. } => LineStyle::Solid,
+                        ReferenceKind::Band { .. } => LineStyle::dashed_dense(),
+                    };
+                    for y in reference.kind.values() {
+                        if let Some(y) = scale.position_of(y) {
+                            plot_ui.hline(
+                                HLine::new(y)
+                                    .color(reference.color)
+                                    .style(style)
+                                    .name(&reference.label),
+                            );
+                        }
+                    }
+                }
+            }
+            for (x, marker) in &markers {
+                plot_ui.vline(
+                    VLine::new(*x)
+                        .color(marker.color)
+                        .style(LineStyle::dashed_loose()),
                 );
+            }
+
+            for (((_, settings), points), label) in
+                zip(zip(&self.settings.y_fields, plotted), &legend_labels)
+            {
                 plot_ui.line(
-                    Line::new(&points[..])
+                    Line::new(PlotPoints::Owned(points))
                         .color(settings.color)
                         .width(settings.width)
-                        .name(legend_label),
+                        .name(label),
                 );
             }
-            plot_ui
-                .response()
-                .context_menu(|ui| show_menu(ui, &mut self.settings_visible, &mut self.settings));
+
+            let hovered_x = plot_ui
+                .pointer_coordinate()
+                .filter(|_| self.paused && plot_ui.response().hovered())
+                .map(|p| p.x);
+            let cursor_x = match link_id {
+                Some(id) => shared_cursor(plot_ui.ctx(), id, hovered_x),
+                None => hovered_x,
+            };
+            if let Some(x) = cursor_x.filter(|_| self.paused) {
+                plot_ui.vline(VLine::new(x).color(Color32::GRAY).width(1.0));
+            }
+            (hovered_x, export)
         });
 
-        egui::Window::new("Plot Settings")
-            .id(ui.auto_id_with("plot_settings"))
-            .auto_sized()
-            .collapsible(true)
-            .movable(true)
-            .open(&mut self.settings_visible)
-            .show(ui.ctx(), |ui| sources_window(ui, &mut self.settings));
+        let (hovered_x, export) = plot_response.inner;
+        if let Some(export) = export {
+            self.open_export_dialog(ui.ctx(), &export);
+            self.pending_export = Some(export);
+        }
+        self.show_export_dialog(ui.ctx());
 
-        if data_settings_digest != self.settings.data_digest() {
-            self.state_valid = false;
+        // the bands are shaded over the plot, not to extend its bounds
+        let transform = plot_response.transform;
+        let painter = ui.painter_at(*transform.frame());
+        for (series_references, scale) in zip(&references, &series_scales) {
+            for reference in series_references {
+                if let ReferenceKind::Band { low, high } = reference.kind
+                    && let (Some(low), Some(high)) =
+                        (scale.position_of(low), scale.position_of(high))
+                {
+                    let frame = transform.frame();
+                    let band = Rect::from_two_pos(
+                        pos2(frame.left(), transform.position_from_point_y(low)),
+                        pos2(frame.right(), transform.position_from_point_y(high)),
+                    );
+                    painter.rect_filled(band, 0.0, reference.color.gamma_multiply(BAND_OPACITY));
+                }
+            }
         }
 
+        let hovered_markers = plot_response
+            .response
+            .hover_pos()
+            .map(|pointer| {
+                markers
+                    .iter()
+                    .filter(|(x, _)| {
+                        (transform.position_from_point_x(*x) - pointer.x).abs()
+                            <= MARKER_HOVER_DISTANCE
+                    })
+                    .map(|(_, marker)| marker)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        if hovered_x.is_some() || !hovered_markers.is_empty() {
+            plot_response.response.on_hover_ui_at_pointer(|ui| {
+                for marker in &hovered_markers {
+                    ui.label(RichText::new(&marker.text).color(marker.color));
+                }
+                let Some(x) = hovered_x else {
+                    return;
+                };
+                let x_value = if x_is_utc {
+                    format_utc_millis(x)
+                } else {
+                    format!("{x:.2} [{x_unit}]")
+                };
+                ui.label(format!("{x_name}: {x_value}"));
+                egui::Grid::new("cursor_values").show(ui, |ui| {
+                    for (((field, settings), line), unit) in
+                        zip(zip(&self.settings.y_fields, &self.line_data), &y_units)
+                    {
+                        ui.label(RichText::new(field.qualified_name()).color(settings.color));
+                        match line.value_at(x) {
+                            Some(y) => ui.monospace(format!("{y:.5} {unit}")),
+                            None => ui.weak("-"),
+                        };
+                        ui.end_row();
+                    }
+                });
+            });
+        }
+
+        if self.settings.show_stats {
+            self.stats_table(ui, transform.bounds().range_x(), &y_units);
+        }
+
+        self.settings_window(ui, data_settings_digest);
         response
     }
 
@@ -184,53 +509,751 @@ impl PaneBehavior for Plot2DPane {
     fn update(&mut self, messages: &[&TimedMessage]) {
         if !self.state_valid {
             self.line_data.clear();
+            self.color_data.clear();
+            self.color_value = None;
+            self.state_transitions.clear();
         }
+        // one line per series, each filled at the rate of its own message
+        self.line_data
+            .resize(self.settings.y_fields.len(), TimeAwarePlotPoints::new());
+        self.color_data
+            .resize(self.settings.y_fields.len(), TimeAwarePlotPoints::new());
 
         let PlotSettings {
             x_field,
             y_fields,
             points_lifespan,
+            xy,
             ..
         } = &self.settings;
+        let color_field = match &xy.color_by {
+            ColorBy::Field(field) => Some(field),
+            _ => None,
+        };
+
+        // conversions from the native unit of each field to the display unit
+        // of its axis
+        let conversions: Vec<(f64, f64)> = y_fields
+            .iter()
+            .map(|(field, line)| {
+                let unit = field.unit();
+                unit.conversion_to(&unit.display_unit(self.settings.display_unit_of(line)))
+                    .unwrap_or((1.0, 0.0))
+            })
+            .collect();
 
         // iter on filtered messages based on lifespan set
         for msg in messages
             .iter()
             .filter(|msg| points_lifespan > &msg.time.elapsed())
         {
-            let x: f64 = x_field.extract_from_message(msg).log_unwrap();
-            let ys: Vec<f64> = y_fields
-                .iter()
-                .map(|(field, _)| field.extract_from_message(msg).log_unwrap())
-                .collect();
-
-            if self.line_data.len() < ys.len() {
-                self.line_data.resize(ys.len(), TimeAwarePlotPoints::new());
+            if let Some(field) = &self.settings.markers.state_field {
+                self.state_transitions.update(field, msg);
+            }
+            // the color field may come from another message, its latest
+            // value is held
+            if let Some(value) = color_field.and_then(|f| f.extract_from_message(msg).ok()) {
+                self.color_value = Some(value);
             }
 
-            for (points, y) in zip(&mut self.line_data, ys) {
-                points.push(msg.time, PlotPoint::new(x, y));
+            // the x value may be missing from some messages (derived channels,
+            // fields of another message, T0 not set), skip them
+            let Ok(x) = x_field.extract_from_message(msg) else {
+                continue;
+            };
+            let ys: Vec<Option<f64>> = zip(y_fields, &conversions)
+                .map(|((field, _), (scale, offset))| {
+                    field
+                        .extract_from_message(msg)
+                        .ok()
+                        .map(|y| y * scale + offset)
+                })
+                .collect();
+
+            // series of other messages are left untouched
+            for ((points, colors), y) in zip(zip(&mut self.line_data, &mut self.color_data), ys) {
+                if let Some(y) = y {
+                    points.push(msg.time, PlotPoint::new(x, y));
+                    if color_field.is_some() {
+                        let color = self.color_value.unwrap_or(f64::NAN);
+                        colors.push(msg.time, PlotPoint::new(x, color));
+                    }
+                }
             }
         }
 
-        // clear points older than lifespan set
-        for line in &mut self.line_data {
-            line.clear_older_than(*points_lifespan);
+        // clear points older than lifespan set, keeping them longer while
+        // paused so that the frozen view can still be inspected
+        let lifespan = if self.paused {
+            *points_lifespan * PAUSED_LIFESPANS
+        } else {
+            *points_lifespan
+        };
+        for line in self.line_data.iter_mut().chain(&mut self.color_data) {
+            line.clear_older_than(lifespan);
         }
+        self.state_transitions.clear_older_than(lifespan);
 
         self.state_valid = true;
     }
 
     fn get_message_subscriptions(&self) -> Box<dyn Iterator<Item = u32>> {
-        Box::new(Some(self.settings.plot_message_id).into_iter())
+        let mut ids = self.settings.y_msg_ids();
+        ids.extend(self.settings.x_field.msg_ids());
+        ids.extend(
+            self.settings
+                .markers
+                .state_field
+                .as_ref()
+                .map(|f| f.msg_id()),
+        );
+        if let ColorBy::Field(field) = &self.settings.xy.color_by {
+            ids.extend(field.msg_ids());
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        Box::new(ids.into_iter())
     }
 
     fn should_send_message_history(&self) -> bool {
         !self.state_valid
     }
+
+    fn init(&mut self, pane_id: PaneId) {
+        self.pane_id = Some(pane_id);
+    }
 }
 
-fn show_menu(ui: &mut Ui, settings_visible: &mut bool, settings: &mut PlotSettings) {
+impl Plot2DPane {
+    fn settings_window(&mut self, ui: &mut Ui, data_settings_digest: u64) {
+        egui::Window::new("Plot Settings")
+            .id(ui.auto_id_with("plot_settings"))
+            .auto_sized()
+            .collapsible(true)
+            .movable(true)
+            .open(&mut self.settings_visible)
+            .show(ui.ctx(), |ui| sources_window(ui, &mut self.settings));
+
+        if data_settings_digest != self.settings.data_digest() {
+            self.state_valid = false;
+        }
+    }
+
+    /// Table of the statistics of each series over the `x_range`.
+    fn stats_table(&self, ui: &mut Ui, x_range: RangeInclusive<f64>, units: &[UnitOfMeasure]) {
+        egui::Grid::new(ui.auto_id_with("stats"))
+            .num_columns(7)
+            .striped(true)
+            .show(ui, |ui| {
+                for header in ["Series", "Min", "Max", "Mean", "Std", "Last", "Rate"] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+                for (((field, line), points), unit) in
+                    zip(zip(&self.settings.y_fields, &self.line_data), units)
+                {
+                    ui.label(RichText::new(field.qualified_name()).color(line.color));
+                    match SeriesStats::of(points.iter_range(x_range.clone())) {
+                        Some(stats) => {
+                            for value in [stats.min, stats.max, stats.mean, stats.std, stats.last] {
+                                let text = format!("{} {unit}", format_tick(value));
+                                ui.monospace(text.trim_end());
+                            }
+                            match stats.rate {
+                                Some(rate) => ui.monospace(format!("{rate:.1} Hz")),
+                                None => ui.weak("-"),
+                            };
+                        }
+                        None => {
+                            for _ in 0..6 {
+                                ui.weak("-");
+                            }
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    /// Spectrum of the chosen series, computed on its latest points, and kept
+    /// while paused.
+    fn spectrum_ui(
+        &mut self,
+        ui: &mut Ui,
+        response: &mut PaneResponse,
+        ctrl_pressed: bool,
+        follow_request: Option<bool>,
+    ) {
+        let outdated = self
+            .spectrum
+            .as_ref()
+            .is_none_or(|(settings, _)| *settings != self.settings.spectrum);
+        if !self.paused || outdated {
+            self.spectrum = self
+                .compute_spectrum()
+                .map(|spectrum| (self.settings.spectrum.clone(), spectrum));
+        }
+
+        ui.horizontal(|ui| {
+            spectrum_settings(ui, &mut self.settings);
+            match &self.spectrum {
+                Some((settings, spectrum)) => ui.weak(format!(
+                    "{:.1} Hz sampling, {:.3} Hz resolution, {} averaged",
+                    spectrum.sample_rate,
+                    spectrum.sample_rate / settings.window_len as f64,
+                    spectrum.segments
+                )),
+                None => ui.weak("Not enough points"),
+            };
+        });
+
+        let series = self.settings.y_fields.get(self.settings.spectrum.series);
+        let unit = series
+            .map(|(field, line)| {
+                field
+                    .unit()
+                    .display_unit(self.settings.display_unit_of(line))
+            })
+            .unwrap_or(UnitOfMeasure::Adimensional);
+        let y_label = match (self.settings.spectrum.scale, unit) {
+            (SpectrumScale::Amplitude, UnitOfMeasure::Adimensional) => "Amplitude".to_owned(),
+            (SpectrumScale::Amplitude, unit) => format!("Amplitude [{unit}]"),
+            (SpectrumScale::PowerDensity, UnitOfMeasure::Adimensional) => "PSD [dB/Hz]".to_owned(),
+            (SpectrumScale::PowerDensity, unit) => format!("PSD [dB({unit}²/Hz)]"),
+        };
+        let series = series.map(|(field, line)| (field.qualified_name(), line.clone()));
+        let plot = egui_plot::Plot::new("spectrum")
+            .x_axis_label("Frequency [Hz]")
+            .y_axis_label(y_label)
+            .auto_bounds(Vec2b::TRUE)
+            .legend(Legend::default().position(Corner::RightTop))
+            .label_formatter(|_, point| format!("{:.2} Hz\n{:.4}", point.x, point.y))
+            .allow_drag(!ctrl_pressed);
+        plot.show(ui, |plot_ui| {
+            if plot_ui.response().dragged() && ctrl_pressed {
+                response.set_drag_started();
+            }
+            self.paused = follow(plot_ui, follow_request);
+
+            if let (Some((name, line)), Some((_, spectrum))) = (series, &self.spectrum) {
+                plot_ui.line(
+                    Line::new(PlotPoints::Owned(spectrum.points.clone()))
+                        .color(line.color)
+                        .width(line.width)
+                        .name(name),
+                );
+            }
+            plot_ui.response().context_menu(|ui| {
+                show_menu(ui, &mut self.settings_visible, &mut self.settings, None)
+            });
+        });
+    }
+
+    /// Spectrum of the latest points of the chosen series, sampled at the rate
+    /// measured on the x axis if a time, as the onboard timestamps are more
+    /// regular than the receipt times, or on the receipt times otherwise.
+    fn compute_spectrum(&self) -> Option<Spectrum> {
+        let settings = &self.settings.spectrum;
+        let points = self.line_data.get(settings.series)?;
+        let (times, points): (Vec<Instant>, Vec<PlotPoint>) =
+            points.iter_last(settings.samples_needed()).unzip();
+        let span = match self.settings.x_field.unit() {
+            UnitOfMeasure::Time(ref unit) => (points.last()?.x - points.first()?.x) * unit.scale(),
+            _ => times.last()?.duration_since(*times.first()?).as_secs_f64(),
+        };
+        let sample_rate = (points.len() - 1) as f64 / span;
+        let samples: Vec<f64> = points.iter().map(|p| p.y).collect();
+        Spectrum::of(&samples, sample_rate, settings).filter(|_| span > 0.0)
+    }
+
+    /// Series against the field of the x axis, in the order they are received,
+    /// colored by the chosen value.
+    fn xy_ui(
+        &mut self,
+        ui: &mut Ui,
+        response: &mut PaneResponse,
+        ctrl_pressed: bool,
+        follow_request: Option<bool>,
+    ) {
+        ui.horizontal(|ui| xy_settings(ui, &mut self.settings));
+        let xy = self.settings.xy.clone();
+
+        // the trail ends at the latest point received
+        let latest = self
+            .line_data
+            .iter()
+            .filter_map(|points| points.iter_last(1).next().map(|(time, _)| time))
+            .max();
+        let since = xy
+            .trail
+            .zip(latest)
+            .and_then(|(trail, latest)| latest.checked_sub(trail));
+        let in_trail = |points: &TimeAwarePlotPoints| -> Vec<(Instant, PlotPoint)> {
+            match since {
+                Some(since) => points.iter_since(since).collect(),
+                None => points.iter_last(usize::MAX).collect(),
+            }
+        };
+        let trails: Vec<_> = self.line_data.iter().map(in_trail).collect();
+        // values the colors are mapped from, none to use the series color
+        let values: Vec<Vec<f64>> = match &xy.color_by {
+            ColorBy::Series => vec![Vec::new(); trails.len()],
+            ColorBy::Time => trails
+                .iter()
+                .map(|trail| {
+                    trail
+                        .iter()
+                        .map(|(time, _)| {
+                            let age = latest.map(|l| l.duration_since(*time)).unwrap_or_default();
+                            -age.as_secs_f64()
+                        })
+                        .collect()
+                })
+                .collect(),
+            ColorBy::Field(_) => self
+                .color_data
+                .iter()
+                .map(|colors| in_trail(colors).into_iter().map(|(_, p)| p.y).collect())
+                .collect(),
+        };
+        let range = value_range(values.iter().flatten().copied());
+
+        let x_name = self.settings.x_field.name();
+        let x_label = name_with_unit(&x_name, &self.settings.x_field.unit());
+        let y_units: Vec<_> = self
+            .settings
+            .y_fields
+            .iter()
+            .map(|(field, line)| {
+                field
+                    .unit()
+                    .display_unit(self.settings.display_unit_of(line))
+            })
+            .collect();
+        let y_label = match &self.settings.y_fields[..] {
+            [(field, _)] => name_with_unit(&field.name(), &y_units[0]),
+            _ => axis_label(y_units.iter()),
+        };
+        let series: Vec<_> = self
+            .settings
+            .y_fields
+            .iter()
+            .map(|(field, line)| (field.qualified_name(), line.clone()))
+            .collect();
+
+        let mut plot = egui_plot::Plot::new("xy")
+            .x_axis_label(x_label)
+            .y_axis_label(y_label)
+            .legend(Legend::default().position(Corner::RightTop))
+            .label_formatter(move |name, point| {
+                format!("{name}\n{x_name}: {:.3}\n{:.3}", point.x, point.y)
+            })
+            .allow_drag(!ctrl_pressed);
+        if xy.equal_aspect {
+            plot = plot.data_aspect(1.0);
+        }
+        let colorbar = range.clone().filter(|_| xy.color_by != ColorBy::Series);
+        if colorbar.is_some() {
+            let row_height =
+                ui.text_style_height(&egui::TextStyle::Body) + ui.spacing().item_spacing.y;
+            plot = plot.height((ui.available_height() - row_height).max(50.0));
+        }
+        plot.show(ui, |plot_ui| {
+            if plot_ui.response().dragged() && ctrl_pressed {
+                response.set_drag_started();
+            }
+            self.paused = follow(plot_ui, follow_request);
+
+            for (((name, line), trail), values) in zip(zip(&series, &trails), &values) {
+                let points: Vec<PlotPoint> = trail.iter().map(|(_, p)| *p).collect();
+                let levels: Vec<Option<usize>> = match &range {
+                    Some(range) => values.iter().map(|v| color_level(*v, range)).collect(),
+                    None => Vec::new(),
+                };
+                show_xy_series(plot_ui, name, line, &points, &levels, &xy);
+            }
+            plot_ui.response().context_menu(|ui| {
+                show_menu(ui, &mut self.settings_visible, &mut self.settings, None)
+            });
+        });
+
+        if let Some(range) = colorbar {
+            let (label, unit) = match &xy.color_by {
+                ColorBy::Field(field) => (field.qualified_name(), field.unit()),
+                _ => ("Age".to_owned(), UnitOfMeasure::Time(TimeUnits::Second)),
+            };
+            show_colorbar(ui, xy.colormap, &name_with_unit(&label, &unit), range);
+        }
+    }
+
+    /// References of each series, in the display unit of its axis, with the
+    /// limits of the alarm rules monitoring it if enabled.
+    fn series_references(&self, ctx: &Context) -> Vec<Vec<Reference>> {
+        let engine = self.settings.markers.alarm_limits.then(|| ctx.alarms());
+        let engine = engine.as_ref().map(|engine| engine.lock());
+        self.settings
+            .y_fields
+            .iter()
+            .map(|(field, line)| {
+                let mut references = line.references.clone();
+                if let Some(engine) = &engine {
+                    let unit = field.unit();
+                    let conversion = unit
+                        .conversion_to(&unit.display_unit(self.settings.display_unit_of(line)))
+                        .unwrap_or((1.0, 0.0));
+                    let rules = engine.alarms().map(|(_, rule, _)| rule);
+                    references.extend(
+                        Reference::alarm_limits(field, rules)
+                            .into_iter()
+                            .map(|r| r.converted(conversion)),
+                    );
+                }
+                references
+            })
+            .collect()
+    }
+
+    /// Markers of the enabled sources, with their position on the x axis,
+    /// only shown on receipt time axes.
+    fn event_markers(&self, ctx: &Context) -> Vec<(f64, EventMarker)> {
+        let lifespan = self.settings.points_lifespan;
+        let mut markers: Vec<EventMarker> = self.state_transitions.markers().cloned().collect();
+        if self.settings.markers.commands {
+            markers.extend(command_markers(&ctx.tc_log().lock(), lifespan));
+        }
+        if self.settings.markers.alarms {
+            markers.extend(alarm_markers(&ctx.alarms().lock(), lifespan));
+        }
+        markers
+            .into_iter()
+            .filter_map(|marker| Some((self.settings.x_field.receipt_x(marker.time)?, marker)))
+            .collect()
+    }
+
+    fn export_dialog_id(&self) -> Id {
+        *(self.pane_id.unwrap_or(PaneId::from_str("plot_pane")) / "export_dialog")
+    }
+
+    fn open_export_dialog(&self, ctx: &Context, export: &PendingExport) {
+        let initial_path = eframe::storage_dir(APP_NAME);
+        let mut file_dialog = FileDialog::save_file(initial_path).title(match export {
+            PendingExport::Csv(_) => "Export Visible Data",
+            PendingExport::Image(_) => "Save Image",
+        });
+        file_dialog.open();
+        let id = self.export_dialog_id();
+        ctx.memory_mut(|m| m.data.insert_temp(id, Arc::new(Mutex::new(file_dialog))));
+    }
+
+    fn show_export_dialog(&mut self, ctx: &Context) {
+        let id = self.export_dialog_id();
+        let Some(file_dialog) = ctx.memory_mut(|m| m.data.get_temp::<Arc<Mutex<FileDialog>>>(id))
+        else {
+            return;
+        };
+        let mut file_dialog = file_dialog.lock();
+        let selected = file_dialog.show(ctx).selected();
+        let Some(path) = file_dialog.path().filter(|_| selected) else {
+            return;
+        };
+        let result = match self.pending_export.take() {
+            Some(PendingExport::Csv(x_range)) => {
+                self.export_csv(&path.with_extension("csv"), x_range)
+            }
+            Some(PendingExport::Image(snapshot)) => snapshot.save(&path),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            error!("Failed to export the plot: {:#}", e);
+        }
+    }
+
+    /// Writes the points of all the series in the `x_range` to a CSV file.
+    fn export_csv(&self, path: &Path, x_range: RangeInclusive<f64>) -> anyhow::Result<()> {
+        let series = zip(&self.settings.y_fields, &self.line_data)
+            .map(|((field, line), points)| CsvSeries {
+                name: field.qualified_name(),
+                unit: field
+                    .unit()
+                    .display_unit(self.settings.display_unit_of(line))
+                    .to_string(),
+                points,
+            })
+            .collect::<Vec<_>>();
+        let x_header = format!(
+            "{} [{}]",
+            self.settings.x_field.name(),
+            self.settings.x_field.unit()
+        );
+        let file = BufWriter::new(File::create(path)?);
+        write_csv(file, &x_header, &series, x_range)?;
+        Ok(())
+    }
+}
+
+/// Export waiting for the file to be chosen.
+#[derive(Clone, Debug)]
+enum PendingExport {
+    /// Data in the x range that was shown
+    Csv(RangeInclusive<f64>),
+    /// Image of the plot as it was shown
+    Image(Box<Snapshot>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ExportKind {
+    Csv,
+    Image,
+}
+
+/// Snapshot of the plot shown in the `bounds`, of the given `size`.
+fn snapshot<'a>(
+    settings: &PlotSettings,
+    bounds: PlotBounds,
+    size: Vec2,
+    scales: &[AxisScale],
+    y_units: &[UnitOfMeasure],
+    series: impl Iterator<Item = ((&'a String, &'a Vec<PlotPoint>), &'a Vec<Reference>)>,
+    markers: &[(f64, EventMarker)],
+) -> Snapshot {
+    const TICKS: usize = 5;
+    let [x_min, y_min] = bounds.min();
+    let [x_max, y_max] = bounds.max();
+    let x_unit = settings.x_field.unit();
+    let x_ticks = nice_ticks(x_min, x_max, TICKS)
+        .into_iter()
+        .map(|x| {
+            let label = match x_unit {
+                _ if settings.x_field == XPlotField::ReceiptUtc => format_utc_millis(x),
+                UnitOfMeasure::Time(ref time_unit) => format_time_tick(x, x_max - x_min, time_unit),
+                _ => format_tick(x),
+            };
+            (x, label)
+        })
+        .collect();
+    let x_label = match x_unit {
+        UnitOfMeasure::Time(_) => settings.x_field.name(),
+        _ => format!("{} [{}]", settings.x_field.name(), x_unit),
+    };
+    let y_axes = zip(&settings.y_axes, scales)
+        .enumerate()
+        .map(|(i, (axis, scale))| {
+            let units = zip(&settings.y_fields, y_units)
+                .filter(|((_, line), _)| settings.axis_index(line) == i)
+                .map(|(_, unit)| unit);
+            SnapshotAxis {
+                side: axis.side,
+                label: axis_label(units),
+                ticks: scale
+                    .ticks([y_min, y_max], TICKS)
+                    .into_iter()
+                    .map(|(position, value)| (position, format_tick(value)))
+                    .collect(),
+            }
+        })
+        .collect();
+    let mut references = Vec::new();
+    let series = zip(&settings.y_fields, series)
+        .map(|((_, line), ((name, points), series_references))| {
+            // the references are mapped to plot space with the scale of the series
+            let scale = scales[settings.axis_index(line)];
+            references.extend(series_references.iter().filter_map(|reference| {
+                let kind = match reference.kind {
+                    ReferenceKind::Line { value } => ReferenceKind::Line {
+                        value: scale.position_of(value)?,
+                    },
+                    ReferenceKind::Band { low, high } => ReferenceKind::Band {
+                        low: scale.position_of(low)?,
+                        high: scale.position_of(high)?,
+                    },
+                };
+                Some(SnapshotReference {
+                    label: reference.label.clone(),
+                    kind,
+                    color: reference.color,
+                })
+            }));
+            SnapshotSeries {
+                name: name.clone(),
+                color: line.color,
+                width: line.width,
+                points: points.clone(),
+            }
+        })
+        .collect();
+    let markers = markers
+        .iter()
+        .map(|(x, marker)| SnapshotMarker {
+            x: *x,
+            color: marker.color,
+        })
+        .collect();
+    Snapshot {
+        size: [size.x.max(1.0) as u32, size.y.max(1.0) as u32],
+        x_label,
+        x_range: [x_min, x_max],
+        y_range: [y_min, y_max],
+        x_ticks,
+        y_axes,
+        series,
+        references,
+        markers,
+    }
+}
+
+/// Formats a tick of a time axis, with a precision suited to its `span`.
+fn format_time_tick(value: f64, span: f64, time_unit: &TimeUnits) -> String {
+    let scaling_factor_to_nanos = time_unit.scale() * 1e9;
+    let r_span_in_nanos = span.abs() * scaling_factor_to_nanos;
+    let m_in_nanos = value * scaling_factor_to_nanos;
+    // all the following numbers are arbitrary
+    // they are chosen based on common sense
+    if r_span_in_nanos < 4e3 {
+        format!("{m_in_nanos:.0}ns")
+    } else if r_span_in_nanos < 4e6 {
+        format!("{:.0}µs", m_in_nanos / 1e3)
+    } else if r_span_in_nanos < 4e9 {
+        format!("{:.0}ms", m_in_nanos / 1e6)
+    } else if r_span_in_nanos < 24e10 {
+        format!("{:.0}s", m_in_nanos / 1e9)
+    } else if r_span_in_nanos < 144e11 {
+        format!("{:.0}m{:.0}s", m_in_nanos / 60e9, (m_in_nanos % 60e9) / 1e9)
+    } else if r_span_in_nanos < 3456e11 {
+        format!(
+            "{:.0}h{:.0}m",
+            m_in_nanos / 3600e9,
+            (m_in_nanos % 3600e9) / 60e9
+        )
+    } else {
+        format!(
+            "{:.0}d{:.0}h",
+            m_in_nanos / 86400e9,
+            (m_in_nanos % 86400e9) / 3600e9
+        )
+    }
+}
+
+/// Formats milliseconds since the Unix epoch as a UTC time of day.
+fn format_utc_millis(millis: f64) -> String {
+    Timestamp::from_millisecond(millis as i64)
+        .map(|t| t.strftime("%H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+/// Opacity of the shading of the limit bands.
+const BAND_OPACITY: f32 = 0.15;
+/// Distance in points from a marker within which it is described on hover.
+const MARKER_HOVER_DISTANCE: f32 = 4.0;
+/// Number of lifespans the points are kept for while paused, so that the view
+/// can be inspected for a while without the buffers growing without limit.
+const PAUSED_LIFESPANS: u32 = 4;
+
+/// Number of groups the plots can be linked in.
+const LINK_GROUPS: u8 = 4;
+
+fn link_group_id(group: u8) -> Id {
+    Id::new("plot_link_group").with(group)
+}
+
+/// Publishes the inspection cursor of the hovered plot to its link group, and
+/// returns the cursor of the group when another plot of it is hovered.
+fn shared_cursor(ctx: &Context, group: Id, hovered_x: Option<f64>) -> Option<f64> {
+    let id = group.with("cursor");
+    let pass = ctx.cumulative_pass_nr();
+    if let Some(x) = hovered_x {
+        ctx.data_mut(|d| d.insert_temp(id, (x, pass)));
+        return Some(x);
+    }
+    // plots drawn before the hovered one see the cursor of the last pass
+    ctx.data(|d| d.get_temp::<(f64, u64)>(id))
+        .filter(|(_, set_at)| pass <= set_at + 1)
+        .map(|(x, _)| x)
+}
+
+/// Draws a series of an XY plot, each run of points of the same color `levels`
+/// as a separate item, in the color of the series if there are no levels.
+fn show_xy_series(
+    plot_ui: &mut PlotUi,
+    name: &str,
+    line: &LineSettings,
+    points: &[PlotPoint],
+    levels: &[Option<usize>],
+    xy: &XySettings,
+) {
+    let runs = if levels.is_empty() {
+        vec![(None, points.to_vec())]
+    } else {
+        level_runs(points, levels)
+    };
+    for (level, run) in runs {
+        let color = level.map_or(line.color, |level| xy.colormap.level_color(level));
+        if xy.style.has_line() {
+            plot_ui.line(
+                Line::new(PlotPoints::Owned(run.clone()))
+                    .color(color)
+                    .width(line.width)
+                    .name(name),
+            );
+        }
+        if xy.style.has_points() {
+            plot_ui.points(
+                Points::new(PlotPoints::Owned(run))
+                    .color(color)
+                    .radius(xy.point_radius)
+                    .name(name),
+            );
+        }
+    }
+    // ring around the latest point, as the current position along a track
+    if let Some(last) = points.last() {
+        plot_ui.points(
+            Points::new(PlotPoints::Owned(vec![*last]))
+                .color(line.color)
+                .radius(xy.point_radius + 4.0)
+                .filled(false)
+                .name(name),
+        );
+    }
+}
+
+/// Legend of the colors of an XY plot, from the start to the end of `range`.
+fn show_colorbar(ui: &mut Ui, colormap: Colormap, label: &str, range: RangeInclusive<f64>) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.monospace(format_tick(*range.start()));
+        let height = ui.text_style_height(&egui::TextStyle::Body);
+        let (rect, _) = ui.allocate_exact_size(Vec2::new(160.0, height), Sense::hover());
+        let width = rect.width() / COLOR_LEVELS as f32;
+        for level in 0..COLOR_LEVELS {
+            let left = rect.left() + width * level as f32;
+            let slice =
+                Rect::from_min_max(pos2(left, rect.top()), pos2(left + width, rect.bottom()));
+            ui.painter()
+                .rect_filled(slice, 0.0, colormap.level_color(level));
+        }
+        ui.monospace(format_tick(*range.end()));
+    });
+}
+
+/// Pauses or follows the data as requested, returns whether paused. Panning
+/// or zooming disables the auto bounds, pausing the view.
+fn follow(plot_ui: &mut PlotUi, follow_request: Option<bool>) -> bool {
+    match follow_request {
+        Some(live) => {
+            plot_ui.set_auto_bounds(Vec2b::new(live, live));
+            !live
+        }
+        None => !plot_ui.auto_bounds().x,
+    }
+}
+
+/// Context menu of the plot, offering the exports if `export` is given.
+fn show_menu(
+    ui: &mut Ui,
+    settings_visible: &mut bool,
+    settings: &mut PlotSettings,
+    export: Option<&mut Option<ExportKind>>,
+) {
     ui.set_max_width(200.0); // To make sure we wrap long text
 
     if ui.button("Source Data Settings…").clicked() {
@@ -239,20 +1262,74 @@ fn show_menu(ui: &mut Ui, settings_visible: &mut bool, settings: &mut PlotSettin
     }
 
     ui.checkbox(&mut settings.axes_visible, "Show Axes");
+
+    ui.menu_button("Link Group", |ui| {
+        ui.radio_value(&mut settings.link_group, None, "None");
+        for group in 1..=LINK_GROUPS {
+            ui.radio_value(
+                &mut settings.link_group,
+                Some(group),
+                format!("Group {group}"),
+            );
+        }
+    })
+    .response
+    .on_hover_text("Plots in the same group share the x range, the pause and the cursor");
+
+    let Some(export) = export else {
+        return;
+    };
+    ui.separator();
+    if ui.button("Export Visible Data (CSV)…").clicked() {
+        *export = Some(ExportKind::Csv);
+        ui.close_menu();
+    }
+    if ui.button("Save Image (PNG/SVG)…").clicked() {
+        *export = Some(ExportKind::Image);
+        ui.close_menu();
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct PlotSettings {
-    /// The message id to plot
+    /// The message whose fields are offered for the x-axis and new series
     pub(super) plot_message_id: u32,
-    /// The field to plot on the x-axis
+    /// The field to plot on the x-axis, shared by all the series
     pub(super) x_field: XPlotField,
-    /// The fields to plot, with their respective line settings
+    /// The fields to plot, of any message, with their respective line settings
     pub(super) y_fields: Vec<(YPlotField, LineSettings)>,
     /// Whether to show the axes of the plot
     pub(super) axes_visible: bool,
     /// Points will be shown for this duration before being removed
     pub(super) points_lifespan: Duration,
+    /// The y axes the series are assigned to, at least one
+    #[serde(default = "default_y_axes")]
+    pub(super) y_axes: Vec<YAxisSettings>,
+    /// Group of plots sharing the x range, the pause and the cursor
+    #[serde(default)]
+    pub(super) link_group: Option<u8>,
+    /// Sources of the markers of events
+    #[serde(default)]
+    pub(super) markers: MarkerSettings,
+    /// Whether the series are shown over time or as a spectrum
+    #[serde(default)]
+    pub(super) mode: PlotMode,
+    /// Whether to show the statistics of the series over the visible window
+    #[serde(default)]
+    pub(super) show_stats: bool,
+    #[serde(default)]
+    pub(super) spectrum: SpectrumSettings,
+    #[serde(default)]
+    pub(super) xy: XySettings,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+enum PlotMode {
+    #[default]
+    TimeSeries,
+    Spectrum,
+    /// Series against the field of the x axis, in the order received
+    Xy,
 }
 
 impl PlotSettings {
@@ -261,9 +1338,47 @@ impl PlotSettings {
         self.y_fields.push((field, line_settings));
     }
 
-    fn clear_fields(&mut self) {
-        self.x_field = XPlotField::MsgReceiptTimestamp;
-        self.y_fields.clear();
+    /// Index of the axis of the series, the first one if no longer existing.
+    fn axis_index(&self, line: &LineSettings) -> usize {
+        if line.axis < self.y_axes.len() {
+            line.axis
+        } else {
+            0
+        }
+    }
+
+    /// Display unit of the axis of the series, if chosen.
+    fn display_unit_of(&self, line: &LineSettings) -> Option<&str> {
+        self.y_axes
+            .get(self.axis_index(line))
+            .and_then(|axis| axis.display_unit.as_deref())
+    }
+
+    /// Removes the axis, moving its series to the first one.
+    fn remove_axis(&mut self, index: usize) {
+        if self.y_axes.len() <= 1 {
+            return;
+        }
+        self.y_axes.remove(index);
+        for (_, line) in &mut self.y_fields {
+            if line.axis == index {
+                line.axis = 0;
+            } else if line.axis > index {
+                line.axis -= 1;
+            }
+        }
+    }
+
+    /// Ids of the messages the series are read from, sorted and deduplicated.
+    fn y_msg_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self
+            .y_fields
+            .iter()
+            .flat_map(|(field, _)| field.msg_ids())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
     }
 
     /// Returns a digest of the data settings, used to check if the settings
@@ -272,10 +1387,14 @@ impl PlotSettings {
     fn data_digest(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
         self.x_field.hash(&mut hasher);
-        for (field, _) in &self.y_fields {
+        for (field, line) in &self.y_fields {
             field.hash(&mut hasher);
+            // the values are stored in the display unit of the axis
+            self.display_unit_of(line).hash(&mut hasher);
         }
         self.points_lifespan.as_secs().hash(&mut hasher);
+        self.markers.state_field.hash(&mut hasher);
+        self.xy.color_by.hash(&mut hasher);
         hasher.finish()
     }
 }
@@ -291,14 +1410,54 @@ impl Default for PlotSettings {
             y_fields,
             axes_visible: true,
             points_lifespan: Duration::from_secs(600),
+            y_axes: default_y_axes(),
+            link_group: None,
+            markers: MarkerSettings::default(),
+            mode: PlotMode::default(),
+            show_stats: false,
+            spectrum: SpectrumSettings::default(),
+            xy: XySettings::default(),
         }
     }
 }
 
+fn default_y_axes() -> Vec<YAxisSettings> {
+    vec![YAxisSettings::default()]
+}
+
+/// Name followed by the unit, if any.
+fn name_with_unit(name: &str, unit: &UnitOfMeasure) -> String {
+    match unit {
+        UnitOfMeasure::Adimensional => name.to_owned(),
+        unit => format!("{name} [{unit}]"),
+    }
+}
+
+/// Label of an axis, from the unit of its series if they share it.
+fn axis_label<'a>(mut units: impl Iterator<Item = &'a UnitOfMeasure>) -> String {
+    let Some(first) = units.next() else {
+        return String::new();
+    };
+    if !units.all(|unit| unit == first) {
+        return String::new();
+    }
+    match (first.quantity(), first) {
+        (_, UnitOfMeasure::Adimensional) => String::new(),
+        (Some(quantity), unit) => format!("{quantity} [{unit}]"),
+        (None, unit) => format!("[{unit}]"),
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct LineSettings {
     width: f32,
     color: Color32,
+    /// Index of the y axis the series is plotted on
+    #[serde(default)]
+    axis: usize,
+    /// Reference lines and limit bands, in the display unit of the axis
+    #[serde(default)]
+    references: Vec<Reference>,
 }
 
 impl Default for LineSettings {
@@ -306,6 +1465,8 @@ impl Default for LineSettings {
         Self {
             width: 1.0,
             color: Color32::BLUE,
+            axis: 0,
+            references: Vec::new(),
         }
     }
 }
@@ -314,36 +1475,6 @@ impl Hash for LineSettings {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.width.to_bits().hash(state);
         self.color.hash(state);
-    }
-}
-
-#[derive(Clone, Debug)]
-struct TimeAwarePlotPoints {
-    times: Vec<Instant>,
-    points: Vec<PlotPoint>,
-}
-
-impl TimeAwarePlotPoints {
-    fn new() -> Self {
-        Self {
-            times: Vec::new(),
-            points: Vec::new(),
-        }
-    }
-
-    fn push(&mut self, time: Instant, point: PlotPoint) {
-        self.times.push(time);
-        self.points.push(point);
-    }
-
-    fn clear_older_than(&mut self, lifespan: Duration) {
-        while let Some(time) = self.times.first().copied() {
-            if time.elapsed() > lifespan {
-                self.times.remove(0);
-                self.points.remove(0);
-            } else {
-                break;
-            }
-        }
+        self.axis.hash(state);
     }
 }
@@ -0,0 +1,295 @@
+//! Annotations drawn over the plot: reference lines and limit bands of the
+//! series, and vertical markers of the events happened while plotting.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use egui::Color32;
+use jiff::{Timestamp, Zoned};
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter};
+
+use crate::{
+    alarms::{AlarmCondition, AlarmEngine, AlarmEventKind, AlarmRule},
+    mavlink::{TimedMessage, reflection::IndexedField},
+    tc_log::TcLog,
+};
+
+use super::fields::YPlotField;
+
+/// Horizontal reference of a series, in the display unit of its axis.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reference {
+    /// Shown in the legend, if not empty
+    pub label: String,
+    pub kind: ReferenceKind,
+    pub color: Color32,
+}
+
+impl Default for Reference {
+    fn default() -> Self {
+        Self {
+            label: String::new(),
+            kind: ReferenceKind::Line { value: 0.0 },
+            color: Color32::RED,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Display, EnumIter)]
+pub enum ReferenceKind {
+    /// Line at a value, as the MEOP of a tank
+    Line { value: f64 },
+    /// Shaded band between two values, as a redline range
+    Band { low: f64, high: f64 },
+}
+
+impl ReferenceKind {
+    /// Values the lines of the reference are drawn at.
+    pub fn values(&self) -> Vec<f64> {
+        match *self {
+            ReferenceKind::Line { value } => vec![value],
+            ReferenceKind::Band { low, high } => vec![low, high],
+        }
+    }
+
+    /// Same reference as the given kind, keeping the values where possible.
+    pub fn converted_to(self, kind: ReferenceKind) -> Self {
+        match (self, kind) {
+            (ReferenceKind::Band { low, .. }, ReferenceKind::Line { .. }) => {
+                ReferenceKind::Line { value: low }
+            }
+            (ReferenceKind::Line { value }, ReferenceKind::Band { .. }) => ReferenceKind::Band {
+                low: value,
+                high: value,
+            },
+            (kind, _) => kind,
+        }
+    }
+}
+
+impl Reference {
+    /// References at the limits of the enabled threshold alarm rules
+    /// monitoring the field, in the unit of the field.
+    pub fn alarm_limits<'a>(
+        field: &YPlotField,
+        rules: impl IntoIterator<Item = &'a AlarmRule>,
+    ) -> Vec<Reference> {
+        let field = field.telemetry_field();
+        rules
+            .into_iter()
+            .filter(|rule| rule.enabled && rule.field.as_ref() == Some(&field))
+            .flat_map(|rule| {
+                let AlarmCondition::Threshold { low, high, .. } = rule.condition else {
+                    return Vec::new();
+                };
+                [(low, "low"), (high, "high")]
+                    .into_iter()
+                    .filter_map(|(limit, side)| {
+                        Some(Reference {
+                            label: format!("{} ({side})", rule.name),
+                            kind: ReferenceKind::Line { value: limit? },
+                            color: rule.severity.color(),
+                        })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Converts the values of the reference with the `(scale, offset)` pair of
+    /// a unit conversion.
+    pub fn converted(mut self, (scale, offset): (f64, f64)) -> Self {
+        let convert = |v: f64| v * scale + offset;
+        self.kind = match self.kind {
+            ReferenceKind::Line { value } => ReferenceKind::Line {
+                value: convert(value),
+            },
+            ReferenceKind::Band { low, high } => ReferenceKind::Band {
+                low: convert(low),
+                high: convert(high),
+            },
+        };
+        self
+    }
+}
+
+/// Sources of the event markers, all disabled by default.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MarkerSettings {
+    /// State field whose transitions are marked, as the state of the FMM
+    pub state_field: Option<IndexedField>,
+    /// Mark the commands sent, with their outcome
+    pub commands: bool,
+    /// Mark the alarms raised, the connection drops and the NACKs
+    pub alarms: bool,
+    /// Show the limits of the threshold alarm rules monitoring the series
+    pub alarm_limits: bool,
+}
+
+/// Vertical marker of an event, described when hovered.
+#[derive(Clone, Debug)]
+pub struct EventMarker {
+    pub time: Instant,
+    pub text: String,
+    pub color: Color32,
+}
+
+/// Transitions of a state field, read from the incoming messages.
+#[derive(Clone, Debug, Default)]
+pub struct StateTransitions {
+    last_value: Option<String>,
+    markers: VecDeque<EventMarker>,
+}
+
+impl StateTransitions {
+    pub fn update(&mut self, field: &IndexedField, message: &TimedMessage) {
+        if field.msg_id() != message.id() {
+            return;
+        }
+        let value = if field.field().enumtype.is_some() {
+            field.extract_as_enum_str(&message.message)
+        } else {
+            field.extract_as_string(&message.message)
+        };
+        let Ok(value) = value else {
+            return;
+        };
+        match &self.last_value {
+            Some(last) if *last == value => {}
+            Some(last) => {
+                self.markers.push_back(EventMarker {
+                    time: message.time,
+                    text: format!("{}: {} → {}", field.field().name, last, value),
+                    color: Color32::from_rgb(0x8b, 0x5c, 0xf6),
+                });
+                self.last_value = Some(value);
+            }
+            // the first value received is not a transition
+            None => self.last_value = Some(value),
+        }
+    }
+
+    pub fn markers(&self) -> impl Iterator<Item = &EventMarker> {
+        self.markers.iter()
+    }
+
+    pub fn clear_older_than(&mut self, lifespan: Duration) {
+        while self
+            .markers
+            .front()
+            .is_some_and(|m| m.time.elapsed() > lifespan)
+        {
+            self.markers.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.last_value = None;
+        self.markers.clear();
+    }
+}
+
+/// Markers of the commands sent in the last `lifespan`.
+pub fn command_markers(log: &TcLog, lifespan: Duration) -> Vec<EventMarker> {
+    log.records()
+        .iter()
+        .filter_map(|record| {
+            let time = instant_of(&record.sent_at).filter(|t| t.elapsed() < lifespan)?;
+            Some(EventMarker {
+                time,
+                text: format!(
+                    "{} ({}): {}",
+                    record.name(),
+                    record.source.label,
+                    record.reply
+                ),
+                color: record.reply.color(),
+            })
+        })
+        .collect()
+}
+
+/// Markers of the alarm events needing attention reported in the last
+/// `lifespan`.
+pub fn alarm_markers(engine: &AlarmEngine, lifespan: Duration) -> Vec<EventMarker> {
+    engine
+        .history()
+        .iter()
+        .filter(|event| {
+            matches!(
+                event.kind,
+                AlarmEventKind::Raised | AlarmEventKind::ConnectionLost | AlarmEventKind::Nack
+            )
+        })
+        .filter_map(|event| {
+            let time = instant_of(&event.time).filter(|t| t.elapsed() < lifespan)?;
+            let mut text = format!("{}: {}", event.kind, event.source);
+            if let Some(value) = &event.value {
+                text.push_str(&format!(" ({value})"));
+            }
+            Some(EventMarker {
+                time,
+                text,
+                color: event.severity.color(),
+            })
+        })
+        .collect()
+}
+
+/// Instant of a past wall-clock time, to place it on the receipt time axes.
+fn instant_of(time: &Zoned) -> Option<Instant> {
+    let ago = Duration::try_from(Timestamp::now().duration_since(time.timestamp())).ok()?;
+    Instant::now().checked_sub(ago)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{alarms::Severity, derived::DerivedField};
+
+    use super::*;
+
+    #[test]
+    fn alarm_limits_of_the_series() {
+        let field = YPlotField::Derived(DerivedField::new("tank_pressure"));
+        let rule = |name: &str, field: &YPlotField, enabled| AlarmRule {
+            name: name.to_owned(),
+            field: Some(field.telemetry_field()),
+            condition: AlarmCondition::Threshold {
+                low: None,
+                high: Some(60.0),
+                hysteresis: 1.0,
+            },
+            severity: Severity::Critical,
+            enabled,
+        };
+        let other = YPlotField::Derived(DerivedField::new("other"));
+        let rules = [
+            rule("redline", &field, true),
+            rule("disabled", &field, false),
+            rule("other", &other, true),
+        ];
+
+        let limits = Reference::alarm_limits(&field, &rules);
+        assert_eq!(limits.len(), 1);
+        assert_eq!(limits[0].label, "redline (high)");
+        assert_eq!(limits[0].color, Severity::Critical.color());
+
+        // bar to kPa
+        let converted = limits[0].clone().converted((100.0, 0.0));
+        assert_eq!(converted.kind, ReferenceKind::Line { value: 6000.0 });
+    }
+
+    #[test]
+    fn references_keep_their_values_across_kinds() {
+        let band = ReferenceKind::Line { value: 2.0 }.converted_to(ReferenceKind::Band {
+            low: 0.0,
+            high: 0.0,
+        });
+        assert_eq!(band.values(), vec![2.0, 2.0]);
+        let line = band.converted_to(ReferenceKind::Line { value: 0.0 });
+        assert_eq!(line, ReferenceKind::Line { value: 2.0 });
+    }
+}
@@ -0,0 +1,232 @@
+//! Y axes of the plot, each with its own scale and unit.
+//!
+//! egui_plot has a single coordinate system, so when more than one y axis is
+//! shown, or the range of an axis is fixed, the values of each axis are mapped
+//! to `0..=1` in plot space, and the tick labels of the axis map them back.
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter};
+
+/// Fraction of the range left empty above and below the data of an auto
+/// scaled axis, for the lines not to touch the borders.
+const AUTO_RANGE_MARGIN: f64 = 0.05;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct YAxisSettings {
+    pub side: AxisSide,
+    pub log_scale: bool,
+    /// Fixed range of the axis, in the display unit, auto scaled if `None`
+    pub range: Option<[f64; 2]>,
+    /// Unit used to display the values, if different from the native one
+    pub display_unit: Option<String>,
+}
+
+impl Default for YAxisSettings {
+    fn default() -> Self {
+        Self {
+            side: AxisSide::Right,
+            log_scale: false,
+            range: None,
+            display_unit: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Display, EnumIter)]
+pub enum AxisSide {
+    Left,
+    Right,
+}
+
+/// Mapping of the values of an axis to plot space, as
+/// `(f(value) - low) / (high - low)` with `f` the logarithm for log scales.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AxisScale {
+    log: bool,
+    low: f64,
+    high: f64,
+}
+
+impl AxisScale {
+    /// Scale of the axis for the given values. If not `normalized` the values
+    /// are only mapped by the logarithm, if a log scale, so that the axis can
+    /// be zoomed and panned as usual.
+    pub fn fit(
+        settings: &YAxisSettings,
+        normalized: bool,
+        values: impl IntoIterator<Item = f64>,
+    ) -> Self {
+        let log = settings.log_scale;
+        let mut scale = Self {
+            log,
+            low: 0.0,
+            high: 1.0,
+        };
+        if !normalized {
+            return scale;
+        }
+        let (low, high) = match settings.range {
+            Some([low, high]) => (scale.transform(low), scale.transform(high)),
+            None => {
+                let (low, high) = values
+                    .into_iter()
+                    .filter_map(|v| scale.transform(v))
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), v| {
+                        (low.min(v), high.max(v))
+                    });
+                let margin = (high - low) * AUTO_RANGE_MARGIN;
+                (
+                    Some(low - margin).filter(|l| l.is_finite()),
+                    Some(high + margin).filter(|h| h.is_finite()),
+                )
+            }
+        };
+        match (low, high) {
+            (Some(low), Some(high)) if high > low => {
+                scale.low = low;
+                scale.high = high;
+            }
+            // a flat line is shown in the middle of the axis
+            (Some(v), _) | (_, Some(v)) => {
+                scale.low = v - 0.5;
+                scale.high = v + 0.5;
+            }
+            (None, None) => {}
+        }
+        scale
+    }
+
+    fn transform(&self, value: f64) -> Option<f64> {
+        if self.log {
+            (value > 0.0).then(|| value.log10())
+        } else {
+            Some(value)
+        }
+    }
+
+    /// Position of the value in plot space, `None` if it cannot be shown, as
+    /// non positive values on log scales.
+    pub fn position_of(&self, value: f64) -> Option<f64> {
+        self.transform(value)
+            .map(|v| (v - self.low) / (self.high - self.low))
+    }
+
+    /// Value at the position in plot space.
+    pub fn value_at(&self, position: f64) -> f64 {
+        let value = position * (self.high - self.low) + self.low;
+        if self.log { 10f64.powf(value) } else { value }
+    }
+}
+
+impl AxisScale {
+    /// Ticks of the axis in the `plot_range` of plot space, as pairs of
+    /// position and value: powers of ten on log scales spanning more than a
+    /// decade, round values otherwise.
+    pub fn ticks(&self, plot_range: [f64; 2], count: usize) -> Vec<(f64, f64)> {
+        let [low, high] = plot_range.map(|p| self.value_at(p));
+        if self.log {
+            let decades = (low.log10().ceil() as i32)..=(high.log10().floor() as i32);
+            if decades.clone().count() >= 2 {
+                return decades
+                    .map(|d| 10f64.powi(d))
+                    .filter_map(|v| Some((self.position_of(v)?, v)))
+                    .collect();
+            }
+        }
+        nice_ticks(low, high, count)
+            .into_iter()
+            .filter_map(|v| Some((self.position_of(v)?, v)))
+            .collect()
+    }
+}
+
+/// About `count` round values, multiples of 1, 2 or 5 times a power of ten,
+/// between `low` and `high`.
+pub fn nice_ticks(low: f64, high: f64, count: usize) -> Vec<f64> {
+    let span = high - low;
+    if !span.is_finite() || span <= 0.0 || count == 0 {
+        return Vec::new();
+    }
+    let rough = span / count as f64;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude);
+    let first = (low / step).ceil() as i64;
+    let last = (high / step).floor() as i64;
+    // multiplying avoids accumulating rounding errors
+    (first..=last).map(|i| i as f64 * step).collect()
+}
+
+/// Formats a tick label with a few significant digits.
+pub fn format_tick(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude != 0.0 && !(1e-3..1e5).contains(&magnitude) {
+        format!("{value:.2e}")
+    } else {
+        let text = format!("{value:.3}");
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalized_axes_map_their_range_to_unit() {
+        let settings = YAxisSettings {
+            range: Some([0.0, 50.0]),
+            ..Default::default()
+        };
+        let scale = AxisScale::fit(&settings, true, []);
+        assert_eq!(scale.position_of(25.0), Some(0.5));
+        assert_eq!(scale.value_at(1.0), 50.0);
+
+        // a constant signal, as a closed valve, stays in the middle
+        let scale = AxisScale::fit(&YAxisSettings::default(), true, [1.0, 1.0]);
+        assert_eq!(scale.position_of(1.0), Some(0.5));
+    }
+
+    #[test]
+    fn log_scales_skip_non_positive_values() {
+        let settings = YAxisSettings {
+            log_scale: true,
+            ..Default::default()
+        };
+        let scale = AxisScale::fit(&settings, false, []);
+        assert_eq!(scale.position_of(100.0), Some(2.0));
+        assert_eq!(scale.position_of(0.0), None);
+        assert!((scale.value_at(3.0) - 1000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ticks_are_round() {
+        assert_eq!(
+            nice_ticks(0.0, 10.0, 5),
+            vec![0.0, 2.0, 4.0, 6.0, 8.0, 10.0]
+        );
+        let labels: Vec<String> = nice_ticks(0.13, 0.31, 2)
+            .into_iter()
+            .map(format_tick)
+            .collect();
+        assert_eq!(labels, ["0.2", "0.3"]);
+
+        let settings = YAxisSettings {
+            log_scale: true,
+            ..Default::default()
+        };
+        let scale = AxisScale::fit(&settings, false, []);
+        let values: Vec<f64> = scale.ticks([0.0, 3.0], 5).iter().map(|t| t.1).collect();
+        assert_eq!(values, vec![1.0, 10.0, 100.0, 1000.0]);
+    }
+
+    #[test]
+    fn ticks_are_short() {
+        assert_eq!(format_tick(2.5), "2.5");
+        assert_eq!(format_tick(100.0), "100");
+        assert_eq!(format_tick(123456.0), "1.23e5");
+    }
+}
@@ -0,0 +1,681 @@
+//! Export of the plot data to CSV and of the plot to PNG or SVG images.
+//!
+//! Images are rendered from the series data rather than grabbed from the
+//! screen, so that they do not depend on the window and can be produced
+//! without a display.
+
+use std::{fmt::Write as _, io::Write, ops::RangeInclusive, path::Path, time::Instant};
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
+use anyhow::{Context, anyhow};
+use egui::Color32;
+use egui_plot::PlotPoint;
+use jiff::Timestamp;
+use tiny_skia::{Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+use crate::ui::font::NOTO;
+
+use super::{
+    BAND_OPACITY, annotations::ReferenceKind, axes::AxisSide, series::TimeAwarePlotPoints,
+};
+
+const TEXT_SIZE: f32 = 12.0;
+/// Width of the margin taken by each y axis
+const AXIS_WIDTH: f32 = 60.0;
+const MARGIN: f32 = 15.0;
+/// Height of the margin below the plot, for the x ticks and label
+const BOTTOM_MARGIN: f32 = 45.0;
+/// Height of the margin above the plot, for the labels of the y axes
+const TOP_MARGIN: f32 = 30.0;
+const FRAME_COLOR: Color32 = Color32::from_gray(120);
+const GRID_COLOR: Color32 = Color32::from_gray(225);
+/// Length of the dashes, and of the gaps, of the limits of the bands
+const BAND_DASH: f32 = 5.0;
+/// Length of the dashes, and of the gaps, of the event markers
+const MARKER_DASH: f32 = 10.0;
+
+/// A series of the CSV export.
+pub struct CsvSeries<'a> {
+    pub name: String,
+    pub unit: String,
+    pub points: &'a TimeAwarePlotPoints,
+}
+
+/// Writes the points of the series in the `x_range`, one per row, with the
+/// time they were received at.
+pub fn write_csv(
+    mut writer: impl Write,
+    x_header: &str,
+    series: &[CsvSeries],
+    x_range: RangeInclusive<f64>,
+) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "receipt_time,series,{},value,unit",
+        csv_field(x_header)
+    )?;
+    let now = (Instant::now(), Timestamp::now());
+    for CsvSeries { name, unit, points } in series {
+        for (time, point) in points.iter_range(x_range.clone()) {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                now.1 - now.0.duration_since(time),
+                csv_field(name),
+                point.x,
+                point.y,
+                csv_field(unit)
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// The plot as shown, in plot space, to be rendered to an image.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub size: [u32; 2],
+    pub x_label: String,
+    pub x_range: [f64; 2],
+    pub y_range: [f64; 2],
+    /// Positions and labels of the ticks of the x axis
+    pub x_ticks: Vec<(f64, String)>,
+    pub y_axes: Vec<SnapshotAxis>,
+    pub series: Vec<SnapshotSeries>,
+    pub references: Vec<SnapshotReference>,
+    pub markers: Vec<SnapshotMarker>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SnapshotAxis {
+    pub side: AxisSide,
+    pub label: String,
+    /// Positions in plot space and labels of the ticks
+    pub ticks: Vec<(f64, String)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SnapshotSeries {
+    pub name: String,
+    pub color: Color32,
+    pub width: f32,
+    pub points: Vec<PlotPoint>,
+}
+
+/// Reference line or limit band, with the values in plot space.
+#[derive(Clone, Debug)]
+pub struct SnapshotReference {
+    /// Shown in the legend, if not empty
+    pub label: String,
+    pub kind: ReferenceKind,
+    pub color: Color32,
+}
+
+/// Vertical marker of an event, at a position in plot space.
+#[derive(Clone, Debug)]
+pub struct SnapshotMarker {
+    pub x: f64,
+    pub color: Color32,
+}
+
+impl Snapshot {
+    pub fn to_svg(&self) -> String {
+        let [width, height] = self.size;
+        let mut canvas = SvgCanvas {
+            svg: format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+                 viewBox=\"0 0 {width} {height}\">\n\
+                 <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n"
+            ),
+        };
+        self.draw(&mut canvas);
+        canvas.svg.push_str("</svg>\n");
+        canvas.svg
+    }
+
+    pub fn to_png(&self) -> anyhow::Result<Vec<u8>> {
+        let [width, height] = self.size;
+        let mut pixmap = Pixmap::new(width, height)
+            .ok_or_else(|| anyhow!("Invalid image size {width}x{height}"))?;
+        pixmap.fill(tiny_skia::Color::WHITE);
+        let font = FontRef::try_from_slice(NOTO)?;
+        let mut canvas = PngCanvas { pixmap, font };
+        self.draw(&mut canvas);
+        Ok(canvas.pixmap.encode_png()?)
+    }
+
+    /// Saves the image, as SVG if the extension of the file is `svg` and as
+    /// PNG otherwise, replacing the extension with `png`.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let is_svg = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("svg"));
+        let (path, data) = if is_svg {
+            (path.to_path_buf(), self.to_svg().into_bytes())
+        } else {
+            (path.with_extension("png"), self.to_png()?)
+        };
+        std::fs::write(&path, data).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn draw(&self, canvas: &mut impl Canvas) {
+        let [width, height] = self.size.map(|s| s as f32);
+        let axes_on = |side| self.y_axes.iter().filter(|a| a.side == side).count() as f32;
+        let left = MARGIN + AXIS_WIDTH * axes_on(AxisSide::Left);
+        let right = width - MARGIN - AXIS_WIDTH * axes_on(AxisSide::Right);
+        let (top, bottom) = (TOP_MARGIN, height - BOTTOM_MARGIN);
+        let [x0, x1] = self.x_range;
+        let [y0, y1] = self.y_range;
+        let to_screen = |p: &PlotPoint| {
+            (
+                left + ((p.x - x0) / (x1 - x0)) as f32 * (right - left),
+                bottom - ((p.y - y0) / (y1 - y0)) as f32 * (bottom - top),
+            )
+        };
+        let screen_x = |x: f64| to_screen(&PlotPoint::new(x, y0)).0;
+        let screen_y = |y: f64| to_screen(&PlotPoint::new(x0, y)).1;
+        let in_x = |x: f32| (left..=right).contains(&x);
+        let in_y = |y: f32| (top..=bottom).contains(&y);
+
+        // grid, on the ticks of the x axis and of the first y axis
+        for (x, _) in &self.x_ticks {
+            let x = screen_x(*x);
+            if in_x(x) {
+                canvas.polyline(&[(x, top), (x, bottom)], GRID_COLOR, 1.0);
+            }
+        }
+        for (y, _) in self.y_axes.iter().take(1).flat_map(|a| &a.ticks) {
+            let y = screen_y(*y);
+            if in_y(y) {
+                canvas.polyline(&[(left, y), (right, y)], GRID_COLOR, 1.0);
+            }
+        }
+
+        // bands, references and markers are drawn below the series
+        for reference in &self.references {
+            if let ReferenceKind::Band { low, high } = reference.kind {
+                let (y0, y1) = (screen_y(low), screen_y(high));
+                let (y0, y1) = (y0.min(y1).max(top), y0.max(y1).min(bottom));
+                if y0 < y1 {
+                    canvas.rect(
+                        [left, y0, right, y1],
+                        reference.color.gamma_multiply(BAND_OPACITY),
+                    );
+                }
+            }
+        }
+        for reference in &self.references {
+            for y in reference.kind.values() {
+                let y = screen_y(y);
+                if !in_y(y) {
+                    continue;
+                }
+                match reference.kind {
+                    ReferenceKind::Line { .. } => {
+                        canvas.polyline(&[(left, y), (right, y)], reference.color, 1.0);
+                    }
+                    ReferenceKind::Band { .. } => {
+                        for dash in dashes((left, y), (right, y), BAND_DASH) {
+                            canvas.polyline(&dash, reference.color, 1.0);
+                        }
+                    }
+                }
+            }
+        }
+        for marker in &self.markers {
+            let x = screen_x(marker.x);
+            if in_x(x) {
+                for dash in dashes((x, top), (x, bottom), MARKER_DASH) {
+                    canvas.polyline(&dash, marker.color, 1.0);
+                }
+            }
+        }
+
+        for series in &self.series {
+            let points: Vec<_> = series.points.iter().map(to_screen).collect();
+            for part in clip_polyline(&points, [left, top, right, bottom]) {
+                canvas.polyline(&part, series.color, series.width);
+            }
+        }
+
+        // frame and x axis
+        canvas.polyline(
+            &[
+                (left, top),
+                (right, top),
+                (right, bottom),
+                (left, bottom),
+                (left, top),
+            ],
+            FRAME_COLOR,
+            1.0,
+        );
+        for (x, label) in &self.x_ticks {
+            let x = screen_x(*x);
+            if in_x(x) {
+                canvas.polyline(&[(x, bottom), (x, bottom + 4.0)], FRAME_COLOR, 1.0);
+                canvas.text((x, bottom + 18.0), label, Anchor::Middle, Color32::BLACK);
+            }
+        }
+        canvas.text(
+            ((left + right) / 2.0, height - 8.0),
+            &self.x_label,
+            Anchor::Middle,
+            Color32::BLACK,
+        );
+
+        // y axes, stacked outwards on each side
+        let (mut lefts, mut rights) = (0.0, 0.0);
+        for axis in &self.y_axes {
+            let (x, direction, anchor) = match axis.side {
+                AxisSide::Left => {
+                    lefts += 1.0;
+                    (left - AXIS_WIDTH * (lefts - 1.0), -1.0, Anchor::End)
+                }
+                AxisSide::Right => {
+                    rights += 1.0;
+                    (right + AXIS_WIDTH * (rights - 1.0), 1.0, Anchor::Start)
+                }
+            };
+            canvas.polyline(&[(x, top), (x, bottom)], FRAME_COLOR, 1.0);
+            for (y, label) in &axis.ticks {
+                let y = screen_y(*y);
+                if in_y(y) {
+                    canvas.polyline(&[(x, y), (x + 4.0 * direction, y)], FRAME_COLOR, 1.0);
+                    canvas.text(
+                        (x + 6.0 * direction, y + TEXT_SIZE / 3.0),
+                        label,
+                        anchor,
+                        Color32::BLACK,
+                    );
+                }
+            }
+            canvas.text((x, top - 10.0), &axis.label, anchor, Color32::BLACK);
+        }
+
+        // legend, with the references having a label
+        let entries = self.series.iter().map(|s| (&s.name, s.color)).chain(
+            self.references
+                .iter()
+                .filter(|r| !r.label.is_empty())
+                .map(|r| (&r.label, r.color)),
+        );
+        for (i, (name, color)) in entries.enumerate() {
+            let y = top + 14.0 + 16.0 * i as f32;
+            canvas.polyline(&[(left + 8.0, y - 4.0), (left + 24.0, y - 4.0)], color, 2.0);
+            canvas.text((left + 30.0, y), name, Anchor::Start, Color32::BLACK);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+trait Canvas {
+    fn polyline(&mut self, points: &[(f32, f32)], color: Color32, width: f32);
+    /// Fills the `[left, top, right, bottom]` rectangle.
+    fn rect(&mut self, rect: [f32; 4], color: Color32);
+    /// Draws the text with the baseline at `pos`.
+    fn text(&mut self, pos: (f32, f32), text: &str, anchor: Anchor, color: Color32);
+}
+
+struct SvgCanvas {
+    svg: String,
+}
+
+impl Canvas for SvgCanvas {
+    fn polyline(&mut self, points: &[(f32, f32)], color: Color32, width: f32) {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let _ = write!(self.svg, "<polyline points=\"");
+        for (x, y) in points {
+            let _ = write!(self.svg, "{x:.1},{y:.1} ");
+        }
+        let _ = writeln!(
+            self.svg,
+            "\" fill=\"none\" stroke=\"rgb({r},{g},{b})\" stroke-opacity=\"{:.3}\" \
+             stroke-width=\"{width}\" stroke-linejoin=\"round\"/>",
+            a as f32 / 255.0
+        );
+    }
+
+    fn rect(&mut self, [left, top, right, bottom]: [f32; 4], color: Color32) {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let _ = writeln!(
+            self.svg,
+            "<rect x=\"{left:.1}\" y=\"{top:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+             fill=\"rgb({r},{g},{b})\" fill-opacity=\"{:.3}\"/>",
+            right - left,
+            bottom - top,
+            a as f32 / 255.0
+        );
+    }
+
+    fn text(&mut self, (x, y): (f32, f32), text: &str, anchor: Anchor, color: Color32) {
+        let [r, g, b, _] = color.to_srgba_unmultiplied();
+        let anchor = match anchor {
+            Anchor::Start => "start",
+            Anchor::Middle => "middle",
+            Anchor::End => "end",
+        };
+        let text = text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let _ = writeln!(
+            self.svg,
+            "<text x=\"{x:.1}\" y=\"{y:.1}\" font-family=\"sans-serif\" font-size=\"{TEXT_SIZE}\" \
+             text-anchor=\"{anchor}\" fill=\"rgb({r},{g},{b})\">{text}</text>"
+        );
+    }
+}
+
+struct PngCanvas {
+    pixmap: Pixmap,
+    font: FontRef<'static>,
+}
+
+impl PngCanvas {
+    /// Blends the color over the pixel with the given coverage.
+    fn blend(&mut self, x: i32, y: i32, color: [u8; 4], coverage: f32) {
+        let (width, height) = (self.pixmap.width() as i32, self.pixmap.height() as i32);
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return;
+        }
+        let alpha = coverage.clamp(0.0, 1.0) * color[3] as f32 / 255.0;
+        let index = (y * width + x) as usize * 4;
+        // the background is opaque, so premultiplied and straight colors match
+        if let Some(pixel) = self.pixmap.data_mut().get_mut(index..index + 3) {
+            for (channel, value) in pixel.iter_mut().zip(color) {
+                *channel = (*channel as f32 * (1.0 - alpha) + value as f32 * alpha).round() as u8;
+            }
+        }
+    }
+}
+
+impl Canvas for PngCanvas {
+    fn polyline(&mut self, points: &[(f32, f32)], color: Color32, width: f32) {
+        let mut builder = PathBuilder::new();
+        let mut points = points.iter();
+        let Some((x, y)) = points.next() else {
+            return;
+        };
+        builder.move_to(*x, *y);
+        for (x, y) in points {
+            builder.line_to(*x, *y);
+        }
+        let Some(path) = builder.finish() else {
+            return;
+        };
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let mut paint = Paint {
+            anti_alias: true,
+            ..Default::default()
+        };
+        paint.set_color_rgba8(r, g, b, a);
+        let stroke = Stroke {
+            width,
+            ..Default::default()
+        };
+        self.pixmap
+            .stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+    }
+
+    fn rect(&mut self, [left, top, right, bottom]: [f32; 4], color: Color32) {
+        let Some(rect) = tiny_skia::Rect::from_ltrb(left, top, right, bottom) else {
+            return;
+        };
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(r, g, b, a);
+        self.pixmap
+            .fill_rect(rect, &paint, Transform::identity(), None);
+    }
+
+    fn text(&mut self, (x, y): (f32, f32), text: &str, anchor: Anchor, color: Color32) {
+        let font = self.font.clone();
+        let scaled = font.as_scaled(PxScale::from(TEXT_SIZE));
+        let width: f32 = text
+            .chars()
+            .map(|c| scaled.h_advance(scaled.glyph_id(c)))
+            .sum();
+        let mut caret = match anchor {
+            Anchor::Start => x,
+            Anchor::Middle => x - width / 2.0,
+            Anchor::End => x - width,
+        };
+        let color = color.to_srgba_unmultiplied();
+        for c in text.chars() {
+            let glyph = scaled.scaled_glyph(c);
+            let advance = scaled.h_advance(glyph.id);
+            let glyph = glyph.id.with_scale_and_position(TEXT_SIZE, point(caret, y));
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    self.blend(
+                        bounds.min.x as i32 + gx as i32,
+                        bounds.min.y as i32 + gy as i32,
+                        color,
+                        coverage,
+                    );
+                });
+            }
+            caret += advance;
+        }
+    }
+}
+
+/// Splits the polyline in the parts inside the `[left, top, right, bottom]`
+/// rectangle.
+fn clip_polyline(points: &[(f32, f32)], rect: [f32; 4]) -> Vec<Vec<(f32, f32)>> {
+    let mut parts = Vec::new();
+    let mut current: Vec<(f32, f32)> = Vec::new();
+    for segment in points.windows(2) {
+        let [a, b] = [segment[0], segment[1]];
+        match clip_segment(a, b, rect) {
+            Some((start, end)) => {
+                if current.last() != Some(&start) {
+                    if current.len() > 1 {
+                        parts.push(std::mem::take(&mut current));
+                    }
+                    current = vec![start];
+                }
+                current.push(end);
+            }
+            None => {
+                if current.len() > 1 {
+                    parts.push(std::mem::take(&mut current));
+                }
+                current.clear();
+            }
+        }
+    }
+    if current.len() > 1 {
+        parts.push(current);
+    }
+    parts
+}
+
+/// Splits the segment in dashes of the given length, spaced by gaps as long.
+fn dashes(from: (f32, f32), to: (f32, f32), length: f32) -> Vec<[(f32, f32); 2]> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let total = dx.hypot(dy);
+    if total <= 0.0 || length <= 0.0 {
+        return Vec::new();
+    }
+    let at = |distance: f32| {
+        let distance = distance.min(total);
+        (
+            from.0 + dx * distance / total,
+            from.1 + dy * distance / total,
+        )
+    };
+    (0..)
+        .map(|i| 2.0 * length * i as f32)
+        .take_while(|&start| start < total)
+        .map(|start| [at(start), at(start + length)])
+        .collect()
+}
+
+/// Liang-Barsky clipping of the segment to the rectangle.
+fn clip_segment(
+    (x0, y0): (f32, f32),
+    (x1, y1): (f32, f32),
+    [left, top, right, bottom]: [f32; 4],
+) -> Option<((f32, f32), (f32, f32))> {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    for (p, q) in [
+        (-dx, x0 - left),
+        (dx, right - x0),
+        (-dy, y0 - top),
+        (dy, bottom - y0),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    (t0 <= t1).then_some(((x0 + t0 * dx, y0 + t0 * dy), (x0 + t1 * dx, y0 + t1 * dy)))
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            size: [400, 300],
+            x_label: "time [s]".to_owned(),
+            x_range: [0.0, 10.0],
+            y_range: [0.0, 1.0],
+            x_ticks: vec![(0.0, "0".to_owned()), (5.0, "5".to_owned())],
+            y_axes: vec![SnapshotAxis {
+                side: AxisSide::Right,
+                label: "Pressure [bar]".to_owned(),
+                ticks: vec![(0.5, "0.5".to_owned())],
+            }],
+            series: vec![SnapshotSeries {
+                name: "pressure <cc>".to_owned(),
+                color: Color32::BLUE,
+                width: 1.0,
+                points: (0..=20)
+                    .map(|i| PlotPoint::new(i as f64, (i as f64 / 3.0).sin()))
+                    .collect(),
+            }],
+            references: vec![
+                SnapshotReference {
+                    label: "MEOP".to_owned(),
+                    kind: ReferenceKind::Line { value: 0.9 },
+                    color: Color32::RED,
+                },
+                SnapshotReference {
+                    label: String::new(),
+                    kind: ReferenceKind::Band {
+                        low: 0.2,
+                        high: 0.4,
+                    },
+                    color: Color32::GREEN,
+                },
+            ],
+            markers: vec![SnapshotMarker {
+                x: 2.5,
+                color: Color32::from_rgb(0x8b, 0x5c, 0xf6),
+            }],
+        }
+    }
+
+    #[test]
+    fn snapshots_render_without_a_display() {
+        let svg = snapshot().to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Pressure [bar]"));
+        assert!(svg.contains("pressure &lt;cc&gt;"));
+
+        let png = snapshot().to_png().unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn annotations_are_rendered() {
+        let svg = snapshot().to_svg();
+        // the band is shaded over the background
+        assert_eq!(svg.matches("<rect").count(), 2);
+        // the labelled reference is in the legend, the band is not
+        assert!(svg.contains(">MEOP</text>"));
+        assert!(svg.contains("stroke=\"rgb(255,0,0)\""));
+        assert!(svg.contains("stroke=\"rgb(139,92,246)\""));
+
+        let mut plain = snapshot();
+        plain.references.clear();
+        plain.markers.clear();
+        assert_ne!(plain.to_png().unwrap(), snapshot().to_png().unwrap());
+    }
+
+    #[test]
+    fn dashes_cover_the_segment() {
+        assert_eq!(
+            dashes((0.0, 0.0), (0.0, 25.0), 5.0),
+            vec![
+                [(0.0, 0.0), (0.0, 5.0)],
+                [(0.0, 10.0), (0.0, 15.0)],
+                [(0.0, 20.0), (0.0, 25.0)]
+            ]
+        );
+        assert!(dashes((1.0, 1.0), (1.0, 1.0), 5.0).is_empty());
+    }
+
+    #[test]
+    fn lines_are_clipped_to_the_plot() {
+        let parts = clip_polyline(
+            &[(-10.0, 5.0), (5.0, 5.0), (5.0, 20.0), (9.0, 4.0)],
+            [0.0, 0.0, 10.0, 10.0],
+        );
+        assert_eq!(
+            parts,
+            vec![
+                vec![(0.0, 5.0), (5.0, 5.0), (5.0, 10.0)],
+                vec![(7.5, 10.0), (9.0, 4.0)]
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_has_units_and_escaped_names() {
+        let mut points = TimeAwarePlotPoints::new();
+        points.push(Instant::now(), PlotPoint::new(1.0, 2.5));
+        points.push(Instant::now(), PlotPoint::new(20.0, 3.0));
+        let series = [CsvSeries {
+            name: "a, b".to_owned(),
+            unit: "bar".to_owned(),
+            points: &points,
+        }];
+        let mut csv = Vec::new();
+        write_csv(&mut csv, "time [ms]", &series, 0.0..=10.0).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "receipt_time,series,time [ms],value,unit");
+        assert!(lines[1].ends_with(",\"a, b\",1,2.5,bar"));
+    }
+}
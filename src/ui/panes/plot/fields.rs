@@ -1,38 +1,128 @@
+use std::time::Instant;
+
+use jiff::Timestamp;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     APP_START_TIMESTAMP_ORIGIN,
-    mavlink::{TimedMessage, reflection::IndexedField},
-    utils::units::{TimeUnits, UnitOfMeasure},
+    derived::{DerivedField, TelemetryField},
+    mavlink::{
+        TimedMessage,
+        reflection::{FieldLike, IndexedField, MAVLINK_PROFILE},
+    },
+    utils::{
+        timer::MISSION_T0,
+        units::{TimeUnits, UnitOfMeasure},
+    },
 };
 
+/// Name of the field holding the onboard time of the messages.
+const ONBOARD_TIMESTAMP_FIELD: &str = "timestamp";
+
 #[derive(Clone, Debug, PartialEq, Hash, Serialize, Deserialize)]
 pub enum XPlotField {
     MsgReceiptTimestamp,
+    /// Receipt time relative to the T0 of the mission
+    MissionTime,
+    /// Receipt time as UTC, in milliseconds since the Unix epoch
+    ReceiptUtc,
+    /// Onboard time of each message, read from its timestamp field and
+    /// converted to milliseconds so that series of different messages line up
+    OnboardTimestamp,
     Field(IndexedField),
+    Derived(DerivedField),
 }
 
 impl XPlotField {
+    /// Axes that can be shared by series of any message.
+    pub const TIME_BASES: [XPlotField; 4] = [
+        XPlotField::MsgReceiptTimestamp,
+        XPlotField::MissionTime,
+        XPlotField::ReceiptUtc,
+        XPlotField::OnboardTimestamp,
+    ];
+
     pub fn unit(&self) -> UnitOfMeasure {
         match self {
-            XPlotField::MsgReceiptTimestamp => UnitOfMeasure::Time(TimeUnits::Millisecond),
+            XPlotField::MsgReceiptTimestamp
+            | XPlotField::MissionTime
+            | XPlotField::ReceiptUtc
+            | XPlotField::OnboardTimestamp => UnitOfMeasure::Time(TimeUnits::Millisecond),
             XPlotField::Field(field) => UnitOfMeasure::from(field.field().unit.as_ref()),
+            XPlotField::Derived(field) => field.unit(),
         }
     }
 
     pub fn name(&self) -> String {
         match self {
             XPlotField::MsgReceiptTimestamp => "receival timestamp".to_string(),
+            XPlotField::MissionTime => "mission time (T+)".to_string(),
+            XPlotField::ReceiptUtc => "receival time (UTC)".to_string(),
+            XPlotField::OnboardTimestamp => "onboard timestamp".to_string(),
             XPlotField::Field(field) => field.field().name.clone(),
+            XPlotField::Derived(field) => field.name.clone(),
+        }
+    }
+
+    /// Whether series of any message can be plotted against this axis.
+    pub fn is_time_base(&self) -> bool {
+        Self::TIME_BASES.contains(self)
+    }
+
+    /// Ids of the messages the axis is read from, if specific.
+    pub fn msg_ids(&self) -> Vec<u32> {
+        match self {
+            XPlotField::Field(field) => vec![field.msg_id()],
+            XPlotField::Derived(field) => field.msg_ids(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Value on the axis of something received at `time`, if a receipt time
+    /// axis, as for placing the markers of events.
+    pub fn receipt_x(&self, time: Instant) -> Option<f64> {
+        match self {
+            XPlotField::MsgReceiptTimestamp => Some(
+                time.saturating_duration_since(*APP_START_TIMESTAMP_ORIGIN)
+                    .as_millis() as f64,
+            ),
+            XPlotField::MissionTime => {
+                let t0 = (*MISSION_T0.read())?;
+                // messages received before T0 have negative times
+                Some(if time >= t0 {
+                    (time - t0).as_millis() as f64
+                } else {
+                    -((t0 - time).as_millis() as f64)
+                })
+            }
+            XPlotField::ReceiptUtc => {
+                let received = Timestamp::now() - time.elapsed();
+                Some(received.as_millisecond() as f64)
+            }
+            _ => None,
         }
     }
 
     pub fn extract_from_message(&self, message: &TimedMessage) -> Result<f64, String> {
         match self {
-            XPlotField::MsgReceiptTimestamp => {
-                Ok((message.time - *APP_START_TIMESTAMP_ORIGIN).as_millis() as f64)
+            XPlotField::MsgReceiptTimestamp | XPlotField::ReceiptUtc => self
+                .receipt_x(message.time)
+                .ok_or("Not a receipt time".to_string()),
+            XPlotField::MissionTime => self.receipt_x(message.time).ok_or("T0 not set".to_string()),
+            XPlotField::OnboardTimestamp => {
+                let field = ONBOARD_TIMESTAMP_FIELD.to_mav_field(message.id(), &MAVLINK_PROFILE)?;
+                let (scale, offset) = UnitOfMeasure::from(field.field().unit.as_ref())
+                    .conversion_to(&UnitOfMeasure::Time(TimeUnits::Millisecond))
+                    .ok_or("Timestamp without a time unit".to_string())?;
+                Ok(field.extract_as_f64(&message.message)? * scale + offset)
+            }
+            XPlotField::Field(field) if field.msg_id() == message.id() => {
+                field.extract_as_f64(&message.message)
             }
-            XPlotField::Field(field) => field.extract_as_f64(&message.message),
+            XPlotField::Field(_) => Err("Field of another message".to_string()),
+            XPlotField::Derived(field) => field
+                .extract_from_message(message)
+                .ok_or("Derived channel not computed".to_string()),
         }
     }
 }
@@ -43,27 +133,66 @@ impl From<IndexedField> for XPlotField {
     }
 }
 
+impl From<DerivedField> for XPlotField {
+    fn from(field: DerivedField) -> Self {
+        Self::Derived(field)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Hash, Serialize, Deserialize)]
 pub enum YPlotField {
     Field(IndexedField),
+    Derived(DerivedField),
 }
 
 impl YPlotField {
     pub fn unit(&self) -> UnitOfMeasure {
         match self {
             YPlotField::Field(field) => UnitOfMeasure::from(field.field().unit.as_ref()),
+            YPlotField::Derived(field) => field.unit(),
         }
     }
 
     pub fn name(&self) -> String {
         match self {
             YPlotField::Field(field) => field.field().name.clone(),
+            YPlotField::Derived(field) => field.name.clone(),
+        }
+    }
+
+    /// Name including the message, to tell apart fields of different messages.
+    pub fn qualified_name(&self) -> String {
+        match self {
+            YPlotField::Field(field) => format!("{}.{}", field.msg().name, field.name()),
+            YPlotField::Derived(field) => field.name.clone(),
+        }
+    }
+
+    /// Ids of the messages the series is read from.
+    pub fn msg_ids(&self) -> Vec<u32> {
+        match self {
+            YPlotField::Field(field) => vec![field.msg_id()],
+            YPlotField::Derived(field) => field.msg_ids(),
+        }
+    }
+
+    /// The same field, as monitored by the alarm rules.
+    pub fn telemetry_field(&self) -> TelemetryField {
+        match self {
+            YPlotField::Field(field) => TelemetryField::Mavlink(field.clone()),
+            YPlotField::Derived(field) => TelemetryField::Derived(field.clone()),
         }
     }
 
     pub fn extract_from_message(&self, message: &TimedMessage) -> Result<f64, String> {
         match self {
-            YPlotField::Field(field) => field.extract_as_f64(&message.message),
+            YPlotField::Field(field) if field.msg_id() == message.id() => {
+                field.extract_as_f64(&message.message)
+            }
+            YPlotField::Field(_) => Err("Field of another message".to_string()),
+            YPlotField::Derived(field) => field
+                .extract_from_message(message)
+                .ok_or("Derived channel not computed".to_string()),
         }
     }
 }
@@ -73,3 +202,9 @@ impl From<IndexedField> for YPlotField {
         Self::Field(field)
     }
 }
+
+impl From<DerivedField> for YPlotField {
+    fn from(field: DerivedField) -> Self {
+        Self::Derived(field)
+    }
+}
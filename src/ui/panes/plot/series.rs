@@ -0,0 +1,343 @@
+//! Storage of the points of a plot line.
+//!
+//! Points are kept in a ring buffer, so that dropping the oldest ones is cheap
+//! also with long lifespans at high rates, and are decimated to the width of
+//! the plot before being drawn.
+
+use std::{
+    collections::VecDeque,
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+use egui_plot::PlotPoint;
+
+#[derive(Clone, Debug)]
+pub struct TimeAwarePlotPoints {
+    times: VecDeque<Instant>,
+    points: VecDeque<PlotPoint>,
+    /// Number of points at the front to drop before the others are sorted by
+    /// x, as it is the case of time based axes. Sorted points allow binary
+    /// searches and decimation.
+    unsorted: usize,
+}
+
+impl Default for TimeAwarePlotPoints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeAwarePlotPoints {
+    pub fn new() -> Self {
+        Self {
+            times: VecDeque::new(),
+            points: VecDeque::new(),
+            unsorted: 0,
+        }
+    }
+
+    /// Whether the points are sorted by x.
+    pub fn is_sorted(&self) -> bool {
+        self.unsorted == 0
+    }
+
+    pub fn last(&self) -> Option<&PlotPoint> {
+        self.points.back()
+    }
+
+    /// Points in the `x_range`, with the time they were received at.
+    pub fn iter_range(
+        &self,
+        x_range: RangeInclusive<f64>,
+    ) -> impl Iterator<Item = (Instant, PlotPoint)> + '_ {
+        let (start, end) = if self.is_sorted() {
+            (
+                self.points.partition_point(|p| p.x < *x_range.start()),
+                self.points.partition_point(|p| p.x <= *x_range.end()),
+            )
+        } else {
+            (0, self.points.len())
+        };
+        self.times
+            .range(start..end)
+            .copied()
+            .zip(self.points.range(start..end).copied())
+            .filter(move |(_, p)| x_range.contains(&p.x))
+    }
+
+    /// The latest `count` points received, with the time they were received at.
+    pub fn iter_last(&self, count: usize) -> impl Iterator<Item = (Instant, PlotPoint)> + '_ {
+        let start = self.points.len().saturating_sub(count);
+        self.times
+            .range(start..)
+            .copied()
+            .zip(self.points.range(start..).copied())
+    }
+
+    /// Points received since `time`, with the time they were received at.
+    pub fn iter_since(&self, time: Instant) -> impl Iterator<Item = (Instant, PlotPoint)> + '_ {
+        // points are pushed in order of time
+        let start = self.times.partition_point(|t| *t < time);
+        self.times
+            .range(start..)
+            .copied()
+            .zip(self.points.range(start..).copied())
+    }
+
+    pub fn push(&mut self, time: Instant, point: PlotPoint) {
+        if self.points.back().is_some_and(|last| point.x < last.x) {
+            // the points are sorted again once the ones before are dropped
+            self.unsorted = self.points.len();
+        }
+        self.times.push_back(time);
+        self.points.push_back(point);
+    }
+
+    pub fn clear_older_than(&mut self, lifespan: Duration) {
+        // points are pushed in order of time, so the old ones are at the front
+        let old = self.times.partition_point(|time| time.elapsed() > lifespan);
+        self.times.drain(..old);
+        self.points.drain(..old);
+        self.unsorted = self.unsorted.saturating_sub(old);
+    }
+
+    /// Value of the point closest to `x`.
+    pub fn value_at(&self, x: f64) -> Option<f64> {
+        if !self.is_sorted() {
+            return self
+                .points
+                .iter()
+                .min_by(|a, b| (a.x - x).abs().total_cmp(&(b.x - x).abs()))
+                .map(|p| p.y);
+        }
+        let i = self.points.partition_point(|p| p.x < x);
+        let before = i.checked_sub(1).and_then(|i| self.points.get(i));
+        let after = self.points.get(i);
+        match (before, after) {
+            (Some(b), Some(a)) if (a.x - x) < (x - b.x) => Some(a.y),
+            (Some(b), _) => Some(b.y),
+            (None, a) => a.map(|a| a.y),
+        }
+    }
+
+    /// Points to draw in the `x_range` on a plot `buckets` pixels wide.
+    ///
+    /// The visible points are split in `buckets` slices of equal x span, and
+    /// only the minimum and the maximum of each are kept, in their order, so
+    /// that spikes are not lost. The points just outside of the range are
+    /// kept as they are, for the line to reach the borders of the plot. All
+    /// the points are returned if not sorted by x, or fewer than the ones to
+    /// keep.
+    pub fn decimated(
+        &self,
+        x_range: Option<RangeInclusive<f64>>,
+        buckets: usize,
+    ) -> Vec<PlotPoint> {
+        if !self.is_sorted() {
+            return self.points.iter().copied().collect();
+        }
+        let (Some(front), Some(back)) = (self.points.front(), self.points.back()) else {
+            return Vec::new();
+        };
+        let (start, end, [x_min, x_max]) = match &x_range {
+            Some(range) => (
+                self.points.partition_point(|p| p.x < *range.start()),
+                self.points.partition_point(|p| p.x <= *range.end()),
+                [*range.start(), *range.end()],
+            ),
+            None => (0, self.points.len(), [front.x, back.x]),
+        };
+        let visible = self.points.range(start..end).copied();
+
+        let mut decimated = Vec::with_capacity(2 * buckets + 2);
+        decimated.extend(start.checked_sub(1).and_then(|i| self.points.get(i)));
+        if end - start <= 2 * buckets.max(1) {
+            decimated.extend(visible);
+        } else {
+            // the buckets span the range, not the points outside of it
+            let bucket_span = (x_max - x_min) / buckets as f64;
+            let mut bucket: Option<(usize, PlotPoint, PlotPoint)> = None;
+            for point in visible {
+                let index = ((point.x - x_min) / bucket_span) as usize;
+                match &mut bucket {
+                    Some((i, min, max)) if *i == index => {
+                        if point.y < min.y {
+                            *min = point;
+                        }
+                        if point.y > max.y {
+                            *max = point;
+                        }
+                    }
+                    _ => {
+                        if let Some((_, min, max)) = bucket.take() {
+                            push_min_max(&mut decimated, min, max);
+                        }
+                        bucket = Some((index, point, point));
+                    }
+                }
+            }
+            if let Some((_, min, max)) = bucket {
+                push_min_max(&mut decimated, min, max);
+            }
+        }
+        decimated.extend(self.points.get(end));
+        decimated
+    }
+}
+
+fn push_min_max(points: &mut Vec<PlotPoint>, min: PlotPoint, max: PlotPoint) {
+    if min.x == max.x && min.y == max.y {
+        points.push(min);
+    } else if min.x <= max.x {
+        points.extend([min, max]);
+    } else {
+        points.extend([max, min]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(len: usize) -> TimeAwarePlotPoints {
+        let mut points = TimeAwarePlotPoints::new();
+        let now = Instant::now();
+        for i in 0..len {
+            let x = i as f64;
+            points.push(now, PlotPoint::new(x, (x / 100.0).sin()));
+        }
+        points
+    }
+
+    #[test]
+    fn decimation_keeps_the_extremes() {
+        let mut points = sine(10_000);
+        points.push(Instant::now(), PlotPoint::new(10_000.0, 5.0));
+        points.push(Instant::now(), PlotPoint::new(10_001.0, 0.0));
+
+        let decimated = points.decimated(None, 100);
+        assert!(decimated.len() <= 2 * 100 + 2);
+        assert!(decimated.windows(2).all(|w| w[0].x <= w[1].x));
+        assert!(decimated.iter().any(|p| p.y == 5.0));
+        assert_eq!(decimated.first().map(|p| p.x), Some(0.0));
+
+        // few points are left untouched
+        assert_eq!(points.decimated(Some(10.0..=20.0), 100).len(), 13);
+    }
+
+    #[test]
+    fn gaps_outside_of_the_range_do_not_widen_the_buckets() {
+        let mut points = TimeAwarePlotPoints::new();
+        let now = Instant::now();
+        points.push(now, PlotPoint::new(0.0, 0.0));
+        for i in 0..10_000 {
+            let x = 1000.0 + i as f64 / 1000.0;
+            points.push(now, PlotPoint::new(x, (x * 10.0).sin()));
+        }
+        points.push(now, PlotPoint::new(2000.0, 0.0));
+
+        let decimated = points.decimated(Some(1000.0..=1010.0), 100);
+        assert!(decimated.len() > 2 * 90);
+        assert!(decimated.len() <= 2 * 100 + 4);
+        // the neighbours are kept as they are
+        assert_eq!(decimated.first().map(|p| p.x), Some(0.0));
+        assert_eq!(decimated.last().map(|p| p.x), Some(2000.0));
+    }
+
+    #[test]
+    fn points_are_sorted_again_once_the_unsorted_are_dropped() {
+        let mut points = TimeAwarePlotPoints::new();
+        let old = Instant::now() - Duration::from_secs(10);
+        points.push(old, PlotPoint::new(5.0, 0.0));
+        // the onboard clock was reset
+        points.push(old, PlotPoint::new(0.0, 0.0));
+        points.push(Instant::now(), PlotPoint::new(1.0, 0.0));
+        assert!(!points.is_sorted());
+
+        points.clear_older_than(Duration::from_secs(5));
+        assert!(points.is_sorted());
+        assert_eq!(points.value_at(1.0), Some(0.0));
+    }
+
+    #[test]
+    fn old_points_are_dropped_from_the_front() {
+        let mut points = TimeAwarePlotPoints::new();
+        let old = Instant::now() - Duration::from_secs(10);
+        points.push(old, PlotPoint::new(0.0, 0.0));
+        points.push(Instant::now(), PlotPoint::new(1.0, 1.0));
+        points.clear_older_than(Duration::from_secs(5));
+        assert_eq!(points.value_at(0.0), Some(1.0));
+        assert_eq!(points.decimated(None, 100).len(), 1);
+    }
+
+    #[test]
+    fn points_since_a_time() {
+        let mut points = TimeAwarePlotPoints::new();
+        let start = Instant::now();
+        for i in 0..10 {
+            let time = start + Duration::from_secs(i);
+            // unsorted x, as in XY plots
+            points.push(time, PlotPoint::new(-(i as f64), i as f64));
+        }
+        let since: Vec<_> = points
+            .iter_since(start + Duration::from_secs(7))
+            .map(|(_, p)| p.y)
+            .collect();
+        assert_eq!(since, vec![7.0, 8.0, 9.0]);
+    }
+
+    /// Checks that 30 minutes of telemetry are updated and drawn within the
+    /// frame budget. Ignored since timings are only meaningful in release
+    /// builds, run with `just bench`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_update_and_render() {
+        // 30 minutes at 100 Hz, for each of 4 series
+        const LEN: usize = 30 * 60 * 100;
+        const SERIES: usize = 4;
+        // share of a 60 fps frame left to a plot
+        const FRAME_BUDGET: Duration = Duration::from_millis(4);
+        const WIDTH: usize = 1920;
+        // at most the first and the last point of each column, plus the
+        // neighbours of the range
+        const MAX_DRAWN: usize = SERIES * (2 * WIDTH + 4);
+        let mut series = vec![TimeAwarePlotPoints::new(); SERIES];
+
+        let start = Instant::now();
+        let now = Instant::now();
+        for i in 0..LEN {
+            let x = i as f64 * 10.0;
+            for (j, points) in series.iter_mut().enumerate() {
+                points.push(now, PlotPoint::new(x, (x / 1000.0 + j as f64).sin()));
+            }
+        }
+        let update = start.elapsed();
+        assert!(update < FRAME_BUDGET * 100, "update: {update:?}");
+
+        let start = Instant::now();
+        for points in &mut series {
+            points.clear_older_than(Duration::from_secs(3600));
+        }
+        let lifespan_check = start.elapsed();
+        assert!(
+            lifespan_check < FRAME_BUDGET / 100,
+            "lifespan check: {lifespan_check:?}"
+        );
+
+        let frames = 100;
+        for range in [None, Some(LEN as f64..=LEN as f64 * 2.0)] {
+            let start = Instant::now();
+            let mut drawn = 0;
+            for _ in 0..frames {
+                for points in &series {
+                    drawn += points.decimated(range.clone(), WIDTH).len();
+                }
+            }
+            let per_frame = start.elapsed() / frames;
+            let drawn = drawn / frames as usize;
+            assert!(per_frame < FRAME_BUDGET, "render {range:?}: {per_frame:?}");
+            assert!(drawn <= MAX_DRAWN, "render {range:?}: {drawn} points drawn");
+        }
+    }
+}
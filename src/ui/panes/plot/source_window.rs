@@ -1,10 +1,21 @@
-use std::time::Duration;
+use std::{iter::zip, mem::discriminant, time::Duration};
 
-use crate::{error::ErrInstrument, mavlink::reflection::MAVLINK_PROFILE};
+use strum::IntoEnumIterator;
+
+use crate::{
+    derived::{DERIVED_CHANNELS, DerivedField},
+    error::ErrInstrument,
+    mavlink::reflection::{IndexedField, MAVLINK_PROFILE},
+    ui::widgets::UnitSelector,
+};
 
 use super::{
     LineSettings, PlotSettings,
+    annotations::{Reference, ReferenceKind},
+    axes::{AxisSide, YAxisSettings},
     fields::{XPlotField, YPlotField},
+    spectrum::{SpectrumScale, WINDOW_LENGTHS, WindowFunction},
+    xy::{ColorBy, Colormap, XyStyle},
 };
 
 #[profiling::function]
@@ -28,12 +39,8 @@ pub fn sources_window(ui: &mut egui::Ui, plot_settings: &mut PlotSettings) {
 
     ui.add_sized([250., 10.], egui::Separator::default());
 
-    let data_settings_digest = plot_settings.data_digest();
     // extract the msg name from the id to show it in the combo box
-    let msg_name = MAVLINK_PROFILE
-        .get_msg(plot_settings.plot_message_id)
-        .map(|m| m.name.clone())
-        .unwrap_or_default();
+    let msg_name = msg_name(plot_settings.plot_message_id);
 
     // show the first combo box with the message name selection
     egui::ComboBox::from_label("Message Kind")
@@ -42,73 +49,89 @@ pub fn sources_window(ui: &mut egui::Ui, plot_settings: &mut PlotSettings) {
             for msg in MAVLINK_PROFILE.get_sorted_msgs() {
                 ui.selectable_value(&mut plot_settings.plot_message_id, msg.id, &msg.name);
             }
-        });
+        })
+        .response
+        .on_hover_text("Message whose fields are offered for the X axis and new series");
 
-    // reset fields if the message is changed
-    if data_settings_digest != plot_settings.data_digest() {
-        plot_settings.clear_fields();
+    // time bases are shared by all messages, fields only by their own
+    let mut x_fields = XPlotField::TIME_BASES.to_vec();
+    x_fields.extend(
+        series_fields(plot_settings.plot_message_id)
+            .into_iter()
+            .map(|f| match f {
+                YPlotField::Field(f) => XPlotField::Field(f),
+                YPlotField::Derived(f) => XPlotField::Derived(f),
+            }),
+    );
+    // keep the previous x field while valid, also after changing message
+    if !x_fields.contains(&plot_settings.x_field) {
+        if is_valid_x_field(&plot_settings.x_field) {
+            x_fields.push(plot_settings.x_field.clone());
+        } else {
+            plot_settings.x_field = XPlotField::MsgReceiptTimestamp;
+        }
     }
 
-    // check fields and assign a default field_x and field_y once the msg is changed
-    let fields = MAVLINK_PROFILE
-        .get_plottable_fields(plot_settings.plot_message_id)
-        .log_expect("Invalid message id");
-    let mut x_fields = vec![XPlotField::MsgReceiptTimestamp];
-    let y_fields = fields
-        .clone()
-        .into_iter()
-        .map(|f| f.into())
-        .collect::<Vec<_>>();
-    x_fields.extend(fields.into_iter().map(|f| f.into()));
-    // get the first field that is in the list of fields or the previous if valid
-    let x_field = &plot_settings.x_field;
-    let new_field_x = if x_fields.iter().any(|f| f == x_field) {
-        x_field.to_owned()
-    } else {
-        XPlotField::MsgReceiptTimestamp
-    };
-
-    // update the field_x
-    plot_settings.x_field = new_field_x;
-
-    // if fields are valid, show the combo boxes for the x_axis
     let x_field = &mut plot_settings.x_field;
     egui::ComboBox::from_label("X Axis")
         .selected_text(x_field.name())
         .show_ui(ui, |ui| {
-            for msg in x_fields.iter() {
-                ui.selectable_value(x_field, msg.to_owned(), msg.name());
+            for field in x_fields.iter() {
+                ui.selectable_value(x_field, field.to_owned(), field.name());
             }
         });
 
-    // retain only the fields that are in y_fields
+    // retain only the series that are still valid, e.g. removed derived channels
     plot_settings
         .y_fields
-        .retain(|(field, _)| y_fields.iter().any(|f: &YPlotField| f == field));
+        .retain(|(field, _)| series_fields(field_msg_id(field)).contains(field));
 
-    // populate the plot_lines with the first field if it is empty and there are more than 1 fields
-    if plot_settings.y_fields.is_empty() && y_fields.len() > 1 {
-        plot_settings.add_field(y_fields[0].clone());
+    // populate the plot_lines with the first field if it is empty
+    if plot_settings.y_fields.is_empty()
+        && let Some(field) = series_fields(plot_settings.plot_message_id).first()
+    {
+        plot_settings.add_field(field.clone());
     }
 
     // check how many fields are left and how many are selected
     let plot_lines_len = plot_settings.y_fields.len();
+    let axes_len = plot_settings.y_axes.len();
+    let mut to_remove = None;
     egui::Grid::new(ui.auto_id_with("y_axis"))
-        .num_columns(3)
+        .num_columns(6)
         .spacing([10.0, 2.5])
         .show(ui, |ui| {
             for (i, (field, line_settings)) in plot_settings.y_fields[..].iter_mut().enumerate() {
-                let LineSettings { width, color } = line_settings;
+                let LineSettings {
+                    width, color, axis, ..
+                } = line_settings;
                 let widget_label = if plot_lines_len > 1 {
-                    format!("Y Axis {}", i + 1)
+                    format!("Series {}", i + 1)
                 } else {
-                    "Y Axis".to_owned()
+                    "Series".to_owned()
                 };
+
+                // each series can come from a different message
+                let mut msg_id = field_msg_id(field);
+                egui::ComboBox::from_id_salt(("y_msg", i))
+                    .selected_text(msg_name(msg_id))
+                    .show_ui(ui, |ui| {
+                        for msg in MAVLINK_PROFILE.get_sorted_msgs() {
+                            ui.selectable_value(&mut msg_id, msg.id, &msg.name);
+                        }
+                    });
+                let fields = series_fields(msg_id);
+                if msg_id != field_msg_id(field)
+                    && let Some(first) = fields.first()
+                {
+                    *field = first.clone();
+                }
+
                 egui::ComboBox::from_label(widget_label)
                     .selected_text(field.name())
                     .show_ui(ui, |ui| {
-                        for msg in y_fields.iter() {
-                            ui.selectable_value(field, msg.to_owned(), msg.name());
+                        for f in fields.iter() {
+                            ui.selectable_value(field, f.to_owned(), f.name());
                         }
                     });
                 ui.color_edit_button_srgba(color);
@@ -119,15 +142,61 @@ pub fn sources_window(ui: &mut egui::Ui, plot_settings: &mut PlotSettings) {
                         .suffix(" pt"),
                 )
                 .on_hover_text("Width of the line in points");
+                if axes_len > 1 {
+                    egui::ComboBox::from_id_salt(("y_axis_of", i))
+                        .width(60.0)
+                        .selected_text(format!("Axis {}", *axis + 1))
+                        .show_ui(ui, |ui| {
+                            for a in 0..axes_len {
+                                ui.selectable_value(axis, a, format!("Axis {}", a + 1));
+                            }
+                        });
+                } else {
+                    ui.label("");
+                }
+                if ui
+                    .add_enabled(plot_lines_len > 1, egui::Button::new("🗑"))
+                    .on_hover_text("Remove this series")
+                    .clicked()
+                {
+                    to_remove = Some(i);
+                }
                 ui.end_row();
             }
         });
+    if let Some(i) = to_remove {
+        plot_settings.y_fields.remove(i);
+    }
 
-    // if we have fields left, show the add button
-    if y_fields.len().saturating_sub(plot_lines_len + 1) > 0
+    // series of other messages have no value of a message field to pair with
+    let other_messages = plot_settings
+        .y_msg_ids()
+        .into_iter()
+        .any(|id| !plot_settings.x_field.msg_ids().contains(&id));
+    if !plot_settings.x_field.is_time_base() && other_messages {
+        ui.label(
+            egui::RichText::new(
+                "Series of other messages than the X axis need a time based X axis",
+            )
+            .color(egui::Color32::YELLOW),
+        );
+    }
+
+    ui.add_sized([250., 10.], egui::Separator::default());
+    axes_settings(ui, plot_settings);
+    ui.add_sized([250., 10.], egui::Separator::default());
+    ui.collapsing("References", |ui| references_settings(ui, plot_settings));
+    ui.collapsing("Event Markers", |ui| markers_settings(ui, plot_settings));
+    ui.add_sized([250., 10.], egui::Separator::default());
+
+    // new series are taken from the selected message
+    let y_fields = series_fields(plot_settings.plot_message_id);
+    if y_fields
+        .iter()
+        .any(|field| !plot_settings.y_fields.iter().any(|(f, _)| f == field))
         && ui
-            .button("Add Y Axis")
-            .on_hover_text("Add another Y axis")
+            .button("Add Series")
+            .on_hover_text("Add another series from the selected message")
             .clicked()
     {
         // get the first field that is not in the plot_lines
@@ -138,3 +207,386 @@ pub fn sources_window(ui: &mut egui::Ui, plot_settings: &mut PlotSettings) {
         plot_settings.add_field(next_field.to_owned());
     }
 }
+
+fn axes_settings(ui: &mut egui::Ui, plot_settings: &mut PlotSettings) {
+    let PlotSettings {
+        y_fields, y_axes, ..
+    } = plot_settings;
+    let axes_len = y_axes.len();
+    let mut to_remove = None;
+    egui::Grid::new(ui.auto_id_with("y_axes"))
+        .num_columns(6)
+        .spacing([10.0, 2.5])
+        .show(ui, |ui| {
+            for (i, axis) in y_axes.iter_mut().enumerate() {
+                ui.label(format!("Axis {}", i + 1));
+                egui::ComboBox::from_id_salt(("axis_side", i))
+                    .width(60.0)
+                    .selected_text(axis.side.to_string())
+                    .show_ui(ui, |ui| {
+                        for side in AxisSide::iter() {
+                            ui.selectable_value(&mut axis.side, side, side.to_string());
+                        }
+                    });
+                ui.checkbox(&mut axis.log_scale, "Log")
+                    .on_hover_text("Logarithmic scale, non positive values are not shown");
+
+                ui.horizontal(|ui| {
+                    let mut fixed = axis.range.is_some();
+                    if ui
+                        .checkbox(&mut fixed, "Fixed")
+                        .on_hover_text("Fixed range instead of fitting the data")
+                        .changed()
+                    {
+                        axis.range = fixed.then_some([0.0, 1.0]);
+                    }
+                    if let Some([low, high]) = &mut axis.range {
+                        ui.add(egui::DragValue::new(low).speed(0.1).range(f64::MIN..=*high));
+                        ui.label("to");
+                        ui.add(egui::DragValue::new(high).speed(0.1).range(*low..=f64::MAX));
+                    }
+                });
+
+                // the display unit is chosen among the ones of the first series
+                let native = y_fields
+                    .iter()
+                    .find(|(_, line)| line.axis == i)
+                    .map(|(field, _)| field.unit());
+                match native {
+                    Some(native) if native.compatible_units().len() > 1 => {
+                        ui.add(UnitSelector::new(
+                            ui.auto_id_with(("y_unit", i)),
+                            &native,
+                            &mut axis.display_unit,
+                        ))
+                        .on_hover_text("Series with a compatible unit are converted to this unit");
+                    }
+                    _ => {
+                        ui.label("");
+                    }
+                }
+
+                if ui
+                    .add_enabled(axes_len > 1, egui::Button::new("🗑"))
+                    .on_hover_text("Remove this axis, moving its series to the first one")
+                    .clicked()
+                {
+                    to_remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+    if let Some(i) = to_remove {
+        plot_settings.remove_axis(i);
+    }
+
+    if ui
+        .button("Add Axis")
+        .on_hover_text("Add a Y axis with its own scale, to assign series to")
+        .clicked()
+    {
+        // alternate the sides, starting from the right one
+        let side = if plot_settings.y_axes.len() % 2 == 0 {
+            AxisSide::Right
+        } else {
+            AxisSide::Left
+        };
+        plot_settings.y_axes.push(YAxisSettings {
+            side,
+            ..Default::default()
+        });
+    }
+}
+
+fn references_settings(ui: &mut egui::Ui, plot_settings: &mut PlotSettings) {
+    let units: Vec<String> = plot_settings
+        .y_fields
+        .iter()
+        .map(|(field, line)| {
+            field
+                .unit()
+                .display_unit(plot_settings.display_unit_of(line))
+                .to_string()
+        })
+        .collect();
+    let mut to_remove = None;
+    egui::Grid::new(ui.auto_id_with("references"))
+        .num_columns(6)
+        .spacing([10.0, 2.5])
+        .show(ui, |ui| {
+            for (i, ((field, line), unit)) in
+                zip(plot_settings.y_fields.iter_mut(), &units).enumerate()
+            {
+                for (j, reference) in line.references.iter_mut().enumerate() {
+                    ui.label(field.name());
+                    ui.add(
+                        egui::TextEdit::singleline(&mut reference.label)
+                            .hint_text("Label")
+                            .desired_width(80.0),
+                    );
+                    egui::ComboBox::from_id_salt(("reference_kind", i, j))
+                        .width(60.0)
+                        .selected_text(reference.kind.to_string())
+                        .show_ui(ui, |ui| {
+                            for kind in ReferenceKind::iter() {
+                                let selected = discriminant(&kind) == discriminant(&reference.kind);
+                                if ui.selectable_label(selected, kind.to_string()).clicked() {
+                                    reference.kind = reference.kind.converted_to(kind);
+                                }
+                            }
+                        });
+                    ui.horizontal(|ui| match &mut reference.kind {
+                        ReferenceKind::Line { value } => {
+                            ui.add(
+                                egui::DragValue::new(value)
+                                    .speed(0.1)
+                                    .suffix(format!(" {unit}")),
+                            );
+                        }
+                        ReferenceKind::Band { low, high } => {
+                            ui.add(egui::DragValue::new(low).speed(0.1).range(f64::MIN..=*high));
+                            ui.label("to");
+                            ui.add(
+                                egui::DragValue::new(high)
+                                    .speed(0.1)
+                                    .range(*low..=f64::MAX)
+                                    .suffix(format!(" {unit}")),
+                            );
+                        }
+                    });
+                    ui.color_edit_button_srgba(&mut reference.color);
+                    if ui
+                        .button("🗑")
+                        .on_hover_text("Remove this reference")
+                        .clicked()
+                    {
+                        to_remove = Some((i, j));
+                    }
+                    ui.end_row();
+                }
+            }
+        });
+    if let Some((i, j)) = to_remove {
+        plot_settings.y_fields[i].1.references.remove(j);
+    }
+
+    ui.menu_button("Add Reference", |ui| {
+        for (i, (field, line)) in plot_settings.y_fields.iter_mut().enumerate() {
+            let label = format!("Series {}: {}", i + 1, field.name());
+            if ui.button(label).clicked() {
+                line.references.push(Reference::default());
+                ui.close_menu();
+            }
+        }
+    })
+    .response
+    .on_hover_text("Add a reference line or limit band to a series");
+}
+
+fn markers_settings(ui: &mut egui::Ui, plot_settings: &mut PlotSettings) {
+    if !plot_settings.x_field.is_time_base()
+        || plot_settings.x_field == XPlotField::OnboardTimestamp
+    {
+        ui.label(
+            egui::RichText::new("Markers are shown only on receipt time X axes")
+                .color(egui::Color32::YELLOW),
+        );
+    }
+
+    // state fields of the messages shown in the plot
+    let mut msg_ids = plot_settings.y_msg_ids();
+    msg_ids.push(plot_settings.plot_message_id);
+    msg_ids.sort_unstable();
+    msg_ids.dedup();
+    let state_fields: Vec<_> = msg_ids
+        .into_iter()
+        .flat_map(|id| MAVLINK_PROFILE.get_all_state_fields(id).unwrap_or_default())
+        .collect();
+    let field_name = |f: &IndexedField| format!("{}.{}", f.msg().name, f.field().name);
+    let markers = &mut plot_settings.markers;
+    egui::ComboBox::from_label("State Transitions")
+        .selected_text(
+            markers
+                .state_field
+                .as_ref()
+                .map(field_name)
+                .unwrap_or_else(|| "None".to_owned()),
+        )
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut markers.state_field, None, "None");
+            for field in state_fields {
+                let name = field_name(&field);
+                ui.selectable_value(&mut markers.state_field, Some(field), name);
+            }
+        })
+        .response
+        .on_hover_text("Mark the changes of a state field, as the state of the FMM");
+    ui.checkbox(&mut markers.commands, "Sent commands")
+        .on_hover_text("Mark the commands sent, with their outcome");
+    ui.checkbox(&mut markers.alarms, "Alarms")
+        .on_hover_text("Mark the alarms raised, the connection drops and the NACKs");
+    ui.checkbox(&mut markers.alarm_limits, "Alarm limits")
+        .on_hover_text(
+            "Reference lines at the thresholds of the alarm rules monitoring the series",
+        );
+}
+
+fn msg_name(msg_id: u32) -> String {
+    MAVLINK_PROFILE
+        .get_msg(msg_id)
+        .map(|m| m.name.clone())
+        .unwrap_or_default()
+}
+
+/// Message a series is shown under, the first triggering it for derived
+/// channels.
+fn field_msg_id(field: &YPlotField) -> u32 {
+    field.msg_ids().first().copied().unwrap_or_default()
+}
+
+/// Whether the x field can still be computed, derived channels may have been
+/// removed or made invalid since selected.
+fn is_valid_x_field(field: &XPlotField) -> bool {
+    match field {
+        XPlotField::Derived(f) => DERIVED_CHANNELS
+            .read()
+            .valid_channels()
+            .any(|c| c.name == f.name),
+        _ => true,
+    }
+}
+
+/// Fields of the message that can be plotted, including the derived channels
+/// computed on it.
+fn series_fields(msg_id: u32) -> Vec<YPlotField> {
+    let fields = MAVLINK_PROFILE
+        .get_plottable_fields(msg_id)
+        .unwrap_or_default();
+    let registry = DERIVED_CHANNELS.read();
+    let derived_fields = registry
+        .valid_channels()
+        .filter(|c| registry.trigger_ids(&c.name).contains(&msg_id))
+        .map(|c| DerivedField::new(&c.name));
+    fields
+        .into_iter()
+        .map(YPlotField::from)
+        .chain(derived_fields.map(YPlotField::from))
+        .collect()
+}
+
+/// Controls of the spectrum, shown above it.
+pub fn spectrum_settings(ui: &mut egui::Ui, plot_settings: &mut PlotSettings) {
+    let names: Vec<_> = plot_settings
+        .y_fields
+        .iter()
+        .map(|(field, _)| field.qualified_name())
+        .collect();
+    let spectrum = &mut plot_settings.spectrum;
+    if spectrum.series >= names.len() {
+        spectrum.series = 0;
+    }
+    egui::ComboBox::from_id_salt(ui.auto_id_with("spectrum_series"))
+        .selected_text(names.get(spectrum.series).cloned().unwrap_or_default())
+        .show_ui(ui, |ui| {
+            for (i, name) in names.into_iter().enumerate() {
+                ui.selectable_value(&mut spectrum.series, i, name);
+            }
+        })
+        .response
+        .on_hover_text("Series analyzed");
+    egui::ComboBox::from_id_salt(ui.auto_id_with("spectrum_window_len"))
+        .selected_text(format!("{} pts", spectrum.window_len))
+        .show_ui(ui, |ui| {
+            for len in WINDOW_LENGTHS {
+                ui.selectable_value(&mut spectrum.window_len, len, len.to_string());
+            }
+        })
+        .response
+        .on_hover_text("Samples in each segment, longer for a finer resolution");
+    egui::ComboBox::from_id_salt(ui.auto_id_with("spectrum_window"))
+        .selected_text(spectrum.window.to_string())
+        .show_ui(ui, |ui| {
+            for window in WindowFunction::iter() {
+                ui.selectable_value(&mut spectrum.window, window, window.to_string());
+            }
+        })
+        .response
+        .on_hover_text("Window function applied to each segment");
+    ui.add(
+        egui::DragValue::new(&mut spectrum.averages)
+            .range(1..=32)
+            .prefix("avg "),
+    )
+    .on_hover_text("Segments averaged, overlapping by half");
+    egui::ComboBox::from_id_salt(ui.auto_id_with("spectrum_scale"))
+        .selected_text(spectrum.scale.to_string())
+        .show_ui(ui, |ui| {
+            for scale in SpectrumScale::iter() {
+                ui.selectable_value(&mut spectrum.scale, scale, scale.to_string());
+            }
+        });
+}
+
+/// Controls of the XY plot, shown above it.
+pub fn xy_settings(ui: &mut egui::Ui, plot_settings: &mut PlotSettings) {
+    if plot_settings.x_field.is_time_base() {
+        ui.label(
+            egui::RichText::new("Choose a field as X axis in the settings")
+                .color(egui::Color32::YELLOW),
+        );
+    }
+    let color_fields = series_fields(plot_settings.plot_message_id);
+    let xy = &mut plot_settings.xy;
+    egui::ComboBox::from_id_salt(ui.auto_id_with("xy_style"))
+        .selected_text(xy.style.to_string())
+        .show_ui(ui, |ui| {
+            for style in XyStyle::iter() {
+                ui.selectable_value(&mut xy.style, style, style.to_string());
+            }
+        });
+    if xy.style.has_points() {
+        ui.add(
+            egui::DragValue::new(&mut xy.point_radius)
+                .range(0.5..=10.0)
+                .speed(0.1)
+                .prefix("r "),
+        )
+        .on_hover_text("Radius of the points");
+    }
+    egui::ComboBox::from_id_salt(ui.auto_id_with("xy_color_by"))
+        .selected_text(xy.color_by.name())
+        .show_ui(ui, |ui| {
+            for color_by in [ColorBy::Series, ColorBy::Time]
+                .into_iter()
+                .chain(color_fields.into_iter().map(ColorBy::Field))
+            {
+                let name = color_by.name();
+                ui.selectable_value(&mut xy.color_by, color_by, name);
+            }
+        })
+        .response
+        .on_hover_text("Value the color of the points is mapped from");
+    if xy.color_by != ColorBy::Series {
+        egui::ComboBox::from_id_salt(ui.auto_id_with("xy_colormap"))
+            .selected_text(xy.colormap.to_string())
+            .show_ui(ui, |ui| {
+                for colormap in Colormap::iter() {
+                    ui.selectable_value(&mut xy.colormap, colormap, colormap.to_string());
+                }
+            });
+    }
+    let mut has_trail = xy.trail.is_some();
+    ui.checkbox(&mut has_trail, "Trail")
+        .on_hover_text("Draw only the latest part of the series");
+    xy.trail = has_trail.then(|| {
+        let mut trail_sec = xy.trail.map_or(30, |trail| trail.as_secs());
+        ui.add(
+            egui::DragValue::new(&mut trail_sec)
+                .range(1..=3600)
+                .suffix(" s"),
+        );
+        Duration::from_secs(trail_sec)
+    });
+    ui.checkbox(&mut xy.equal_aspect, "1:1")
+        .on_hover_text("Same scale on both axes, as for ground tracks");
+}
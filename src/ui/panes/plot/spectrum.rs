@@ -0,0 +1,311 @@
+//! Spectrum of a series, estimated with Welch's method: the latest samples are
+//! split in segments overlapping by half, each one windowed and transformed
+//! with an FFT, and their periodograms are averaged.
+//!
+//! The samples are assumed to be uniformly spaced, the sample rate being
+//! estimated from their time span.
+
+use std::{
+    f64::consts::PI,
+    iter::zip,
+    ops::{Add, Mul, Sub},
+};
+
+use egui_plot::PlotPoint;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter};
+
+/// Lengths of the segments that can be chosen, powers of two as needed by
+/// the FFT.
+pub const WINDOW_LENGTHS: [usize; 9] = [64, 128, 256, 512, 1024, 2048, 4096, 8192, 16384];
+
+/// Floor of the power spectral density, avoiding infinite decibels.
+const MIN_DENSITY: f64 = 1e-30;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpectrumSettings {
+    /// Index of the series analyzed
+    pub series: usize,
+    /// Samples in each segment, a power of two
+    pub window_len: usize,
+    pub window: WindowFunction,
+    /// Number of segments averaged, at most
+    pub averages: usize,
+    pub scale: SpectrumScale,
+}
+
+impl Default for SpectrumSettings {
+    fn default() -> Self {
+        Self {
+            series: 0,
+            window_len: 1024,
+            window: WindowFunction::Hann,
+            averages: 4,
+            scale: SpectrumScale::PowerDensity,
+        }
+    }
+}
+
+impl SpectrumSettings {
+    /// Number of latest samples used when all the segments are available.
+    pub fn samples_needed(&self) -> usize {
+        self.window_len * (self.averages.max(1) + 1) / 2
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Display, EnumIter)]
+pub enum WindowFunction {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl WindowFunction {
+    /// Coefficients of the periodic window of `len` samples.
+    fn coefficients(&self, len: usize) -> Vec<f64> {
+        (0..len)
+            .map(|i| {
+                let phase = 2.0 * PI * i as f64 / len as f64;
+                match self {
+                    WindowFunction::Rectangular => 1.0,
+                    WindowFunction::Hann => 0.5 - 0.5 * phase.cos(),
+                    WindowFunction::Hamming => 0.54 - 0.46 * phase.cos(),
+                    WindowFunction::Blackman => {
+                        0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Display, EnumIter)]
+pub enum SpectrumScale {
+    /// Amplitude of the sinusoids, in the unit of the series
+    Amplitude,
+    /// Power spectral density, in decibels of the squared unit per hertz
+    #[strum(to_string = "PSD (dB)")]
+    PowerDensity,
+}
+
+/// One sided spectrum, from 0 Hz to the Nyquist frequency.
+#[derive(Clone, Debug, Default)]
+pub struct Spectrum {
+    /// Frequency in hertz and value of each bin
+    pub points: Vec<PlotPoint>,
+    pub sample_rate: f64,
+    /// Number of segments averaged
+    pub segments: usize,
+}
+
+impl Spectrum {
+    /// Spectrum of the latest samples, taken at `sample_rate` hertz. `None` if
+    /// fewer samples than a segment are available.
+    pub fn of(samples: &[f64], sample_rate: f64, settings: &SpectrumSettings) -> Option<Self> {
+        let n = settings.window_len;
+        if !n.is_power_of_two() || n < 2 || samples.len() < n || sample_rate <= 0.0 {
+            return None;
+        }
+        let window = settings.window.coefficients(n);
+        let window_sum: f64 = window.iter().sum();
+        let window_power: f64 = window.iter().map(|w| w * w).sum();
+
+        // segments overlapping by half, ending at the latest sample
+        let hop = n / 2;
+        let segments = (1 + (samples.len() - n) / hop).min(settings.averages.max(1));
+        let start = samples.len() - (n + (segments - 1) * hop);
+        let mut power = vec![0.0; n / 2 + 1];
+        let mut buffer = vec![Complex::default(); n];
+        for segment in (0..segments).map(|s| &samples[start + s * hop..start + s * hop + n]) {
+            // the mean would leak into the lowest bins
+            let mean = segment.iter().sum::<f64>() / n as f64;
+            for ((c, x), w) in zip(zip(&mut buffer, segment), &window) {
+                *c = Complex::new((x - mean) * w, 0.0);
+            }
+            fft(&mut buffer);
+            for (p, c) in zip(&mut power, &buffer) {
+                *p += c.norm_sqr();
+            }
+        }
+
+        let bin_width = sample_rate / n as f64;
+        let points = power
+            .into_iter()
+            .enumerate()
+            .map(|(k, p)| {
+                let p = p / segments as f64;
+                // the negative frequencies are folded on the positive ones
+                let fold = if k == 0 || k == n / 2 { 1.0 } else { 2.0 };
+                let value = match settings.scale {
+                    SpectrumScale::Amplitude => fold * p.sqrt() / window_sum,
+                    SpectrumScale::PowerDensity => {
+                        let density = fold * p / (sample_rate * window_power);
+                        10.0 * density.max(MIN_DENSITY).log10()
+                    }
+                };
+                PlotPoint::new(k as f64 * bin_width, value)
+            })
+            .collect();
+        Some(Self {
+            points,
+            sample_rate,
+            segments,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn norm_sqr(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+/// In place iterative radix-2 FFT, the length must be a power of two.
+fn fft(data: &mut [Complex]) {
+    let n = data.len();
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        let angle = -2.0 * PI / len as f64;
+        for k in 0..half {
+            let (sin, cos) = (angle * k as f64).sin_cos();
+            let twiddle = Complex::new(cos, sin);
+            for start in (0..n).step_by(len) {
+                let a = data[start + k];
+                let b = data[start + k + half] * twiddle;
+                data[start + k] = a + b;
+                data[start + k + half] = a - b;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(window: WindowFunction, scale: SpectrumScale) -> SpectrumSettings {
+        SpectrumSettings {
+            window,
+            scale,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sine_amplitude_and_frequency() {
+        // 2 units at 125 Hz, sampled at 1 kHz, exactly on a bin
+        let samples: Vec<f64> = (0..4096)
+            .map(|i| 1.0 + 2.0 * (2.0 * PI * 125.0 * i as f64 / 1000.0).sin())
+            .collect();
+        let spectrum = Spectrum::of(
+            &samples,
+            1000.0,
+            &settings(WindowFunction::Hann, SpectrumScale::Amplitude),
+        )
+        .unwrap();
+        assert_eq!(spectrum.segments, 4);
+        assert_eq!(spectrum.points.len(), 513);
+
+        let peak = spectrum
+            .points
+            .iter()
+            .max_by(|a, b| a.y.total_cmp(&b.y))
+            .copied()
+            .unwrap();
+        assert_eq!(peak.x, 125.0);
+        assert!((peak.y - 2.0).abs() < 1e-6, "{}", peak.y);
+        // the mean is removed
+        assert!(spectrum.points[0].y < 1e-9);
+    }
+
+    #[test]
+    fn density_integrates_to_the_variance() {
+        // deterministic white noise
+        let mut state: u64 = 1;
+        let samples: Vec<f64> = (0..1024)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 11) as f64 / (1u64 << 53) as f64 - 0.5
+            })
+            .collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+
+        let spectrum = Spectrum::of(
+            &samples,
+            200.0,
+            &settings(WindowFunction::Rectangular, SpectrumScale::PowerDensity),
+        )
+        .unwrap()
+        .points;
+        let bin_width = 200.0 / 1024.0;
+        let power: f64 = spectrum
+            .iter()
+            .map(|p| 10f64.powf(p.y / 10.0) * bin_width)
+            .sum();
+        assert!((power - variance).abs() < 1e-9 * variance.max(1.0));
+    }
+
+    #[test]
+    fn too_few_samples() {
+        let settings = SpectrumSettings::default();
+        assert!(Spectrum::of(&[0.0; 1000], 100.0, &settings).is_none());
+        assert_eq!(settings.samples_needed(), 2560);
+    }
+}
@@ -0,0 +1,88 @@
+//! Live statistics of the series over the visible window.
+
+use std::time::Instant;
+
+use egui_plot::PlotPoint;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SeriesStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Sample standard deviation, zero for a single point
+    pub std: f64,
+    /// Value of the point with the greatest x
+    pub last: f64,
+    /// Points received per second, if received over some time
+    pub rate: Option<f64>,
+}
+
+impl SeriesStats {
+    /// Statistics of the points, sorted by x, with the time they were received
+    /// at. `None` if there are no points.
+    pub fn of(points: impl IntoIterator<Item = (Instant, PlotPoint)>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let (time, first) = points.next()?;
+        let mut stats = Self {
+            count: 1,
+            min: first.y,
+            max: first.y,
+            mean: first.y,
+            std: 0.0,
+            last: first.y,
+            rate: None,
+        };
+        let (mut oldest, mut newest) = (time, time);
+        // Welford's algorithm, stable also with large offsets
+        let mut squares = 0.0;
+        for (time, point) in points {
+            stats.count += 1;
+            stats.min = stats.min.min(point.y);
+            stats.max = stats.max.max(point.y);
+            let delta = point.y - stats.mean;
+            stats.mean += delta / stats.count as f64;
+            squares += delta * (point.y - stats.mean);
+            stats.last = point.y;
+            oldest = oldest.min(time);
+            newest = newest.max(time);
+        }
+        if stats.count > 1 {
+            stats.std = (squares / (stats.count - 1) as f64).sqrt();
+        }
+        let span = newest.duration_since(oldest).as_secs_f64();
+        stats.rate = (span > 0.0).then(|| (stats.count - 1) as f64 / span);
+        Some(stats)
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn statistics_of_the_window() {
+        let start = Instant::now();
+        let points = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, y)| {
+                let time = start + Duration::from_millis(100 * i as u64);
+                (time, PlotPoint::new(i as f64, 1e6 + y))
+            });
+        let stats = SeriesStats::of(points).unwrap();
+        assert_eq!(stats.count, 8);
+        assert_eq!(
+            (stats.min, stats.max, stats.last),
+            (1e6 + 2.0, 1e6 + 9.0, 1e6 + 9.0)
+        );
+        assert!((stats.mean - (1e6 + 5.0)).abs() < 1e-9);
+        assert!((stats.std - (32.0f64 / 7.0).sqrt()).abs() < 1e-9);
+        assert!(stats.rate.is_some_and(|rate| (rate - 10.0).abs() < 1e-9));
+
+        assert_eq!(SeriesStats::of([]), None);
+    }
+}
@@ -0,0 +1,238 @@
+//! XY plots: the series drawn against the field of the x axis in the order
+//! they are received, as ground tracks or phase diagrams, optionally colored
+//! by a third value.
+
+use std::{ops::RangeInclusive, time::Duration};
+
+use egui::Color32;
+use egui_plot::PlotPoint;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter};
+
+use super::fields::YPlotField;
+
+/// Number of colors the values are binned in, each drawn as a separate item.
+pub const COLOR_LEVELS: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct XySettings {
+    pub style: XyStyle,
+    pub point_radius: f32,
+    pub color_by: ColorBy,
+    pub colormap: Colormap,
+    /// Latest part of the series drawn, all the points kept if `None`
+    pub trail: Option<Duration>,
+    /// Same scale on both axes, as for ground tracks
+    pub equal_aspect: bool,
+}
+
+impl Default for XySettings {
+    fn default() -> Self {
+        Self {
+            style: XyStyle::LineAndPoints,
+            point_radius: 2.0,
+            color_by: ColorBy::Series,
+            colormap: Colormap::Viridis,
+            trail: None,
+            equal_aspect: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Display, EnumIter)]
+pub enum XyStyle {
+    Points,
+    Line,
+    #[strum(to_string = "Line and points")]
+    LineAndPoints,
+}
+
+impl XyStyle {
+    pub fn has_points(&self) -> bool {
+        matches!(self, XyStyle::Points | XyStyle::LineAndPoints)
+    }
+
+    pub fn has_line(&self) -> bool {
+        matches!(self, XyStyle::Line | XyStyle::LineAndPoints)
+    }
+}
+
+/// Value the color of the points is mapped from.
+#[derive(Clone, Debug, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ColorBy {
+    /// The color of the series
+    Series,
+    /// Receipt time, the oldest points in the colors at the low end of the map
+    Time,
+    /// Latest value of a field when each point is received
+    Field(YPlotField),
+}
+
+impl ColorBy {
+    pub fn name(&self) -> String {
+        match self {
+            ColorBy::Series => "Series color".to_owned(),
+            ColorBy::Time => "Receipt time".to_owned(),
+            ColorBy::Field(field) => field.qualified_name(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Display, EnumIter)]
+pub enum Colormap {
+    Viridis,
+    Plasma,
+    Turbo,
+    Grayscale,
+}
+
+impl Colormap {
+    /// Colors evenly spaced along the map, interpolated linearly.
+    fn stops(&self) -> &'static [[u8; 3]] {
+        match self {
+            Colormap::Viridis => &[
+                [0x44, 0x01, 0x54],
+                [0x3b, 0x52, 0x8b],
+                [0x21, 0x91, 0x8c],
+                [0x5e, 0xc9, 0x62],
+                [0xfd, 0xe7, 0x25],
+            ],
+            Colormap::Plasma => &[
+                [0x0d, 0x08, 0x87],
+                [0x7e, 0x03, 0xa8],
+                [0xcc, 0x47, 0x78],
+                [0xf8, 0x95, 0x40],
+                [0xf0, 0xf9, 0x21],
+            ],
+            Colormap::Turbo => &[
+                [0x30, 0x12, 0x3b],
+                [0x46, 0x86, 0xfb],
+                [0x1b, 0xe5, 0xb5],
+                [0xa4, 0xfc, 0x3c],
+                [0xfb, 0x7e, 0x21],
+                [0x7a, 0x04, 0x03],
+            ],
+            Colormap::Grayscale => &[[0x30, 0x30, 0x30], [0xf0, 0xf0, 0xf0]],
+        }
+    }
+
+    /// Color at `t`, from 0 to 1, of the map.
+    pub fn color_at(&self, t: f64) -> Color32 {
+        let stops = self.stops();
+        let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let i = (position as usize).min(stops.len() - 2);
+        let fraction = position - i as f64;
+        let [r, g, b] = [0, 1, 2].map(|c| {
+            let (from, to) = (stops[i][c] as f64, stops[i + 1][c] as f64);
+            (from + (to - from) * fraction).round() as u8
+        });
+        Color32::from_rgb(r, g, b)
+    }
+
+    /// Color of a level out of `COLOR_LEVELS`, taken at its middle.
+    pub fn level_color(&self, level: usize) -> Color32 {
+        self.color_at((level as f64 + 0.5) / COLOR_LEVELS as f64)
+    }
+}
+
+/// Range of the finite values, `None` if there are none.
+pub fn value_range(values: impl IntoIterator<Item = f64>) -> Option<RangeInclusive<f64>> {
+    values
+        .into_iter()
+        .filter(|v| v.is_finite())
+        .fold(None, |range, v| match range {
+            None => Some(v..=v),
+            Some(range) => Some(range.start().min(v)..=range.end().max(v)),
+        })
+}
+
+/// Level of the value among `COLOR_LEVELS` equal slices of the range, `None`
+/// if unknown.
+pub fn color_level(value: f64, range: &RangeInclusive<f64>) -> Option<usize> {
+    if !value.is_finite() {
+        return None;
+    }
+    let span = range.end() - range.start();
+    if span <= 0.0 {
+        return Some(COLOR_LEVELS / 2);
+    }
+    let t = ((value - range.start()) / span).clamp(0.0, 1.0);
+    Some(((t * COLOR_LEVELS as f64) as usize).min(COLOR_LEVELS - 1))
+}
+
+/// Runs of consecutive points of the same level, each ending on the first
+/// point of the next one so that their lines join.
+pub fn level_runs(
+    points: &[PlotPoint],
+    levels: &[Option<usize>],
+) -> Vec<(Option<usize>, Vec<PlotPoint>)> {
+    let mut runs: Vec<(Option<usize>, Vec<PlotPoint>)> = Vec::new();
+    for (&point, &level) in points.iter().zip(levels) {
+        match runs.last_mut() {
+            Some((last_level, run)) if *last_level == level => run.push(point),
+            Some((_, run)) => {
+                run.push(point);
+                runs.push((level, vec![point]));
+            }
+            None => runs.push((level, vec![point])),
+        }
+    }
+    runs
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colormaps_span_their_stops() {
+        assert_eq!(
+            Colormap::Viridis.color_at(0.0),
+            Color32::from_rgb(0x44, 0x01, 0x54)
+        );
+        assert_eq!(
+            Colormap::Viridis.color_at(1.0),
+            Color32::from_rgb(0xfd, 0xe7, 0x25)
+        );
+        assert_eq!(
+            Colormap::Grayscale.color_at(0.5),
+            Color32::from_rgb(0x90, 0x90, 0x90)
+        );
+        // out of range values are clamped
+        assert_eq!(
+            Colormap::Turbo.color_at(-1.0),
+            Colormap::Turbo.color_at(0.0)
+        );
+    }
+
+    #[test]
+    fn values_are_binned_in_levels() {
+        let range = value_range([3.0, f64::NAN, -1.0, 1.0]).unwrap();
+        assert_eq!(range, -1.0..=3.0);
+        assert_eq!(color_level(-1.0, &range), Some(0));
+        assert_eq!(color_level(3.0, &range), Some(COLOR_LEVELS - 1));
+        assert_eq!(color_level(1.0, &range), Some(COLOR_LEVELS / 2));
+        assert_eq!(color_level(f64::NAN, &range), None);
+        assert_eq!(value_range([]), None);
+    }
+
+    #[test]
+    fn runs_join_their_lines() {
+        let points: Vec<_> = (0..5).map(|i| PlotPoint::new(i as f64, 0.0)).collect();
+        let levels = [Some(0), Some(0), Some(1), None, None];
+        let runs = level_runs(&points, &levels);
+        let shape: Vec<_> = runs
+            .iter()
+            .map(|(level, run)| (*level, run.iter().map(|p| p.x).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(
+            shape,
+            vec![
+                (Some(0), vec![0.0, 1.0, 2.0]),
+                (Some(1), vec![2.0, 3.0]),
+                (None, vec![3.0, 4.0]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,400 @@
+mod procedure;
+mod run;
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use egui::{
+    Button, Color32, Context, DragValue, Frame, ProgressBar, RichText, ScrollArea, Ui, mutex::Mutex,
+};
+use egui_file::FileDialog;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    APP_NAME,
+    commands::COMMAND_LIBRARY,
+    mavlink::{MavHeader, MavMessage, TimedMessage, reflection::MAVLINK_PROFILE},
+    tc_log::{PendingReply, TcReply},
+    ui::{app::PaneResponse, widgets::CommandArming},
+    utils::id::PaneId,
+};
+
+use super::PaneBehavior;
+
+use procedure::{Procedure, Step, format_duration};
+use run::{Outcome, Run};
+
+/// Runs a procedure read from a text file, i.e. an ordered list of
+/// telecommands, waits and conditions. Telecommands are sent only once
+/// confirmed by the operator, and armed first if critical in the command
+/// library.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScriptPane {
+    /// File the procedure is read from
+    path: Option<PathBuf>,
+    system_id: u8,
+
+    #[serde(skip)]
+    pane_id: Option<PaneId>,
+    /// Procedure read from the file, or the reason it could not be read
+    #[serde(skip)]
+    loaded: Option<Result<Procedure, String>>,
+    /// Never restored from the layout, procedures are always started by the user
+    #[serde(skip)]
+    run: Option<Run>,
+    /// Arming of the command of the current step, if critical
+    #[serde(skip)]
+    arming: CommandArming,
+    #[serde(skip)]
+    settings_visible: bool,
+    #[serde(skip)]
+    to_send: Vec<(MavHeader, MavMessage)>,
+}
+
+impl Default for ScriptPane {
+    fn default() -> Self {
+        Self {
+            path: None,
+            system_id: 1, // Default system ID
+            pane_id: None,
+            loaded: None,
+            run: None,
+            arming: CommandArming::default(),
+            settings_visible: false,
+            to_send: Vec::new(),
+        }
+    }
+}
+
+impl PartialEq for ScriptPane {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.system_id == other.system_id
+    }
+}
+
+impl PaneBehavior for ScriptPane {
+    #[profiling::function]
+    fn ui(&mut self, ui: &mut Ui) -> PaneResponse {
+        let mut pane_response = PaneResponse::default();
+
+        if self.loaded.is_none() && self.path.is_some() {
+            self.load();
+        }
+        if let (Some(run), Some(Ok(procedure))) = (&mut self.run, &mut self.loaded) {
+            run.poll(procedure);
+        }
+
+        let res = ui
+            .scope(|ui| {
+                self.toolbar(ui);
+                ui.separator();
+                match &self.loaded {
+                    Some(Ok(_)) => self.procedure_ui(ui),
+                    Some(Err(e)) => {
+                        ui.label(RichText::new(e).color(Color32::RED));
+                    }
+                    None => {
+                        ui.label(RichText::new("Open a procedure to run it").italics());
+                    }
+                }
+            })
+            .response;
+
+        if res.interact(egui::Sense::click_and_drag()).dragged() {
+            pane_response.set_drag_started();
+        }
+
+        self.show_file_dialog(ui.ctx());
+
+        let mut settings_visible = self.settings_visible;
+        egui::Window::new("Script Settings")
+            .id(ui.auto_id_with("script_settings"))
+            .auto_sized()
+            .collapsible(true)
+            .movable(true)
+            .open(&mut settings_visible)
+            .show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    let label = ui.label("System ID:");
+                    ui.add(DragValue::new(&mut self.system_id).range(1..=255))
+                        .labelled_by(label.id);
+                });
+            });
+        self.settings_visible = settings_visible;
+
+        // Waits and conditions advance on their own
+        if self.run.as_ref().is_some_and(Run::is_running) {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+
+        pane_response
+    }
+
+    fn update(&mut self, messages: &[&TimedMessage]) {
+        if let Some(run) = &mut self.run {
+            run.update(messages);
+        }
+    }
+
+    fn get_message_subscriptions(&self) -> Box<dyn Iterator<Item = u32>> {
+        let (Some(run), Some(Ok(procedure))) = (&self.run, &self.loaded) else {
+            return Box::new(None.into_iter());
+        };
+        if !run.is_running() {
+            return Box::new(None.into_iter());
+        }
+        Box::new(procedure.input_message_ids().into_iter())
+    }
+
+    fn drain_outgoing_messages(&mut self) -> Vec<(MavHeader, MavMessage)> {
+        self.to_send.drain(..).collect()
+    }
+
+    fn handle_reply(&mut self, pending: &PendingReply, reply: &TcReply) {
+        if let Some(run) = &mut self.run {
+            run.handle_reply(pending, reply);
+        }
+    }
+
+    fn source_label(&self) -> Option<String> {
+        let name = self.path.as_ref()?.file_name()?;
+        Some(name.to_string_lossy().into_owned())
+    }
+
+    fn init(&mut self, pane_id: PaneId) {
+        self.pane_id = Some(pane_id);
+    }
+}
+
+impl ScriptPane {
+    fn is_running(&self) -> bool {
+        self.run.as_ref().is_some_and(Run::is_running)
+    }
+
+    /// Reads the procedure from the file, dropping the last run.
+    fn load(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        self.run = None;
+        self.loaded = Some(
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("Unable to read {}: {}", path.display(), e))
+                .and_then(|text| Procedure::parse(&text).map_err(|e| e.to_string())),
+        );
+        if let Some(Err(e)) = &self.loaded {
+            error!("Failed to load the procedure: {}", e);
+        }
+    }
+
+    fn toolbar(&mut self, ui: &mut Ui) {
+        let running = self.is_running();
+        ui.horizontal_wrapped(|ui| {
+            if ui
+                .add_enabled(!running, Button::new("📂 Open…"))
+                .on_disabled_hover_text("Abort the procedure first")
+                .clicked()
+            {
+                self.open_file_dialog(ui.ctx());
+            }
+            if ui
+                .add_enabled(!running && self.path.is_some(), Button::new("⟲ Reload"))
+                .on_hover_text("Read the procedure again from the file")
+                .clicked()
+            {
+                self.load();
+            }
+            if ui.button("⚙").on_hover_text("Settings").clicked() {
+                self.settings_visible = !self.settings_visible;
+            }
+            if let Some(path) = &self.path {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                ui.label(RichText::new(name).strong())
+                    .on_hover_text(path.display().to_string());
+            }
+        });
+    }
+
+    fn procedure_ui(&mut self, ui: &mut Ui) {
+        let Some(Ok(procedure)) = &self.loaded else {
+            return;
+        };
+        let steps = procedure.steps.len();
+
+        // Controls and progress
+        let mut start = false;
+        let mut abort = false;
+        ui.horizontal(|ui| {
+            if self.is_running() {
+                abort = ui.button("⏹ Abort").clicked();
+            } else {
+                start = ui
+                    .add_enabled(steps > 0, Button::new("▶ Start"))
+                    .on_hover_text("Telecommands are sent only once confirmed")
+                    .clicked();
+            }
+            let done = self.run.as_ref().map_or(0, |r| r.current.min(steps));
+            let progress = if steps > 0 {
+                done as f32 / steps as f32
+            } else {
+                0.0
+            };
+            ui.add(ProgressBar::new(progress).text(format!("{done} / {steps} steps")));
+        });
+        if let Some(Outcome::Aborted(reason)) = self.run.as_ref().and_then(|r| r.outcome.as_ref()) {
+            ui.label(RichText::new(format!("Aborted: {reason}")).color(Color32::RED));
+        }
+
+        // Command sent by the current step, named as in the library
+        let command = self
+            .run
+            .as_ref()
+            .and_then(|r| procedure.steps.get(r.current))
+            .and_then(|s| match &s.step {
+                Step::Send(message) => Some(message.message_id()),
+                _ => None,
+            })
+            .map(|id| {
+                let name = MAVLINK_PROFILE.get_msg(id).map(|m| m.name.clone());
+                (id, name.unwrap_or_default())
+            });
+
+        // Step waiting for the operator
+        let mut confirmed = false;
+        if let Some(run) = self
+            .run
+            .as_ref()
+            .filter(|r| r.is_waiting_operator(procedure))
+        {
+            let step = &procedure.steps[run.current];
+            Frame::group(ui.style())
+                .fill(ui.visuals().warn_fg_color.gamma_multiply(0.15))
+                .show(ui, |ui| {
+                    ui.set_width(ui.available_width());
+                    ui.label(
+                        RichText::new(format!("Step {}: {}", run.current + 1, step.step)).strong(),
+                    );
+                    let Some((message_id, name)) = &command else {
+                        confirmed = ui.button(RichText::new("✔ Confirm").strong()).clicked();
+                        return;
+                    };
+                    // critical commands are sent only once armed and confirmed
+                    confirmed = self.arming.show_armed(ui, name);
+                    if !self.arming.is_armed()
+                        && ui.button(RichText::new("📤 Send").strong()).clicked()
+                    {
+                        self.arming.criticality = COMMAND_LIBRARY
+                            .read()
+                            .criticality_of(self.system_id, *message_id);
+                        confirmed = self.arming.request(ui.ctx(), name);
+                    }
+                });
+        }
+
+        ui.separator();
+        self.steps_list(ui, procedure);
+        self.run_log(ui);
+
+        if start {
+            info!("Procedure started");
+            self.run = Some(Run::start(procedure));
+        }
+        if let Some(run) = self.run.as_mut().filter(|_| abort) {
+            run.abort("Aborted by the operator".to_owned());
+        }
+        // an armed command is not sent once the run is over
+        if let Some((_, name)) = &command
+            && !self.is_running()
+        {
+            self.arming.disarm(ui.ctx(), name, "procedure aborted");
+        }
+        if let Some(run) = self.run.as_mut().filter(|_| confirmed) {
+            self.to_send.extend(run.confirm(procedure, self.system_id));
+        }
+    }
+
+    fn steps_list(&self, ui: &mut Ui, procedure: &Procedure) {
+        let max_height = ui.available_height() * 0.6;
+        ScrollArea::vertical()
+            .id_salt(ui.id().with("script_steps"))
+            .max_height(max_height)
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                for (i, step) in procedure.steps.iter().enumerate() {
+                    let (icon, color) = match &self.run {
+                        Some(run) if i < run.current => ("✔", Color32::GREEN),
+                        Some(run) if i == run.current => match run.outcome {
+                            Some(Outcome::Aborted(_)) => ("✖", Color32::RED),
+                            _ => ("▶", ui.visuals().strong_text_color()),
+                        },
+                        _ => ("  ", ui.visuals().text_color()),
+                    };
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(icon).color(color).monospace());
+                        ui.label(
+                            RichText::new(format!("{:>3}", step.line))
+                                .weak()
+                                .monospace(),
+                        )
+                        .on_hover_text("Line in the file");
+                        let mut text = RichText::new(step.step.to_string()).color(color);
+                        if step.step.is_gated() {
+                            text = text.strong();
+                        }
+                        ui.label(text);
+                        // time spent waiting on the current step
+                        let waiting = self
+                            .run
+                            .as_ref()
+                            .filter(|r| r.is_running() && r.current == i && !step.step.is_gated());
+                        if let Some(run) = waiting {
+                            ui.label(RichText::new(format_duration(run.step_elapsed())).weak());
+                        }
+                    });
+                }
+            });
+    }
+
+    fn run_log(&self, ui: &mut Ui) {
+        let Some(run) = &self.run else {
+            return;
+        };
+        ui.separator();
+        ScrollArea::vertical()
+            .id_salt(ui.id().with("script_log"))
+            .stick_to_bottom(true)
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                for (time, entry) in &run.log {
+                    ui.label(format!("{} {}", time.strftime("%H:%M:%S"), entry));
+                }
+            });
+    }
+
+    fn dialog_id(&self) -> egui::Id {
+        *(self.pane_id.unwrap_or(PaneId::from_str("script_pane")) / "load_file_dialog")
+    }
+
+    fn open_file_dialog(&self, ctx: &Context) {
+        // Create a new file dialog and store it in the memory
+        let initial_path = self.path.clone().or_else(|| eframe::storage_dir(APP_NAME));
+        let mut file_dialog = FileDialog::open_file(initial_path);
+        file_dialog.open();
+        let id = self.dialog_id();
+        ctx.memory_mut(|m| m.data.insert_temp(id, Arc::new(Mutex::new(file_dialog))));
+    }
+
+    fn show_file_dialog(&mut self, ctx: &Context) {
+        let id = self.dialog_id();
+        let Some(file_dialog) = ctx.memory_mut(|m| m.data.get_temp::<Arc<Mutex<FileDialog>>>(id))
+        else {
+            return;
+        };
+        let mut file_dialog = file_dialog.lock();
+        let selected = file_dialog.show(ctx).selected();
+        if let Some(path) = file_dialog.path().filter(|_| selected) {
+            self.path = Some(path.to_path_buf());
+            self.load();
+        }
+    }
+}
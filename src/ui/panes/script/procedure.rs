@@ -0,0 +1,331 @@
+//! Text format of the procedures run by the script pane.
+//!
+//! A procedure has one step per line, in the [line-based
+//! format](crate::utils::text) shared with the checklists:
+//!
+//! ```text
+//! # Oxidizer filling valve checkout
+//! confirm "Check that the pad is clear"
+//! send WIGGLE_SERVO_TC servo_id=OX_FILLING_VALVE
+//! until ACK timeout 5 s
+//! wait 2 s
+//! send SET_ATOMIC_VALVE_TIMING_TC servo_id=OX_FILLING_VALVE maximum_timing=1000
+//! until ACK
+//! until GSE_TM.ox_filling_valve_state == 1 timeout 10 s
+//! ```
+//!
+//! - `send MESSAGE field=value ...` sends a telecommand, with the given fields
+//!   set to a number or to an enum entry and the others left to default;
+//! - `wait DURATION` waits for the given time, e.g. `2 s`, `500 ms` or `1 min`;
+//! - `until ACK [timeout DURATION]` waits for the reply to the last command;
+//! - `until CONDITION [timeout DURATION]` waits until the condition is true,
+//!   written with the syntax of the derived channels (see [`expression`]);
+//! - `confirm "TEXT"` waits for the operator to confirm.
+//!
+//! Telecommands are only sent once confirmed by the operator (see R15 in the
+//! README), the other steps run on their own.
+//!
+//! [`expression`]: crate::derived::expression
+
+use std::{fmt::Display, time::Duration};
+
+use mavlink_bindgen::parser::MavType;
+
+use crate::{
+    derived::condition::{self, Condition},
+    error::ErrInstrument,
+    mavlink::{
+        MavMessage, Message,
+        reflection::{
+            FieldLike, FieldLookup, IndexedField, MAVLINK_PROFILE, MapConvertible, MessageMap,
+        },
+    },
+    tc_log::DEFAULT_REPLY_TIMEOUT,
+    utils::text::{LineError, parse_lines},
+};
+
+/// Ordered list of steps.
+#[derive(Debug, Clone)]
+pub struct Procedure {
+    pub steps: Vec<ScriptStep>,
+}
+
+/// Step of a procedure, with the line it was read from.
+#[derive(Debug, Clone)]
+pub struct ScriptStep {
+    pub line: usize,
+    pub step: Step,
+}
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Sends the message, once confirmed by the operator
+    Send(MessageMap),
+    Wait(Duration),
+    /// Waits for the ACK of the last command sent, failing on NACK, WACK or
+    /// when no reply arrives within the timeout
+    UntilAck(Duration),
+    /// Waits until the condition is true, failing after the timeout, if any
+    Until {
+        condition: Condition,
+        timeout: Option<Duration>,
+    },
+    /// Waits for the operator to confirm
+    Confirm(String),
+}
+
+impl Step {
+    /// Whether the step waits for the operator before running.
+    pub fn is_gated(&self) -> bool {
+        matches!(self, Self::Send(_) | Self::Confirm(_))
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Send(message) => {
+                let name = MAVLINK_PROFILE
+                    .get_msg(message.message_id())
+                    .map(|m| m.name.as_str())
+                    .unwrap_or("?");
+                write!(f, "Send {name}")
+            }
+            Self::Wait(duration) => write!(f, "Wait {}", format_duration(*duration)),
+            Self::UntilAck(timeout) => {
+                write!(f, "Until ACK (timeout {})", format_duration(*timeout))
+            }
+            Self::Until { condition, timeout } => match timeout {
+                Some(timeout) => {
+                    write!(
+                        f,
+                        "Until {condition} (timeout {})",
+                        format_duration(*timeout)
+                    )
+                }
+                None => write!(f, "Until {condition}"),
+            },
+            Self::Confirm(text) => write!(f, "Confirm: {text}"),
+        }
+    }
+}
+
+impl Procedure {
+    pub fn parse(text: &str) -> Result<Self, LineError> {
+        let steps = parse_lines(text, parse_step)?
+            .into_iter()
+            .map(|(line, step)| ScriptStep { line, step })
+            .collect();
+        Ok(Self { steps })
+    }
+
+    /// Ids of the messages read by the conditions of the procedure.
+    pub fn input_message_ids(&self) -> Vec<u32> {
+        condition::input_message_ids(self.steps.iter().filter_map(|s| match &s.step {
+            Step::Until { condition, .. } => Some(condition),
+            _ => None,
+        }))
+    }
+}
+
+fn parse_step(line: &str) -> Result<Step, String> {
+    let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    match keyword {
+        "send" => parse_send(rest).map(Step::Send),
+        "wait" => parse_duration(rest).map(Step::Wait),
+        "until" => {
+            let (source, timeout) = split_timeout(rest)?;
+            if source.eq_ignore_ascii_case("ack") {
+                Ok(Step::UntilAck(timeout.unwrap_or(DEFAULT_REPLY_TIMEOUT)))
+            } else {
+                let condition = Condition::parse_with_derived(source).map_err(|e| e.to_string())?;
+                Ok(Step::Until { condition, timeout })
+            }
+        }
+        "confirm" => {
+            let text = rest.trim_matches('"').trim();
+            if text.is_empty() {
+                Err("Missing the text to confirm".to_owned())
+            } else {
+                Ok(Step::Confirm(text.to_owned()))
+            }
+        }
+        _ => Err(format!("Unknown step \"{keyword}\"")),
+    }
+}
+
+fn parse_send(rest: &str) -> Result<MessageMap, String> {
+    let mut words = rest.split_whitespace();
+    let name = words.next().ok_or("Missing the message to send")?;
+    let id = MAVLINK_PROFILE
+        .get_msg(name)
+        .map(|m| m.id)
+        .ok_or_else(|| format!("Unknown message {name}"))?;
+    let mut map = MavMessage::default_message_from_id(id)
+        .log_unwrap()
+        .as_map();
+    for assignment in words {
+        let (field, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("Expected field=value, found \"{assignment}\""))?;
+        let field = field.to_mav_field(id, &MAVLINK_PROFILE)?;
+        let value = match value.parse::<f64>() {
+            Ok(value) => value,
+            // entries of the enum of the field
+            Err(_) => field
+                .field()
+                .enumtype
+                .as_ref()
+                .and_then(|e| MAVLINK_PROFILE.get_enum_entry_value(e, value))
+                .ok_or_else(|| format!("Invalid value \"{value}\""))? as f64,
+        };
+        set_field(&mut map, field, value)?;
+    }
+    Ok(map)
+}
+
+/// Sets a numeric field, checking that the value fits its type.
+fn set_field(map: &mut MessageMap, field: IndexedField, value: f64) -> Result<(), String> {
+    let name = field.name().to_owned();
+    macro_rules! set_int {
+        ($type:ty) => {{
+            if value.fract() != 0.0 || value < <$type>::MIN as f64 || value > <$type>::MAX as f64 {
+                return Err(format!("Value {value} out of range for field {name}"));
+            }
+            let target: &mut $type = map.get_mut_field(field).log_unwrap();
+            *target = value as $type;
+        }};
+    }
+    match field.field().mavtype {
+        MavType::UInt8MavlinkVersion | MavType::UInt8 => set_int!(u8),
+        MavType::UInt16 => set_int!(u16),
+        MavType::UInt32 => set_int!(u32),
+        MavType::UInt64 => set_int!(u64),
+        MavType::Int8 => set_int!(i8),
+        MavType::Int16 => set_int!(i16),
+        MavType::Int32 => set_int!(i32),
+        MavType::Int64 => set_int!(i64),
+        MavType::Float => {
+            let target: &mut f32 = map.get_mut_field(field).log_unwrap();
+            *target = value as f32;
+        }
+        MavType::Double => {
+            let target: &mut f64 = map.get_mut_field(field).log_unwrap();
+            *target = value;
+        }
+        MavType::Char | MavType::Array(_, _) => {
+            return Err(format!("Field {name} cannot be set from a script"));
+        }
+    }
+    Ok(())
+}
+
+/// Splits the optional `timeout DURATION` clause at the end of an `until` step
+/// from its source. The duration has to start with a number, so that the
+/// condition can still read a derived channel called `timeout`.
+fn split_timeout(rest: &str) -> Result<(&str, Option<Duration>), String> {
+    match rest.rsplit_once(" timeout ") {
+        Some((source, timeout))
+            if timeout
+                .trim_start()
+                .starts_with(|c: char| c.is_ascii_digit()) =>
+        {
+            Ok((source.trim(), Some(parse_duration(timeout)?)))
+        }
+        _ => Ok((rest, None)),
+    }
+}
+
+/// Parses a duration like `2 s`, `500ms` or `1.5 min`.
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("Invalid duration \"{text}\""))?;
+    let secs = match unit.trim() {
+        "ms" => value / 1000.0,
+        "s" | "" => value,
+        "min" => value * 60.0,
+        unit => return Err(format!("Unknown unit \"{unit}\", use ms, s or min")),
+    };
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("Duration \"{text}\" is too long"))
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs < 1.0 {
+        format!("{} ms", duration.as_millis())
+    } else {
+        format!("{secs:.1} s")
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_are_parsed() {
+        let steps = Procedure::parse(
+            "# checkout\n\
+             \n\
+             confirm \"Valve #2 closed\"  # operator check\n\
+             wait 500 ms\n\
+             until ACK timeout 5 s\n\
+             until ack",
+        )
+        .unwrap()
+        .steps;
+        let lines: Vec<usize> = steps.iter().map(|s| s.line).collect();
+        assert_eq!(lines, vec![3, 4, 5, 6]);
+        assert!(matches!(&steps[0].step, Step::Confirm(text) if text == "Valve #2 closed"));
+        assert!(matches!(steps[1].step, Step::Wait(d) if d == Duration::from_millis(500)));
+        assert!(matches!(steps[2].step, Step::UntilAck(d) if d == Duration::from_secs(5)));
+        assert!(matches!(steps[3].step, Step::UntilAck(d) if d == DEFAULT_REPLY_TIMEOUT));
+    }
+
+    #[test]
+    fn unknown_steps_are_rejected() {
+        assert!(parse_step("jump 3").is_err());
+        assert!(parse_step("wait 2 hours").is_err());
+        assert!(parse_step("confirm \"\"").is_err());
+    }
+
+    #[test]
+    fn timeouts_are_split_from_the_condition() {
+        let five_seconds = Some(Duration::from_secs(5));
+        assert_eq!(split_timeout("ACK timeout 5 s"), Ok(("ACK", five_seconds)));
+        assert_eq!(split_timeout("ACK"), Ok(("ACK", None)));
+        // a derived channel called timeout
+        assert_eq!(
+            split_timeout("a + timeout > 3"),
+            Ok(("a + timeout > 3", None))
+        );
+        assert_eq!(
+            split_timeout("a + timeout > 3 timeout 5 s"),
+            Ok(("a + timeout > 3", five_seconds))
+        );
+        assert!(split_timeout("ACK timeout 5 hours").is_err());
+    }
+
+    #[test]
+    fn durations_accept_units() {
+        assert_eq!(parse_duration("2 s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1.5 min"), Ok(Duration::from_secs(90)));
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("99999999999999999999 s").is_err());
+    }
+
+    #[test]
+    fn too_long_durations_report_the_line() {
+        let error = Procedure::parse("wait 1 s\nuntil ACK timeout 99999999999999999999 min").err();
+        assert_eq!(error.map(|e| e.line), Some(2));
+    }
+}
@@ -0,0 +1,199 @@
+use std::time::{Duration, Instant};
+
+use jiff::Zoned;
+use tracing::{info, warn};
+
+use crate::{
+    derived::condition::LatestValues,
+    error::ErrInstrument,
+    mavlink::{
+        MavHeader, MavMessage, TimedMessage, reflection::MapConvertible, stamp_current_time,
+    },
+    tc_log::{PendingReply, ReplyKind, ReplyTimeout, TcReply},
+};
+
+use super::procedure::{Procedure, Step, format_duration};
+
+/// Outcome of a run, once over.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Completed,
+    Aborted(String),
+}
+
+/// Execution of a procedure, one step at a time.
+#[derive(Debug, Clone)]
+pub struct Run {
+    /// Index of the current step
+    pub current: usize,
+    step_started: Instant,
+    /// Last command sent, with its outcome once delivered
+    last_command: Option<(PendingReply, Option<TcReply>)>,
+    values: LatestValues,
+    pub outcome: Option<Outcome>,
+    pub log: Vec<(Zoned, String)>,
+}
+
+impl Run {
+    pub fn start(procedure: &Procedure) -> Self {
+        let mut run = Self {
+            current: 0,
+            step_started: Instant::now(),
+            last_command: None,
+            values: LatestValues::default(),
+            outcome: None,
+            log: Vec::new(),
+        };
+        run.record("Started");
+        if procedure.steps.is_empty() {
+            run.finish(Outcome::Completed);
+        }
+        run
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.outcome.is_none()
+    }
+
+    /// Whether the current step waits for the operator.
+    pub fn is_waiting_operator(&self, procedure: &Procedure) -> bool {
+        self.is_running()
+            && procedure
+                .steps
+                .get(self.current)
+                .is_some_and(|s| s.step.is_gated())
+    }
+
+    /// Time spent on the current step.
+    pub fn step_elapsed(&self) -> Duration {
+        self.step_started.elapsed()
+    }
+
+    /// Caches the values read by the conditions.
+    pub fn update(&mut self, messages: &[&TimedMessage]) {
+        for message in messages {
+            self.values.update(message);
+        }
+    }
+
+    /// Captures the outcome of the last command sent. A NACK or WACK aborts
+    /// the run.
+    pub fn handle_reply(&mut self, pending: &PendingReply, reply: &TcReply) {
+        let Some((_, outcome)) = self
+            .last_command
+            .as_mut()
+            .filter(|(sent, _)| sent == pending)
+        else {
+            return;
+        };
+        *outcome = Some(reply.clone());
+        let rejection = reply.kind().filter(ReplyKind::is_rejection);
+        if let Some(kind) = rejection.filter(|_| self.is_running()) {
+            self.abort(format!("Command rejected: {kind}"));
+        }
+    }
+
+    /// Runs the automatic steps, until one waits for the operator or for a
+    /// condition to be met.
+    pub fn poll(&mut self, procedure: &mut Procedure) {
+        while self.is_running() {
+            let Some(script_step) = procedure.steps.get_mut(self.current) else {
+                break;
+            };
+            let elapsed = self.step_started.elapsed();
+            match &mut script_step.step {
+                Step::Send(_) | Step::Confirm(_) => break,
+                Step::Wait(duration) => {
+                    if elapsed < *duration {
+                        break;
+                    }
+                }
+                Step::UntilAck(timeout) => match &self.last_command {
+                    None => {
+                        self.abort("No command sent before waiting for its ACK".to_owned());
+                        break;
+                    }
+                    Some((_, Some(reply))) if reply.kind() == Some(ReplyKind::Ack) => {}
+                    Some(_) if elapsed > *timeout => {
+                        self.abort(format!("No ACK within {}", format_duration(*timeout)));
+                        break;
+                    }
+                    Some(_) => break,
+                },
+                Step::Until { condition, timeout } => {
+                    if condition.is_met(&self.values) != Some(true) {
+                        if let Some(timeout) = timeout.filter(|t| elapsed > *t) {
+                            let reason =
+                                format!("{condition} not met within {}", format_duration(timeout));
+                            self.abort(reason);
+                        }
+                        break;
+                    }
+                }
+            }
+            self.next_step(procedure);
+        }
+    }
+
+    /// Runs the current step on behalf of the operator. Returns the command to
+    /// send, if the step sends one.
+    pub fn confirm(
+        &mut self,
+        procedure: &Procedure,
+        system_id: u8,
+    ) -> Option<(MavHeader, MavMessage)> {
+        if !self.is_waiting_operator(procedure) {
+            return None;
+        }
+        let mut command = None;
+        if let Some(Step::Send(map)) = procedure.steps.get(self.current).map(|s| &s.step) {
+            let mut map = map.clone();
+            stamp_current_time(&mut map);
+            let mut header = MavHeader {
+                system_id,
+                ..Default::default()
+            };
+            let message = MavMessage::from_map(map).log_unwrap();
+            // the reply is waited for as long as the step waiting for it allows
+            let timeout = match procedure.steps.get(self.current + 1).map(|s| &s.step) {
+                Some(Step::UntilAck(timeout)) => ReplyTimeout(*timeout),
+                _ => ReplyTimeout::default(),
+            };
+            self.last_command = Some((PendingReply::send(&mut header, &message, timeout), None));
+            command = Some((header, message));
+        }
+        self.next_step(procedure);
+        command
+    }
+
+    pub fn abort(&mut self, reason: String) {
+        warn!("Script aborted at step {}: {}", self.current + 1, reason);
+        self.finish(Outcome::Aborted(reason));
+    }
+
+    fn next_step(&mut self, procedure: &Procedure) {
+        if let Some(step) = procedure.steps.get(self.current) {
+            self.record(format!("Step {} done: {}", self.current + 1, step.step));
+        }
+        self.current += 1;
+        self.step_started = Instant::now();
+        if self.current >= procedure.steps.len() {
+            self.finish(Outcome::Completed);
+        }
+    }
+
+    fn finish(&mut self, outcome: Outcome) {
+        match &outcome {
+            Outcome::Completed => {
+                info!("Script completed");
+                self.record("Completed");
+            }
+            Outcome::Aborted(reason) => self.record(format!("Aborted: {reason}")),
+        }
+        self.outcome = Some(outcome);
+    }
+
+    fn record(&mut self, entry: impl Into<String>) {
+        self.log.push((Zoned::now(), entry.into()));
+    }
+}
@@ -0,0 +1,631 @@
+use std::time::{Duration, Instant};
+
+use egui::{
+    Align2, Color32, CornerRadius, CursorIcon, FontId, Painter, PointerButton, Rect, Response,
+    RichText, Sense, Stroke, StrokeKind, Ui, Visuals,
+    ahash::{HashMap, HashMapExt},
+};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mavlink::{
+        TimedMessage,
+        reflection::{IndexedField, MAVLINK_PROFILE},
+    },
+    ui::{
+        app::PaneResponse,
+        utils::{egui_to_glam, glam_to_egui},
+    },
+    utils::{
+        id::{IdGenerator, PaneId},
+        timer::format_clock,
+    },
+};
+
+use super::{
+    PaneBehavior,
+    pid_drawing_tool::grid::{CONNECTION_LINE_THICKNESS, CONNECTION_LINE_THRESHOLD, GridInfo},
+};
+
+/// Size of the state nodes, in grid units
+const STATE_SIZE: Vec2 = Vec2::new(14.0, 4.0);
+/// Space left between the states generated from an enum, in grid units
+const STATE_SPACING: Vec2 = Vec2::new(6.0, 4.0);
+/// Number of states per row when generating the diagram from an enum
+const GENERATED_COLUMNS: usize = 4;
+
+#[derive(Clone, Debug)]
+enum Action {
+    Connect(u32),
+    ContextMenu(Vec2),
+    DragState(u32),
+    DragGrid,
+}
+
+/// Diagram of a state machine, with the current state of a state field highlighted
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct StateMachinePane {
+    // Persistent internal state
+    field: Option<IndexedField>,
+    states: HashMap<u32, State>,
+    transitions: Vec<Transition>,
+    grid: GridInfo,
+
+    // Temporary internal state
+    #[serde(skip)]
+    current: Option<ActiveState>,
+    #[serde(skip)]
+    previous: Option<String>,
+    #[serde(skip)]
+    state_valid: bool,
+    #[serde(skip)]
+    id_generator: IdGenerator,
+    #[serde(skip)]
+    action: Option<Action>,
+    #[serde(skip)]
+    editable: bool,
+    #[serde(skip)]
+    settings_visible: bool,
+    #[serde(skip)]
+    settings_msg_id: Option<u32>,
+}
+
+impl Default for StateMachinePane {
+    fn default() -> Self {
+        Self {
+            field: None,
+            states: HashMap::new(),
+            transitions: Vec::new(),
+            grid: GridInfo::default(),
+            current: None,
+            previous: None,
+            state_valid: false,
+            id_generator: IdGenerator::new(),
+            action: None,
+            editable: false,
+            settings_visible: false,
+            settings_msg_id: None,
+        }
+    }
+}
+
+impl PartialEq for StateMachinePane {
+    fn eq(&self, other: &Self) -> bool {
+        self.field == other.field
+            && self.states == other.states
+            && self.transitions == other.transitions
+            && self.grid == other.grid
+    }
+}
+
+/// A node of the diagram, matching one value of the state field
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+struct State {
+    /// Value of the field, as an enum entry name or as a number
+    value: String,
+    /// Text shown in the diagram
+    label: String,
+    /// Center position in grid coordinates
+    position: Vec2,
+}
+
+/// An expected transition between two states, drawn as an arrow
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+struct Transition {
+    from: u32,
+    to: u32,
+}
+
+#[derive(Clone, Debug)]
+struct ActiveState {
+    value: String,
+    since: Instant,
+}
+
+impl PaneBehavior for StateMachinePane {
+    #[profiling::function]
+    fn ui(&mut self, ui: &mut Ui) -> PaneResponse {
+        let mut pane_response = PaneResponse::default();
+        let field_digest = self.field.clone();
+
+        self.status_bar(ui);
+        ui.separator();
+
+        let canvas = ui.available_rect_before_wrap();
+        let visuals = ui.visuals().clone();
+        if self.editable {
+            self.grid
+                .draw(ui, visuals.widgets.noninteractive.bg_stroke.color);
+        }
+        let painter = ui.painter_at(canvas);
+        self.draw_transitions(&painter, &visuals);
+        self.draw_states(&painter, &visuals);
+
+        // Handle things that require knowing the position of the pointer
+        let (_, response) = ui.allocate_at_least(canvas.size(), Sense::click_and_drag());
+        if let Some(pointer_pos) = response.hover_pos().map(|p| egui_to_glam(p.to_vec2())) {
+            if self.editable {
+                let scroll_delta = ui.input(|i| i.raw_scroll_delta).y;
+                self.grid.apply_scroll_delta(scroll_delta, pointer_pos);
+
+                if self.hovers_state(pointer_pos).is_some() {
+                    ui.ctx()
+                        .output_mut(|output| output.cursor_icon = CursorIcon::Grab);
+                }
+            }
+
+            self.detect_action(&response, pointer_pos);
+            self.handle_actions(&response, pointer_pos);
+        }
+
+        // The context menu has to be shown even if the pointer goes off screen
+        if let Some(Action::ContextMenu(pointer_pos)) = self.action.clone() {
+            response.context_menu(|ui| self.draw_context_menu(ui, pointer_pos));
+        }
+
+        let mut settings_visible = self.settings_visible;
+        egui::Window::new("State Machine Settings")
+            .id(ui.auto_id_with("state_machine_settings"))
+            .auto_sized()
+            .collapsible(true)
+            .movable(true)
+            .open(&mut settings_visible)
+            .show(ui.ctx(), |ui| {
+                settings_window(ui, self, egui_to_glam(canvas.min.to_vec2()))
+            });
+        self.settings_visible = settings_visible;
+
+        if field_digest != self.field {
+            self.state_valid = false;
+        }
+
+        // Keep the time in state updated
+        if self.current.is_some() {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+
+        // Check if the user is dragging the pane
+        let ctrl_pressed = ui.input(|i| i.modifiers.ctrl);
+        if response.dragged() && (ctrl_pressed || !self.editable) {
+            pane_response.set_drag_started();
+        }
+
+        pane_response
+    }
+
+    #[profiling::function]
+    fn update(&mut self, messages: &[&TimedMessage]) {
+        if !self.state_valid {
+            self.current = None;
+            self.previous = None;
+        }
+
+        if let Some(field) = &self.field {
+            for msg in messages.iter().filter(|msg| msg.id() == field.msg_id()) {
+                // Values outside the enum are kept as numbers, to flag them
+                let value = field
+                    .extract_as_enum_str(&msg.message)
+                    .or_else(|_| field.extract_as_string(&msg.message));
+                let Ok(value) = value else {
+                    continue;
+                };
+                match &self.current {
+                    Some(current) if current.value == value => {}
+                    _ => {
+                        self.previous = self.current.take().map(|c| c.value);
+                        self.current = Some(ActiveState {
+                            value,
+                            since: msg.time,
+                        });
+                    }
+                }
+            }
+        }
+
+        self.state_valid = true;
+    }
+
+    fn get_message_subscriptions(&self) -> Box<dyn Iterator<Item = u32>> {
+        Box::new(self.field.as_ref().map(|f| f.msg_id()).into_iter())
+    }
+
+    fn should_send_message_history(&self) -> bool {
+        !self.state_valid
+    }
+
+    fn init(&mut self, _pane_id: PaneId) {
+        self.id_generator
+            .sync_used_ids(&self.states.keys().copied().collect::<Vec<_>>());
+    }
+}
+
+impl State {
+    fn new(value: &str, position: Vec2) -> Self {
+        Self {
+            value: value.to_owned(),
+            label: value.to_owned(),
+            position,
+        }
+    }
+
+    /// Check if the given position is inside the state
+    fn contains(&self, p_g: Vec2) -> bool {
+        let d = (p_g - self.position).abs();
+        d.x <= STATE_SIZE.x / 2.0 && d.y <= STATE_SIZE.y / 2.0
+    }
+}
+
+impl StateMachinePane {
+    /// Returns the id of the state matching the given value, if any
+    fn state_of(&self, value: &str) -> Option<u32> {
+        self.states
+            .iter()
+            .find(|(_, state)| state.value == value)
+            .map(|(id, _)| *id)
+    }
+
+    /// Returns the id of the state the point is on, if any
+    fn hovers_state(&self, p_s: Vec2) -> Option<u32> {
+        let p_g = self.grid.screen_to_grid(p_s);
+        self.states
+            .iter()
+            .find(|(_, state)| state.contains(p_g))
+            .map(|(id, _)| *id)
+    }
+
+    /// Returns the index of the transition the point is on, if any
+    fn hovers_transition(&self, p_s: Vec2) -> Option<usize> {
+        self.transitions.iter().position(|transition| {
+            self.transition_ends(transition).is_some_and(|(a, b)| {
+                let ab = b - a;
+                let t = ((p_s - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0);
+                p_s.distance(a + ab * t) <= CONNECTION_LINE_THRESHOLD
+            })
+        })
+    }
+
+    /// Start and end of the arrow of a transition, in screen coordinates
+    fn transition_ends(&self, transition: &Transition) -> Option<(Vec2, Vec2)> {
+        let from = self
+            .grid
+            .grid_to_screen(self.states.get(&transition.from)?.position);
+        let to = self
+            .grid
+            .grid_to_screen(self.states.get(&transition.to)?.position);
+        let half_size = STATE_SIZE * self.grid.size() / 2.0;
+        Some((
+            border_point(from, to, half_size),
+            border_point(to, from, half_size),
+        ))
+    }
+
+    /// Shows the current and previous states, flagging unknown values
+    fn status_bar(&self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            let Some(field) = &self.field else {
+                ui.label(RichText::new("No state field selected").italics());
+                return;
+            };
+            ui.strong(format!("{}.{}", field.msg().name, field.field().name));
+            ui.separator();
+
+            let Some(current) = &self.current else {
+                ui.label("State: N/A");
+                return;
+            };
+            let current_id = self.state_of(&current.value);
+            ui.label("State:");
+            ui.label(RichText::new(&current.value).strong());
+            ui.label(format!("for {}", format_clock(current.since.elapsed())));
+            if current_id.is_none() {
+                ui.colored_label(Color32::RED, "⚠ Unknown state");
+            }
+
+            if let Some(previous) = &self.previous {
+                ui.separator();
+                ui.label(format!("Previous: {previous}"));
+                let expected = self
+                    .transitions
+                    .iter()
+                    .any(|t| Some(t.from) == self.state_of(previous) && Some(t.to) == current_id);
+                if current_id.is_some() && !self.transitions.is_empty() && !expected {
+                    ui.colored_label(Color32::ORANGE, "⚠ Unexpected transition");
+                }
+            }
+        });
+    }
+
+    fn draw_states(&self, painter: &Painter, visuals: &Visuals) {
+        let current = self.current.as_ref().map(|c| c.value.as_str());
+        let previous = self.previous.as_deref();
+        let size = self.grid.size();
+
+        for state in self.states.values() {
+            let center = glam_to_egui(self.grid.grid_to_screen(state.position)).to_pos2();
+            let rect = Rect::from_center_size(center, glam_to_egui(STATE_SIZE * size));
+            let (fill, stroke) = if current == Some(state.value.as_str()) {
+                (visuals.selection.bg_fill, visuals.selection.stroke)
+            } else if previous == Some(state.value.as_str()) {
+                (
+                    visuals.selection.bg_fill.gamma_multiply(0.35),
+                    visuals.widgets.inactive.fg_stroke,
+                )
+            } else {
+                (
+                    visuals.widgets.inactive.bg_fill,
+                    visuals.widgets.inactive.bg_stroke,
+                )
+            };
+            let stroke = Stroke::new(CONNECTION_LINE_THICKNESS * size, stroke.color);
+            painter.rect(
+                rect,
+                CornerRadius::same((size * 0.8) as u8),
+                fill,
+                stroke,
+                StrokeKind::Inside,
+            );
+            painter.text(
+                center,
+                Align2::CENTER_CENTER,
+                &state.label,
+                FontId::proportional(size * 1.4),
+                visuals.strong_text_color(),
+            );
+        }
+    }
+
+    fn draw_transitions(&self, painter: &Painter, visuals: &Visuals) {
+        let current = self.current.as_ref().and_then(|c| self.state_of(&c.value));
+        let previous = self.previous.as_deref().and_then(|p| self.state_of(p));
+        let size = self.grid.size();
+
+        for transition in &self.transitions {
+            let Some((from, to)) = self.transition_ends(transition) else {
+                continue;
+            };
+            // The last transition taken is highlighted
+            let color = if Some(transition.from) == previous && Some(transition.to) == current {
+                visuals.selection.stroke.color
+            } else {
+                visuals.text_color()
+            };
+            painter.arrow(
+                glam_to_egui(from).to_pos2(),
+                glam_to_egui(to - from),
+                Stroke::new(CONNECTION_LINE_THICKNESS * size, color),
+            );
+        }
+    }
+
+    fn draw_context_menu(&mut self, ui: &mut Ui, pointer_pos: Vec2) {
+        ui.set_max_width(180.0); // To make sure we wrap long text
+
+        if !self.editable {
+            if ui.button("Enable editing").clicked() {
+                self.editable = true;
+                self.id_generator
+                    .sync_used_ids(&self.states.keys().copied().collect::<Vec<_>>());
+                self.action.take();
+                ui.close_menu();
+            }
+            if ui.button("State machine settings…").clicked() {
+                self.settings_visible = true;
+                ui.close_menu();
+            }
+            return;
+        }
+
+        if let Some(id) = self.hovers_state(pointer_pos) {
+            if ui.button("Connect").clicked() {
+                self.action = Some(Action::Connect(id));
+                ui.close_menu();
+            }
+            if let Some(state) = self.states.get_mut(&id) {
+                ui.horizontal(|ui| {
+                    ui.label("Value:");
+                    ui.text_edit_singleline(&mut state.value);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Label:");
+                    ui.text_edit_singleline(&mut state.label);
+                });
+            }
+            if ui.button("Delete").clicked() {
+                self.transitions.retain(|t| t.from != id && t.to != id);
+                self.states.remove(&id);
+                self.id_generator.release_id(id);
+                self.action.take();
+                ui.close_menu();
+            }
+        } else if let Some(idx) = self.hovers_transition(pointer_pos) {
+            if ui.button("Delete transition").clicked() {
+                self.transitions.remove(idx);
+                self.action.take();
+                ui.close_menu();
+            }
+        } else {
+            let position = self.grid.screen_to_grid(pointer_pos).round();
+            ui.menu_button("Add state", |ui| {
+                let missing: Vec<String> = self
+                    .enum_entries()
+                    .into_iter()
+                    .filter(|entry| self.state_of(entry).is_none())
+                    .collect();
+                for entry in missing {
+                    if ui.button(&entry).clicked() {
+                        self.add_state(&entry, position);
+                        ui.close_menu();
+                    }
+                }
+                if ui.button("Custom state").clicked() {
+                    self.add_state("NEW_STATE", position);
+                    ui.close_menu();
+                }
+            });
+        }
+
+        if ui.button("State machine settings…").clicked() {
+            self.settings_visible = true;
+            ui.close_menu();
+        }
+        if ui.button("Disable editing").clicked() {
+            self.editable = false;
+            ui.close_menu();
+        }
+    }
+
+    fn add_state(&mut self, value: &str, position: Vec2) {
+        self.states
+            .insert(self.id_generator.next_id(), State::new(value, position));
+        self.action.take();
+    }
+
+    /// Names of the entries of the enum of the state field, if it has one
+    fn enum_entries(&self) -> Vec<String> {
+        self.field
+            .as_ref()
+            .and_then(|f| f.field().enumtype.as_ref())
+            .and_then(|e| MAVLINK_PROFILE.get_enum(e))
+            .map(|e| e.entries.iter().map(|entry| entry.name.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the diagram with one state for each entry of the enum of the
+    /// state field, laid out in rows starting from the given screen position
+    fn generate_from_enum(&mut self, origin: Vec2) {
+        self.states.clear();
+        self.transitions.clear();
+        self.id_generator.sync_used_ids(&[]);
+        // the action may refer to a removed state
+        self.action.take();
+        self.grid.zero_pos = origin;
+
+        let step = STATE_SIZE + STATE_SPACING;
+        for (i, entry) in self.enum_entries().iter().enumerate() {
+            let cell = Vec2::new(
+                (i % GENERATED_COLUMNS) as f32,
+                (i / GENERATED_COLUMNS) as f32,
+            );
+            let position = STATE_SPACING + STATE_SIZE / 2.0 + cell * step;
+            self.states
+                .insert(self.id_generator.next_id(), State::new(entry, position));
+        }
+    }
+
+    fn detect_action(&mut self, response: &Response, pointer_pos: Vec2) {
+        if response.clicked_by(PointerButton::Secondary) {
+            self.action = Some(Action::ContextMenu(pointer_pos));
+        } else if self.editable {
+            if response.drag_started() {
+                if response.dragged_by(PointerButton::Middle) {
+                    self.action = Some(Action::DragGrid);
+                } else if let Some(id) = self.hovers_state(pointer_pos) {
+                    self.action = Some(Action::DragState(id));
+                }
+            } else if response.drag_stopped() {
+                self.action.take();
+            }
+        }
+    }
+
+    fn handle_actions(&mut self, response: &Response, pointer_pos: Vec2) {
+        match self.action {
+            Some(Action::Connect(from)) if response.clicked() => {
+                if let Some(to) = self.hovers_state(pointer_pos) {
+                    let transition = Transition { from, to };
+                    if from != to && !self.transitions.contains(&transition) {
+                        self.transitions.push(transition);
+                    }
+                    self.action.take();
+                }
+            }
+            Some(Action::DragState(id)) => {
+                let pointer_pos_g = self.grid.screen_to_grid(pointer_pos).round();
+                if let Some(state) = self.states.get_mut(&id) {
+                    state.position = pointer_pos_g;
+                } else {
+                    // the state was removed while dragging it
+                    self.action.take();
+                }
+            }
+            Some(Action::DragGrid) => {
+                self.grid.zero_pos += egui_to_glam(response.drag_delta());
+            }
+            // Context menu has to be handled outside since it does not require the pointer's position
+            Some(Action::Connect(_) | Action::ContextMenu(_)) | None => {}
+        }
+    }
+}
+
+fn settings_window(ui: &mut Ui, pane: &mut StateMachinePane, origin: Vec2) {
+    let msg_id = pane.settings_msg_id.get_or_insert_with(|| {
+        pane.field
+            .as_ref()
+            .map(|f| f.msg_id())
+            .or_else(|| MAVLINK_PROFILE.get_sorted_msgs().first().map(|m| m.id))
+            .unwrap_or_default()
+    });
+    let msg_name = MAVLINK_PROFILE
+        .get_msg(*msg_id)
+        .map(|m| m.name.clone())
+        .unwrap_or_default();
+    egui::ComboBox::from_label("Message Kind")
+        .selected_text(msg_name)
+        .show_ui(ui, |ui| {
+            for msg in MAVLINK_PROFILE.get_sorted_msgs() {
+                ui.selectable_value(msg_id, msg.id, &msg.name);
+            }
+        });
+
+    match MAVLINK_PROFILE.get_all_state_fields(*msg_id) {
+        None => {
+            ui.label(RichText::new("Unknown message").italics());
+        }
+        Some(fields) if fields.is_empty() => {
+            ui.label(RichText::new("No state fields in this message").italics());
+        }
+        Some(fields) => {
+            egui::ComboBox::from_label("State Field")
+                .selected_text(
+                    pane.field
+                        .as_ref()
+                        .map(|f| f.field().name.as_str())
+                        .unwrap_or("None"),
+                )
+                .show_ui(ui, |ui| {
+                    for field in fields {
+                        let name = field.field().name.clone();
+                        ui.selectable_value(&mut pane.field, Some(field), name);
+                    }
+                });
+        }
+    }
+
+    ui.separator();
+    let has_enum = !pane.enum_entries().is_empty();
+    if ui
+        .add_enabled(has_enum, egui::Button::new("Generate from enum"))
+        .on_hover_text("Replace the diagram with a state for each entry of the field's enum")
+        .on_disabled_hover_text("The selected field is not an enum")
+        .clicked()
+    {
+        pane.generate_from_enum(origin);
+    }
+}
+
+/// Point where the segment from `center` towards `target` leaves a box with the given half size
+fn border_point(center: Vec2, target: Vec2, half_size: Vec2) -> Vec2 {
+    let d = target - center;
+    let scale_x = if d.x != 0.0 {
+        half_size.x / d.x.abs()
+    } else {
+        f32::INFINITY
+    };
+    let scale_y = if d.y != 0.0 {
+        half_size.y / d.y.abs()
+    } else {
+        f32::INFINITY
+    };
+    center + d * scale_x.min(scale_y).min(1.0)
+}
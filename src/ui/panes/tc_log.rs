@@ -0,0 +1,284 @@
+use std::collections::VecDeque;
+
+use egui::{Context, Grid, Modal, RichText, ScrollArea, Ui};
+use egui_extras::{Column, TableBuilder};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    commands::COMMAND_LIBRARY,
+    error::ErrInstrument,
+    mavlink::{MavHeader, MavMessage, Message, reflection::MapConvertible, stamp_current_time},
+    tc_log::{PendingReply, ReplyTimeout, TcLog, TcLogExt, TcRecord, TcReply},
+    ui::{
+        panes::{PaneBehavior, PaneResponse},
+        widgets::CommandArming,
+    },
+};
+
+/// Log of the telecommands sent during the session, with their replies.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TcLogPane {
+    /// Only commands whose name or source contain this text are shown
+    search: String,
+    /// Only commands that were not acknowledged are shown
+    failed_only: bool,
+
+    /// Id of the record shown in the details
+    #[serde(skip)]
+    selected: Option<u64>,
+    /// Id of the record waiting for the confirmation to be sent again
+    #[serde(skip)]
+    confirm_resend: Option<u64>,
+    /// Arming of the command to send again, if critical
+    #[serde(skip)]
+    arming: CommandArming,
+    #[serde(skip)]
+    to_send: Vec<(MavHeader, MavMessage)>,
+}
+
+impl PartialEq for TcLogPane {
+    fn eq(&self, other: &Self) -> bool {
+        self.search == other.search && self.failed_only == other.failed_only
+    }
+}
+
+impl PaneBehavior for TcLogPane {
+    #[profiling::function]
+    fn ui(&mut self, ui: &mut Ui) -> PaneResponse {
+        let mut pane_response = PaneResponse::default();
+
+        let tc_log = ui.ctx().tc_log();
+        let res = ui
+            .scope(|ui| {
+                let mut tc_log = tc_log.lock();
+                self.toolbar(ui, &mut tc_log);
+                ui.separator();
+                self.records_table(ui, tc_log.records());
+                ui.separator();
+                match self.selected.and_then(|id| tc_log.get(id)) {
+                    Some(record) => {
+                        if record_details(ui, record) {
+                            self.confirm_resend = self.selected;
+                            self.arming.criticality = COMMAND_LIBRARY.read().criticality_of(
+                                record.header.system_id,
+                                record.message.message_id(),
+                            );
+                        }
+                    }
+                    None => {
+                        ui.label(RichText::new("Select a command to inspect it").italics());
+                    }
+                }
+                self.resend_confirmation(ui, &tc_log);
+            })
+            .response;
+
+        if res.interact(egui::Sense::click_and_drag()).dragged() {
+            pane_response.set_drag_started();
+        }
+
+        pane_response
+    }
+
+    fn drain_outgoing_messages(&mut self) -> Vec<(MavHeader, MavMessage)> {
+        self.to_send.drain(..).collect()
+    }
+}
+
+impl TcLogPane {
+    fn matches(&self, record: &TcRecord) -> bool {
+        let search = self.search.to_lowercase();
+        (!self.failed_only || !matches!(record.reply, TcReply::Ack { .. } | TcReply::Pending))
+            && (search.is_empty()
+                || record.name().to_lowercase().contains(&search)
+                || record.source.label.to_lowercase().contains(&search))
+    }
+
+    fn toolbar(&mut self, ui: &mut Ui, tc_log: &mut TcLog) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Search:");
+            ui.add(egui::TextEdit::singleline(&mut self.search).desired_width(120.0));
+            ui.checkbox(&mut self.failed_only, "Failed only");
+            if ui
+                .button("Clear")
+                .on_hover_text("Clear the telecommand log")
+                .clicked()
+            {
+                self.close_resend(ui.ctx(), tc_log);
+                tc_log.clear();
+                self.selected = None;
+            }
+        });
+    }
+
+    fn records_table(&mut self, ui: &mut Ui, records: &VecDeque<TcRecord>) {
+        // Newest first
+        let visible: Vec<&TcRecord> = records.iter().rev().filter(|r| self.matches(r)).collect();
+
+        let max_height = ui.available_height() * 0.6;
+        TableBuilder::new(ui)
+            .id_salt("tc_log_table")
+            .striped(true)
+            .resizable(true)
+            .sense(egui::Sense::click())
+            .auto_shrink([false, true])
+            .max_scroll_height(max_height)
+            .column(Column::auto().at_least(90.0))
+            .column(Column::initial(110.0).clip(true))
+            .column(Column::initial(180.0).clip(true))
+            .column(Column::auto())
+            .column(Column::remainder())
+            .header(20.0, |mut header| {
+                for label in ["Time", "Source", "Command", "Latency", "Reply"] {
+                    header.col(|ui| {
+                        ui.strong(label);
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(18.0, visible.len(), |mut row| {
+                    let record = visible[row.index()];
+                    row.set_selected(self.selected == Some(record.id));
+                    row.col(|ui| {
+                        ui.label(record.sent_at.strftime("%H:%M:%S%.3f").to_string());
+                    });
+                    row.col(|ui| {
+                        ui.label(&record.source.label);
+                    });
+                    row.col(|ui| {
+                        ui.label(record.name());
+                    });
+                    row.col(|ui| {
+                        if let Some(latency) = record.reply.latency() {
+                            ui.label(format!("{} ms", latency.as_millis()));
+                        }
+                    });
+                    row.col(|ui| {
+                        ui.colored_label(record.reply.color(), record.reply.to_string());
+                    });
+                    if row.response().clicked() {
+                        self.selected = Some(record.id);
+                    }
+                });
+            });
+    }
+
+    fn resend_confirmation(&mut self, ui: &mut Ui, tc_log: &TcLog) {
+        let Some(record) = self.confirm_resend.and_then(|id| tc_log.get(id)) else {
+            return;
+        };
+        let name = record.name();
+        let mut sent = false;
+        let mut dismissed = false;
+        let modal = Modal::new(ui.auto_id_with("resend_confirmation")).show(ui.ctx(), |ui| {
+            ui.set_max_width(320.0);
+            ui.heading("Re-send command?");
+            ui.label(format!(
+                "{} will be sent again to system {}, with the fields:",
+                name, record.header.system_id
+            ));
+            fields_grid(ui, record);
+            ui.separator();
+            // critical commands are sent only once armed and confirmed
+            let send_confirmed = self.arming.show_armed(ui, &name);
+            let mut send_invoked = false;
+            egui::Sides::new().show(
+                ui,
+                |_| {},
+                |ui| {
+                    if !self.arming.is_armed() && ui.button("Send").clicked() {
+                        send_invoked = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        dismissed = true;
+                    }
+                },
+            );
+            if (send_invoked && self.arming.request(ui.ctx(), &name)) || send_confirmed {
+                self.to_send.push(resend(record));
+                sent = true;
+            }
+        });
+        if sent {
+            self.confirm_resend = None;
+        } else if dismissed || modal.should_close() {
+            self.arming.disarm(ui.ctx(), &name, "cancelled");
+            self.confirm_resend = None;
+        }
+    }
+
+    /// Closes the confirmation of the command to send again, if any.
+    fn close_resend(&mut self, ctx: &Context, tc_log: &TcLog) {
+        if let Some(record) = self.confirm_resend.take().and_then(|id| tc_log.get(id)) {
+            self.arming.disarm(ctx, &record.name(), "cancelled");
+        }
+    }
+}
+
+/// Command to send again: a new command, with its own sequence number and the
+/// timestamp set to the current time.
+fn resend(record: &TcRecord) -> (MavHeader, MavMessage) {
+    info!("Sending {} again from the telecommand log", record.name());
+    let mut map = record.message.as_map();
+    stamp_current_time(&mut map);
+    let mut header = record.header;
+    let message = MavMessage::from_map(map).log_unwrap();
+    PendingReply::send(&mut header, &message, ReplyTimeout::default());
+    (header, message)
+}
+
+/// Shows the details of a command. Returns whether it has to be sent again.
+fn record_details(ui: &mut Ui, record: &TcRecord) -> bool {
+    let mut resend = false;
+    ScrollArea::vertical()
+        .id_salt("tc_log_details")
+        .auto_shrink([false, true])
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading(record.name());
+                if ui
+                    .button("Re-send…")
+                    .on_hover_text("Send the command again, after confirmation")
+                    .clicked()
+                {
+                    resend = true;
+                }
+            });
+            Grid::new(ui.auto_id_with("tc_log_record_info"))
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Sent at:");
+                    ui.label(record.sent_at.strftime("%Y-%m-%d %H:%M:%S%.3f").to_string());
+                    ui.end_row();
+                    ui.label("Source:");
+                    ui.label(&record.source.label);
+                    ui.end_row();
+                    ui.label("System / component:");
+                    ui.label(format!(
+                        "{} / {}",
+                        record.header.system_id, record.header.component_id
+                    ));
+                    ui.end_row();
+                    ui.label("Reply:");
+                    ui.colored_label(record.reply.color(), record.reply.to_string());
+                    ui.end_row();
+                });
+            ui.separator();
+            fields_grid(ui, record);
+        });
+    resend
+}
+
+fn fields_grid(ui: &mut Ui, record: &TcRecord) {
+    Grid::new(ui.auto_id_with("tc_log_fields"))
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for (name, value) in record.fields() {
+                ui.label(RichText::new(name).monospace());
+                ui.label(value);
+                ui.end_row();
+            }
+        });
+}
@@ -0,0 +1,434 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    time::Instant,
+};
+
+use egui::{Color32, RichText, ScrollArea, Ui, Vec2b, ecolor::Hsva};
+use egui_plot::{AxisHints, Bar, BarChart, Plot, uniform_grid_spacer};
+use jiff::{Timestamp, tz::TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mavlink::{
+        TimedMessage,
+        reflection::{IndexedField, MAVLINK_PROFILE},
+    },
+    ui::app::PaneResponse,
+};
+
+use super::PaneBehavior;
+
+/// Thickness of the bands, as a fraction of the space between two tracks.
+const BAND_HEIGHT: f64 = 0.6;
+
+/// Timeline showing how state fields change over time, each field as a band
+/// of segments colored by value.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TimelinePane {
+    /// State fields shown as tracks, from top to bottom
+    tracks: Vec<IndexedField>,
+    /// Whether the list of transitions is shown below the timeline
+    show_transitions: bool,
+
+    #[serde(skip)]
+    segments: Vec<Vec<Segment>>,
+    #[serde(skip)]
+    state_valid: bool,
+    #[serde(skip)]
+    settings_visible: bool,
+    /// Message selected in the settings window
+    #[serde(skip)]
+    settings_msg_id: Option<u32>,
+}
+
+impl PartialEq for TimelinePane {
+    fn eq(&self, other: &Self) -> bool {
+        self.tracks == other.tracks && self.show_transitions == other.show_transitions
+    }
+}
+
+/// Time interval in which a state field kept the same value.
+#[derive(Clone, Debug)]
+struct Segment {
+    value: String,
+    start: Instant,
+    end: Instant,
+}
+
+impl PaneBehavior for TimelinePane {
+    #[profiling::function]
+    fn ui(&mut self, ui: &mut Ui) -> PaneResponse {
+        let mut response = PaneResponse::default();
+        let tracks_digest = self.tracks.clone();
+        let ctrl_pressed = ui.input(|i| i.modifiers.ctrl);
+
+        // Reference used to convert the reception instants to wall-clock time
+        let clock = WallClock::now();
+
+        let names: Vec<String> = self
+            .tracks
+            .iter()
+            .map(|f| format!("{}.{}", f.msg().name, f.field().name))
+            .collect();
+        let y_axis = AxisHints::new_y().formatter({
+            let names = names.clone();
+            move |mark, _| {
+                track_at(mark.value, names.len())
+                    .map(|i| names[i].clone())
+                    .unwrap_or_default()
+            }
+        });
+        let x_axis = AxisHints::new_x()
+            .label("Time")
+            .formatter(|mark, _| format_wall_clock(mark.value, "%H:%M:%S"));
+
+        let transitions_height = if self.show_transitions { 150.0 } else { 0.0 };
+        let plot = Plot::new(ui.auto_id_with("timeline"))
+            .height((ui.available_height() - transitions_height).max(50.0))
+            .custom_x_axes(vec![x_axis])
+            .custom_y_axes(vec![y_axis])
+            .y_grid_spacer(uniform_grid_spacer(|_| [1.0, 1.0, 1.0]))
+            .allow_zoom(Vec2b::new(true, false))
+            .allow_drag(Vec2b::new(true, false))
+            .allow_scroll(Vec2b::new(true, false))
+            .include_y(0.5)
+            .include_y(0.5 - self.tracks.len().max(1) as f64)
+            .label_formatter(|_, point| format_wall_clock(point.x, "%H:%M:%S%.3f"));
+
+        plot.show(ui, |plot_ui| {
+            if plot_ui.response().dragged() && ctrl_pressed {
+                response.set_drag_started();
+            }
+
+            for (i, (segments, name)) in self.segments.iter().zip(&names).enumerate() {
+                let y = -(i as f64);
+                let last = segments.len().saturating_sub(1);
+                let bars = segments
+                    .iter()
+                    .enumerate()
+                    .map(|(j, segment)| {
+                        let start = clock.to_unix_secs(segment.start);
+                        // the last value holds until a new one is received
+                        let end = if j == last {
+                            clock.unix_secs
+                        } else {
+                            clock.to_unix_secs(segment.end)
+                        };
+                        Bar::new(y, end - start)
+                            .base_offset(start)
+                            .width(BAND_HEIGHT)
+                            .fill(value_color(&segment.value))
+                            .name(&segment.value)
+                    })
+                    .collect();
+                plot_ui.bar_chart(
+                    BarChart::new(bars)
+                        .horizontal()
+                        .name(name)
+                        .element_formatter(Box::new(|bar, _| {
+                            let start = bar.base_offset.unwrap_or_default();
+                            format!(
+                                "{}\n{} → {}",
+                                bar.name,
+                                format_wall_clock(start, "%H:%M:%S%.3f"),
+                                format_wall_clock(start + bar.value, "%H:%M:%S%.3f"),
+                            )
+                        })),
+                );
+            }
+
+            plot_ui.response().context_menu(|ui| show_menu(ui, self));
+        });
+
+        if self.show_transitions {
+            transitions_list(ui, &names, &self.segments);
+        }
+
+        let mut settings_visible = self.settings_visible;
+        egui::Window::new("Timeline Settings")
+            .id(ui.auto_id_with("timeline_settings"))
+            .auto_sized()
+            .collapsible(true)
+            .movable(true)
+            .open(&mut settings_visible)
+            .show(ui.ctx(), |ui| settings_window(ui, self));
+        self.settings_visible = settings_visible;
+
+        if tracks_digest != self.tracks {
+            self.state_valid = false;
+        }
+
+        response
+    }
+
+    #[profiling::function]
+    fn update(&mut self, messages: &[&TimedMessage]) {
+        if !self.state_valid {
+            self.segments.clear();
+        }
+        self.segments.resize(self.tracks.len(), Vec::new());
+
+        for msg in messages {
+            for (field, segments) in self.tracks.iter().zip(&mut self.segments) {
+                if field.msg_id() != msg.id() {
+                    continue;
+                }
+                let value = if field.field().enumtype.is_some() {
+                    field.extract_as_enum_str(&msg.message)
+                } else {
+                    field.extract_as_string(&msg.message)
+                };
+                if let Ok(value) = value {
+                    push_value(segments, value, msg.time);
+                }
+            }
+        }
+
+        self.state_valid = true;
+    }
+
+    fn get_message_subscriptions(&self) -> Box<dyn Iterator<Item = u32>> {
+        let mut ids: Vec<u32> = self.tracks.iter().map(|f| f.msg_id()).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        Box::new(ids.into_iter())
+    }
+
+    fn should_send_message_history(&self) -> bool {
+        !self.state_valid
+    }
+}
+
+impl Segment {
+    fn new(value: String, time: Instant) -> Self {
+        Self {
+            value,
+            start: time,
+            end: time,
+        }
+    }
+}
+
+/// Extends the last segment with a value received at `time`, or starts a new
+/// segment if the value changed.
+fn push_value(segments: &mut Vec<Segment>, value: String, time: Instant) {
+    match segments.last_mut() {
+        Some(segment) if segment.value == value => segment.end = time,
+        Some(segment) => {
+            // the previous value holds until the transition
+            segment.end = time;
+            segments.push(Segment::new(value, time));
+        }
+        None => segments.push(Segment::new(value, time)),
+    }
+}
+
+/// Converts the reception instants of the messages to UNIX time.
+#[derive(Clone, Copy)]
+struct WallClock {
+    instant: Instant,
+    unix_secs: f64,
+}
+
+impl WallClock {
+    fn now() -> Self {
+        Self {
+            instant: Instant::now(),
+            unix_secs: Timestamp::now().as_millisecond() as f64 / 1000.0,
+        }
+    }
+
+    fn to_unix_secs(self, instant: Instant) -> f64 {
+        self.unix_secs
+            - self
+                .instant
+                .saturating_duration_since(instant)
+                .as_secs_f64()
+    }
+}
+
+/// Formats UNIX time in seconds as local wall-clock time.
+fn format_wall_clock(unix_secs: f64, format: &str) -> String {
+    Timestamp::from_millisecond((unix_secs * 1000.0) as i64)
+        .map(|t| t.to_zoned(TimeZone::system()).strftime(format).to_string())
+        .unwrap_or_default()
+}
+
+/// Returns the index of the track drawn at the given y coordinate.
+fn track_at(y: f64, tracks: usize) -> Option<usize> {
+    let index = (-y).round();
+    ((-y - index).abs() < 1e-6 && index >= 0.0 && (index as usize) < tracks)
+        .then_some(index as usize)
+}
+
+/// Returns a stable color for each state value.
+fn value_color(value: &str) -> Color32 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    let hue = (hasher.finish() % 360) as f32 / 360.0;
+    Hsva::new(hue, 0.55, 0.8, 1.0).into()
+}
+
+fn transitions_list(ui: &mut Ui, names: &[String], segments: &[Vec<Segment>]) {
+    let clock = WallClock::now();
+    let mut transitions: Vec<(f64, &str, Option<&str>, &str)> = names
+        .iter()
+        .zip(segments)
+        .flat_map(|(name, segments)| {
+            segments.iter().enumerate().map(move |(i, segment)| {
+                let from = i.checked_sub(1).map(|i| segments[i].value.as_str());
+                (
+                    clock.to_unix_secs(segment.start),
+                    name.as_str(),
+                    from,
+                    segment.value.as_str(),
+                )
+            })
+        })
+        .collect();
+    // newest first
+    transitions.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    ui.separator();
+    ScrollArea::vertical()
+        .id_salt(ui.id().with("timeline_transitions"))
+        .auto_shrink([false, true])
+        .show(ui, |ui| {
+            egui::Grid::new(ui.auto_id_with("transitions_grid"))
+                .striped(true)
+                .show(ui, |ui| {
+                    for (time, name, from, to) in transitions {
+                        ui.label(format_wall_clock(time, "%H:%M:%S%.3f"));
+                        ui.label(name);
+                        ui.label(match from {
+                            Some(from) => format!("{from} → {to}"),
+                            None => to.to_owned(),
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+fn show_menu(ui: &mut Ui, pane: &mut TimelinePane) {
+    ui.set_max_width(200.0);
+    if ui.button("Timeline Settings…").clicked() {
+        pane.settings_visible = true;
+        ui.close_menu();
+    }
+    ui.checkbox(&mut pane.show_transitions, "Show Transitions");
+    if ui.button("Clear").clicked() {
+        pane.segments.iter_mut().for_each(Vec::clear);
+        ui.close_menu();
+    }
+}
+
+fn settings_window(ui: &mut Ui, pane: &mut TimelinePane) {
+    let msg_id = pane.settings_msg_id.get_or_insert_with(|| {
+        pane.tracks
+            .last()
+            .map(|f| f.msg_id())
+            .or_else(|| MAVLINK_PROFILE.get_sorted_msgs().first().map(|m| m.id))
+            .unwrap_or_default()
+    });
+    let msg_name = MAVLINK_PROFILE
+        .get_msg(*msg_id)
+        .map(|m| m.name.clone())
+        .unwrap_or_default();
+    egui::ComboBox::from_label("Message Kind")
+        .selected_text(msg_name)
+        .show_ui(ui, |ui| {
+            for msg in MAVLINK_PROFILE.get_sorted_msgs() {
+                ui.selectable_value(msg_id, msg.id, &msg.name);
+            }
+        });
+
+    match MAVLINK_PROFILE.get_all_state_fields(*msg_id) {
+        None => {
+            ui.label(RichText::new("Unknown message").italics());
+        }
+        Some(fields) if fields.is_empty() => {
+            ui.label(RichText::new("No state fields in this message").italics());
+        }
+        Some(fields) => {
+            for field in fields {
+                let mut shown = pane.tracks.contains(&field);
+                if ui.checkbox(&mut shown, &field.field().name).changed() {
+                    if shown {
+                        pane.tracks.push(field);
+                    } else {
+                        pane.tracks.retain(|f| *f != field);
+                    }
+                }
+            }
+        }
+    }
+
+    ui.separator();
+    ui.label("Tracks:");
+    let mut to_remove = None;
+    for (i, field) in pane.tracks.iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!("{}.{}", field.msg().name, field.field().name));
+            if ui.small_button("🗑").clicked() {
+                to_remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = to_remove {
+        pane.tracks.remove(i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn values(segments: &[Segment]) -> Vec<&str> {
+        segments.iter().map(|s| s.value.as_str()).collect()
+    }
+
+    #[test]
+    fn repeated_values_extend_the_segment() {
+        let t0 = Instant::now();
+        let mut segments = Vec::new();
+        for i in 0..3 {
+            push_value(
+                &mut segments,
+                "IDLE".to_owned(),
+                t0 + Duration::from_secs(i),
+            );
+        }
+        assert_eq!(values(&segments), ["IDLE"]);
+        assert_eq!(segments[0].start, t0);
+        assert_eq!(segments[0].end, t0 + Duration::from_secs(2));
+    }
+
+    #[test]
+    fn transitions_start_a_new_segment() {
+        let t0 = Instant::now();
+        let at = |secs| t0 + Duration::from_secs(secs);
+        let mut segments = Vec::new();
+        push_value(&mut segments, "IDLE".to_owned(), at(0));
+        push_value(&mut segments, "ARMED".to_owned(), at(1));
+        push_value(&mut segments, "ARMED".to_owned(), at(2));
+        push_value(&mut segments, "IDLE".to_owned(), at(3));
+
+        assert_eq!(values(&segments), ["IDLE", "ARMED", "IDLE"]);
+        // each value holds until the next transition
+        assert_eq!(segments[0].end, at(1));
+        assert_eq!((segments[1].start, segments[1].end), (at(1), at(3)));
+        assert_eq!((segments[2].start, segments[2].end), (at(3), at(3)));
+    }
+
+    #[test]
+    fn tracks_are_found_only_on_their_row() {
+        assert_eq!(track_at(0.0, 2), Some(0));
+        assert_eq!(track_at(-1.0, 2), Some(1));
+        assert_eq!(track_at(-0.5, 2), None);
+        assert_eq!(track_at(-2.0, 2), None);
+        assert_eq!(track_at(1.0, 2), None);
+    }
+}
@@ -0,0 +1,355 @@
+use std::time::{Duration, Instant};
+
+use egui::{Button, Color32, DragValue, RichText, TextEdit, Ui};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+use tracing::info;
+
+use crate::{
+    derived::condition::{Condition, LatestValues},
+    mavlink::TimedMessage,
+    ui::app::PaneResponse,
+    utils::timer::{Countdown, MISSION_T0, Stopwatch, format_clock},
+};
+
+use super::PaneBehavior;
+
+/// Countdown, stopwatch or mission elapsed time (R12). The T0 of the countdown
+/// and of the mission elapsed time can be set by a telemetry condition, and
+/// shared with the other panes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimerPane {
+    mode: TimerMode,
+    countdown: Countdown,
+    /// Condition setting T0 once met, disabled if empty
+    t0_condition: String,
+    /// Whether T0 is shared with the other panes, e.g. for the T+ axis of plots
+    broadcast_t0: bool,
+    text_size: f32,
+
+    /// Never restored from the layout, timers are always started by the user
+    #[serde(skip)]
+    stopwatch: Stopwatch,
+    #[serde(skip)]
+    t0: Option<Instant>,
+    /// Condition compiled from `t0_condition`, or the reason it is not valid
+    #[serde(skip)]
+    condition: Option<Result<Condition, String>>,
+    #[serde(skip)]
+    values: LatestValues,
+    #[serde(skip)]
+    settings_visible: bool,
+}
+
+impl Default for TimerPane {
+    fn default() -> Self {
+        Self {
+            mode: TimerMode::Countdown,
+            countdown: Countdown::new(Duration::from_secs(600)),
+            t0_condition: String::new(),
+            broadcast_t0: true,
+            text_size: 36.0,
+            stopwatch: Stopwatch::default(),
+            t0: None,
+            condition: None,
+            values: LatestValues::default(),
+            settings_visible: false,
+        }
+    }
+}
+
+impl PartialEq for TimerPane {
+    fn eq(&self, other: &Self) -> bool {
+        self.mode == other.mode
+            && self.countdown == other.countdown
+            && self.t0_condition == other.t0_condition
+            && self.broadcast_t0 == other.broadcast_t0
+            && self.text_size == other.text_size
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Display, EnumIter)]
+enum TimerMode {
+    /// T-minus countdown to T0, with holds
+    Countdown,
+    Stopwatch,
+    /// Time elapsed since T0
+    #[strum(to_string = "Mission elapsed time")]
+    MissionElapsed,
+}
+
+impl PaneBehavior for TimerPane {
+    #[profiling::function]
+    fn ui(&mut self, ui: &mut Ui) -> PaneResponse {
+        let mut pane_response = PaneResponse::default();
+
+        if self.condition.is_none() && !self.t0_condition.trim().is_empty() {
+            self.compile_condition();
+        }
+        // the countdown sets T0 when reaching zero
+        if self.mode == TimerMode::Countdown
+            && self.t0.is_none()
+            && let Some(overrun) = self.countdown.overrun()
+        {
+            self.set_t0(Instant::now() - overrun);
+        }
+
+        let res = ui
+            .scope(|ui| {
+                ui.vertical_centered(|ui| {
+                    self.clock_ui(ui);
+                    self.controls_ui(ui);
+                });
+            })
+            .response;
+
+        if res.interact(egui::Sense::click_and_drag()).dragged() {
+            pane_response.set_drag_started();
+        }
+        res.context_menu(|ui| {
+            if ui.button("Timer Settings…").clicked() {
+                self.settings_visible = true;
+                ui.close_menu();
+            }
+        });
+
+        let mut settings_visible = self.settings_visible;
+        egui::Window::new("Timer Settings")
+            .id(ui.auto_id_with("timer_settings"))
+            .auto_sized()
+            .collapsible(true)
+            .movable(true)
+            .open(&mut settings_visible)
+            .show(ui.ctx(), |ui| self.settings_ui(ui));
+        self.settings_visible = settings_visible;
+
+        if self.countdown.is_running() || self.stopwatch.is_running() || self.t0.is_some() {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+
+        pane_response
+    }
+
+    fn update(&mut self, messages: &[&TimedMessage]) {
+        if !self.waits_for_condition() {
+            return;
+        }
+        let Some(Ok(condition)) = &mut self.condition else {
+            return;
+        };
+        let mut t0 = None;
+        for message in messages {
+            self.values.update(message);
+            if condition.is_met(&self.values) == Some(true) {
+                info!("T0 condition {} met", condition);
+                t0 = Some(message.time);
+                break;
+            }
+        }
+        if let Some(t0) = t0 {
+            self.set_t0(t0);
+        }
+    }
+
+    fn get_message_subscriptions(&self) -> Box<dyn Iterator<Item = u32>> {
+        match &self.condition {
+            Some(Ok(condition)) if self.waits_for_condition() => {
+                Box::new(condition.input_message_ids().into_iter())
+            }
+            _ => Box::new(None.into_iter()),
+        }
+    }
+}
+
+impl TimerPane {
+    /// Whether T0 is still to be set by the condition.
+    fn waits_for_condition(&self) -> bool {
+        self.mode != TimerMode::Stopwatch && self.t0.is_none() && !self.t0_condition.is_empty()
+    }
+
+    fn set_t0(&mut self, t0: Instant) {
+        info!("T0 set by the {} timer", self.mode);
+        self.t0 = Some(t0);
+        if self.broadcast_t0 {
+            *MISSION_T0.write() = Some(t0);
+        }
+    }
+
+    /// Stops the timer, clearing T0 if set by this pane.
+    fn reset(&mut self) {
+        self.countdown.reset();
+        self.stopwatch.reset();
+        if let Some(t0) = self.t0.take() {
+            let mut mission_t0 = MISSION_T0.write();
+            if *mission_t0 == Some(t0) {
+                *mission_t0 = None;
+            }
+        }
+        // values received before the reset must not set the new T0
+        self.values = LatestValues::default();
+    }
+
+    fn compile_condition(&mut self) {
+        if self.t0_condition.trim().is_empty() {
+            self.condition = None;
+            return;
+        }
+        self.condition =
+            Some(Condition::parse_with_derived(&self.t0_condition).map_err(|e| e.to_string()));
+    }
+
+    fn clock_ui(&mut self, ui: &mut Ui) {
+        let (text, status) = match self.mode {
+            TimerMode::Countdown => match self.t0 {
+                Some(t0) => (format!("T+{}", format_clock(t0.elapsed())), None),
+                None => {
+                    // round up, so that T0 is reached when showing zero
+                    let remaining = self.countdown.remaining().as_secs_f64().ceil();
+                    let text = format!("T-{}", format_clock(Duration::from_secs_f64(remaining)));
+                    let status = if self.countdown.is_running() {
+                        None
+                    } else if self.countdown.is_started() {
+                        Some(RichText::new("HOLD").strong().color(Color32::YELLOW))
+                    } else {
+                        Some(RichText::new("Not started").weak())
+                    };
+                    (text, status)
+                }
+            },
+            TimerMode::Stopwatch => {
+                let elapsed = self.stopwatch.elapsed();
+                let text = format!(
+                    "{}.{}",
+                    format_clock(elapsed),
+                    elapsed.subsec_millis() / 100
+                );
+                let status = (!self.stopwatch.is_running() && self.stopwatch.is_started())
+                    .then(|| RichText::new("Stopped").weak());
+                (text, status)
+            }
+            TimerMode::MissionElapsed => match self.t0 {
+                Some(t0) => (format!("T+{}", format_clock(t0.elapsed())), None),
+                None => (
+                    "T+--:--".to_owned(),
+                    Some(RichText::new("Waiting for T0").weak()),
+                ),
+            },
+        };
+        ui.label(
+            RichText::new(text)
+                .monospace()
+                .strong()
+                .size(self.text_size),
+        );
+        if let Some(status) = status {
+            ui.label(status);
+        }
+        if self.waits_for_condition() {
+            ui.label(RichText::new(format!("T0 on {}", self.t0_condition)).weak());
+        }
+    }
+
+    fn controls_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| match self.mode {
+            TimerMode::Countdown => {
+                let before_t0 = self.t0.is_none();
+                if self.countdown.is_running() {
+                    if ui.add_enabled(before_t0, Button::new("⏸ Hold")).clicked() {
+                        info!("Countdown on hold");
+                        self.countdown.hold();
+                    }
+                } else {
+                    let text = if self.countdown.is_started() {
+                        "▶ Resume"
+                    } else {
+                        "▶ Start"
+                    };
+                    if ui.add_enabled(before_t0, Button::new(text)).clicked() {
+                        info!("Countdown started");
+                        self.countdown.start();
+                    }
+                }
+                if ui.button("⟲ Reset").clicked() {
+                    self.reset();
+                }
+            }
+            TimerMode::Stopwatch => {
+                if self.stopwatch.is_running() {
+                    if ui.button("⏹ Stop").clicked() {
+                        self.stopwatch.hold();
+                    }
+                } else if ui.button("▶ Start").clicked() {
+                    self.stopwatch.start();
+                }
+                if ui.button("⟲ Reset").clicked() {
+                    self.reset();
+                }
+            }
+            TimerMode::MissionElapsed => {
+                if ui
+                    .add_enabled(self.t0.is_none(), Button::new("Set T0 now"))
+                    .clicked()
+                {
+                    self.set_t0(Instant::now());
+                }
+                if ui.button("⟲ Reset").clicked() {
+                    self.reset();
+                }
+            }
+        });
+    }
+
+    fn settings_ui(&mut self, ui: &mut Ui) {
+        let mode = self.mode;
+        egui::ComboBox::from_label("Mode")
+            .selected_text(self.mode.to_string())
+            .show_ui(ui, |ui| {
+                for mode in TimerMode::iter() {
+                    ui.selectable_value(&mut self.mode, mode, mode.to_string());
+                }
+            });
+        if self.mode != mode {
+            self.reset();
+        }
+
+        if self.mode == TimerMode::Countdown {
+            ui.horizontal(|ui| {
+                let label = ui.label("Countdown length:");
+                let mut secs = self.countdown.length().as_secs();
+                ui.add_enabled(
+                    !self.countdown.is_started(),
+                    DragValue::new(&mut secs)
+                        .range(1..=86400)
+                        .custom_formatter(|secs, _| format_clock(Duration::from_secs_f64(secs))),
+                )
+                .labelled_by(label.id)
+                .on_disabled_hover_text("Reset the countdown to change its length");
+                self.countdown.set_length(Duration::from_secs(secs));
+            });
+        }
+
+        if self.mode != TimerMode::Stopwatch {
+            ui.label("T0 condition:");
+            let response = ui.add(
+                TextEdit::singleline(&mut self.t0_condition)
+                    .hint_text("e.g. ROCKET_FLIGHT_TM.fmm_state == 5")
+                    .desired_width(260.0),
+            );
+            if response.changed() {
+                self.compile_condition();
+            }
+            if let Some(Err(e)) = &self.condition {
+                ui.label(RichText::new(e).color(Color32::RED));
+            }
+            ui.checkbox(&mut self.broadcast_t0, "Share T0 with the other panes")
+                .on_hover_text("Used by the plots showing the mission time on the x axis");
+        }
+
+        ui.horizontal(|ui| {
+            let label = ui.label("Text size:");
+            ui.add(DragValue::new(&mut self.text_size).range(10.0..=120.0))
+                .labelled_by(label.id);
+        });
+    }
+}
@@ -0,0 +1,623 @@
+mod camera;
+mod track;
+
+use std::time::Duration;
+
+use egui::{
+    Align2, Color32, FontId, Painter, PointerButton, Pos2, RichText, Sense, Shape, Stroke, Ui,
+};
+use glam::{DQuat, DVec3};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+
+use crate::{
+    mavlink::{
+        TimedMessage,
+        reflection::{IndexedField, MAVLINK_PROFILE},
+    },
+    ui::app::PaneResponse,
+    utils::units::UnitOfMeasure,
+};
+
+use super::PaneBehavior;
+use camera::{OrbitCamera, Projection};
+use track::{Geodetic, Track};
+
+/// Lines of the ground grid on each side of its center.
+const GRID_HALF_LINES: i32 = 10;
+/// Length of the attitude axes, as a fraction of the distance of the camera.
+const AXES_LENGTH: f64 = 0.08;
+
+/// 3D view of the trajectory of the vehicle, with its attitude at the latest
+/// position, projected on the CPU and drawn with the egui painter.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Trajectory3DPane {
+    settings: TrajectorySettings,
+    #[serde(default)]
+    camera: OrbitCamera,
+
+    #[serde(skip)]
+    track: Track,
+    /// Latest attitude, rotating the body frame to the north-east-down frame
+    #[serde(skip)]
+    attitude: Option<DQuat>,
+    #[serde(skip)]
+    state_valid: bool,
+    #[serde(skip)]
+    settings_visible: bool,
+}
+
+impl PartialEq for Trajectory3DPane {
+    fn eq(&self, other: &Self) -> bool {
+        self.settings == other.settings
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct TrajectorySettings {
+    position: Option<PositionFields>,
+    /// Fields of the x, y, z and w components of the attitude quaternion
+    attitude: Option<[IndexedField; 4]>,
+    color: Color32,
+    /// Keep the latest position at the center of the view
+    follow: bool,
+    /// Positions will be shown for this duration before being removed
+    points_lifespan: Duration,
+}
+
+impl Default for TrajectorySettings {
+    fn default() -> Self {
+        Self {
+            position: None,
+            attitude: None,
+            color: Color32::LIGHT_BLUE,
+            follow: false,
+            points_lifespan: Duration::from_secs(600),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct PositionFields {
+    frame: PositionFrame,
+    fields: [IndexedField; 3],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Display, EnumIter)]
+enum PositionFrame {
+    /// North, east and down from the origin, in meters
+    #[strum(to_string = "NED")]
+    Ned,
+    /// Latitude and longitude in degrees, altitude in meters
+    #[strum(to_string = "Lat/Lon/Alt")]
+    Geodetic,
+}
+
+impl PositionFrame {
+    fn labels(&self) -> [&'static str; 3] {
+        match self {
+            PositionFrame::Ned => ["North", "East", "Down"],
+            PositionFrame::Geodetic => ["Latitude", "Longitude", "Altitude"],
+        }
+    }
+
+    /// Units the fields are converted to, if they have a compatible one.
+    fn units(&self) -> [&'static str; 3] {
+        match self {
+            PositionFrame::Ned => ["m", "m", "m"],
+            PositionFrame::Geodetic => ["deg", "deg", "m"],
+        }
+    }
+
+    /// Usual names of the fields of each coordinate.
+    fn field_names(&self) -> [&'static [&'static str]; 3] {
+        match self {
+            PositionFrame::Ned => [&["n", "north"], &["e", "east"], &["d", "down"]],
+            PositionFrame::Geodetic => [
+                &["latitude", "lat"],
+                &["longitude", "lon"],
+                &["altitude", "alt", "height"],
+            ],
+        }
+    }
+}
+
+impl PositionFields {
+    fn msg_id(&self) -> u32 {
+        self.fields[0].msg_id()
+    }
+
+    /// Coordinates of the position in the message, in the units of the frame.
+    fn extract(&self, message: &TimedMessage) -> Option<[f64; 3]> {
+        let mut coordinates = [0.0; 3];
+        for ((coordinate, field), unit) in coordinates
+            .iter_mut()
+            .zip(&self.fields)
+            .zip(self.frame.units())
+        {
+            let value = field.extract_as_f64(&message.message).ok()?;
+            *coordinate = UnitOfMeasure::from(field.field().unit.as_ref())
+                .convert(value, &UnitOfMeasure::from(Some(unit)))
+                .unwrap_or(value);
+        }
+        Some(coordinates)
+    }
+}
+
+impl PaneBehavior for Trajectory3DPane {
+    #[profiling::function]
+    fn ui(&mut self, ui: &mut Ui) -> PaneResponse {
+        let mut response = PaneResponse::default();
+        let fields_digest = (
+            self.settings.position.clone(),
+            self.settings.attitude.clone(),
+        );
+        let ctrl_pressed = ui.input(|i| i.modifiers.ctrl);
+
+        ui.horizontal(|ui| {
+            if ui
+                .small_button("⛶ Fit")
+                .on_hover_text("Fit the whole trajectory in the view, or double click it")
+                .clicked()
+            {
+                self.fit_view();
+            }
+            ui.toggle_value(&mut self.settings.follow, "🎯 Follow")
+                .on_hover_text("Keep the latest position at the center of the view");
+            if let Some(last) = self.track.last() {
+                let launch = self.track.launch().unwrap_or_default();
+                let range = (last - launch).truncate().length();
+                ui.separator();
+                ui.monospace(format!("Height {:.1} m", self.track.height_of(last)));
+                ui.monospace(format!("Range {range:.1} m"));
+            }
+        });
+
+        let (rect, view) = ui.allocate_exact_size(ui.available_size(), Sense::click_and_drag());
+        if view.dragged() && ctrl_pressed {
+            response.set_drag_started();
+        } else if view.dragged_by(PointerButton::Secondary)
+            || (view.dragged_by(PointerButton::Primary) && ui.input(|i| i.modifiers.shift))
+        {
+            self.camera.pan(view.drag_delta(), rect);
+        } else if view.dragged_by(PointerButton::Primary) {
+            self.camera.orbit(view.drag_delta());
+        }
+        if view.hovered() {
+            let (scroll, zoom) = ui.input(|i| (i.smooth_scroll_delta.y, i.zoom_delta()));
+            self.camera
+                .zoom(zoom as f64 * (scroll as f64 / 200.0).exp());
+        }
+        if view.double_clicked() {
+            self.fit_view();
+        }
+        view.context_menu(|ui| show_menu(ui, self));
+
+        if self.settings.follow
+            && let Some(last) = self.track.last()
+        {
+            self.camera.target = last;
+        }
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+        let projection = self.camera.projection(rect);
+        let text_color = ui.visuals().text_color();
+        self.draw_grid(&painter, &projection, ui.visuals().weak_text_color());
+        self.draw_track(&painter, &projection, text_color);
+        self.draw_attitude(&painter, &projection);
+        if self.track.is_empty() {
+            let hint = if self.settings.position.is_none() {
+                "Choose the position fields in the settings"
+            } else {
+                "Waiting for positions"
+            };
+            painter.text(
+                rect.center(),
+                Align2::CENTER_CENTER,
+                hint,
+                FontId::proportional(14.0),
+                ui.visuals().weak_text_color(),
+            );
+        }
+
+        let mut settings_visible = self.settings_visible;
+        egui::Window::new("Trajectory Settings")
+            .id(ui.auto_id_with("trajectory_settings"))
+            .auto_sized()
+            .collapsible(true)
+            .movable(true)
+            .open(&mut settings_visible)
+            .show(ui.ctx(), |ui| settings_window(ui, &mut self.settings));
+        self.settings_visible = settings_visible;
+
+        if fields_digest
+            != (
+                self.settings.position.clone(),
+                self.settings.attitude.clone(),
+            )
+        {
+            self.state_valid = false;
+        }
+
+        response
+    }
+
+    #[profiling::function]
+    fn update(&mut self, messages: &[&TimedMessage]) {
+        if !self.state_valid {
+            self.track.clear();
+            self.attitude = None;
+        }
+
+        for msg in messages {
+            if let Some(position) = &self.settings.position
+                && position.msg_id() == msg.id()
+                && let Some([a, b, c]) = position.extract(msg)
+            {
+                match position.frame {
+                    PositionFrame::Ned => self.track.push_ned(msg.time, DVec3::new(a, b, c)),
+                    PositionFrame::Geodetic => self.track.push_geodetic(
+                        msg.time,
+                        Geodetic {
+                            latitude: a,
+                            longitude: b,
+                            altitude: c,
+                        },
+                    ),
+                }
+            }
+            if let Some(fields) = &self.settings.attitude
+                && fields[0].msg_id() == msg.id()
+                && let Some(attitude) = extract_quaternion(fields, msg)
+            {
+                self.attitude = Some(attitude);
+            }
+        }
+        self.track.clear_older_than(self.settings.points_lifespan);
+
+        self.state_valid = true;
+    }
+
+    fn get_message_subscriptions(&self) -> Box<dyn Iterator<Item = u32>> {
+        let mut ids: Vec<u32> = self.settings.position.iter().map(|p| p.msg_id()).collect();
+        ids.extend(
+            self.settings
+                .attitude
+                .iter()
+                .map(|fields| fields[0].msg_id()),
+        );
+        ids.sort_unstable();
+        ids.dedup();
+        Box::new(ids.into_iter())
+    }
+
+    fn should_send_message_history(&self) -> bool {
+        !self.state_valid
+    }
+}
+
+impl Trajectory3DPane {
+    fn fit_view(&mut self) {
+        if let Some((min, max)) = self.track.bounds() {
+            self.camera.fit(min, max);
+        }
+    }
+
+    /// Height of the ground, taken as the one of the launch point.
+    fn ground_down(&self) -> f64 {
+        self.track.launch().map_or(0.0, |launch| launch.z)
+    }
+
+    /// Grid on the ground under the target of the camera, with a spacing
+    /// following the zoom.
+    fn draw_grid(&self, painter: &Painter, projection: &Projection, color: Color32) {
+        let step = 10f64.powf((self.camera.distance / 4.0).log10().floor());
+        let down = self.ground_down();
+        let center = (self.camera.target / step).round() * step;
+        let extent = GRID_HALF_LINES as f64 * step;
+        let stroke = Stroke::new(1.0, color.gamma_multiply(0.4));
+        for i in -GRID_HALF_LINES..=GRID_HALF_LINES {
+            let offset = i as f64 * step;
+            let lines = [
+                (
+                    DVec3::new(center.x + offset, center.y - extent, down),
+                    DVec3::new(center.x + offset, center.y + extent, down),
+                ),
+                (
+                    DVec3::new(center.x - extent, center.y + offset, down),
+                    DVec3::new(center.x + extent, center.y + offset, down),
+                ),
+            ];
+            for (a, b) in lines {
+                if let Some(segment) = projection.project_segment(a, b) {
+                    painter.line_segment(segment, stroke);
+                }
+            }
+        }
+        let north = DVec3::new(center.x + extent + step, center.y, down);
+        if let Some(pos) = projection.project(north) {
+            painter.text(
+                pos,
+                Align2::CENTER_CENTER,
+                "N",
+                FontId::monospace(14.0),
+                color,
+            );
+        }
+        painter.text(
+            painter.clip_rect().left_bottom() + egui::vec2(4.0, -4.0),
+            Align2::LEFT_BOTTOM,
+            format!("grid {step} m"),
+            FontId::monospace(11.0),
+            color,
+        );
+    }
+
+    /// Trajectory with its shadow on the ground, and the markers of the
+    /// launch, the apogee and the landing.
+    fn draw_track(&self, painter: &Painter, projection: &Projection, text_color: Color32) {
+        let down = self.ground_down();
+        let color = self.settings.color;
+        draw_polyline(
+            painter,
+            projection,
+            self.track.positions().map(|p| DVec3::new(p.x, p.y, down)),
+            Stroke::new(1.0, color.gamma_multiply(0.3)),
+        );
+        draw_polyline(
+            painter,
+            projection,
+            self.track.positions(),
+            Stroke::new(2.0, color),
+        );
+
+        let mut markers = Vec::new();
+        if let Some(launch) = self.track.launch() {
+            markers.push((launch, "Launch".to_owned(), Color32::GREEN));
+        }
+        if let Some(apogee) = self.track.apogee() {
+            let height = self.track.height_of(apogee);
+            markers.push((apogee, format!("Apogee {height:.0} m"), Color32::ORANGE));
+            // drop line, to read where the apogee is over the ground
+            let ground = DVec3::new(apogee.x, apogee.y, down);
+            if let Some(segment) = projection.project_segment(apogee, ground) {
+                painter.line_segment(
+                    segment,
+                    Stroke::new(1.0, Color32::ORANGE.gamma_multiply(0.5)),
+                );
+            }
+        }
+        if let Some(landing) = self.track.landing() {
+            markers.push((landing, "Landing".to_owned(), Color32::RED));
+        }
+        for (position, label, marker_color) in markers {
+            if let Some(pos) = projection.project(position) {
+                painter.circle_filled(pos, 4.0, marker_color);
+                painter.text(
+                    pos + egui::vec2(6.0, -6.0),
+                    Align2::LEFT_BOTTOM,
+                    label,
+                    FontId::proportional(12.0),
+                    text_color,
+                );
+            }
+        }
+
+        if let Some(pos) = self.track.last().and_then(|p| projection.project(p)) {
+            painter.circle_stroke(pos, 6.0, Stroke::new(2.0, color));
+        }
+    }
+
+    /// Body axes at the latest position: x red, y green and z blue.
+    fn draw_attitude(&self, painter: &Painter, projection: &Projection) {
+        let (Some(attitude), Some(position)) = (self.attitude, self.track.last()) else {
+            return;
+        };
+        let length = self.camera.distance * AXES_LENGTH;
+        let Some(origin) = projection.project(position) else {
+            return;
+        };
+        let axes = [
+            (DVec3::X, Color32::RED, 3.0),
+            (DVec3::Y, Color32::GREEN, 1.5),
+            (DVec3::Z, Color32::LIGHT_BLUE, 1.5),
+        ];
+        for (axis, color, width) in axes {
+            if let Some(end) = projection.project(position + attitude * axis * length) {
+                painter.line_segment([origin, end], Stroke::new(width, color));
+            }
+        }
+    }
+}
+
+/// Draws the points as a line, broken where they are behind the camera.
+fn draw_polyline(
+    painter: &Painter,
+    projection: &Projection,
+    points: impl Iterator<Item = DVec3>,
+    stroke: Stroke,
+) {
+    let mut line: Vec<Pos2> = Vec::new();
+    for point in points {
+        match projection.project(point) {
+            // points closer than a pixel add nothing to the line
+            Some(pos) if line.last().is_some_and(|last| last.distance(pos) < 1.0) => {}
+            Some(pos) => line.push(pos),
+            None => {
+                if line.len() > 1 {
+                    painter.add(Shape::line(std::mem::take(&mut line), stroke));
+                } else {
+                    line.clear();
+                }
+            }
+        }
+    }
+    if line.len() > 1 {
+        painter.add(Shape::line(line, stroke));
+    }
+}
+
+/// Normalized attitude quaternion in the message, from its x, y, z and w
+/// fields.
+fn extract_quaternion(fields: &[IndexedField; 4], message: &TimedMessage) -> Option<DQuat> {
+    let mut components = [0.0; 4];
+    for (component, field) in components.iter_mut().zip(fields) {
+        *component = field.extract_as_f64(&message.message).ok()?;
+    }
+    let [x, y, z, w] = components;
+    let quaternion = DQuat::from_xyzw(x, y, z, w);
+    (quaternion.is_finite() && quaternion.length() > 0.0).then(|| quaternion.normalize())
+}
+
+/// Fields of the message named as one of the `names` of each coordinate, the
+/// first plottable ones otherwise.
+fn guess_fields<const N: usize>(msg_id: u32, names: [&[&str]; N]) -> Option<[IndexedField; N]> {
+    let fields = MAVLINK_PROFILE
+        .get_plottable_fields(msg_id)
+        .unwrap_or_default();
+    let mut fallback = fields.iter();
+    let guessed: Vec<IndexedField> = names
+        .iter()
+        .map(|names| {
+            fields
+                .iter()
+                .find(|f| names.contains(&f.name().to_lowercase().as_str()))
+                .or_else(|| fallback.next())
+                .cloned()
+        })
+        .collect::<Option<_>>()?;
+    guessed.try_into().ok()
+}
+
+fn show_menu(ui: &mut Ui, pane: &mut Trajectory3DPane) {
+    ui.set_max_width(200.0);
+    if ui.button("Trajectory Settings…").clicked() {
+        pane.settings_visible = true;
+        ui.close_menu();
+    }
+    if ui.button("Reset View").clicked() {
+        pane.camera = OrbitCamera::default();
+        ui.close_menu();
+    }
+    if ui.button("Clear").clicked() {
+        pane.track.clear();
+        pane.attitude = None;
+        ui.close_menu();
+    }
+}
+
+fn settings_window(ui: &mut Ui, settings: &mut TrajectorySettings) {
+    ui.label(RichText::new("Position").strong());
+    let position_msg = settings.position.as_ref().map(|p| p.msg_id());
+    if let Some(msg_id) = message_combo(ui, "Position Message", position_msg, false) {
+        let frame = settings
+            .position
+            .as_ref()
+            .map_or(PositionFrame::Ned, |p| p.frame);
+        settings.position = msg_id
+            .and_then(|id| guess_fields(id, frame.field_names()))
+            .map(|fields| PositionFields { frame, fields });
+    }
+    if let Some(position) = &mut settings.position {
+        let msg_id = position.msg_id();
+        ui.horizontal(|ui| {
+            for frame in PositionFrame::iter() {
+                if ui
+                    .selectable_value(&mut position.frame, frame, frame.to_string())
+                    .changed()
+                    && let Some(fields) = guess_fields(msg_id, frame.field_names())
+                {
+                    position.fields = fields;
+                }
+            }
+        });
+        for (field, label) in position.fields.iter_mut().zip(position.frame.labels()) {
+            field_combo(ui, label, field);
+        }
+    }
+
+    ui.separator();
+    ui.label(RichText::new("Attitude").strong());
+    let attitude_msg = settings.attitude.as_ref().map(|fields| fields[0].msg_id());
+    if let Some(msg_id) = message_combo(ui, "Attitude Message", attitude_msg, true) {
+        let names: [&[&str]; 4] = [
+            &["qx", "q_x"],
+            &["qy", "q_y"],
+            &["qz", "q_z"],
+            &["qw", "q_w"],
+        ];
+        settings.attitude = msg_id.and_then(|id| guess_fields(id, names));
+    }
+    if let Some(fields) = &mut settings.attitude {
+        for (field, label) in fields.iter_mut().zip(["qx", "qy", "qz", "qw"]) {
+            field_combo(ui, label, field);
+        }
+        ui.label(
+            RichText::new("Quaternion rotating the body frame to the NED frame")
+                .weak()
+                .small(),
+        );
+    }
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.label("Color");
+        ui.color_edit_button_srgba(&mut settings.color);
+    });
+    let mut points_lifespan_sec = settings.points_lifespan.as_secs();
+    ui.horizontal(|ui| {
+        ui.label("Points Lifespan: ");
+        ui.add(
+            egui::DragValue::new(&mut points_lifespan_sec)
+                .range(5..=1800)
+                .speed(1)
+                .update_while_editing(false)
+                .suffix(" seconds"),
+        )
+    })
+    .inner
+    .on_hover_text("How long the positions are shown in the view");
+    settings.points_lifespan = Duration::from_secs(points_lifespan_sec);
+}
+
+/// Combo box of the messages, returning the new choice if changed, `None`
+/// meaning no message.
+fn message_combo(
+    ui: &mut Ui,
+    label: &str,
+    selected: Option<u32>,
+    optional: bool,
+) -> Option<Option<u32>> {
+    let name = |id: Option<u32>| {
+        id.and_then(|id| MAVLINK_PROFILE.get_msg(id))
+            .map_or_else(|| "None".to_owned(), |m| m.name.clone())
+    };
+    let mut choice = selected;
+    egui::ComboBox::from_label(label)
+        .selected_text(name(selected))
+        .show_ui(ui, |ui| {
+            if optional {
+                ui.selectable_value(&mut choice, None, "None");
+            }
+            for msg in MAVLINK_PROFILE.get_sorted_msgs() {
+                ui.selectable_value(&mut choice, Some(msg.id), &msg.name);
+            }
+        });
+    (choice != selected).then_some(choice)
+}
+
+fn field_combo(ui: &mut Ui, label: &str, field: &mut IndexedField) {
+    let fields = MAVLINK_PROFILE
+        .get_plottable_fields(field.msg_id())
+        .unwrap_or_default();
+    egui::ComboBox::from_label(label)
+        .selected_text(field.name().to_owned())
+        .show_ui(ui, |ui| {
+            for choice in fields {
+                let name = choice.name().to_owned();
+                ui.selectable_value(field, choice, name);
+            }
+        });
+}
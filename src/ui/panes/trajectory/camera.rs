@@ -0,0 +1,241 @@
+//! Orbit camera projecting the scene on the pane with a pinhole model, so that
+//! it can be drawn with the egui painter without a GPU.
+//!
+//! The scene is in the north-east-down frame, in meters.
+
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+
+use egui::{Pos2, Rect, Vec2};
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+
+/// Vertical field of view, in radians.
+const FOV_Y: f64 = FRAC_PI_4;
+/// Radians the camera orbits by for each pixel dragged.
+const ORBIT_SPEED: f64 = 0.005;
+/// Limit of the elevation, avoiding the degenerate views from straight above
+/// or below.
+const MAX_PITCH: f64 = FRAC_PI_2 - 0.01;
+const MIN_DISTANCE: f64 = 1.0;
+const MAX_DISTANCE: f64 = 1e7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrbitCamera {
+    /// Point orbited
+    pub target: DVec3,
+    /// Azimuth of the camera from the target, clockwise from north, in radians
+    pub yaw: f64,
+    /// Elevation of the camera above the target, in radians
+    pub pitch: f64,
+    /// Distance from the target, in meters
+    pub distance: f64,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        // from south-west, looking north-east
+        Self {
+            target: DVec3::ZERO,
+            yaw: 225f64.to_radians(),
+            pitch: 30f64.to_radians(),
+            distance: 500.0,
+        }
+    }
+}
+
+impl OrbitCamera {
+    pub fn eye(&self) -> DVec3 {
+        let horizontal = self.distance * self.pitch.cos();
+        self.target
+            + DVec3::new(
+                horizontal * self.yaw.cos(),
+                horizontal * self.yaw.sin(),
+                -self.distance * self.pitch.sin(),
+            )
+    }
+
+    /// Orbits around the target by the pixels dragged.
+    pub fn orbit(&mut self, drag: Vec2) {
+        self.yaw -= drag.x as f64 * ORBIT_SPEED;
+        self.pitch = (self.pitch + drag.y as f64 * ORBIT_SPEED).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Moves the target in the plane of the view, following the pixels dragged
+    /// in the `viewport`.
+    pub fn pan(&mut self, drag: Vec2, viewport: Rect) {
+        let (_, right, up) = self.basis();
+        let meters_per_pixel = self.view_height() / viewport.height().max(1.0) as f64;
+        self.target -= (right * drag.x as f64 - up * drag.y as f64) * meters_per_pixel;
+    }
+
+    /// Moves closer to the target by `factor`, away if lower than one.
+    pub fn zoom(&mut self, factor: f64) {
+        self.distance = (self.distance / factor).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+
+    /// Centers the view on the box between `min` and `max`, fitting it whole.
+    pub fn fit(&mut self, min: DVec3, max: DVec3) {
+        self.target = (min + max) / 2.0;
+        let radius = (max - min).length() / 2.0;
+        self.distance = (1.2 * radius / (FOV_Y / 2.0).sin()).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+
+    /// Height of the scene visible at the distance of the target.
+    fn view_height(&self) -> f64 {
+        2.0 * self.distance * (FOV_Y / 2.0).tan()
+    }
+
+    /// Forward, right and up directions of the view.
+    fn basis(&self) -> (DVec3, DVec3, DVec3) {
+        let forward = (self.target - self.eye()).normalize();
+        let right = forward.cross(DVec3::NEG_Z).normalize();
+        let up = right.cross(forward);
+        (forward, right, up)
+    }
+
+    /// Projection of the scene on the `viewport` from the current view.
+    pub fn projection(&self, viewport: Rect) -> Projection {
+        let (forward, right, up) = self.basis();
+        Projection {
+            eye: self.eye(),
+            forward,
+            right,
+            up,
+            focal: viewport.height() as f64 / 2.0 / (FOV_Y / 2.0).tan(),
+            center: viewport.center(),
+            near: self.distance * 1e-3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Projection {
+    eye: DVec3,
+    forward: DVec3,
+    right: DVec3,
+    up: DVec3,
+    /// Distance of the image plane, in pixels
+    focal: f64,
+    center: Pos2,
+    /// Points closer than this to the camera plane are not drawn
+    near: f64,
+}
+
+impl Projection {
+    /// Position of `point` on the screen, `None` if behind the camera.
+    pub fn project(&self, point: DVec3) -> Option<Pos2> {
+        let depth = (point - self.eye).dot(self.forward);
+        (depth >= self.near && depth.is_finite()).then(|| self.screen_position(point))
+    }
+
+    fn screen_position(&self, point: DVec3) -> Pos2 {
+        let relative = point - self.eye;
+        let depth = relative.dot(self.forward);
+        let x = relative.dot(self.right) / depth * self.focal;
+        let y = relative.dot(self.up) / depth * self.focal;
+        Pos2::new(self.center.x + x as f32, self.center.y - y as f32)
+    }
+
+    /// Segment between `a` and `b` on the screen, cut where it passes behind
+    /// the camera.
+    pub fn project_segment(&self, a: DVec3, b: DVec3) -> Option<[Pos2; 2]> {
+        let depth_a = (a - self.eye).dot(self.forward);
+        let depth_b = (b - self.eye).dot(self.forward);
+        let cut = |from: DVec3, to: DVec3, depth_from: f64, depth_to: f64| {
+            let t = (self.near - depth_from) / (depth_to - depth_from);
+            from + (to - from) * t
+        };
+        if !(depth_a.is_finite() && depth_b.is_finite()) {
+            return None;
+        }
+        let (a, b) = match (depth_a >= self.near, depth_b >= self.near) {
+            (true, true) => (a, b),
+            (true, false) => (a, cut(a, b, depth_a, depth_b)),
+            (false, true) => (cut(a, b, depth_a, depth_b), b),
+            (false, false) => return None,
+        };
+        // the cut ends lie on the near plane
+        Some([self.screen_position(a), self.screen_position(b)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+
+    use super::*;
+
+    fn viewport() -> Rect {
+        Rect::from_min_size(pos2(0.0, 0.0), Vec2::new(800.0, 600.0))
+    }
+
+    /// Camera south of the origin, looking north.
+    fn looking_north() -> OrbitCamera {
+        OrbitCamera {
+            target: DVec3::ZERO,
+            yaw: 180f64.to_radians(),
+            pitch: 0.0,
+            distance: 100.0,
+        }
+    }
+
+    #[test]
+    fn projects_the_scene_as_seen_from_the_camera() {
+        let projection = looking_north().projection(viewport());
+        let center = projection.project(DVec3::ZERO);
+        assert_eq!(center, Some(pos2(400.0, 300.0)));
+
+        // east is to the right, up is up on the screen
+        let east = projection.project(DVec3::new(0.0, 10.0, 0.0));
+        assert!(east.is_some_and(|p| p.x > 400.0 && (p.y - 300.0).abs() < 1e-3));
+        let above = projection.project(DVec3::new(0.0, 0.0, -10.0));
+        assert!(above.is_some_and(|p| p.y < 300.0 && (p.x - 400.0).abs() < 1e-3));
+
+        // farther points are closer to the center
+        let near = projection.project(DVec3::new(-50.0, 10.0, 0.0));
+        assert!(near.zip(east).is_some_and(|(near, east)| near.x > east.x));
+
+        let behind = DVec3::new(-200.0, 0.0, 0.0);
+        assert_eq!(projection.project(behind), None);
+        // segments passing behind are cut
+        let segment = projection.project_segment(DVec3::ZERO, behind);
+        assert!(segment.is_some_and(|[a, _]| a == pos2(400.0, 300.0)));
+    }
+
+    #[test]
+    fn fitted_views_show_the_whole_box() {
+        let (min, max) = (
+            DVec3::new(-300.0, 50.0, -3000.0),
+            DVec3::new(800.0, 400.0, 0.0),
+        );
+        let mut camera = OrbitCamera::default();
+        camera.fit(min, max);
+        let projection = camera.projection(viewport());
+        for corner in 0..8 {
+            let pick = |bit: usize, min: f64, max: f64| if corner & bit == 0 { min } else { max };
+            let point = DVec3::new(
+                pick(1, min.x, max.x),
+                pick(2, min.y, max.y),
+                pick(4, min.z, max.z),
+            );
+            let projected = projection.project(point);
+            assert!(projected.is_some_and(|p| viewport().contains(p)), "{point}");
+        }
+    }
+
+    #[test]
+    fn orbiting_keeps_the_target_at_the_center() {
+        let mut camera = OrbitCamera {
+            target: DVec3::new(10.0, 20.0, -30.0),
+            ..Default::default()
+        };
+        camera.orbit(Vec2::new(120.0, 1000.0));
+        assert!(camera.pitch <= MAX_PITCH);
+        assert_eq!(
+            camera.projection(viewport()).project(camera.target),
+            Some(pos2(400.0, 300.0))
+        );
+        let distance = camera.eye().distance(camera.target);
+        assert!((distance - camera.distance).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,246 @@
+//! Trajectory of the vehicle, as positions in a north-east-down frame.
+//!
+//! Geodetic positions are converted to the local frame centered on the first
+//! one received, approximating the Earth as flat around it, which holds over
+//! the few kilometers of a flight.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use glam::DVec3;
+
+/// Mean radius of the Earth, in meters.
+const EARTH_RADIUS: f64 = 6_371_000.0;
+/// Height above the launch point under which the vehicle is considered
+/// landed, once past the apogee.
+const LANDING_HEIGHT: f64 = 5.0;
+/// Height to climb for the highest point to be an apogee.
+const MIN_APOGEE_HEIGHT: f64 = 4.0 * LANDING_HEIGHT;
+
+/// Latitude and longitude in degrees, altitude in meters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Geodetic {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+}
+
+impl Geodetic {
+    /// Offset from `origin` in its local north-east-down frame.
+    pub fn ned_from(&self, origin: &Geodetic) -> DVec3 {
+        let north = (self.latitude - origin.latitude).to_radians() * EARTH_RADIUS;
+        let east = (self.longitude - origin.longitude).to_radians()
+            * EARTH_RADIUS
+            * origin.latitude.to_radians().cos();
+        DVec3::new(north, east, origin.altitude - self.altitude)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Track {
+    /// Origin of the local frame, if the positions are geodetic
+    origin: Option<Geodetic>,
+    /// First position received, kept also once dropped from the points
+    launch: Option<DVec3>,
+    points: VecDeque<(Instant, DVec3)>,
+    /// Highest position, updated as the points are pushed and kept also once
+    /// dropped from the points
+    highest: Option<DVec3>,
+    /// First position after the highest one back down near the height of the
+    /// launch point
+    landing: Option<DVec3>,
+}
+
+impl Track {
+    pub fn push_ned(&mut self, time: Instant, position: DVec3) {
+        if !position.is_finite() {
+            return;
+        }
+        self.launch.get_or_insert(position);
+        self.points.push_back((time, position));
+        if self.highest.is_none_or(|highest| position.z < highest.z) {
+            self.highest = Some(position);
+            self.landing = None;
+        } else if self.landing.is_none()
+            && self.apogee().is_some()
+            && self.height_of(position) <= LANDING_HEIGHT
+        {
+            self.landing = Some(position);
+        }
+    }
+
+    pub fn push_geodetic(&mut self, time: Instant, position: Geodetic) {
+        let origin = *self.origin.get_or_insert(position);
+        self.push_ned(time, position.ned_from(&origin));
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Drops the points received more than `lifespan` ago, keeping the
+    /// launch, the apogee and the landing.
+    pub fn clear_older_than(&mut self, lifespan: Duration) {
+        // points are pushed in order of time, so the old ones are at the front
+        let old = self
+            .points
+            .partition_point(|(time, _)| time.elapsed() > lifespan);
+        self.points.drain(..old);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn origin(&self) -> Option<Geodetic> {
+        self.origin
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = DVec3> + '_ {
+        self.points.iter().map(|(_, p)| *p)
+    }
+
+    pub fn get(&self, index: usize) -> Option<DVec3> {
+        self.points.get(index).map(|(_, p)| *p)
+    }
+
+    /// Where the vehicle was first seen, taken as the launch point.
+    pub fn launch(&self) -> Option<DVec3> {
+        self.launch
+    }
+
+    pub fn last(&self) -> Option<DVec3> {
+        self.points.back().map(|(_, p)| *p)
+    }
+
+    /// Height of `position` above the launch point.
+    pub fn height_of(&self, position: DVec3) -> f64 {
+        self.launch()
+            .map_or(-position.z, |launch| launch.z - position.z)
+    }
+
+    /// Corners of the box bounding the trajectory.
+    pub fn bounds(&self) -> Option<(DVec3, DVec3)> {
+        let first = self.get(0)?;
+        Some(
+            self.positions()
+                .fold((first, first), |(min, max), p| (min.min(p), max.max(p))),
+        )
+    }
+
+    /// Highest position, if the vehicle climbed enough.
+    pub fn apogee(&self) -> Option<DVec3> {
+        self.highest
+            .filter(|highest| self.height_of(*highest) >= MIN_APOGEE_HEIGHT)
+    }
+
+    /// First position after the apogee back down near the height of the
+    /// launch point.
+    pub fn landing(&self) -> Option<DVec3> {
+        self.landing
+    }
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geodetic_positions_are_local() {
+        let origin = Geodetic {
+            latitude: 41.8,
+            longitude: 14.05,
+            altitude: 1400.0,
+        };
+        let position = Geodetic {
+            latitude: 41.81,
+            longitude: 14.06,
+            altitude: 2400.0,
+        };
+        let ned = position.ned_from(&origin);
+        // 0.01° is about 1.1 km north, and less east at this latitude
+        assert!((ned.x - 1111.9).abs() < 1.0, "{ned}");
+        assert!((ned.y - 829.0).abs() < 1.0, "{ned}");
+        assert_eq!(ned.z, -1000.0);
+
+        let mut track = Track::default();
+        let now = Instant::now();
+        track.push_geodetic(now, origin);
+        track.push_geodetic(now, position);
+        assert_eq!(track.launch(), Some(DVec3::ZERO));
+        assert_eq!(track.origin(), Some(origin));
+    }
+
+    #[test]
+    fn apogee_and_landing() {
+        let mut track = Track::default();
+        let now = Instant::now();
+        // launch from 10 m below the origin of the frame
+        let heights = [0.0, 1.0, 50.0, 300.0, 420.0, 380.0, 200.0, 30.0, 4.0, 0.0];
+        for (i, height) in heights.into_iter().enumerate() {
+            track.push_ned(now, DVec3::new(i as f64, 0.0, 10.0 - height));
+        }
+        assert_eq!(track.apogee(), track.get(4));
+        assert_eq!(track.landing(), track.get(8));
+        assert_eq!(track.height_of(track.apogee().unwrap()), 420.0);
+        assert_eq!(
+            track.bounds(),
+            Some((DVec3::new(0.0, 0.0, -410.0), DVec3::new(9.0, 0.0, 10.0)))
+        );
+
+        // still on the pad
+        let mut track = Track::default();
+        track.push_ned(now, DVec3::ZERO);
+        track.push_ned(now, DVec3::new(0.0, 0.0, -1.0));
+        track.push_ned(now, DVec3::new(f64::NAN, 0.0, 0.0));
+        assert_eq!(track.apogee(), None);
+        assert_eq!(track.landing(), None);
+        assert_eq!(track.positions().count(), 2);
+    }
+
+    #[test]
+    fn old_points_are_dropped_keeping_the_launch() {
+        let mut track = Track::default();
+        let now = Instant::now();
+        let old = now - Duration::from_secs(10);
+        let heights = [
+            (old, 0.0),
+            (old, 100.0),
+            (now, 300.0),
+            (now, 50.0),
+            (now, 2.0),
+        ];
+        for (i, (time, height)) in heights.into_iter().enumerate() {
+            track.push_ned(time, DVec3::new(i as f64, 0.0, -height));
+        }
+        let apogee = DVec3::new(2.0, 0.0, -300.0);
+        let landing = DVec3::new(4.0, 0.0, -2.0);
+        assert_eq!(
+            (track.apogee(), track.landing()),
+            (Some(apogee), Some(landing))
+        );
+
+        track.clear_older_than(Duration::from_secs(5));
+        assert_eq!(track.positions().count(), 3);
+        assert_eq!(track.launch(), Some(DVec3::ZERO));
+        assert_eq!(
+            (track.apogee(), track.landing()),
+            (Some(apogee), Some(landing))
+        );
+
+        // the apogee is kept once its point is dropped
+        let mut track = Track::default();
+        track.push_ned(old, DVec3::ZERO);
+        track.push_ned(old, DVec3::new(0.0, 0.0, -300.0));
+        track.push_ned(now, DVec3::new(0.0, 0.0, -100.0));
+        track.push_ned(now, DVec3::new(0.0, 0.0, -200.0));
+        track.clear_older_than(Duration::from_secs(5));
+        assert_eq!(track.positions().count(), 2);
+        let apogee = track.apogee().unwrap();
+        assert_eq!(track.height_of(apogee), 300.0);
+        assert_eq!(track.landing(), None);
+    }
+}
@@ -17,19 +17,22 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use skyward_mavlink::{
     mavlink::{MavHeader, MessageData},
-    orion::{ACK_TM_DATA, GSE_TM_DATA, NACK_TM_DATA, VALVE_INFO_TM_DATA, WACK_TM_DATA},
+    orion::{GSE_TM_DATA, VALVE_INFO_TM_DATA},
 };
 use strum::IntoEnumIterator;
 use tracing::{debug, info};
 
 use crate::{
+    alarms::{AlarmEngineExt, AlarmEvent, AlarmEventKind, Severity},
     mavlink::{MavMessage, TimedMessage},
+    tc_log::{PendingReply, ReplyTimeout, TcReply},
     ui::{
         app::PaneResponse,
         panes::valve_control::valves::ParameterValue,
         shortcuts::{ShortcutHandler, ShortcutHandlerExt},
         widgets::ShortcutCard,
     },
+    utils::timer::{Countdown, format_clock},
 };
 
 use super::PaneBehavior;
@@ -65,6 +68,9 @@ fn map_symbol_to_key(symbol: char) -> Key {
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct ValveControlPane {
     system_id: u8,
+    /// Time to wait for the reply to a valve command
+    #[serde(default)]
+    reply_timeout: ReplyTimeout,
 
     // INTERNAL
     #[serde(skip)]
@@ -73,6 +79,9 @@ pub struct ValveControlPane {
     // VALVE COMMANDS LIST
     #[serde(skip)]
     commands: Vec<CommandSM>,
+    /// Rejected commands, to be reported in the event log
+    #[serde(skip)]
+    rejected_commands: Vec<String>,
 
     // REFRESH SETTINGS
     auto_refresh: Option<Duration>,
@@ -103,8 +112,10 @@ impl Default for ValveControlPane {
             .collect();
         Self {
             system_id: 1, // Default system ID, can be changed later
+            reply_timeout: ReplyTimeout::default(),
             valves_state: ValveStateManager::default(),
             commands: vec![],
+            rejected_commands: vec![],
             auto_refresh: None,
             manual_refresh: false,
             last_refresh: None,
@@ -125,6 +136,18 @@ impl PaneBehavior for ValveControlPane {
         // process commands to update state
         self.process_commands();
 
+        // report the rejected commands in the event log
+        if !self.rejected_commands.is_empty() {
+            let alarms = ui.ctx().alarms();
+            let mut alarms = alarms.lock();
+            for rejection in self.rejected_commands.drain(..) {
+                alarms.record(
+                    AlarmEvent::new(AlarmEventKind::Nack, Severity::Warning, "Valve Control")
+                        .with_value(rejection),
+                );
+            }
+        }
+
         // Set this to at least double the maximum icon size used
         Icon::init_cache(ui.ctx(), (100, 100));
 
@@ -183,6 +206,7 @@ impl PaneBehavior for ValveControlPane {
                     ui.ctx(),
                     Self::settings_window_ui(
                         &mut self.system_id,
+                        &mut self.reply_timeout,
                         &mut self.auto_refresh,
                         &mut self.safety_venting,
                     ),
@@ -194,20 +218,12 @@ impl PaneBehavior for ValveControlPane {
 
     #[profiling::function]
     fn get_message_subscriptions(&self) -> Box<dyn Iterator<Item = u32>> {
-        let mut subscriptions = vec![VALVE_INFO_TM_DATA::ID, GSE_TM_DATA::ID];
+        let subscriptions = vec![VALVE_INFO_TM_DATA::ID, GSE_TM_DATA::ID];
         if self.needs_refresh() {
             // TODO
             // subscriptions.push();
         }
 
-        // Subscribe to ACK, NACK, WACK messages if any command is waiting for a response
-        if self.commands.iter().any(CommandSM::is_waiting_for_response) {
-            let ids = [ACK_TM_DATA::ID, NACK_TM_DATA::ID, WACK_TM_DATA::ID];
-            for &id in &ids {
-                subscriptions.push(id);
-            }
-        }
-
         Box::new(subscriptions.into_iter())
     }
 
@@ -254,12 +270,6 @@ impl PaneBehavior for ValveControlPane {
                     update_valve_state!(ox_release_valve_state, Valve::OxRelease);
                     update_valve_state!(ox_venting_valve_state, Valve::OxVenting);
                 }
-                MavMessage::ACK_TM(_) | MavMessage::NACK_TM(_) | MavMessage::WACK_TM(_) => {
-                    for cmd in self.commands.iter_mut() {
-                        // intercept all ACK/NACK/WACK messages
-                        cmd.capture_response(&message.message);
-                    }
-                }
                 _ => (),
             }
         }
@@ -273,17 +283,25 @@ impl PaneBehavior for ValveControlPane {
 
         // Pack and send the next command
         for cmd in self.commands.iter_mut() {
-            if let Some(message) = cmd.pack_and_wait() {
-                let header = MavHeader {
-                    system_id: self.system_id,
-                    ..Default::default()
-                };
+            let mut header = MavHeader {
+                system_id: self.system_id,
+                ..Default::default()
+            };
+            if let Some(message) = cmd.pack_and_wait(&mut header, self.reply_timeout) {
                 outgoing.push((header, message));
             }
         }
 
         outgoing
     }
+
+    fn handle_reply(&mut self, pending: &PendingReply, reply: &TcReply) {
+        for cmd in self.commands.iter_mut() {
+            if let Some(rejection) = cmd.capture_reply(pending, reply) {
+                self.rejected_commands.push(rejection);
+            }
+        }
+    }
 }
 
 // ┌────────────────────────┐
@@ -293,8 +311,6 @@ impl ValveControlPane {
     fn process_commands(&mut self) {
         // Process the commands
         for cmd in self.commands.iter_mut() {
-            // If the command is waiting for a response, check if it has expired
-            cmd.cancel_expired(Duration::from_secs(3));
             // If a response was captured, consume the command and update the valve state
             if let Some((valve, Some(parameter))) = cmd.consume_response() {
                 debug!("Valve state updated: {:?}", parameter);
@@ -342,11 +358,7 @@ impl ValveControlPane {
                 });
             let time_left = self.safety_venting.time_left();
             let time_left_fmt = time_left
-                .map(|d| {
-                    let minutes = d.as_secs() / 60;
-                    let seconds = d.as_secs() % 60;
-                    format!("{:02}:{:02}", minutes, seconds)
-                })
+                .map(format_clock)
                 .unwrap_or_else(|| "N/A".to_owned());
             ui.add_space(3.0);
             ui.horizontal(|ui| {
@@ -385,6 +397,7 @@ impl ValveControlPane {
 
     fn settings_window_ui(
         system_id: &mut u8,
+        reply_timeout: &mut ReplyTimeout,
         auto_refresh_setting: &mut Option<Duration>,
         safety_venting: &mut SafetyVentingWatcher,
     ) -> impl FnOnce(&mut Ui) {
@@ -398,6 +411,7 @@ impl ValveControlPane {
                 ui.add(DragValue::new(system_id).range(1..=255))
                     .labelled_by(label.id);
             });
+            ui.add(reply_timeout);
             ui.horizontal(|ui| {
                 ui.checkbox(&mut auto_refresh, "Auto Refresh");
                 if auto_refresh {
@@ -418,7 +432,7 @@ impl ValveControlPane {
                 }
             });
             ui.separator();
-            let mut emergency_venting_secs = safety_venting.timeout.as_secs();
+            let mut emergency_venting_secs = safety_venting.countdown.length().as_secs();
             ui.horizontal(|ui| {
                 ui.label("Safety Venting timeout:");
                 DragValue::new(&mut emergency_venting_secs)
@@ -653,30 +667,27 @@ struct SafetyVentingWatcher {
     last_valve_state: HashMap<Valve, u8>,
     /// Valves that refresh the safety ventime timer
     reset_valves: HashMap<Valve, bool>,
-    /// Timeout for safety venting
-    timeout: Duration,
-    /// Instant of the last valve actuation that reset the timer
-    #[serde(skip)]
-    last_reset: Option<Instant>,
+    /// Countdown to safety venting, restarted on valve actuation
+    #[serde(rename = "timeout")]
+    countdown: Countdown,
 }
 
 impl Default for SafetyVentingWatcher {
     fn default() -> Self {
         let last_valve_state = Valve::iter().map(|v| (v, 0)).collect::<HashMap<_, _>>();
         let reset_valves = Valve::iter().map(|v| (v, false)).collect::<HashMap<_, _>>();
-        let timeout = Duration::from_secs(300); // Default 5 minutes
+        let countdown = Countdown::new(Duration::from_secs(300)); // Default 5 minutes
         Self {
             last_valve_state,
             reset_valves,
-            timeout,
-            last_reset: None,
+            countdown,
         }
     }
 }
 
 impl SafetyVentingWatcher {
     fn update_timeout(&mut self, seconds: u64) {
-        self.timeout = Duration::from_secs(seconds);
+        self.countdown.set_length(Duration::from_secs(seconds));
     }
 
     fn update_valve_state(&mut self, valve: Valve, state: u8) {
@@ -685,21 +696,14 @@ impl SafetyVentingWatcher {
         {
             *last_state = state;
             if self.reset_valves[&valve] {
-                self.last_reset = Some(Instant::now());
+                self.countdown.restart();
             }
         }
     }
 
     fn time_left(&self) -> Option<Duration> {
-        if let Some(last_reset) = self.last_reset {
-            let elapsed = last_reset.elapsed();
-            if elapsed >= self.timeout {
-                Some(Duration::ZERO)
-            } else {
-                Some(self.timeout - elapsed)
-            }
-        } else {
-            None
-        }
+        self.countdown
+            .is_started()
+            .then(|| self.countdown.remaining())
     }
 }
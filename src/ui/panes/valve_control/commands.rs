@@ -1,10 +1,10 @@
-use std::time::{Duration, Instant};
-
 use skyward_mavlink::orion::{GET_VALVE_INFO_TC_DATA, WIGGLE_SERVO_TC_DATA};
 
-use crate::mavlink::{
-    ACK_TM_DATA, MavMessage, MessageData, NACK_TM_DATA, SET_ATOMIC_VALVE_TIMING_TC_DATA,
-    SET_VALVE_MAXIMUM_APERTURE_TC_DATA, WACK_TM_DATA,
+use crate::{
+    mavlink::{
+        MavHeader, MavMessage, SET_ATOMIC_VALVE_TIMING_TC_DATA, SET_VALVE_MAXIMUM_APERTURE_TC_DATA,
+    },
+    tc_log::{PendingReply, ReplyKind, ReplyTimeout, TcReply},
 };
 
 use super::valves::{ParameterValue, Valve, ValveParameter};
@@ -12,52 +12,55 @@ use super::valves::{ParameterValue, Valve, ValveParameter};
 #[derive(Debug, Clone, PartialEq)]
 pub enum CommandSM {
     Request(Command),
-    WaitingForResponse((Instant, Command)),
+    WaitingForResponse((PendingReply, Command)),
     Response((Valve, Option<ValveParameter>)),
     Consumed,
 }
 
 impl CommandSM {
-    pub fn pack_and_wait(&mut self) -> Option<MavMessage> {
+    /// Packs the command to be sent with the given header, which is stamped
+    /// to recognize the reply, waited for up to `timeout`.
+    pub fn pack_and_wait(
+        &mut self,
+        header: &mut MavHeader,
+        timeout: ReplyTimeout,
+    ) -> Option<MavMessage> {
         match self {
             Self::Request(command) => {
                 let message = MavMessage::from(command.clone());
-                *self = CommandSM::WaitingForResponse((Instant::now(), command.clone()));
+                let pending = PendingReply::send(header, &message, timeout);
+                *self = CommandSM::WaitingForResponse((pending, command.clone()));
                 Some(message)
             }
             _ => None,
         }
     }
 
-    pub fn cancel_expired(&mut self, timeout: Duration) {
-        if let Self::WaitingForResponse((instant, cmd)) = self {
-            if instant.elapsed() > timeout {
-                let Command { kind, valve } = cmd;
-                *self = Self::Response((*valve, kind.to_missing_parameter()));
-            }
+    /// Captures the outcome of the command, if it is the one delivered.
+    /// Returns a description of the rejection if the command was not accepted
+    /// (NACK or WACK).
+    pub fn capture_reply(&mut self, delivered: &PendingReply, reply: &TcReply) -> Option<String> {
+        let Self::WaitingForResponse((pending, Command { kind, valve })) = self else {
+            return None;
+        };
+        if pending != delivered {
+            return None;
         }
-    }
-
-    pub fn capture_response(&mut self, message: &MavMessage) {
-        if let Self::WaitingForResponse((_, Command { kind, valve })) = self {
-            let id = kind.message_id() as u8;
-            match message {
-                MavMessage::ACK_TM(ACK_TM_DATA { recv_msgid, .. }) if *recv_msgid == id => {
-                    *self = CommandSM::Response((*valve, kind.to_valid_parameter()));
-                }
-                MavMessage::NACK_TM(NACK_TM_DATA {
-                    err_id, recv_msgid, ..
-                }) if *recv_msgid == id => {
-                    *self = CommandSM::Response((*valve, kind.to_invalid_parameter(*err_id)));
-                }
-                MavMessage::WACK_TM(WACK_TM_DATA {
-                    err_id, recv_msgid, ..
-                }) if *recv_msgid == id => {
-                    *self = CommandSM::Response((*valve, kind.to_invalid_parameter(*err_id)));
-                }
-                _ => {}
+        match reply.kind() {
+            Some(ReplyKind::Ack) => {
+                *self = CommandSM::Response((*valve, kind.to_valid_parameter()));
+            }
+            Some(reply @ (ReplyKind::Nack { err_id } | ReplyKind::Wack { err_id })) => {
+                let rejection = format!("{kind:?} on {valve}: {reply}");
+                *self = CommandSM::Response((*valve, kind.to_invalid_parameter(err_id)));
+                return Some(rejection);
+            }
+            // timed out or not sent
+            None => {
+                *self = Self::Response((*valve, kind.to_missing_parameter()));
             }
         }
+        None
     }
 
     pub fn consume_response(&mut self) -> Option<(Valve, Option<ValveParameter>)> {
@@ -71,10 +74,6 @@ impl CommandSM {
         }
     }
 
-    pub fn is_waiting_for_response(&self) -> bool {
-        matches!(self, Self::WaitingForResponse(_))
-    }
-
     pub fn is_consumed(&self) -> bool {
         matches!(self, Self::Consumed)
     }
@@ -171,15 +170,6 @@ enum CommandKind {
 }
 
 impl CommandKind {
-    fn message_id(&self) -> u32 {
-        match self {
-            Self::Wiggle => WIGGLE_SERVO_TC_DATA::ID,
-            Self::Refresh => GET_VALVE_INFO_TC_DATA::ID,
-            Self::SetAtomicValveTiming(_) => SET_ATOMIC_VALVE_TIMING_TC_DATA::ID,
-            Self::SetValveMaximumAperture(_) => SET_VALVE_MAXIMUM_APERTURE_TC_DATA::ID,
-        }
-    }
-
     fn to_valid_parameter(self) -> Option<ValveParameter> {
         self.try_into().ok()
     }
@@ -1,5 +1,17 @@
+mod alarm_banner;
+mod command_arming;
+mod field_picker;
+mod library_command_selector;
+mod message_editor;
 mod reception_led;
 mod shortcut_widget;
+mod unit_selector;
 
+pub use alarm_banner::AlarmBanner;
+pub use command_arming::CommandArming;
+pub use field_picker::FieldPicker;
+pub use library_command_selector::LibraryCommandSelector;
+pub use message_editor::{field_editor, fields_editor, message_selector, settable_fields};
 pub use reception_led::ReceptionLed;
 pub use shortcut_widget::ShortcutCard;
+pub use unit_selector::UnitSelector;
@@ -0,0 +1,71 @@
+use egui::{Button, Color32, Frame, Margin, Response, RichText, Ui, Widget};
+
+use crate::alarms::AlarmEngine;
+
+/// Maximum number of alarms listed by name in the banner.
+const MAX_LISTED_ALARMS: usize = 3;
+
+/// Banner summarizing the annunciated alarms, colored by the most severe one.
+pub struct AlarmBanner<'a> {
+    engine: &'a mut AlarmEngine,
+}
+
+impl<'a> AlarmBanner<'a> {
+    pub fn new(engine: &'a mut AlarmEngine) -> Self {
+        Self { engine }
+    }
+}
+
+impl Widget for AlarmBanner<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let annunciated = self.engine.annunciated();
+        let Some((_, top, _)) = annunciated.first() else {
+            return ui.allocate_response(egui::Vec2::ZERO, egui::Sense::hover());
+        };
+        let fill = top.severity.color();
+
+        let summary = annunciated
+            .iter()
+            .take(MAX_LISTED_ALARMS)
+            .map(|(_, rule, state)| match &state.last_value {
+                Some(value) => format!("{} ({value})", rule.name),
+                None => rule.name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let others = annunciated.len().saturating_sub(MAX_LISTED_ALARMS);
+        let unacked = annunciated.iter().any(|(_, _, s)| !s.acknowledged);
+
+        let mut ack_all = false;
+        let response = Frame::new()
+            .fill(fill)
+            .inner_margin(Margin::symmetric(8, 4))
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.horizontal(|ui| {
+                    ui.label(
+                        RichText::new(format!("🔔 {}", top.severity))
+                            .strong()
+                            .color(Color32::WHITE),
+                    );
+                    let mut text = summary;
+                    if others > 0 {
+                        text.push_str(&format!(" and {others} more"));
+                    }
+                    ui.label(RichText::new(text).color(Color32::WHITE));
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ack_all = ui
+                            .add_enabled(unacked, Button::new("Ack all"))
+                            .on_hover_text("Acknowledge all the alarms")
+                            .clicked();
+                    });
+                });
+            })
+            .response;
+
+        if ack_all {
+            self.engine.acknowledge_all();
+        }
+        response
+    }
+}
@@ -0,0 +1,293 @@
+use std::time::{Duration, Instant};
+
+use egui::{Button, Color32, Context, Frame, RichText, Stroke, TextEdit, Ui};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    alarms::{AlarmEngineExt, AlarmEvent, AlarmEventKind, Severity},
+    commands::{CommandLibrary, Criticality},
+    mavlink::reflection::MessageMap,
+};
+
+/// Time after which an armed command is disarmed if not confirmed.
+const ARMED_TIMEOUT: Duration = Duration::from_secs(10);
+
+const ARMED_COLOR: Color32 = Color32::from_rgb(0xdc, 0x26, 0x26);
+
+/// Two-step confirmation of critical commands.
+///
+/// A critical command is not sent when invoked: the first invocation arms it,
+/// and it is sent only by a separate confirmation before the countdown expires.
+/// Optionally, the operator has to type a confirmation text to enable it.
+/// Arming and disarming are recorded in the event log.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandArming {
+    #[serde(flatten)]
+    pub criticality: Criticality,
+    /// Criticality the command library sets for the message sent, applied
+    /// also if the own one is not critical
+    #[serde(skip)]
+    required: Criticality,
+
+    /// Never restored from the layout, commands are always armed by the user
+    #[serde(skip)]
+    armed_at: Option<Instant>,
+    #[serde(skip)]
+    typed_text: String,
+}
+
+impl PartialEq for CommandArming {
+    fn eq(&self, other: &Self) -> bool {
+        self.criticality == other.criticality
+    }
+}
+
+impl CommandArming {
+    pub fn is_armed(&self) -> bool {
+        self.armed_at.is_some()
+    }
+
+    /// Requires the criticality the library sets for the message sent to the
+    /// system, so a command critical in the library is critical wherever it
+    /// is sent from.
+    pub fn require_library(
+        &mut self,
+        library: &CommandLibrary,
+        system_id: u8,
+        message: Option<&MessageMap>,
+    ) {
+        self.required = message
+            .map(|m| library.criticality_of(system_id, m.message_id()))
+            .unwrap_or_default();
+    }
+
+    /// Whether the command has to be armed and confirmed, because of its own
+    /// criticality or the one required by the library.
+    pub fn is_critical(&self) -> bool {
+        self.criticality.critical || self.required.critical
+    }
+
+    /// Text to type to enable the confirmation: the own one, or the one
+    /// required by the library if there is none.
+    fn confirmation_text(&self) -> &str {
+        let own = &self.criticality;
+        if (own.critical && !own.confirmation_text.is_empty()) || !self.required.critical {
+            &own.confirmation_text
+        } else {
+            &self.required.confirmation_text
+        }
+    }
+
+    /// Called when the command is invoked. Returns whether it can be sent
+    /// right away, otherwise the command is armed and has to be confirmed.
+    pub fn request(&mut self, ctx: &Context, command: &str) -> bool {
+        if !self.is_critical() {
+            return true;
+        }
+        if !self.is_armed() {
+            warn!("Command {command} armed");
+            self.armed_at = Some(Instant::now());
+            self.typed_text.clear();
+            record_event(
+                ctx,
+                command,
+                AlarmEventKind::Armed,
+                "waiting for confirmation",
+            );
+        }
+        false
+    }
+
+    /// Disarms the command, if armed, logging the reason.
+    pub fn disarm(&mut self, ctx: &Context, command: &str, reason: &str) {
+        if self.armed_at.take().is_some() {
+            info!("Command {command} disarmed: {reason}");
+            self.typed_text.clear();
+            record_event(ctx, command, AlarmEventKind::Disarmed, reason);
+        }
+    }
+
+    /// Shows the armed banner with the countdown and the confirmation
+    /// controls. Returns whether the command was confirmed and has to be sent.
+    pub fn show_armed(&mut self, ui: &mut Ui, command: &str) -> bool {
+        let Some(armed_at) = self.armed_at else {
+            return false;
+        };
+        let Some(remaining) = ARMED_TIMEOUT.checked_sub(armed_at.elapsed()) else {
+            self.disarm(ui.ctx(), command, "timed out");
+            return false;
+        };
+
+        let mut confirmed = false;
+        let mut cancelled = false;
+        // blink the border so the armed state cannot be missed
+        let blink = ui.input(|i| i.time).fract() < 0.5;
+        let stroke_color = if blink { Color32::YELLOW } else { ARMED_COLOR };
+        Frame::canvas(ui.style())
+            .fill(ARMED_COLOR.gamma_multiply(0.35))
+            .stroke(Stroke::new(2.0, stroke_color))
+            .inner_margin(6)
+            .corner_radius(ui.visuals().noninteractive().corner_radius)
+            .show(ui, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.label(
+                        RichText::new(format!("⚠ ARMED: {command}"))
+                            .strong()
+                            .size(15.0)
+                            .color(Color32::WHITE),
+                    );
+                    ui.label(
+                        RichText::new(format!(
+                            "Auto-disarm in {:.0} s",
+                            remaining.as_secs_f32().ceil()
+                        ))
+                        .color(Color32::WHITE),
+                    );
+                    let text_matches = self.confirmation_matches();
+                    let confirmation_text = self.confirmation_text().to_owned();
+                    if !confirmation_text.is_empty() {
+                        ui.add(
+                            TextEdit::singleline(&mut self.typed_text)
+                                .hint_text(format!("Type \"{confirmation_text}\""))
+                                .desired_width(160.0),
+                        );
+                    }
+                    ui.horizontal(|ui| {
+                        let confirm = Button::new(RichText::new("CONFIRM").strong())
+                            .fill(ARMED_COLOR)
+                            .stroke(Stroke::new(1.0, Color32::WHITE));
+                        if ui
+                            .add_enabled(text_matches, confirm)
+                            .on_disabled_hover_text("Type the confirmation text first")
+                            .clicked()
+                        {
+                            confirmed = true;
+                        }
+                        if ui.button("Disarm").clicked() {
+                            cancelled = true;
+                        }
+                    });
+                });
+            });
+
+        if confirmed {
+            self.disarm(ui.ctx(), command, "confirmed");
+        } else if cancelled {
+            self.disarm(ui.ctx(), command, "cancelled");
+        } else {
+            // keep the countdown and the blinking running
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+        confirmed
+    }
+
+    /// Whether the typed text enables the confirmation.
+    fn confirmation_matches(&self) -> bool {
+        let confirmation_text = self.confirmation_text();
+        confirmation_text.is_empty() || self.typed_text == confirmation_text
+    }
+
+    pub fn settings_ui(&mut self, ui: &mut Ui) {
+        self.criticality.settings_ui(ui);
+    }
+}
+
+fn record_event(ctx: &Context, command: &str, kind: AlarmEventKind, reason: &str) {
+    let severity = match kind {
+        AlarmEventKind::Armed => Severity::Warning,
+        _ => Severity::Info,
+    };
+    ctx.alarms()
+        .lock()
+        .record(AlarmEvent::new(kind, severity, command).with_value(reason));
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{CentralPanel, RawInput};
+
+    use super::*;
+
+    fn critical(confirmation_text: &str) -> CommandArming {
+        CommandArming {
+            criticality: Criticality {
+                critical: true,
+                confirmation_text: confirmation_text.to_owned(),
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Shows the armed banner for a frame, returning whether it confirmed.
+    fn show(ctx: &Context, arming: &mut CommandArming) -> bool {
+        let mut confirmed = false;
+        let _ = ctx.run(RawInput::default(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| confirmed = arming.show_armed(ui, "cmd"));
+        });
+        confirmed
+    }
+
+    fn event_kinds(ctx: &Context) -> Vec<AlarmEventKind> {
+        ctx.alarms()
+            .lock()
+            .history()
+            .iter()
+            .map(|e| e.kind)
+            .collect()
+    }
+
+    #[test]
+    fn non_critical_commands_pass_through() {
+        let ctx = Context::default();
+        let mut arming = CommandArming::default();
+        assert!(arming.request(&ctx, "cmd"));
+        assert!(!arming.is_armed());
+        assert!(!show(&ctx, &mut arming));
+        assert!(event_kinds(&ctx).is_empty());
+    }
+
+    #[test]
+    fn armed_commands_disarm_after_the_timeout() {
+        let ctx = Context::default();
+        let mut arming = critical("");
+        assert!(!arming.request(&ctx, "cmd"));
+        assert!(arming.is_armed());
+        // invoking it again does not send it nor restart the countdown
+        let armed_at = arming.armed_at;
+        assert!(!arming.request(&ctx, "cmd"));
+        assert_eq!(arming.armed_at, armed_at);
+
+        assert!(!show(&ctx, &mut arming));
+        assert!(arming.is_armed());
+
+        arming.armed_at = Instant::now().checked_sub(ARMED_TIMEOUT + Duration::from_secs(1));
+        assert!(!show(&ctx, &mut arming));
+        assert!(!arming.is_armed());
+        assert_eq!(
+            event_kinds(&ctx),
+            vec![AlarmEventKind::Armed, AlarmEventKind::Disarmed]
+        );
+    }
+
+    #[test]
+    fn typed_text_has_to_match_the_confirmation() {
+        let ctx = Context::default();
+        let mut arming = critical("FIRE");
+        assert!(!arming.request(&ctx, "cmd"));
+        assert!(!arming.confirmation_matches());
+        arming.typed_text = "fire".to_owned();
+        assert!(!arming.confirmation_matches());
+        arming.typed_text = "FIRE".to_owned();
+        assert!(arming.confirmation_matches());
+
+        // the typed text is cleared when armed again
+        arming.disarm(&ctx, "cmd", "cancelled");
+        assert!(!arming.request(&ctx, "cmd"));
+        assert!(!arming.confirmation_matches());
+
+        // no text to type
+        assert!(critical("").confirmation_matches());
+    }
+}
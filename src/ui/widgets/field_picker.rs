@@ -0,0 +1,97 @@
+use egui::{ComboBox, Id, Response, Ui, Widget};
+
+use crate::{
+    derived::{DERIVED_CHANNELS, DerivedField, TelemetryField},
+    mavlink::reflection::MAVLINK_PROFILE,
+};
+
+/// Pair of combo boxes to choose a field of any message of the profile, or a
+/// derived channel computed on that message.
+pub struct FieldPicker<'a> {
+    id_salt: Id,
+    field: &'a mut Option<TelemetryField>,
+}
+
+impl<'a> FieldPicker<'a> {
+    pub fn new(id_salt: impl std::hash::Hash, field: &'a mut Option<TelemetryField>) -> Self {
+        Self {
+            id_salt: Id::new(id_salt),
+            field,
+        }
+    }
+}
+
+impl Widget for FieldPicker<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let Self { id_salt, field } = self;
+        let id = ui.make_persistent_id(id_salt);
+
+        // The message is remembered while no field is selected
+        let mut msg_id = field
+            .as_ref()
+            .and_then(|f| f.msg_ids().first().copied())
+            .or_else(|| ui.data(|d| d.get_temp::<u32>(id)));
+        let initial_msg_id = msg_id;
+
+        let mut changed = false;
+        let mut response = ui
+            .horizontal(|ui| {
+                let msg_name = msg_id
+                    .and_then(|id| MAVLINK_PROFILE.get_msg(id))
+                    .map(|m| m.name.clone())
+                    .unwrap_or_else(|| "Message".to_owned());
+                ComboBox::from_id_salt(id.with("message"))
+                    .selected_text(msg_name)
+                    .show_ui(ui, |ui| {
+                        for msg in MAVLINK_PROFILE.get_sorted_msgs() {
+                            ui.selectable_value(&mut msg_id, Some(msg.id), &msg.name);
+                        }
+                    });
+                if msg_id != initial_msg_id {
+                    *field = None;
+                    changed = true;
+                }
+
+                let Some(msg_id) = msg_id else {
+                    return;
+                };
+                let mut fields: Vec<TelemetryField> = MAVLINK_PROFILE
+                    .get_fields(msg_id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Into::into)
+                    .collect();
+                {
+                    let registry = DERIVED_CHANNELS.read();
+                    fields.extend(
+                        registry
+                            .valid_channels()
+                            .filter(|c| registry.trigger_ids(&c.name).contains(&msg_id))
+                            .map(|c| DerivedField::new(&c.name).into()),
+                    );
+                }
+                let field_name = field
+                    .as_ref()
+                    .map(|f| f.name())
+                    .unwrap_or_else(|| "Field".to_owned());
+                ComboBox::from_id_salt(id.with("field"))
+                    .selected_text(field_name)
+                    .show_ui(ui, |ui| {
+                        for f in fields {
+                            let name = f.name();
+                            changed |= ui.selectable_value(field, Some(f), name).changed();
+                        }
+                    });
+            })
+            .response;
+
+        match msg_id {
+            Some(msg_id) => ui.data_mut(|d| d.insert_temp(id, msg_id)),
+            None => ui.data_mut(|d| d.remove::<u32>(id)),
+        }
+        if changed {
+            response.mark_changed();
+        }
+        response
+    }
+}
@@ -0,0 +1,52 @@
+use egui::{ComboBox, Id, Response, Ui, Widget};
+
+use crate::commands::COMMAND_LIBRARY;
+
+/// Combo box to bind a command to a definition of the command library, by
+/// name. `None` means that the command is not bound and is configured on its own.
+pub struct LibraryCommandSelector<'a> {
+    id_salt: Id,
+    binding: &'a mut Option<String>,
+}
+
+impl<'a> LibraryCommandSelector<'a> {
+    pub fn new(id_salt: impl std::hash::Hash, binding: &'a mut Option<String>) -> Self {
+        Self {
+            id_salt: Id::new(id_salt),
+            binding,
+        }
+    }
+}
+
+impl Widget for LibraryCommandSelector<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let Self { id_salt, binding } = self;
+        let library = COMMAND_LIBRARY.read();
+        let missing = binding
+            .as_ref()
+            .is_some_and(|name| library.get(name).is_none());
+        let selected_text = match binding.as_deref() {
+            Some(name) if missing => format!("⚠ {name} (missing)"),
+            Some(name) => name.to_owned(),
+            None => "Custom".to_owned(),
+        };
+
+        let mut response = ComboBox::from_id_salt(id_salt)
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                let mut changed = ui.selectable_value(binding, None, "Custom").changed();
+                for command in library.commands() {
+                    changed |= ui
+                        .selectable_value(binding, Some(command.name.clone()), &command.name)
+                        .changed();
+                }
+                changed
+            });
+        if response.inner.unwrap_or(false) {
+            response.response.mark_changed();
+        }
+        response
+            .response
+            .on_hover_text("Command of the library this command is bound to")
+    }
+}
@@ -0,0 +1,195 @@
+use egui::{ComboBox, Response, Ui};
+use mavlink_bindgen::parser::MavType;
+use tracing::warn;
+
+use crate::{
+    error::ErrInstrument,
+    mavlink::{
+        MavMessage, Message,
+        reflection::{
+            FieldLike, FieldLookup, IndexedField, MAVLINK_PROFILE, MapConvertible, MessageMap,
+        },
+    },
+};
+
+/// Combo box to choose the message of a command, optionally among the
+/// telecommands only. When the message changes, its fields are reset to their
+/// default values. Returns whether the message changed.
+pub fn message_selector(ui: &mut Ui, message: &mut Option<MessageMap>, only_tc: bool) -> bool {
+    let mut message_id = message.as_ref().map(|m| m.message_id());
+    let selected_text = message_id
+        .and_then(|id| MAVLINK_PROFILE.get_msg(id))
+        .map(|m| m.name.clone())
+        .unwrap_or("Select a Message".to_string());
+    ComboBox::from_id_salt(ui.id().with("message_selector"))
+        .selected_text(selected_text)
+        .show_ui(ui, |ui| {
+            let mut msgs = MAVLINK_PROFILE.get_sorted_msgs();
+            if only_tc {
+                msgs.retain(|m| m.name.ends_with("_TC"));
+            }
+            for msg in msgs {
+                ui.selectable_value(&mut message_id, Some(msg.id), &msg.name);
+            }
+        });
+
+    // If the message id is changed, update the message
+    if message.as_ref().map(|m| m.message_id()) == message_id {
+        return false;
+    }
+    *message = message_id.map(|id| {
+        MavMessage::default_message_from_id(id)
+            .log_unwrap()
+            .as_map()
+    });
+    true
+}
+
+/// Fields of the message that can be set by the user, i.e. all but the
+/// timestamp, which is set when the message is sent.
+pub fn settable_fields(message_map: &MessageMap) -> Vec<IndexedField> {
+    (0..message_map.field_map().len())
+        .map(|f| {
+            f.to_mav_field(message_map.message_id(), &MAVLINK_PROFILE)
+                .log_unwrap()
+        })
+        .filter(|f| f.field().name.to_lowercase() != "timestamp")
+        .collect()
+}
+
+/// Shows a labelled editor for each settable field of the message.
+pub fn fields_editor(ui: &mut Ui, message_map: &mut MessageMap) {
+    let fields = settable_fields(message_map);
+    if fields.is_empty() {
+        return;
+    }
+    ui.group(|ui| {
+        for field in fields {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}:", &field.field().name.to_uppercase()));
+                field_editor(field, message_map, ui);
+            });
+        }
+    });
+}
+
+/// Editor of the value of a field: a combo box for enums, a drag value for
+/// numbers and a text box for chars.
+pub fn field_editor(field: IndexedField, message_map: &mut MessageMap, ui: &mut Ui) -> Response {
+    // show the combo box for enum types
+    if let Some(enum_type) = &field.field().enumtype {
+        let enum_info = MAVLINK_PROFILE.get_enum(enum_type).log_unwrap();
+        // TODO handle enum advanced options
+        // entries without an explicit value are assigned their position
+        let entries = || {
+            enum_info
+                .entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| (entry.value.unwrap_or(i as u32), &entry.name))
+        };
+        macro_rules! variant_selector_for {
+            ($kind:ty) => {{
+                let value: &mut $kind = message_map.get_mut_field(field).log_unwrap();
+                let selected_text = entries()
+                    .find(|(v, _)| <$kind>::try_from(*v).is_ok_and(|v| v == *value))
+                    .map_or_else(|| format!("unknown ({value})"), |(_, name)| name.clone());
+                ComboBox::from_id_salt(ui.id().with("field_selector"))
+                    .selected_text(selected_text)
+                    .show_ui(ui, |ui| {
+                        // skip the values the field cannot hold
+                        let fitting = entries()
+                            .filter_map(|(v, name)| Some((<$kind>::try_from(v).ok()?, name)));
+                        for (entry_value, name) in fitting {
+                            ui.selectable_value(value, entry_value, name);
+                        }
+                    })
+                    .response
+            }};
+        }
+        match field.field().mavtype {
+            MavType::UInt8 => variant_selector_for!(u8),
+            MavType::UInt16 => variant_selector_for!(u16),
+            MavType::UInt32 => variant_selector_for!(u32),
+            MavType::UInt64 => variant_selector_for!(u64),
+            _ => {
+                // TODO handle other enum types
+                warn!(
+                    "Enum type {} is not supported for field {}",
+                    enum_type,
+                    field.field().name
+                );
+                ui.response()
+            }
+        }
+    } else {
+        // show the drag value for numeric types and text box for char types
+        macro_rules! drag_value_with_range_float {
+            ($_type:ty, $min:expr, $max:expr) => {{
+                let value: &mut $_type = message_map.get_mut_field(field).log_unwrap();
+                ui.add(
+                    egui::DragValue::new(value)
+                        .range($min..=$max)
+                        .clamp_existing_to_range(true)
+                        .min_decimals(0)
+                        .max_decimals(8),
+                )
+            }};
+        }
+        macro_rules! drag_value_with_range {
+            ($_type:ty, $min:expr, $max:expr) => {{
+                let value: &mut $_type = message_map.get_mut_field(field).log_unwrap();
+                ui.add(
+                    egui::DragValue::new(value)
+                        .range($min..=$max)
+                        .clamp_existing_to_range(true),
+                )
+            }};
+        }
+
+        match field.field().mavtype {
+            MavType::UInt8MavlinkVersion | MavType::UInt8 => {
+                drag_value_with_range!(u8, 0, u8::MAX)
+            }
+            MavType::UInt16 => drag_value_with_range!(u16, 0, u16::MAX),
+            MavType::UInt32 => drag_value_with_range!(u32, 0, u32::MAX),
+            MavType::UInt64 => drag_value_with_range!(u64, 0, u64::MAX),
+            MavType::Int8 => drag_value_with_range!(i8, i8::MIN, i8::MAX),
+            MavType::Int16 => {
+                drag_value_with_range!(i16, i16::MIN, i16::MAX)
+            }
+            MavType::Int32 => {
+                drag_value_with_range!(i32, i32::MIN, i32::MAX)
+            }
+            MavType::Int64 => {
+                drag_value_with_range!(i64, i64::MIN, i64::MAX)
+            }
+            MavType::Float => {
+                drag_value_with_range_float!(f32, f32::MIN, f32::MAX)
+            }
+            MavType::Double => {
+                drag_value_with_range_float!(f64, f64::MIN, f64::MAX)
+            }
+            MavType::Char => {
+                let value: &mut char = message_map.get_mut_field(field).log_unwrap();
+                let mut buffer = value.to_string();
+                let res = ui.add(
+                    egui::TextEdit::singleline(&mut buffer)
+                        .hint_text("char")
+                        .char_limit(1),
+                );
+                if let Some(c) = buffer.chars().next() {
+                    *value = c;
+                } else {
+                    warn!("Invalid char input: {}", buffer);
+                    // TODO handle invalid char input (USER ERROR)
+                }
+                res
+            }
+            MavType::Array(_, _) => {
+                warn!("Array types are not supported yet");
+                ui.response()
+            }
+        }
+    }
+}
@@ -1,10 +1,10 @@
 use egui::{
-    Color32, FontId, Frame, KeyboardShortcut, Label, Margin, ModifierNames, RichText, Stroke,
+    Color32, FontId, Frame, Key, KeyboardShortcut, Label, Margin, ModifierNames, RichText, Stroke,
     Widget,
 };
 
 pub struct ShortcutCard {
-    shortcut: KeyboardShortcut,
+    text: String,
     text_size: f32,
     margin: Margin,
     text_color: Option<Color32>,
@@ -13,18 +13,12 @@ pub struct ShortcutCard {
 
 impl Widget for ShortcutCard {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
-        #[cfg(target_os = "macos")]
-        let is_mac = true;
-        #[cfg(not(target_os = "macos"))]
-        let is_mac = false;
-
-        let shortcut_fmt = self.shortcut.format(&ModifierNames::SYMBOLS, is_mac);
         let default_style: &egui::style::WidgetVisuals = ui.style().noninteractive();
         let text_color = self.text_color.unwrap_or(default_style.text_color());
         let fill_color = self.fill_color.unwrap_or(default_style.bg_fill);
         let corner_radius = default_style.corner_radius;
 
-        let number = RichText::new(shortcut_fmt)
+        let number = RichText::new(self.text)
             .color(text_color)
             .font(FontId::monospace(self.text_size));
 
@@ -42,8 +36,23 @@ impl Widget for ShortcutCard {
 
 impl ShortcutCard {
     pub fn new(shortcut: KeyboardShortcut) -> Self {
+        #[cfg(target_os = "macos")]
+        let is_mac = true;
+        #[cfg(not(target_os = "macos"))]
+        let is_mac = false;
+
+        Self::with_text(shortcut.format(&ModifierNames::SYMBOLS, is_mac))
+    }
+
+    /// Card showing a sequence of keys to be pressed one after the other.
+    pub fn sequence(keys: &[Key]) -> Self {
+        let text = keys.iter().map(|k| k.symbol_or_name()).collect::<Vec<_>>();
+        Self::with_text(text.join(" "))
+    }
+
+    fn with_text(text: String) -> Self {
         Self {
-            shortcut,
+            text,
             text_size: 20.,
             margin: Margin::same(5),
             text_color: None,
@@ -0,0 +1,56 @@
+use egui::{ComboBox, Id, Response, Ui, Widget};
+
+use crate::utils::units::UnitOfMeasure;
+
+/// Combo box to choose the display unit of a value, among the units compatible
+/// with its native one. The choice is stored as a unit string, `None` meaning
+/// that the native unit is used.
+pub struct UnitSelector<'a> {
+    id_salt: Id,
+    native: &'a UnitOfMeasure,
+    choice: &'a mut Option<String>,
+}
+
+impl<'a> UnitSelector<'a> {
+    pub fn new(
+        id_salt: impl std::hash::Hash,
+        native: &'a UnitOfMeasure,
+        choice: &'a mut Option<String>,
+    ) -> Self {
+        Self {
+            id_salt: Id::new(id_salt),
+            native,
+            choice,
+        }
+    }
+}
+
+impl Widget for UnitSelector<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let Self {
+            id_salt,
+            native,
+            choice,
+        } = self;
+        let compatible = native.compatible_units();
+        let selected = native.display_unit(choice.as_deref());
+
+        let mut response = ComboBox::from_id_salt(id_salt)
+            .selected_text(selected.to_string())
+            .show_ui(ui, |ui| {
+                let mut changed = ui
+                    .selectable_value(choice, None, format!("{native} (native)"))
+                    .changed();
+                for unit in compatible.iter().filter(|u| *u != native) {
+                    changed |= ui
+                        .selectable_value(choice, Some(unit.to_string()), unit.to_string())
+                        .changed();
+                }
+                changed
+            });
+        if response.inner.unwrap_or(false) {
+            response.response.mark_changed();
+        }
+        response.response.on_hover_text("Display unit")
+    }
+}
@@ -1,9 +1,15 @@
+mod alarm_rules;
+mod command_library;
 #[cfg(feature = "conrig")]
 mod command_switch;
 mod connections;
+mod derived_channels;
 mod layouts;
 
+pub use alarm_rules::AlarmRulesWindow;
+pub use command_library::CommandLibraryWindow;
 #[cfg(feature = "conrig")]
 pub use command_switch::CommandSwitchWindow;
 pub use connections::ConnectionsWindow;
+pub use derived_channels::DerivedChannelsWindow;
 pub use layouts::LayoutManagerWindow;
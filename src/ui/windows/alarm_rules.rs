@@ -0,0 +1,211 @@
+use egui::{Color32, ComboBox, Context, DragValue, Grid, Response, RichText, TextEdit, Ui};
+use strum::IntoEnumIterator;
+
+use crate::{
+    alarms::{AlarmCondition, AlarmEngineExt, AlarmRule, Severity},
+    ui::widgets::FieldPicker,
+};
+
+/// Window used to edit the alarm rules of the current layout.
+#[derive(Default)]
+pub struct AlarmRulesWindow {
+    visible: bool,
+    /// Rules being edited, applied once a text field loses the focus, so that
+    /// the engine is not synced at every keystroke
+    draft: Option<Vec<AlarmRule>>,
+}
+
+impl AlarmRulesWindow {
+    pub fn toggle_open_state(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    #[profiling::function]
+    pub fn show(&mut self, ctx: &Context, rules: &mut Vec<AlarmRule>) {
+        egui::Window::new("Alarm Rules")
+            .collapsible(false)
+            .resizable(true)
+            .open(&mut self.visible)
+            .show(ctx, |ui| {
+                // Apply the edits to a local copy, so that the engine is
+                // synced only once they are committed
+                let mut edited = self.draft.take().unwrap_or_else(|| rules.clone());
+                let mut commit = false;
+                let mut to_remove = None;
+
+                // the engine keys the state of the alarms by rule name
+                let invalid_names: Vec<bool> = edited
+                    .iter()
+                    .map(|rule| {
+                        rule.name.is_empty()
+                            || edited.iter().filter(|r| r.name == rule.name).count() > 1
+                    })
+                    .collect();
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        Grid::new("alarm_rules_grid")
+                            .num_columns(6)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("On");
+                                ui.strong("Name");
+                                ui.strong("Severity");
+                                ui.strong("Field");
+                                ui.strong("Condition");
+                                ui.end_row();
+
+                                for (i, rule) in edited.iter_mut().enumerate() {
+                                    commit |= ui.checkbox(&mut rule.enabled, "").changed();
+                                    let mut name =
+                                        TextEdit::singleline(&mut rule.name).desired_width(120.0);
+                                    if invalid_names[i] {
+                                        name = name.text_color(Color32::RED);
+                                    }
+                                    commit |= ui.add(name).lost_focus();
+                                    ComboBox::from_id_salt(("alarm_severity", i))
+                                        .selected_text(rule.severity.to_string())
+                                        .show_ui(ui, |ui| {
+                                            for severity in Severity::iter() {
+                                                commit |= ui
+                                                    .selectable_value(
+                                                        &mut rule.severity,
+                                                        severity,
+                                                        severity.to_string(),
+                                                    )
+                                                    .changed();
+                                            }
+                                        });
+                                    commit |= ui
+                                        .add(FieldPicker::new(("alarm_field", i), &mut rule.field))
+                                        .changed();
+                                    commit |= condition_ui(ui, i, &mut rule.condition);
+                                    if ui.button("🗑").on_hover_text("Remove").clicked() {
+                                        to_remove = Some(i);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                if let Some(i) = to_remove {
+                    edited.remove(i);
+                    commit = true;
+                }
+
+                ui.separator();
+                if ui.button("Add rule").clicked() {
+                    let name = (edited.len() + 1..)
+                        .map(|n| format!("alarm_{n}"))
+                        .find(|name| edited.iter().all(|r| r.name != *name))
+                        .unwrap_or_default();
+                    edited.push(AlarmRule {
+                        name,
+                        ..Default::default()
+                    });
+                    commit = true;
+                }
+
+                let names_valid = edited.iter().enumerate().all(|(i, r)| {
+                    !r.name.is_empty()
+                        && edited.iter().skip(i + 1).all(|other| other.name != r.name)
+                });
+                if !names_valid {
+                    ui.label(
+                        RichText::new("Alarm names must be unique and not empty")
+                            .color(Color32::RED),
+                    );
+                }
+
+                if edited == *rules {
+                    return;
+                }
+                if commit && names_valid {
+                    *rules = edited;
+                    ui.ctx().alarms().lock().sync(rules);
+                } else {
+                    self.draft = Some(edited);
+                }
+            });
+    }
+}
+
+/// Shows the editor of the condition, returning whether an edit was committed.
+fn condition_ui(ui: &mut Ui, index: usize, condition: &mut AlarmCondition) -> bool {
+    let mut commit = false;
+    ui.horizontal(|ui| {
+        ComboBox::from_id_salt(("alarm_condition", index))
+            .selected_text(condition.to_string())
+            .show_ui(ui, |ui| {
+                for kind in AlarmCondition::iter() {
+                    let selected =
+                        std::mem::discriminant(condition) == std::mem::discriminant(&kind);
+                    if ui.selectable_label(selected, kind.to_string()).clicked() && !selected {
+                        *condition = kind;
+                        commit = true;
+                    }
+                }
+            });
+
+        match condition {
+            AlarmCondition::Threshold {
+                low,
+                high,
+                hysteresis,
+            } => {
+                commit |= optional_value(ui, "low", low);
+                commit |= optional_value(ui, "high", high);
+                ui.label("hyst.");
+                commit |=
+                    committed(ui.add(DragValue::new(hysteresis).speed(0.1).range(0.0..=f64::MAX)));
+            }
+            AlarmCondition::RateOfChange {
+                max_rate,
+                hysteresis,
+            } => {
+                ui.label("max |d/dt|");
+                commit |= committed(ui.add(DragValue::new(max_rate).speed(0.1).suffix("/s")));
+                ui.label("hyst.");
+                commit |=
+                    committed(ui.add(DragValue::new(hysteresis).speed(0.1).range(0.0..=f64::MAX)));
+            }
+            AlarmCondition::Stale { timeout } => {
+                ui.label("timeout");
+                commit |= committed(
+                    ui.add(
+                        DragValue::new(timeout)
+                            .speed(0.1)
+                            .range(0.1..=3600.0)
+                            .suffix(" s"),
+                    ),
+                );
+            }
+            AlarmCondition::EnumEquals { entry } => {
+                ui.label("entry");
+                commit |= ui
+                    .add(TextEdit::singleline(entry).desired_width(120.0))
+                    .lost_focus();
+            }
+        }
+    });
+    commit
+}
+
+fn optional_value(ui: &mut Ui, label: &str, value: &mut Option<f64>) -> bool {
+    let mut enabled = value.is_some();
+    let mut commit = false;
+    if ui.checkbox(&mut enabled, label).changed() {
+        *value = enabled.then_some(0.0);
+        commit = true;
+    }
+    if let Some(value) = value {
+        commit |= committed(ui.add(DragValue::new(value).speed(0.1)));
+    }
+    commit
+}
+
+/// Whether the edit of a drag value is over, either by dragging or by typing.
+fn committed(response: Response) -> bool {
+    response.drag_stopped() || response.lost_focus()
+}
@@ -0,0 +1,207 @@
+use egui::{Button, Checkbox, Color32, Context, DragValue, RichText, ScrollArea, TextEdit, Ui};
+use tracing::error;
+
+use crate::{
+    commands::{COMMAND_LIBRARY, CommandDefinition, MAX_EDITABLE_FIELDS},
+    ui::widgets::{field_editor, message_selector, settable_fields},
+};
+
+/// Window used to edit the command library, shared by all the layouts.
+#[derive(Default)]
+pub struct CommandLibraryWindow {
+    visible: bool,
+    /// Definitions being edited, applied to the library when saved
+    draft: Option<Vec<CommandDefinition>>,
+    selected: Option<usize>,
+    show_only_tc: bool,
+    save_error: Option<String>,
+}
+
+impl CommandLibraryWindow {
+    pub fn toggle_open_state(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    #[profiling::function]
+    pub fn show(&mut self, ctx: &Context) {
+        let mut visible = self.visible;
+        egui::Window::new("Command Library")
+            .collapsible(false)
+            .resizable(true)
+            .open(&mut visible)
+            .show(ctx, |ui| self.library_ui(ui));
+        self.visible = visible;
+    }
+
+    fn library_ui(&mut self, ui: &mut Ui) {
+        let draft = self
+            .draft
+            .get_or_insert_with(|| COMMAND_LIBRARY.read().commands().to_vec());
+
+        ui.label(
+            "Commands defined here can be bound by name to command panes and to the \
+            command switch, which are updated whenever the definition changes.",
+        );
+        ui.separator();
+
+        ui.horizontal_top(|ui| {
+            // List of the commands
+            ui.vertical(|ui| {
+                ui.set_width(160.0);
+                ScrollArea::vertical()
+                    .id_salt("command_library_list")
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for (i, command) in draft.iter().enumerate() {
+                            let duplicated =
+                                draft.iter().filter(|c| c.name == command.name).count() > 1;
+                            let mut text = RichText::new(&command.name);
+                            if duplicated {
+                                text = text.color(Color32::RED);
+                            }
+                            if ui
+                                .selectable_label(self.selected == Some(i), text)
+                                .clicked()
+                            {
+                                self.selected = Some(i);
+                            }
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("Add").clicked() {
+                        draft.push(CommandDefinition::new(format!(
+                            "command_{}",
+                            draft.len() + 1
+                        )));
+                        self.selected = Some(draft.len() - 1);
+                    }
+                    let selected = self.selected.filter(|&i| i < draft.len());
+                    if ui
+                        .add_enabled(selected.is_some(), Button::new("🗑"))
+                        .on_hover_text("Remove")
+                        .clicked()
+                    {
+                        if let Some(i) = selected {
+                            draft.remove(i);
+                        }
+                        self.selected = None;
+                    }
+                });
+            });
+            ui.separator();
+
+            // Editor of the selected command
+            ui.vertical(|ui| match self.selected.and_then(|i| draft.get_mut(i)) {
+                Some(command) => definition_editor(ui, command, &mut self.show_only_tc),
+                None => {
+                    ui.label(RichText::new("Select a command to edit it").italics());
+                }
+            });
+        });
+
+        ui.separator();
+        let library = COMMAND_LIBRARY.read();
+        let changed = library.commands() != draft.as_slice();
+        drop(library);
+        let names_valid = draft.iter().enumerate().all(|(i, c)| {
+            !c.name.is_empty() && draft.iter().skip(i + 1).all(|other| other.name != c.name)
+        });
+        let mut revert = false;
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(changed && names_valid, Button::new("Save"))
+                .on_disabled_hover_text("Command names must be unique and not empty")
+                .clicked()
+            {
+                self.save_error = COMMAND_LIBRARY
+                    .write()
+                    .update(draft.clone())
+                    .map_err(|e| {
+                        error!("Unable to save the command library: {}", e);
+                        e.to_string()
+                    })
+                    .err();
+            }
+            revert = ui.add_enabled(changed, Button::new("Revert")).clicked();
+            if changed {
+                ui.label(RichText::new("Unsaved changes").italics());
+            }
+        });
+        if revert {
+            self.draft = None;
+            self.save_error = None;
+        }
+        if let Some(error) = &self.save_error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+    }
+}
+
+fn definition_editor(ui: &mut Ui, command: &mut CommandDefinition, show_only_tc: &mut bool) {
+    ui.horizontal(|ui| {
+        ui.label("Name:");
+        ui.add(TextEdit::singleline(&mut command.name).desired_width(160.0));
+    });
+    ui.horizontal(|ui| {
+        let label = ui.label("System ID:");
+        ui.add(DragValue::new(&mut command.system_id).range(1..=255))
+            .labelled_by(label.id);
+    });
+    command.criticality.settings_ui(ui);
+    ui.separator();
+
+    ui.checkbox(show_only_tc, "Show only TC messages");
+    if message_selector(ui, &mut command.message, *show_only_tc) {
+        command.editable_fields.clear();
+    }
+
+    // Default value of each field, and whether it can be changed when sending
+    let Some(message_map) = command.message.as_mut() else {
+        return;
+    };
+    let fields = settable_fields(message_map);
+    if fields.is_empty() {
+        return;
+    }
+    ui.label("Fields (checked ones can be changed before sending):");
+    let editable_count = command.editable_fields.len();
+    if editable_count > MAX_EDITABLE_FIELDS {
+        ui.label(
+            RichText::new(format!(
+                "At most {MAX_EDITABLE_FIELDS} fields can be editable, uncheck {}",
+                editable_count - MAX_EDITABLE_FIELDS
+            ))
+            .color(Color32::RED),
+        );
+    }
+    ui.group(|ui| {
+        for field in fields {
+            ui.horizontal(|ui| {
+                let mut editable = command.editable_fields.contains(&field.id());
+                // one number key selects each editable field in the command switch
+                let can_change = editable || editable_count < MAX_EDITABLE_FIELDS;
+                if ui
+                    .add_enabled(
+                        can_change,
+                        Checkbox::new(
+                            &mut editable,
+                            format!("{}:", field.field().name.to_uppercase()),
+                        ),
+                    )
+                    .on_hover_text("Editable before sending")
+                    .on_disabled_hover_text(format!(
+                        "At most {MAX_EDITABLE_FIELDS} fields can be editable"
+                    ))
+                    .changed()
+                {
+                    if editable {
+                        command.editable_fields.insert(field.id());
+                    } else {
+                        command.editable_fields.remove(&field.id());
+                    }
+                }
+                field_editor(field, message_map, ui);
+            });
+        }
+    });
+}
@@ -1,23 +1,23 @@
 mod command;
 mod configurable;
 mod direct;
+mod keys;
 mod state;
 
-use std::{
-    collections::HashSet,
-    time::{Duration, Instant},
-};
+use std::collections::HashSet;
 
 use egui::{
-    Color32, Frame, Key, Label, Margin, ModifierNames, Modifiers, Response, RichText, Sense,
-    Stroke, Ui, UiBuilder, Vec2, Widget, response::Flags,
+    Color32, Frame, Key, Label, Margin, Modifiers, Response, RichText, Sense, Stroke, Ui,
+    UiBuilder, Vec2, Widget, response::Flags,
 };
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::{
     error::ErrInstrument,
     mavlink::{MavHeader, MavMessage, reflection::MapConvertible},
+    tc_log::{PendingReply, ReplyKind, ReplyTimeout, TcReply},
     ui::{
         shortcuts::{ShortcutAppState, ShortcutHandlerExt, ShortcutLease},
         widgets::ShortcutCard,
@@ -27,15 +27,28 @@ use crate::{
 
 use command::{BaseCommand, Command};
 
-const MAXIMUM_REPLY_TIMEOUT: Duration = Duration::from_secs(3);
-
-#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct CommandSwitchWindow {
     commands: Vec<Command>,
     #[serde(skip)]
     state: state::StateManager,
     #[serde(skip)]
     messages_to_send: Vec<(MavHeader, MavMessage)>,
+    /// Time to wait for the reply to a command
+    #[serde(default)]
+    reply_timeout: ReplyTimeout,
+    /// Keys typed so far after opening the command switch
+    #[serde(skip)]
+    typed_keys: Vec<Key>,
+    /// Whether the commands loaded from the layout were checked
+    #[serde(skip)]
+    validated: bool,
+}
+
+impl PartialEq for CommandSwitchWindow {
+    fn eq(&self, other: &Self) -> bool {
+        self.commands == other.commands && self.reply_timeout == other.reply_timeout
+    }
 }
 
 impl CommandSwitchWindow {
@@ -44,6 +57,11 @@ impl CommandSwitchWindow {
     }
 
     pub fn show(&mut self, ui: &mut Ui) {
+        if !self.validated {
+            self.validate_commands();
+            self.validated = true;
+        }
+
         let slash_pressed = ui
             .ctx()
             .shortcuts()
@@ -57,22 +75,22 @@ impl CommandSwitchWindow {
             })
             .unwrap_or_default();
         if !self.commands.is_empty() && slash_pressed {
-            // First reset the reply state of all commands
+            // First reset the reply state of all commands, and disarm them
             for cmd in self.commands.iter_mut() {
-                cmd.base_mut().reply_state.reset();
+                let base = cmd.base_mut();
+                base.reply_state.reset();
+                base.arming
+                    .disarm(ui.ctx(), &base.name, "command switch toggled");
             }
             // Then toggle the visibility of the command switch window
+            self.typed_keys.clear();
             self.state.switch_command();
         }
+        // Pick up the changes of the library commands
+        for cmd in self.commands.iter_mut() {
+            cmd.sync_with_library();
+        }
         if self.state.is_command_switch() {
-            // Update the the state of the expired commands
-            for cmd in self.commands.iter_mut() {
-                if let ReplyState::WaitingForReply(instant) = cmd.base().reply_state {
-                    if instant.elapsed() > MAXIMUM_REPLY_TIMEOUT {
-                        cmd.base_mut().reply_state = ReplyState::TimeoutNack;
-                    }
-                }
-            }
             // Show the command switch window
             show_command_switch_window(ui, self);
         } else {
@@ -80,6 +98,45 @@ impl CommandSwitchWindow {
         }
     }
 
+    /// Fixes the commands loaded from a layout: duplicated ids are reassigned,
+    /// and missing, invalid or conflicting key sequences are replaced.
+    fn validate_commands(&mut self) {
+        let mut ids = HashSet::new();
+        let mut next_id = self
+            .commands
+            .iter()
+            .map(|c| c.base().id + 1)
+            .max()
+            .unwrap_or(0);
+        let mut used: Vec<Vec<Key>> = Vec::new();
+        for cmd in self.commands.iter_mut() {
+            let base = cmd.base_mut();
+            if !ids.insert(base.id) {
+                warn!(
+                    "Duplicated id {} of command {}, reassigned",
+                    base.id, base.name
+                );
+                base.id = next_id;
+                ids.insert(next_id);
+                next_id += 1;
+            }
+            let mut sequence = base
+                .keys
+                .take()
+                .or_else(|| keys::legacy_sequence(base.id))
+                .unwrap_or_default();
+            if !keys::is_valid(&sequence) || used.iter().any(|u| keys::conflicts(u, &sequence)) {
+                warn!("Invalid key sequence of command {}, reassigned", base.name);
+                sequence = keys::next_free_sequence(&used);
+                if sequence.is_empty() {
+                    warn!("No free key sequence left for command {}", base.name);
+                }
+            }
+            used.push(sequence.clone());
+            base.keys = Some(sequence);
+        }
+    }
+
     pub fn consume_messages_to_send(&mut self) -> Vec<(MavHeader, MavMessage)> {
         if self.messages_to_send.is_empty() {
             return vec![];
@@ -87,30 +144,28 @@ impl CommandSwitchWindow {
         std::mem::take(&mut self.messages_to_send)
     }
 
-    pub fn handle_acknowledgements(&mut self, messages: Vec<&MavMessage>) {
-        let mut acks_ids = HashSet::new();
-        let mut nacks_ids = HashSet::new();
-        for message in messages {
-            match message {
-                MavMessage::ACK_TM(ack) => {
-                    acks_ids.insert(ack.recv_msgid as u32);
-                }
-                MavMessage::NACK_TM(nack) => {
-                    nacks_ids.insert(nack.recv_msgid as u32);
-                }
-                _ => continue,
-            }
-        }
-        for cmd in self.commands.iter_mut() {
-            let base = cmd.base_mut();
-            if let (ReplyState::WaitingForReply(_), Some(msg)) = (base.reply_state, &base.message) {
-                if acks_ids.contains(&msg.message_id()) {
-                    base.reply_state = ReplyState::ExplicitAck;
-                } else if nacks_ids.contains(&msg.message_id()) {
-                    base.reply_state = ReplyState::ExplicitNack;
-                }
-            }
-        }
+    /// Updates the reply state of the command waiting for the delivered
+    /// outcome. Returns its name if it was rejected, with the reply.
+    pub fn handle_reply(
+        &mut self,
+        pending: &PendingReply,
+        reply: &TcReply,
+    ) -> Option<(String, ReplyKind)> {
+        let base = self
+            .commands
+            .iter_mut()
+            .map(Command::base_mut)
+            .find(|base| base.reply_state == ReplyState::WaitingForReply(*pending))?;
+        base.reply_state = match reply.kind() {
+            Some(ReplyKind::Ack) => ReplyState::ExplicitAck,
+            Some(ReplyKind::Nack { .. }) => ReplyState::ExplicitNack,
+            Some(ReplyKind::Wack { .. }) => ReplyState::ExplicitWack,
+            None => ReplyState::TimeoutNack,
+        };
+        reply
+            .kind()
+            .filter(ReplyKind::is_rejection)
+            .map(|kind| (base.name.clone(), kind))
     }
 }
 
@@ -119,6 +174,9 @@ fn show_command_switch_window(ui: &mut Ui, window: &mut CommandSwitchWindow) {
         state,
         commands,
         messages_to_send,
+        typed_keys,
+        reply_timeout,
+        ..
     } = window;
     let mut visible = state.is_command_switch();
     egui::Window::new("Command Switch")
@@ -148,10 +206,17 @@ fn show_command_switch_window(ui: &mut Ui, window: &mut CommandSwitchWindow) {
                 // otherwise we end up inserting weird behavior in text edit of fields due to same id memory management
                 // done by egui
                 ui.scope_builder(UiBuilder::new().id_salt(cmd.base.id), |ui| {
-                    cmd.show_operative_parameters(state, messages_to_send, ui);
+                    cmd.show_operative_parameters(state, messages_to_send, *reply_timeout, ui);
                 });
             } else {
-                show_switch_list(state, commands, messages_to_send, ui);
+                show_switch_list(
+                    state,
+                    commands,
+                    messages_to_send,
+                    *reply_timeout,
+                    typed_keys,
+                    ui,
+                );
             }
         });
     if !visible {
@@ -175,63 +240,162 @@ fn show_switch_list(
     state: &mut state::StateManager,
     commands: &mut [Command],
     messages_to_send: &mut Vec<(MavHeader, MavMessage)>,
+    reply_timeout: ReplyTimeout,
+    typed_keys: &mut Vec<Key>,
     ui: &mut Ui,
 ) {
-    for cmd in commands.iter_mut() {
-        // if actionated {
-        if command_btn(ui, cmd).clicked() {
-            match cmd {
-                Command::Configurable(cmd) => {
-                    // change state to show the configurable command dialog
-                    state.set_configurable_command_dialog();
-                    cmd.parameters_window_visible = true;
+    // Capture the next key of the sequences starting with the typed ones, or
+    // backspace to go back
+    let next_keys: Vec<Key> = commands
+        .iter()
+        .map(|cmd| cmd.base())
+        .filter(|base| base.reply_state.is_enabled())
+        .filter_map(|base| {
+            let keys = base.keys();
+            keys.starts_with(typed_keys)
+                .then(|| keys.get(typed_keys.len()).copied())
+                .flatten()
+        })
+        .unique()
+        .collect();
+    let pressed = ui.ctx().shortcuts().lock().capture_actions(
+        ui.id().with("shortcut_lease"),
+        Box::new(CommandSwitchLease),
+        |s| {
+            if !s.is_operation_mode() {
+                return vec![];
+            }
+            let mut actions: Vec<_> = next_keys
+                .iter()
+                .map(|&key| (Modifiers::NONE, key, Some(key)))
+                .collect();
+            if !typed_keys.is_empty() {
+                actions.push((Modifiers::NONE, Key::Backspace, None));
+            }
+            actions
+        },
+    );
+    match pressed {
+        Some(Some(key)) => typed_keys.push(key),
+        Some(None) => {
+            typed_keys.pop();
+        }
+        None => {}
+    }
+    // The command whose sequence was completed is invoked
+    let invoked = commands
+        .iter()
+        .position(|cmd| !typed_keys.is_empty() && cmd.base().keys() == typed_keys.as_slice());
+    if invoked.is_some() {
+        typed_keys.clear();
+    }
+
+    if !typed_keys.is_empty() {
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(format!("/ {} _", keys::format_sequence(typed_keys)))
+                    .monospace()
+                    .strong(),
+            );
+            ui.label(RichText::new("(backspace to go back)").weak());
+        });
+        ui.separator();
+    }
+
+    egui::ScrollArea::vertical()
+        .max_height(500.0)
+        .show(ui, |ui| {
+            let mut last_group = None;
+            for i in grouped_order(commands) {
+                let cmd = &mut commands[i];
+                let group = &cmd.base().group;
+                if last_group != Some(group.clone()) {
+                    if !group.is_empty() {
+                        ui.label(RichText::new(group).strong());
+                    }
+                    last_group = Some(group.clone());
                 }
-                Command::Direct(cmd) => {
-                    let BaseCommand {
-                        system_id,
-                        message,
-                        reply_state,
-                        ..
-                    } = &mut cmd.base;
-                    if let Some(map) = message {
-                        // append the message to the list of messages to send
-                        let header = MavHeader {
-                            system_id: *system_id,
-                            ..Default::default()
-                        };
-                        messages_to_send
-                            .push((header, MavMessage::from_map(map.clone()).log_unwrap()));
-                        // Update the reply state to waiting for reply
-                        *reply_state = ReplyState::WaitingForReply(Instant::now());
+                let matching = cmd.base().keys().starts_with(typed_keys);
+                if command_btn(ui, cmd, matching, invoked == Some(i)).clicked() {
+                    match cmd {
+                        Command::Configurable(cmd) => {
+                            // change state to show the configurable command dialog
+                            state.set_configurable_command_dialog();
+                            cmd.parameters_window_visible = true;
+                        }
+                        Command::Direct(cmd) => {
+                            let base = &mut cmd.base;
+                            if base.arming.request(ui.ctx(), &base.name) {
+                                send_direct_command(base, messages_to_send, reply_timeout);
+                            }
+                        }
+                    }
+                }
+                // critical direct commands are sent only once confirmed
+                if let Command::Direct(cmd) = cmd {
+                    let base = &mut cmd.base;
+                    let confirmed = ui
+                        .scope_builder(UiBuilder::new().id_salt(base.id), |ui| {
+                            base.arming.show_armed(ui, &base.name)
+                        })
+                        .inner;
+                    if confirmed {
+                        send_direct_command(base, messages_to_send, reply_timeout);
                     }
                 }
             }
-        }
+        });
+}
+
+/// Indices of the commands, with the ones of the same group next to each
+/// other, in order of first appearance.
+fn grouped_order(commands: &[Command]) -> Vec<usize> {
+    let groups: Vec<&String> = commands.iter().map(|c| &c.base().group).unique().collect();
+    groups
+        .into_iter()
+        .flat_map(|group| {
+            commands
+                .iter()
+                .enumerate()
+                .filter(move |(_, c)| c.base().group == *group)
+                .map(|(i, _)| i)
+        })
+        .collect()
+}
+
+fn send_direct_command(
+    base: &mut BaseCommand,
+    messages_to_send: &mut Vec<(MavHeader, MavMessage)>,
+    reply_timeout: ReplyTimeout,
+) {
+    let BaseCommand {
+        system_id,
+        message,
+        reply_state,
+        ..
+    } = base;
+    if let Some(map) = message {
+        // append the message to the list of messages to send
+        let mut header = MavHeader {
+            system_id: *system_id,
+            ..Default::default()
+        };
+        let message = MavMessage::from_map(map.clone()).log_unwrap();
+        // Update the reply state to waiting for reply
+        *reply_state =
+            ReplyState::WaitingForReply(PendingReply::send(&mut header, &message, reply_timeout));
+        messages_to_send.push((header, message));
     }
 }
 
-fn command_btn(ui: &mut Ui, cmd: &Command) -> Response {
-    let shortcut = cmd.base().shortcut_comb()[1];
-    let key = shortcut.logical_key;
-    let shortcut_detected = ui
-        .ctx()
-        .shortcuts()
-        .lock()
-        .capture_actions(
-            ui.id().with("shortcut_lease"),
-            Box::new(CommandSwitchLease),
-            |s| {
-                if s.is_operation_mode() && cmd.base().reply_state.is_enabled() {
-                    vec![(Modifiers::NONE, key, true)]
-                } else {
-                    vec![]
-                }
-            },
-        )
-        .unwrap_or_default();
+/// Button of a command. `matching` tells whether its sequence starts with the
+/// typed keys, and `shortcut_detected` whether it was just completed.
+fn command_btn(ui: &mut Ui, cmd: &Command, matching: bool, shortcut_detected: bool) -> Response {
+    let id = cmd.base().id;
+    let enabled = cmd.base().reply_state.is_enabled() && matching;
     let mut res = ui
-        .add_enabled_ui(cmd.base().reply_state.is_enabled(), |ui| {
-            ui.scope_builder(UiBuilder::new().id_salt(key).sense(Sense::click()), |ui| {
+        .add_enabled_ui(enabled, |ui| {
+            ui.scope_builder(UiBuilder::new().id_salt(id).sense(Sense::click()), |ui| {
                 let mut visuals = *ui.style().interact(&ui.response());
 
                 // override the visuals if the button is pressed
@@ -244,14 +408,16 @@ fn command_btn(ui: &mut Ui, cmd: &Command) -> Response {
                 let valid_fill = uvis.bg_fill.lerp_to_gamma(Color32::GREEN, 0.3);
                 let missing_fill = uvis.bg_fill.lerp_to_gamma(Color32::YELLOW, 0.3);
                 let invalid_fill = uvis.bg_fill.lerp_to_gamma(Color32::RED, 0.3);
+                let armed = cmd.base().arming.is_armed();
                 let bg_fill = match cmd.base().reply_state {
+                    _ if armed => Color32::RED,
                     ReplyState::ReadyForInvocation | ReplyState::WaitingForReply(_) => uvis.bg_fill,
                     ReplyState::ExplicitAck => valid_fill,
                     ReplyState::ExplicitNack => invalid_fill,
-                    ReplyState::TimeoutNack => missing_fill,
+                    ReplyState::ExplicitWack | ReplyState::TimeoutNack => missing_fill,
                 };
 
-                let shortcut_card = ShortcutCard::new(shortcut)
+                let shortcut_card = ShortcutCard::sequence(cmd.base().keys())
                     .text_color(vis.strong_text_color())
                     .fill_color(vis.gray_out(bg_fill))
                     .margin(Margin::symmetric(5, 0))
@@ -262,11 +428,13 @@ fn command_btn(ui: &mut Ui, cmd: &Command) -> Response {
                     .inner_margin(Margin::symmetric(5, 0))
                     .corner_radius(ui.style().noninteractive().corner_radius);
                 let reply_tag_text = match cmd.base().reply_state {
+                    _ if armed => "ARMED",
                     ReplyState::ReadyForInvocation => "",
                     ReplyState::WaitingForReply(_) => "WAITING",
                     ReplyState::ExplicitAck => "ACK",
                     ReplyState::TimeoutNack => "TIMEOUT",
                     ReplyState::ExplicitNack => "NACK",
+                    ReplyState::ExplicitWack => "WACK",
                 };
 
                 Frame::canvas(ui.style())
@@ -290,7 +458,9 @@ fn command_btn(ui: &mut Ui, cmd: &Command) -> Response {
                             .selectable(false)
                             .ui(ui);
                             ui.add_space(1.);
-                            if !matches!(cmd.base().reply_state, ReplyState::ReadyForInvocation) {
+                            if armed
+                                || !matches!(cmd.base().reply_state, ReplyState::ReadyForInvocation)
+                            {
                                 reply_tag.show(ui, |ui| {
                                     Label::new(
                                         RichText::new(reply_tag_text)
@@ -316,7 +486,10 @@ fn command_btn(ui: &mut Ui, cmd: &Command) -> Response {
 
 fn show_command_catalog(ui: &mut Ui, window: &mut CommandSwitchWindow) {
     let CommandSwitchWindow {
-        state, commands, ..
+        state,
+        commands,
+        reply_timeout,
+        ..
     } = window;
     let mut visible = state.is_catalog();
     egui::Window::new("Command Switch")
@@ -326,17 +499,20 @@ fn show_command_catalog(ui: &mut Ui, window: &mut CommandSwitchWindow) {
         .collapsible(false)
         .open(&mut visible)
         .show(ui.ctx(), |ui| {
-            if commands
+            let shown = commands
                 .iter()
-                .all(|cmd| !cmd.base().settings_window_visible)
-            {
-                show_catalog_list(ui, commands);
+                .position(|cmd| cmd.base().settings_window_visible);
+            if let Some(i) = shown {
+                // sequences of the other commands, to check for conflicts
+                let others: Vec<(String, Vec<Key>)> = commands
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, cmd)| (cmd.base().name.clone(), cmd.base().keys().to_vec()))
+                    .collect();
+                commands[i].show_settings(ui, &others);
             } else {
-                let cmd = commands
-                    .iter_mut()
-                    .find(|cmd| cmd.base().settings_window_visible)
-                    .log_unwrap();
-                cmd.show_settings(ui);
+                show_catalog_list(ui, commands, reply_timeout);
             }
         });
     if !visible {
@@ -344,31 +520,54 @@ fn show_command_catalog(ui: &mut Ui, window: &mut CommandSwitchWindow) {
     }
 }
 
-fn show_catalog_list(ui: &mut Ui, commands: &mut Vec<Command>) {
-    for cmd in commands.iter_mut() {
-        #[cfg(target_os = "macos")]
-        let is_mac = true;
-        #[cfg(not(target_os = "macos"))]
-        let is_mac = false;
-        let shortcut_text = cmd
-            .base()
-            .shortcut_comb()
-            .into_iter()
-            .map(|s| s.format(&ModifierNames::SYMBOLS, is_mac))
-            .join(" ");
-        let text = RichText::new(format!("[{}] {}", shortcut_text, &cmd.base().name)).size(17.0);
-        let cmd_btn = ui.add_sized(Vec2::new(300.0, 10.0), egui::Button::new(text));
-        if cmd_btn.clicked() {
-            cmd.base_mut().settings_window_visible = true;
-        }
-    }
-    if commands.len() < 9 {
-        let plus_btn = ui.add_sized(
-            Vec2::new(300.0, 10.0),
-            egui::Button::new(RichText::new("+").size(17.0)),
-        );
-        if plus_btn.clicked() {
-            commands.push(Command::direct(commands.len() + 1));
+fn show_catalog_list(ui: &mut Ui, commands: &mut Vec<Command>, reply_timeout: &mut ReplyTimeout) {
+    egui::ScrollArea::vertical()
+        .max_height(500.0)
+        .show(ui, |ui| {
+            let mut last_group = None;
+            for i in grouped_order(commands) {
+                let cmd = &mut commands[i];
+                let group = &cmd.base().group;
+                if last_group != Some(group.clone()) {
+                    if !group.is_empty() {
+                        ui.label(RichText::new(group).strong());
+                    }
+                    last_group = Some(group.clone());
+                }
+                let text = RichText::new(format!(
+                    "[{}] {}",
+                    cmd.base().shortcut_text(),
+                    &cmd.base().name
+                ))
+                .size(17.0);
+                let cmd_btn = ui.add_sized(Vec2::new(300.0, 10.0), egui::Button::new(text));
+                if cmd_btn.clicked() {
+                    cmd.base_mut().settings_window_visible = true;
+                }
+            }
+        });
+    let plus_btn = ui.add_sized(
+        Vec2::new(300.0, 10.0),
+        egui::Button::new(RichText::new("+").size(17.0)),
+    );
+    if plus_btn.clicked() {
+        let id = commands
+            .iter()
+            .map(|cmd| cmd.base().id + 1)
+            .max()
+            .unwrap_or(1);
+        let used: Vec<Vec<Key>> = commands
+            .iter()
+            .map(|cmd| cmd.base().keys().to_vec())
+            .collect();
+        let mut cmd = Command::direct(id);
+        let sequence = keys::next_free_sequence(&used);
+        if sequence.is_empty() {
+            warn!("No free key sequence left for command {id}, bind one in its settings");
         }
+        cmd.base_mut().keys = Some(sequence);
+        commands.push(cmd);
     }
+    ui.separator();
+    ui.add(reply_timeout);
 }
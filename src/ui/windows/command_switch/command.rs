@@ -1,21 +1,36 @@
-use std::time::Instant;
-
-use crate::mavlink::reflection::MessageMap;
-use egui::{Key, KeyboardShortcut, Modifiers, RichText, Ui};
+use crate::{
+    commands::COMMAND_LIBRARY, mavlink::reflection::MessageMap, tc_log::PendingReply,
+    ui::widgets::CommandArming,
+};
+use egui::{Color32, Key, RichText, TextEdit, Ui};
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use super::{
     configurable::{self},
     direct::{self},
+    keys,
 };
 
 /// Command Base on which all commands are built upon, containing common fields
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BaseCommand {
     pub id: usize,
     pub name: String,
     pub system_id: u8,
     pub message: Option<MessageMap>,
+    /// Key sequence invoking the command, `None` in layouts saved before
+    /// sequences were configurable
+    #[serde(default)]
+    pub keys: Option<Vec<Key>>,
+    /// Group the command is listed under in the command switch
+    #[serde(default)]
+    pub group: String,
+    #[serde(default)]
+    pub arming: CommandArming,
+    /// Name of the library command this command is bound to, if any
+    #[serde(default)]
+    pub library_command: Option<String>,
 
     // UI SETTINGS
     #[serde(skip)]
@@ -24,6 +39,22 @@ pub struct BaseCommand {
     pub settings_window_visible: bool,
     #[serde(skip)]
     pub show_only_tc: bool,
+    /// Revision of the command library the bound command was copied from
+    #[serde(skip)]
+    pub library_revision: u64,
+}
+
+impl PartialEq for BaseCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.name == other.name
+            && self.system_id == other.system_id
+            && self.message == other.message
+            && self.keys == other.keys
+            && self.group == other.group
+            && self.arming == other.arming
+            && self.library_command == other.library_command
+    }
 }
 
 impl BaseCommand {
@@ -33,34 +64,30 @@ impl BaseCommand {
             name: String::from("New Command"),
             system_id: 1,
             message: None,
+            keys: Some(Vec::new()),
+            group: String::new(),
+            arming: CommandArming::default(),
+            library_command: None,
             reply_state: ReplyState::default(),
             settings_window_visible: false,
             show_only_tc: false,
+            library_revision: 0,
         }
     }
 
-    pub fn shortcut_keys(&self) -> Vec<Key> {
-        let key = match self.id {
-            0 => Key::Num0,
-            1 => Key::Num1,
-            2 => Key::Num2,
-            3 => Key::Num3,
-            4 => Key::Num4,
-            5 => Key::Num5,
-            6 => Key::Num6,
-            7 => Key::Num7,
-            8 => Key::Num8,
-            9 => Key::Num9,
-            _ => panic!("Command ID must be between 0 and 9"),
-        };
-        vec![Key::Slash, key]
+    /// Keys to type after opening the command switch to invoke the command.
+    /// Empty if the command can only be clicked.
+    pub fn keys(&self) -> &[Key] {
+        self.keys.as_deref().unwrap_or_default()
     }
 
-    pub fn shortcut_comb(&self) -> Vec<KeyboardShortcut> {
-        self.shortcut_keys()
-            .into_iter()
-            .map(|k| KeyboardShortcut::new(Modifiers::NONE, k))
-            .collect()
+    /// Text of the full shortcut, e.g. `/ 2 3`.
+    pub fn shortcut_text(&self) -> String {
+        if self.keys().is_empty() {
+            "unbound".to_owned()
+        } else {
+            format!("/ {}", keys::format_sequence(self.keys()))
+        }
     }
 }
 
@@ -68,10 +95,11 @@ impl BaseCommand {
 pub enum ReplyState {
     #[default]
     ReadyForInvocation,
-    WaitingForReply(Instant),
+    WaitingForReply(PendingReply),
     ExplicitAck,
     TimeoutNack,
     ExplicitNack,
+    ExplicitWack,
 }
 
 impl ReplyState {
@@ -130,7 +158,42 @@ impl Command {
         }
     }
 
-    pub fn show_settings(&mut self, ui: &mut Ui) {
+    /// Copies the definition of the bound library command, if it changed.
+    /// Commands not bound still require the criticality the library sets for
+    /// their message.
+    pub fn sync_with_library(&mut self) {
+        let library = COMMAND_LIBRARY.read();
+        let base = self.base_mut();
+        base.arming
+            .require_library(&library, base.system_id, base.message.as_ref());
+        let Some(name) = self.base().library_command.clone() else {
+            return;
+        };
+        if library.revision() == self.base().library_revision {
+            return;
+        }
+        let definition = library.get(&name);
+        let base = self.base_mut();
+        base.library_revision = library.revision();
+        match definition {
+            Some(definition) => {
+                base.message = definition.message.clone();
+                base.system_id = definition.system_id;
+                base.arming.criticality = definition.criticality.clone();
+            }
+            None => {
+                warn!("Command {name} not found in the library");
+                base.message = None;
+            }
+        }
+        if let (Command::Configurable(cmd), Some(definition)) = (self, definition) {
+            cmd.selected_fields = definition.editable_fields.iter().copied().collect();
+        }
+    }
+
+    /// Shows the settings of the command. `others` holds the names and key
+    /// sequences of the other commands, to check for conflicts.
+    pub fn show_settings(&mut self, ui: &mut Ui, others: &[(String, Vec<Key>)]) {
         // Common title and separator
         ui.label(RichText::new("Command Settings:").size(15.0));
         ui.separator();
@@ -151,6 +214,7 @@ impl Command {
             *new_cmd.base_mut() = self.base().clone();
             *self = new_cmd;
         }
+        shortcut_settings(ui, self.base_mut(), others);
 
         match self {
             Command::Configurable(cmd) => configurable::show_command_settings(ui, cmd),
@@ -158,3 +222,45 @@ impl Command {
         }
     }
 }
+
+fn shortcut_settings(ui: &mut Ui, base: &mut BaseCommand, others: &[(String, Vec<Key>)]) {
+    ui.horizontal(|ui| {
+        ui.label("Group:");
+        ui.add(TextEdit::singleline(&mut base.group).hint_text("none"));
+    });
+
+    // The text is kept while being edited, and the keys are updated only when valid
+    let text_id = ui.id().with(("keys_text", base.id));
+    let mut text = ui
+        .data(|d| d.get_temp::<String>(text_id))
+        .unwrap_or_else(|| keys::format_sequence(base.keys()));
+    let response = ui
+        .horizontal(|ui| {
+            ui.label("Keys: /");
+            ui.add(
+                TextEdit::singleline(&mut text)
+                    .hint_text("e.g. 2 3")
+                    .desired_width(100.0),
+            )
+        })
+        .inner
+        .on_hover_text("Space separated keys to type after opening the command switch with /");
+    let error = match keys::parse_sequence(&text) {
+        Ok(keys) => match others.iter().find(|(_, o)| keys::conflicts(o, &keys)) {
+            Some((name, _)) => Some(format!("Conflicts with {name}")),
+            None => {
+                base.keys = Some(keys);
+                None
+            }
+        },
+        Err(e) => Some(e),
+    };
+    if let Some(error) = error {
+        ui.label(RichText::new(error).color(Color32::RED));
+    }
+    if response.has_focus() {
+        ui.data_mut(|d| d.insert_temp(text_id, text));
+    } else {
+        ui.data_mut(|d| d.remove::<String>(text_id));
+    }
+}
@@ -1,28 +1,43 @@
-use std::time::Instant;
-
 use egui::{
     Color32, DragValue, Frame, Key, KeyboardShortcut, Label, Margin, Modifiers, Response, RichText,
     Sense, Sides, Stroke, Ui, UiBuilder, Widget,
     ahash::{HashSet, HashSetExt},
     response::Flags,
 };
-use mavlink_bindgen::parser::MavType;
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
 use crate::{
+    commands::MAX_EDITABLE_FIELDS,
     error::ErrInstrument,
     mavlink::{
-        MavHeader, MavMessage, Message,
-        reflection::{
-            FieldLike, FieldLookup, IndexedField, MAVLINK_PROFILE, MapConvertible, MessageMap,
+        MavHeader, MavMessage,
+        reflection::{FieldLike, MAVLINK_PROFILE, MapConvertible, MessageMap},
+    },
+    tc_log::{PendingReply, ReplyTimeout},
+    ui::{
+        shortcuts::ShortcutHandlerExt,
+        widgets::{
+            LibraryCommandSelector, ShortcutCard, field_editor, message_selector, settable_fields,
         },
     },
-    ui::{shortcuts::ShortcutHandlerExt, widgets::ShortcutCard},
 };
 
 use super::{BaseCommand, ReplyState};
 
+/// Keys selecting the editable fields, in order.
+const FIELD_KEYS: [Key; MAX_EDITABLE_FIELDS] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ConfigurableCommand {
     pub base: BaseCommand,
@@ -47,6 +62,7 @@ impl ConfigurableCommand {
         &mut self,
         state: &mut super::state::StateManager,
         messages_to_send: &mut Vec<(MavHeader, MavMessage)>,
+        reply_timeout: ReplyTimeout,
         ui: &mut Ui,
     ) {
         let ConfigurableCommand {
@@ -56,6 +72,7 @@ impl ConfigurableCommand {
                     message,
                     system_id,
                     reply_state,
+                    arming,
                     ..
                 },
             selected_fields,
@@ -74,18 +91,20 @@ impl ConfigurableCommand {
                 ui.label(RichText::new("No fields selected for configuration").italics());
             }
 
-            let keys = vec![
-                Key::Num1,
-                Key::Num2,
-                Key::Num3,
-                Key::Num4,
-                Key::Num5,
-                Key::Num6,
-                Key::Num7,
-                Key::Num8,
-                Key::Num9,
-            ];
-            for (field_id, key) in selected_fields.iter().zip(keys) {
+            // fields beyond the keys can only come from a library edited by hand
+            if selected_fields.len() > FIELD_KEYS.len() {
+                ui.label(
+                    RichText::new(format!(
+                        "Only the first {} editable fields can be changed, fix the command library",
+                        FIELD_KEYS.len()
+                    ))
+                    .color(Color32::RED),
+                );
+            }
+
+            let mut fields: Vec<usize> = selected_fields.iter().copied().collect();
+            fields.sort_unstable();
+            for (field_id, key) in fields.into_iter().zip(FIELD_KEYS) {
                 let field = field_id
                     .to_mav_field(message.message_id(), &MAVLINK_PROFILE)
                     .log_unwrap();
@@ -151,18 +170,23 @@ impl ConfigurableCommand {
             },
         );
         if back_invoked {
+            arming.disarm(ui.ctx(), name, "closed");
             *parameters_window_visible = false;
         }
 
-        if send_invoked {
+        // critical commands are sent only once confirmed
+        let send_confirmed = arming.show_armed(ui, name);
+        if (send_invoked && arming.request(ui.ctx(), name)) || send_confirmed {
             if let Some(map) = message {
                 // append the message to the list of messages to send
-                let header = MavHeader {
+                let mut header = MavHeader {
                     system_id: *system_id,
                     ..Default::default()
                 };
-                messages_to_send.push((header, MavMessage::from_map(map.clone()).log_unwrap()));
-                *reply_state = ReplyState::WaitingForReply(Instant::now());
+                let message = MavMessage::from_map(map.clone()).log_unwrap();
+                let pending = PendingReply::send(&mut header, &message, reply_timeout);
+                *reply_state = ReplyState::WaitingForReply(pending);
+                messages_to_send.push((header, message));
             }
             *parameters_window_visible = false;
         }
@@ -236,113 +260,6 @@ fn shortcut_btn(ui: &mut Ui, text: &str, key: Option<Key>) -> Response {
     res
 }
 
-// TODO: convert this into a widget (and remove code duplication)
-fn field_editor(field: IndexedField, message_map: &mut MessageMap, ui: &mut Ui) -> Response {
-    // show the combo box for enum types
-    if let Some(enum_type) = &field.field().enumtype {
-        let enum_info = MAVLINK_PROFILE.get_enum(enum_type).log_unwrap();
-        // TODO handle enum advanced options
-        macro_rules! variant_selector_for {
-            ($kind:ty) => {{
-                let variant_ix: &mut $kind = message_map.get_mut_field(field).log_unwrap();
-                let selected_text = enum_info.entries[*variant_ix as usize].name.clone();
-                egui::ComboBox::from_id_salt(ui.id().with("field_selector"))
-                    .selected_text(selected_text)
-                    .show_ui(ui, |ui| {
-                        for (index, variant) in enum_info.entries.iter().enumerate() {
-                            ui.selectable_value(variant_ix, index as $kind, &variant.name);
-                        }
-                    })
-                    .response
-            }};
-        }
-        match field.field().mavtype {
-            MavType::UInt8 => variant_selector_for!(u8),
-            MavType::UInt16 => variant_selector_for!(u16),
-            MavType::UInt32 => variant_selector_for!(u32),
-            MavType::UInt64 => variant_selector_for!(u64),
-            _ => {
-                // TODO handle other enum types
-                warn!(
-                    "Enum type {} is not supported for field {}",
-                    enum_type,
-                    field.field().name
-                );
-                ui.response()
-            }
-        }
-    } else {
-        // show the drag value for numeric types and text box for char types
-        macro_rules! drag_value_with_range_float {
-            ($_type:ty, $min:expr, $max:expr) => {{
-                let value: &mut $_type = message_map.get_mut_field(field).log_unwrap();
-                ui.add(
-                    egui::DragValue::new(value)
-                        .range($min..=$max)
-                        .clamp_existing_to_range(true)
-                        .min_decimals(0)
-                        .max_decimals(8),
-                )
-            }};
-        }
-        macro_rules! drag_value_with_range {
-            ($_type:ty, $min:expr, $max:expr) => {{
-                let value: &mut $_type = message_map.get_mut_field(field).log_unwrap();
-                ui.add(
-                    egui::DragValue::new(value)
-                        .range($min..=$max)
-                        .clamp_existing_to_range(true),
-                )
-            }};
-        }
-
-        match field.field().mavtype {
-            MavType::UInt8MavlinkVersion | MavType::UInt8 => {
-                drag_value_with_range!(u8, 0, u8::MAX)
-            }
-            MavType::UInt16 => drag_value_with_range!(u16, 0, u16::MAX),
-            MavType::UInt32 => drag_value_with_range!(u32, 0, u32::MAX),
-            MavType::UInt64 => drag_value_with_range!(u64, 0, u64::MAX),
-            MavType::Int8 => drag_value_with_range!(i8, i8::MIN, i8::MAX),
-            MavType::Int16 => {
-                drag_value_with_range!(i16, i16::MIN, i16::MAX)
-            }
-            MavType::Int32 => {
-                drag_value_with_range!(i32, i32::MIN, i32::MAX)
-            }
-            MavType::Int64 => {
-                drag_value_with_range!(i64, i64::MIN, i64::MAX)
-            }
-            MavType::Float => {
-                drag_value_with_range_float!(f32, f32::MIN, f32::MAX)
-            }
-            MavType::Double => {
-                drag_value_with_range_float!(f64, f64::MIN, f64::MAX)
-            }
-            MavType::Char => {
-                let value: &mut char = message_map.get_mut_field(field).log_unwrap();
-                let mut buffer = value.to_string();
-                let res = ui.add(
-                    egui::TextEdit::singleline(&mut buffer)
-                        .hint_text("char")
-                        .char_limit(1),
-                );
-                if let Some(c) = buffer.chars().next() {
-                    *value = c;
-                } else {
-                    warn!("Invalid char input: {}", buffer);
-                    // TODO handle invalid char input (USER ERROR)
-                }
-                res
-            }
-            MavType::Array(_, _) => {
-                warn!("Array types are not supported yet");
-                ui.response()
-            }
-        }
-    }
-}
-
 pub fn show_command_settings(ui: &mut Ui, command: &mut ConfigurableCommand) {
     let ConfigurableCommand {
         base:
@@ -352,6 +269,9 @@ pub fn show_command_settings(ui: &mut Ui, command: &mut ConfigurableCommand) {
                 message,
                 settings_window_visible,
                 show_only_tc,
+                arming,
+                library_command,
+                library_revision,
                 ..
             },
         selected_fields,
@@ -363,201 +283,46 @@ pub fn show_command_settings(ui: &mut Ui, command: &mut ConfigurableCommand) {
         ui.text_edit_singleline(name);
     });
 
-    // add a label for the system ID
     ui.horizontal(|ui| {
-        let label = ui.label("System ID:");
-        // add a drag value for the system ID
-        ui.add(DragValue::new(system_id).range(1..=255))
-            .labelled_by(label.id);
-    });
-
-    // add a checkbox for filtering sendable messages
-    ui.checkbox(show_only_tc, "Show only TC messages");
-
-    // Create a combo box for selecting the message kind
-    let mut message_id = message.as_ref().map(|m| m.message_id());
-    let selected_text = message_id
-        .and_then(|id| MAVLINK_PROFILE.get_msg(id))
-        .map(|m| m.name.clone())
-        .unwrap_or("Select a Message".to_string());
-    egui::ComboBox::from_id_salt(ui.id().with("message_selector"))
-        .selected_text(selected_text)
-        .show_ui(ui, |ui| {
-            let mut msgs = MAVLINK_PROFILE.get_sorted_msgs();
-            if *show_only_tc {
-                msgs.retain(|m| m.name.ends_with("_TC"));
-            }
-            for msg in msgs {
-                ui.selectable_value(&mut message_id, Some(msg.id), &msg.name);
-            }
-        });
-
-    // If the message id is changed, update the message and selected fields
-    if message
-        .as_ref()
-        .is_none_or(|m| Some(m.message_id()) != message_id)
-    {
-        if let Some(id) = message_id {
-            *message = Some(
-                MavMessage::default_message_from_id(id)
-                    .log_unwrap()
-                    .as_map(),
-            );
-        } else {
-            *message = None;
+        ui.label("Command:");
+        let selector =
+            LibraryCommandSelector::new(ui.id().with("library_command"), library_command);
+        if ui.add(selector).changed() {
+            // force the synchronization with the new definition
+            *library_revision = 0;
         }
-        selected_fields.clear();
-    }
-
-    // For each field in the message, show a checkbox with the field name
-    if let Some(message_map) = message.as_mut() {
-        let mut settable_fields = (0..message_map.field_map().len())
-            .map(|f| {
-                f.to_mav_field(message_map.message_id(), &MAVLINK_PROFILE)
-                    .log_unwrap()
-            })
-            .collect::<Vec<_>>();
+    });
 
-        // filter out the fields that are not settable
-        settable_fields.retain(|f| {
-            // skip the timestamp field
-            f.field().name.to_lowercase() != "timestamp"
+    if library_command.is_some() {
+        ui.label(
+            RichText::new(
+                "Message, system ID, criticality and configurable fields are defined in the command library",
+            )
+            .italics(),
+        );
+    } else {
+        // add a label for the system ID
+        ui.horizontal(|ui| {
+            let label = ui.label("System ID:");
+            // add a drag value for the system ID
+            ui.add(DragValue::new(system_id).range(1..=255))
+                .labelled_by(label.id);
         });
 
-        let num_checked = selected_fields.len();
-        if !settable_fields.is_empty() {
-            ui.group(|ui| {
-                for field in settable_fields {
-                    ui.horizontal(|ui| {
-                        // First, the checkbox for selecting the field to configure in operation mode
-                        let mut field_present = selected_fields.contains(&field.id());
-                        let text = if field_present {
-                            field.field().name.to_uppercase()
-                        } else {
-                            format!("{}: ", field.field().name.to_uppercase())
-                        };
-                        ui.checkbox(&mut field_present, text);
-                        if field_present && num_checked >= 9 {
-                            warn!(
-                                "Maximum number of fields selected for configuration reached (9)."
-                            );
-                        } else if field_present {
-                            // Add the field to the selected fieldss
-                            selected_fields.insert(field.id());
-                        } else {
-                            // Remove the field from the selected fields
-                            selected_fields.remove(&field.id());
+        arming.settings_ui(ui);
+        ui.separator();
 
-                            // show the combo box for enum types
-                            if let Some(enum_type) = &field.field().enumtype {
-                                let enum_info = MAVLINK_PROFILE.get_enum(enum_type).log_unwrap();
-                                // TODO handle enum advanced options
-                                macro_rules! variant_selector_for {
-                                    ($kind:ty) => {{
-                                        let variant_ix: &mut $kind =
-                                            message_map.get_mut_field(field).log_unwrap();
-                                        let selected_text =
-                                            enum_info.entries[*variant_ix as usize].name.clone();
-                                        egui::ComboBox::from_id_salt(
-                                            ui.id().with("field_selector"),
-                                        )
-                                        .selected_text(selected_text)
-                                        .show_ui(ui, |ui| {
-                                            for (index, variant) in
-                                                enum_info.entries.iter().enumerate()
-                                            {
-                                                ui.selectable_value(
-                                                    variant_ix,
-                                                    index as $kind,
-                                                    &variant.name,
-                                                );
-                                            }
-                                        });
-                                    }};
-                                }
-                                match field.field().mavtype {
-                                    MavType::UInt8 => variant_selector_for!(u8),
-                                    MavType::UInt16 => variant_selector_for!(u16),
-                                    MavType::UInt32 => variant_selector_for!(u32),
-                                    MavType::UInt64 => variant_selector_for!(u64),
-                                    _ => {
-                                        // TODO handle other enum types
-                                        warn!(
-                                            "Enum type {} is not supported for field {}",
-                                            enum_type,
-                                            field.field().name
-                                        );
-                                    }
-                                }
-                            } else {
-                                // show the drag value for numeric types and text box for char types
-                                macro_rules! drag_value_with_range {
-                                    ($_type:ty, $min:expr, $max:expr) => {{
-                                        let value: &mut $_type =
-                                            message_map.get_mut_field(field).log_unwrap();
-                                        ui.add(egui::DragValue::new(value).range($min..=$max));
-                                    }};
-                                }
+        // add a checkbox for filtering sendable messages
+        ui.checkbox(show_only_tc, "Show only TC messages");
 
-                                match field.field().mavtype {
-                                    MavType::UInt8MavlinkVersion | MavType::UInt8 => {
-                                        drag_value_with_range!(u8, 0, u8::MAX)
-                                    }
-                                    MavType::UInt16 => drag_value_with_range!(u16, 0, u16::MAX),
-                                    MavType::UInt32 => drag_value_with_range!(u32, 0, u32::MAX),
-                                    MavType::UInt64 => drag_value_with_range!(u64, 0, u64::MAX),
-                                    MavType::Int8 => drag_value_with_range!(i8, i8::MIN, i8::MAX),
-                                    MavType::Int16 => {
-                                        drag_value_with_range!(i16, i16::MIN, i16::MAX)
-                                    }
-                                    MavType::Int32 => {
-                                        drag_value_with_range!(i32, i32::MIN, i32::MAX)
-                                    }
-                                    MavType::Int64 => {
-                                        drag_value_with_range!(i64, i64::MIN, i64::MAX)
-                                    }
-                                    MavType::Float => {
-                                        drag_value_with_range!(f32, f32::MIN, f32::MAX)
-                                    }
-                                    MavType::Double => {
-                                        drag_value_with_range!(f64, f64::MIN, f64::MAX)
-                                    }
-                                    MavType::Char => {
-                                        let value: &mut char =
-                                            message_map.get_mut_field(field).log_unwrap();
-                                        let mut buffer = value.to_string();
-                                        ui.add(
-                                            egui::TextEdit::singleline(&mut buffer)
-                                                .hint_text("char")
-                                                .char_limit(1),
-                                        );
-                                        if let Some(c) = buffer.chars().next() {
-                                            *value = c;
-                                        } else {
-                                            warn!("Invalid char input: {}", buffer);
-                                            // TODO handle invalid char input (USER ERROR)
-                                        }
-                                    }
-                                    MavType::Array(_, _) => {
-                                        warn!("Array types are not supported yet")
-                                    }
-                                }
-                            }
-                        }
-                    });
-                }
-            });
+        // If the message is changed, reset the selected fields
+        if message_selector(ui, message, *show_only_tc) {
+            selected_fields.clear();
         }
 
-        ui.label(
-            RichText::new("Check the fields you'd like to configure in operation mode").italics(),
-        );
-
-        if num_checked >= 9 {
-            ui.label(
-                RichText::new("Maximum number of fields selected for configuration reached (9). Deselect some fields to continue.")
-                    .color(egui::Color32::RED),
-            );
+        // For each field in the message, show a checkbox with the field name
+        if let Some(message_map) = message.as_mut() {
+            fields_selector(ui, message_map, selected_fields);
         }
     }
 
@@ -566,3 +331,51 @@ pub fn show_command_settings(ui: &mut Ui, command: &mut ConfigurableCommand) {
         *settings_window_visible = false;
     }
 }
+
+/// Shows a checkbox to select each field to configure in operation mode, and
+/// the editor of the fixed value of the others.
+fn fields_selector(
+    ui: &mut Ui,
+    message_map: &mut MessageMap,
+    selected_fields: &mut HashSet<usize>,
+) {
+    let fields = settable_fields(message_map);
+    let num_checked = selected_fields.len();
+    if !fields.is_empty() {
+        ui.group(|ui| {
+            for field in fields {
+                ui.horizontal(|ui| {
+                    // First, the checkbox for selecting the field to configure in operation mode
+                    let mut field_present = selected_fields.contains(&field.id());
+                    let text = if field_present {
+                        field.field().name.to_uppercase()
+                    } else {
+                        format!("{}: ", field.field().name.to_uppercase())
+                    };
+                    ui.checkbox(&mut field_present, text);
+                    if field_present && num_checked >= MAX_EDITABLE_FIELDS {
+                        warn!(
+                            "Maximum number of fields selected for configuration reached ({MAX_EDITABLE_FIELDS})."
+                        );
+                    } else if field_present {
+                        // Add the field to the selected fieldss
+                        selected_fields.insert(field.id());
+                    } else {
+                        // Remove the field from the selected fields
+                        selected_fields.remove(&field.id());
+                        field_editor(field, message_map, ui);
+                    }
+                });
+            }
+        });
+    }
+
+    ui.label(RichText::new("Check the fields you'd like to configure in operation mode").italics());
+
+    if num_checked >= MAX_EDITABLE_FIELDS {
+        ui.label(
+            RichText::new(format!("Maximum number of fields selected for configuration reached ({MAX_EDITABLE_FIELDS}). Deselect some fields to continue."))
+                .color(egui::Color32::RED),
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Key sequences invoking the commands of the command switch.
+//!
+//! Once the switch is opened with `/`, a command is invoked by typing its
+//! sequence, e.g. `2 3`. Since the command fires as soon as its sequence is
+//! complete, no sequence can be a prefix of another one.
+
+use egui::Key;
+
+/// Keys used for the sequences assigned automatically, in order.
+const SEQUENCE_KEYS: [Key; 10] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+    Key::Num0,
+];
+
+/// Keys used by the command switch itself, which cannot be part of a sequence.
+const RESERVED_KEYS: [Key; 4] = [Key::Slash, Key::Backspace, Key::Escape, Key::Enter];
+
+/// Whether two sequences cannot be told apart while typing them, i.e. one is
+/// a prefix of the other. Empty sequences (unbound commands) never conflict.
+pub fn conflicts(a: &[Key], b: &[Key]) -> bool {
+    !a.is_empty() && !b.is_empty() && (a.starts_with(b) || b.starts_with(a))
+}
+
+/// Whether the sequence can be assigned to a command.
+pub fn is_valid(keys: &[Key]) -> bool {
+    !keys.iter().any(|k| RESERVED_KEYS.contains(k))
+}
+
+/// Returns the shortest sequence of digits not conflicting with the used
+/// ones, or an empty sequence if they are all taken.
+///
+/// A sequence is only handed out if some other one is still free afterwards,
+/// so the last free digit of a level is kept as the prefix of the next page:
+/// `1`–`9` come first, then `0 1`–`0 9`, then `0 0 1`–`0 0 9` and so on.
+pub fn next_free_sequence(used: &[Vec<Key>]) -> Vec<Key> {
+    for len in 1.. {
+        let candidates = unblocked(used, len);
+        if candidates.is_empty() {
+            break;
+        }
+        let accepted = candidates
+            .into_iter()
+            .filter(|keys| is_free(used, keys))
+            .find(|keys| {
+                let mut with = used.to_vec();
+                with.push(keys.clone());
+                has_free(&with, len + 1)
+            });
+        if let Some(keys) = accepted {
+            return keys;
+        }
+    }
+    Vec::new()
+}
+
+/// Whether the sequence conflicts with none of the used ones.
+fn is_free(used: &[Vec<Key>], keys: &[Key]) -> bool {
+    used.iter().all(|u| !conflicts(u, keys))
+}
+
+/// Whether any sequence up to the given length is free.
+fn has_free(used: &[Vec<Key>], max_len: usize) -> bool {
+    (1..=max_len).any(|len| unblocked(used, len).iter().any(|k| is_free(used, k)))
+}
+
+/// Sequences of digits of the given length not starting with a used one.
+fn unblocked(used: &[Vec<Key>], len: usize) -> Vec<Vec<Key>> {
+    let mut sequences = vec![Vec::new()];
+    for _ in 0..len {
+        sequences = sequences
+            .iter()
+            .flat_map(|prefix| {
+                SEQUENCE_KEYS.iter().map(|key| {
+                    let mut keys = prefix.clone();
+                    keys.push(*key);
+                    keys
+                })
+            })
+            .filter(|keys| !used.iter().any(|u| !u.is_empty() && keys.starts_with(u)))
+            .collect();
+    }
+    sequences
+}
+
+/// Sequence of the commands saved before sequences were configurable, when
+/// the only key was the digit matching the command id.
+pub fn legacy_sequence(id: usize) -> Option<Vec<Key>> {
+    let key = match id {
+        0 => Key::Num0,
+        1..=9 => SEQUENCE_KEYS[id - 1],
+        _ => return None,
+    };
+    Some(vec![key])
+}
+
+/// Formats the sequence as typed after opening the switch, e.g. `2 3`.
+pub fn format_sequence(keys: &[Key]) -> String {
+    keys.iter()
+        .map(|k| k.symbol_or_name())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parses a sequence of space separated key names, e.g. `2 3` or `F1 A`.
+pub fn parse_sequence(text: &str) -> Result<Vec<Key>, String> {
+    let keys = text
+        .split_whitespace()
+        .map(|name| Key::from_name(name).ok_or_else(|| format!("Unknown key \"{name}\"")))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !is_valid(&keys) {
+        return Err("/, Backspace, Escape and Enter are reserved".to_owned());
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_conflict() {
+        assert!(conflicts(&[Key::Num2], &[Key::Num2, Key::Num3]));
+        assert!(conflicts(&[Key::Num2, Key::Num3], &[Key::Num2, Key::Num3]));
+        assert!(!conflicts(&[Key::Num2, Key::Num3], &[Key::Num2, Key::Num4]));
+        assert!(!conflicts(&[], &[Key::Num2]));
+    }
+
+    #[test]
+    fn free_sequences_grow_once_digits_are_taken() {
+        let mut used = Vec::new();
+        for _ in 0..20 {
+            used.push(next_free_sequence(&used));
+        }
+        assert_eq!(used[0], vec![Key::Num1]);
+        assert_eq!(used[8], vec![Key::Num9]);
+        assert_eq!(used[9], vec![Key::Num0, Key::Num1]);
+        assert_eq!(used[17], vec![Key::Num0, Key::Num9]);
+        assert_eq!(used[18], vec![Key::Num0, Key::Num0, Key::Num1]);
+    }
+
+    #[test]
+    fn free_sequences_skip_the_taken_prefixes() {
+        // 0 taken by a legacy command, 9 becomes the prefix of the next page
+        let mut used: Vec<_> = (0..9).filter_map(legacy_sequence).collect();
+        assert_eq!(next_free_sequence(&used), vec![Key::Num9, Key::Num1]);
+
+        used.push(vec![Key::Num9, Key::Num1, Key::Num2]);
+        assert_eq!(next_free_sequence(&used), vec![Key::Num9, Key::Num2]);
+    }
+
+    #[test]
+    fn more_than_ten_commands_are_all_reachable() {
+        let mut used: Vec<Vec<Key>> = Vec::new();
+        for _ in 0..50 {
+            used.push(next_free_sequence(&used));
+        }
+        for (i, keys) in used.iter().enumerate() {
+            assert!(!keys.is_empty(), "command {i} is unbound");
+            // typing the sequence fires this command only, and only once
+            // the sequence is complete
+            for typed in 1..=keys.len() {
+                let fired: Vec<_> = used
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, k)| k.as_slice() == &keys[..typed])
+                    .map(|(j, _)| j)
+                    .collect();
+                let expected = if typed == keys.len() { vec![i] } else { vec![] };
+                assert_eq!(
+                    fired,
+                    expected,
+                    "typing {}",
+                    format_sequence(&keys[..typed])
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sequences_round_trip_through_text() {
+        let keys = vec![Key::Num2, Key::Num3];
+        assert_eq!(parse_sequence(" 2  3 "), Ok(keys.clone()));
+        assert_eq!(format_sequence(&keys), "2 3");
+        assert!(parse_sequence("2 /").is_err());
+        assert!(parse_sequence("2 nope").is_err());
+    }
+}
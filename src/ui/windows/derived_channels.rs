@@ -0,0 +1,129 @@
+use egui::{Button, Color32, Context, Grid, RichText, TextEdit};
+
+use crate::derived::{DERIVED_CHANNELS, DerivedChannel};
+
+/// Window used to edit the derived channels of the current layout.
+#[derive(Default)]
+pub struct DerivedChannelsWindow {
+    visible: bool,
+    /// Channels being edited, applied once a text field loses the focus, so
+    /// that the registry is not recompiled (and the filters reset) at every
+    /// keystroke
+    draft: Option<Vec<DerivedChannel>>,
+}
+
+impl DerivedChannelsWindow {
+    pub fn toggle_open_state(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    #[profiling::function]
+    pub fn show(&mut self, ctx: &Context, channels: &mut Vec<DerivedChannel>) {
+        egui::Window::new("Derived Channels")
+            .collapsible(false)
+            .resizable(true)
+            .open(&mut self.visible)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Expressions can use fields as MESSAGE.field, enum entries as ENUM.ENTRY, \
+                    previously defined channels, math functions (abs, sqrt, norm, atan2, min, max, if, ...), \
+                    convert(x, \"from\", \"to\") and filters (avg(x, n), ddt(x), integ(x), lowpass(x, tau)).",
+                );
+                ui.separator();
+
+                // Apply the edits to a local copy, so that the registry is
+                // recompiled only once they are committed
+                let mut edited = self.draft.take().unwrap_or_else(|| channels.clone());
+                let mut commit = false;
+                let mut to_remove = None;
+                let mut to_move_up = None;
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        Grid::new("derived_channels_grid")
+                            .num_columns(4)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("Name");
+                                ui.strong("Expression");
+                                ui.strong("Unit");
+                                ui.end_row();
+
+                                for (i, channel) in edited.iter_mut().enumerate() {
+                                    commit |= ui
+                                        .add(
+                                            TextEdit::singleline(&mut channel.name)
+                                                .desired_width(120.0),
+                                        )
+                                        .lost_focus();
+                                    ui.vertical(|ui| {
+                                        commit |= ui
+                                            .add(
+                                                TextEdit::singleline(&mut channel.expression)
+                                                    .code_editor()
+                                                    .desired_width(320.0),
+                                            )
+                                            .lost_focus();
+                                        if let Some(error) = DERIVED_CHANNELS.read().error(i) {
+                                            ui.label(
+                                                RichText::new(error.to_string())
+                                                    .color(Color32::RED)
+                                                    .small(),
+                                            );
+                                        }
+                                    });
+                                    let mut unit = channel.unit.clone().unwrap_or_default();
+                                    let response =
+                                        ui.add(TextEdit::singleline(&mut unit).desired_width(50.0));
+                                    if response.changed() {
+                                        channel.unit = (!unit.is_empty()).then_some(unit);
+                                    }
+                                    commit |= response.lost_focus();
+                                    ui.horizontal(|ui| {
+                                        if ui
+                                            .add_enabled(i > 0, Button::new("⏶"))
+                                            .on_hover_text("Move up")
+                                            .clicked()
+                                        {
+                                            to_move_up = Some(i);
+                                        }
+                                        if ui.button("🗑").on_hover_text("Remove").clicked() {
+                                            to_remove = Some(i);
+                                        }
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                if let Some(i) = to_move_up {
+                    edited.swap(i - 1, i);
+                    commit = true;
+                }
+                if let Some(i) = to_remove {
+                    edited.remove(i);
+                    commit = true;
+                }
+
+                ui.separator();
+                if ui.button("Add channel").clicked() {
+                    edited.push(DerivedChannel {
+                        name: format!("derived_{}", edited.len() + 1),
+                        ..Default::default()
+                    });
+                    commit = true;
+                }
+
+                if edited == *channels {
+                    return;
+                }
+                if commit {
+                    *channels = edited;
+                    DERIVED_CHANNELS.write().sync(channels);
+                } else {
+                    self.draft = Some(edited);
+                }
+            });
+    }
+}
@@ -1,3 +1,5 @@
 pub mod id;
 mod ring_buffer;
+pub mod text;
+pub mod timer;
 pub mod units;
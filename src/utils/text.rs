@@ -0,0 +1,115 @@
+//! Helpers for the line-based text formats, such as scripts and checklists.
+//!
+//! These formats are plain text files with one entry per line, where empty
+//! lines and everything after a `#` outside double quotes are ignored, e.g.:
+//!
+//! ```text
+//! # comment
+//! confirm "Valve #2 closed"  # the first `#` is inside the quotes
+//! ```
+
+use thiserror::Error;
+
+/// Error in a line-based text, with the number of the line it was found at.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("Line {line}: {reason}")]
+pub struct LineError {
+    pub line: usize,
+    pub reason: String,
+}
+
+/// Parses each line that is not empty once stripped of its comment, returning
+/// the parsed lines with their numbers, starting from 1, or the first error.
+pub fn parse_lines<T>(
+    text: &str,
+    mut parse: impl FnMut(&str) -> Result<T, String>,
+) -> Result<Vec<(usize, T)>, LineError> {
+    let mut parsed = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let value = parse(line).map_err(|reason| LineError {
+            line: i + 1,
+            reason,
+        })?;
+        parsed.push((i + 1, value));
+    }
+    Ok(parsed)
+}
+
+/// Strips the comment from the line: everything after a `#` that is not
+/// inside double quotes.
+pub fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_start_outside_quotes() {
+        assert_eq!(strip_comment("wait 1 s # settle"), "wait 1 s ");
+        assert_eq!(strip_comment("# only a comment"), "");
+        assert_eq!(strip_comment("no comment"), "no comment");
+        assert_eq!(
+            strip_comment("confirm \"Valve #2 closed\" # check"),
+            "confirm \"Valve #2 closed\" "
+        );
+        // an unterminated quote runs to the end of the line
+        assert_eq!(strip_comment("confirm \"Valve #2"), "confirm \"Valve #2");
+    }
+
+    #[test]
+    fn lines_are_numbered_skipping_comments() {
+        let lines = parse_lines(
+            "# title\n\
+             \n\
+             first  # note\n\
+             \"with #hash\"\n\
+             last",
+            |line| Ok::<_, String>(line.to_owned()),
+        )
+        .unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                (3, "first".to_owned()),
+                (4, "\"with #hash\"".to_owned()),
+                (5, "last".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn the_first_error_is_reported_with_its_line() {
+        let mut parsed = Vec::new();
+        let error = parse_lines("ok\n# skipped\nbad\nworse", |line| {
+            parsed.push(line.to_owned());
+            if line == "ok" {
+                Ok(())
+            } else {
+                Err(format!("unexpected {line}"))
+            }
+        });
+        assert_eq!(
+            error,
+            Err(LineError {
+                line: 3,
+                reason: "unexpected bad".to_owned()
+            })
+        );
+        assert_eq!(parsed, vec!["ok", "bad"]);
+    }
+}
@@ -0,0 +1,193 @@
+//! Timers that can be held and resumed, and the T0 of the mission.
+//!
+//! A [`Stopwatch`] measures the time it has been running for, excluding the
+//! holds, and a [`Countdown`] counts down from a given length using one. The
+//! T0 of the mission is kept in [`MISSION_T0`], set by the timer panes and read
+//! by the panes showing the mission time, e.g. the plots with a T+ axis.
+
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use egui::mutex::RwLock;
+use serde::{Deserialize, Serialize};
+
+/// T0 of the mission, if known.
+pub static MISSION_T0: LazyLock<RwLock<Option<Instant>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Measures the time it has been running for.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stopwatch {
+    /// Time run before the last hold
+    accumulated: Duration,
+    running_since: Option<Instant>,
+}
+
+impl Stopwatch {
+    pub fn is_running(&self) -> bool {
+        self.running_since.is_some()
+    }
+
+    /// Whether the stopwatch was started since the last reset.
+    pub fn is_started(&self) -> bool {
+        self.is_running() || !self.accumulated.is_zero()
+    }
+
+    /// Starts the stopwatch, or resumes it if on hold.
+    pub fn start(&mut self) {
+        self.running_since.get_or_insert_with(Instant::now);
+    }
+
+    pub fn hold(&mut self) {
+        if let Some(since) = self.running_since.take() {
+            self.accumulated += since.elapsed();
+        }
+    }
+
+    /// Stops the stopwatch, clearing the time run.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Starts the stopwatch again from zero.
+    pub fn restart(&mut self) {
+        self.reset();
+        self.start();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.accumulated + self.running_since.map_or(Duration::ZERO, |s| s.elapsed())
+    }
+}
+
+/// Counts down from its length, with holds and resumes. Only the length is
+/// saved, countdowns are always started by the user.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Countdown {
+    length: Duration,
+    #[serde(skip)]
+    stopwatch: Stopwatch,
+}
+
+impl PartialEq for Countdown {
+    fn eq(&self, other: &Self) -> bool {
+        self.length == other.length
+    }
+}
+
+impl Countdown {
+    pub fn new(length: Duration) -> Self {
+        Self {
+            length,
+            stopwatch: Stopwatch::default(),
+        }
+    }
+
+    pub fn length(&self) -> Duration {
+        self.length
+    }
+
+    pub fn set_length(&mut self, length: Duration) {
+        self.length = length;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.stopwatch.is_running()
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.stopwatch.is_started()
+    }
+
+    /// Starts the countdown, or resumes it if on hold.
+    pub fn start(&mut self) {
+        self.stopwatch.start();
+    }
+
+    pub fn hold(&mut self) {
+        self.stopwatch.hold();
+    }
+
+    pub fn reset(&mut self) {
+        self.stopwatch.reset();
+    }
+
+    pub fn restart(&mut self) {
+        self.stopwatch.restart();
+    }
+
+    /// Time left before zero, or zero once expired.
+    pub fn remaining(&self) -> Duration {
+        self.length.saturating_sub(self.stopwatch.elapsed())
+    }
+
+    /// Time elapsed since zero, if started and expired.
+    pub fn overrun(&self) -> Option<Duration> {
+        if !self.is_started() {
+            return None;
+        }
+        self.stopwatch.elapsed().checked_sub(self.length)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.overrun().is_some()
+    }
+}
+
+/// Formats the duration as `HH:MM:SS`, or `MM:SS` if shorter than an hour.
+pub fn format_clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours:02}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes:02}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_stopwatch_keeps_its_time() {
+        let mut stopwatch = Stopwatch::default();
+        assert!(!stopwatch.is_started());
+        stopwatch.start();
+        std::thread::sleep(Duration::from_millis(5));
+        stopwatch.hold();
+        let held = stopwatch.elapsed();
+        assert!(held >= Duration::from_millis(5));
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(stopwatch.elapsed(), held);
+        assert!(stopwatch.is_started() && !stopwatch.is_running());
+
+        stopwatch.start();
+        assert!(stopwatch.elapsed() >= held);
+        stopwatch.reset();
+        assert_eq!(stopwatch.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn countdown_overruns_past_zero() {
+        let mut countdown = Countdown::new(Duration::ZERO);
+        // not expired before it is started
+        assert_eq!(countdown.overrun(), None);
+        countdown.start();
+        assert!(countdown.overrun().is_some());
+        countdown.reset();
+        assert!(!countdown.is_expired());
+        countdown.set_length(Duration::from_secs(60));
+        countdown.start();
+        assert!(!countdown.is_expired());
+        assert!(countdown.remaining() <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn clock_shows_hours_only_when_needed() {
+        assert_eq!(format_clock(Duration::from_secs(83)), "01:23");
+        assert_eq!(format_clock(Duration::from_secs(3723)), "01:02:03");
+    }
+}
@@ -1,22 +1,32 @@
+//! Units of measure and conversions between them.
+//!
+//! Unit strings coming from the MAVLink dialect (or typed by the user) are parsed
+//! into a [`UnitOfMeasure`]. Known units belong to a [`Quantity`] family, and any
+//! two units of the same family can be converted into each other. Unknown unit
+//! strings are kept as they are and are only compatible with themselves.
+
 use std::{fmt::Display, str::FromStr};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UnitOfMeasure {
     Time(TimeUnits),
+    Physical(PhysicalUnit),
     Other(String),
     Adimensional,
 }
 
 impl<T: AsRef<str>> From<Option<T>> for UnitOfMeasure {
     fn from(s: Option<T>) -> Self {
-        let s = s.as_ref();
+        let s = s.as_ref().map(|s| s.as_ref().trim());
         match s {
-            Some(s) if s.as_ref().is_empty() => UnitOfMeasure::Adimensional,
+            Some("") => UnitOfMeasure::Adimensional,
             Some(s) => {
-                if let Ok(unit) = TimeUnits::from_str(s.as_ref()) {
+                if let Ok(unit) = TimeUnits::from_str(s) {
                     UnitOfMeasure::Time(unit)
+                } else if let Ok(unit) = PhysicalUnit::from_str(s) {
+                    UnitOfMeasure::Physical(unit)
                 } else {
-                    UnitOfMeasure::Other(s.as_ref().to_string())
+                    UnitOfMeasure::Other(s.to_string())
                 }
             }
             None => UnitOfMeasure::Adimensional,
@@ -24,10 +34,76 @@ impl<T: AsRef<str>> From<Option<T>> for UnitOfMeasure {
     }
 }
 
+impl UnitOfMeasure {
+    /// Returns the family of the unit, if known.
+    pub fn quantity(&self) -> Option<Quantity> {
+        match self {
+            UnitOfMeasure::Time(_) => Some(Quantity::Time),
+            UnitOfMeasure::Physical(unit) => Some(unit.quantity),
+            UnitOfMeasure::Other(_) | UnitOfMeasure::Adimensional => None,
+        }
+    }
+
+    /// Returns the `(scale, offset)` pair that converts a value from `self` to
+    /// `target` as `value * scale + offset`, or `None` if the units are not
+    /// compatible.
+    pub fn conversion_to(&self, target: &UnitOfMeasure) -> Option<(f64, f64)> {
+        match (self, target) {
+            (UnitOfMeasure::Time(from), UnitOfMeasure::Time(to)) => {
+                Some((from.scale() / to.scale(), 0.0))
+            }
+            (UnitOfMeasure::Physical(from), UnitOfMeasure::Physical(to))
+                if from.quantity == to.quantity =>
+            {
+                Some((from.scale / to.scale, (from.offset - to.offset) / to.scale))
+            }
+            (from, to) if from == to => Some((1.0, 0.0)),
+            _ => None,
+        }
+    }
+
+    /// Converts `value` from `self` to `target`, if the units are compatible.
+    pub fn convert(&self, value: f64, target: &UnitOfMeasure) -> Option<f64> {
+        self.conversion_to(target)
+            .map(|(scale, offset)| value * scale + offset)
+    }
+
+    /// Returns whether a value in this unit can be converted to `target`.
+    pub fn is_compatible_with(&self, target: &UnitOfMeasure) -> bool {
+        self.conversion_to(target).is_some()
+    }
+
+    /// Returns all the units a value in this unit can be converted to,
+    /// including itself.
+    pub fn compatible_units(&self) -> Vec<UnitOfMeasure> {
+        match self {
+            UnitOfMeasure::Time(_) => TimeUnits::ALL
+                .iter()
+                .cloned()
+                .map(UnitOfMeasure::Time)
+                .collect(),
+            UnitOfMeasure::Physical(unit) => PhysicalUnit::of_quantity(unit.quantity)
+                .map(UnitOfMeasure::Physical)
+                .collect(),
+            other => vec![other.clone()],
+        }
+    }
+
+    /// Returns the unit to display given an optional user choice: the choice if
+    /// compatible with `self`, `self` otherwise.
+    pub fn display_unit(&self, choice: Option<&str>) -> UnitOfMeasure {
+        choice
+            .map(|c| UnitOfMeasure::from(Some(c)))
+            .filter(|c| self.is_compatible_with(c))
+            .unwrap_or_else(|| self.clone())
+    }
+}
+
 impl Display for UnitOfMeasure {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             UnitOfMeasure::Time(unit) => write!(f, "{}", unit),
+            UnitOfMeasure::Physical(unit) => write!(f, "{}", unit),
             UnitOfMeasure::Other(unit) => write!(f, "{}", unit),
             UnitOfMeasure::Adimensional => write!(f, ""),
         }
@@ -43,6 +119,13 @@ pub enum TimeUnits {
 }
 
 impl TimeUnits {
+    const ALL: [TimeUnits; 4] = [
+        TimeUnits::Second,
+        TimeUnits::Millisecond,
+        TimeUnits::Microsecond,
+        TimeUnits::Nanosecond,
+    ];
+
     pub fn scale(&self) -> f64 {
         match self {
             TimeUnits::Second => 1.0,
@@ -60,7 +143,7 @@ impl FromStr for TimeUnits {
         match s {
             "s" => Ok(TimeUnits::Second),
             "ms" => Ok(TimeUnits::Millisecond),
-            "us" => Ok(TimeUnits::Microsecond),
+            "us" | "µs" => Ok(TimeUnits::Microsecond),
             "ns" => Ok(TimeUnits::Nanosecond),
             _ => Err(()),
         }
@@ -77,3 +160,187 @@ impl Display for TimeUnits {
         }
     }
 }
+
+/// Families of units that can be converted into each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum_macros::Display)]
+pub enum Quantity {
+    Time,
+    Pressure,
+    Temperature,
+    Length,
+    Speed,
+    Acceleration,
+    Angle,
+    #[strum(to_string = "Angular speed")]
+    AngularSpeed,
+    Mass,
+    Current,
+    Voltage,
+}
+
+/// A known unit of measure, defined by its relation with the reference unit
+/// of its family: `reference = value * scale + offset`.
+#[derive(Debug, Clone, Copy)]
+pub struct PhysicalUnit {
+    pub quantity: Quantity,
+    /// Canonical symbol, used for display
+    pub symbol: &'static str,
+    scale: f64,
+    offset: f64,
+}
+
+impl PhysicalUnit {
+    /// Returns all the known units of the given family.
+    pub fn of_quantity(quantity: Quantity) -> impl Iterator<Item = PhysicalUnit> {
+        UNITS
+            .iter()
+            .map(|(_, unit)| *unit)
+            .filter(move |unit| unit.quantity == quantity)
+    }
+}
+
+impl PartialEq for PhysicalUnit {
+    fn eq(&self, other: &Self) -> bool {
+        self.quantity == other.quantity && self.symbol == other.symbol
+    }
+}
+
+impl Eq for PhysicalUnit {}
+
+impl FromStr for PhysicalUnit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        UNITS
+            .iter()
+            .find(|(aliases, unit)| unit.symbol == s || aliases.contains(&s))
+            .map(|(_, unit)| *unit)
+            .ok_or(())
+    }
+}
+
+impl Display for PhysicalUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol)
+    }
+}
+
+const fn unit(quantity: Quantity, symbol: &'static str, scale: f64, offset: f64) -> PhysicalUnit {
+    PhysicalUnit {
+        quantity,
+        symbol,
+        scale,
+        offset,
+    }
+}
+
+/// Table of the known units, with the alternative spellings found in the dialect.
+/// The first unit of each family is the reference one.
+#[rustfmt::skip]
+static UNITS: &[(&[&str], PhysicalUnit)] = &[
+    // Pressure, reference Pa
+    (&[], unit(Quantity::Pressure, "Pa", 1.0, 0.0)),
+    (&[], unit(Quantity::Pressure, "hPa", 1e2, 0.0)),
+    (&[], unit(Quantity::Pressure, "kPa", 1e3, 0.0)),
+    (&[], unit(Quantity::Pressure, "MPa", 1e6, 0.0)),
+    (&["Bar", "BAR"], unit(Quantity::Pressure, "bar", 1e5, 0.0)),
+    (&["mBar", "millibar"], unit(Quantity::Pressure, "mbar", 1e2, 0.0)),
+    (&["PSI"], unit(Quantity::Pressure, "psi", 6894.757293168, 0.0)),
+    (&[], unit(Quantity::Pressure, "atm", 101325.0, 0.0)),
+    // Temperature, reference K
+    (&[], unit(Quantity::Temperature, "K", 1.0, 0.0)),
+    (&["degC", "celsius"], unit(Quantity::Temperature, "°C", 1.0, 273.15)),
+    (&[], unit(Quantity::Temperature, "cdegC", 1e-2, 273.15)),
+    (&["degF", "fahrenheit"], unit(Quantity::Temperature, "°F", 5.0 / 9.0, 273.15 - 32.0 * 5.0 / 9.0)),
+    // Length, reference m
+    (&[], unit(Quantity::Length, "m", 1.0, 0.0)),
+    (&[], unit(Quantity::Length, "mm", 1e-3, 0.0)),
+    (&[], unit(Quantity::Length, "cm", 1e-2, 0.0)),
+    (&[], unit(Quantity::Length, "km", 1e3, 0.0)),
+    (&["feet"], unit(Quantity::Length, "ft", 0.3048, 0.0)),
+    (&["NM"], unit(Quantity::Length, "nmi", 1852.0, 0.0)),
+    // Speed, reference m/s
+    (&["m s-1", "mps"], unit(Quantity::Speed, "m/s", 1.0, 0.0)),
+    (&["cm s-1"], unit(Quantity::Speed, "cm/s", 1e-2, 0.0)),
+    (&["kmh", "kph"], unit(Quantity::Speed, "km/h", 1.0 / 3.6, 0.0)),
+    (&["fps"], unit(Quantity::Speed, "ft/s", 0.3048, 0.0)),
+    (&["knots", "knot"], unit(Quantity::Speed, "kn", 1852.0 / 3600.0, 0.0)),
+    // Acceleration, reference m/s²
+    (&["m/s2", "m/s^2", "m s-2"], unit(Quantity::Acceleration, "m/s²", 1.0, 0.0)),
+    (&["gee"], unit(Quantity::Acceleration, "g0", 9.80665, 0.0)),
+    (&[], unit(Quantity::Acceleration, "mg0", 9.80665e-3, 0.0)),
+    // Angle, reference rad
+    (&["radians"], unit(Quantity::Angle, "rad", 1.0, 0.0)),
+    (&["degrees", "degree", "°"], unit(Quantity::Angle, "deg", std::f64::consts::PI / 180.0, 0.0)),
+    (&[], unit(Quantity::Angle, "cdeg", std::f64::consts::PI / 18000.0, 0.0)),
+    (&[], unit(Quantity::Angle, "mrad", 1e-3, 0.0)),
+    // Angular speed, reference rad/s
+    (&["rad s-1"], unit(Quantity::AngularSpeed, "rad/s", 1.0, 0.0)),
+    (&["dps", "deg s-1"], unit(Quantity::AngularSpeed, "deg/s", std::f64::consts::PI / 180.0, 0.0)),
+    (&["RPM"], unit(Quantity::AngularSpeed, "rpm", std::f64::consts::PI / 30.0, 0.0)),
+    // Mass, reference kg
+    (&[], unit(Quantity::Mass, "kg", 1.0, 0.0)),
+    (&[], unit(Quantity::Mass, "g", 1e-3, 0.0)),
+    (&[], unit(Quantity::Mass, "t", 1e3, 0.0)),
+    (&["lbs"], unit(Quantity::Mass, "lb", 0.45359237, 0.0)),
+    // Current, reference A
+    (&["amp", "ampere"], unit(Quantity::Current, "A", 1.0, 0.0)),
+    (&[], unit(Quantity::Current, "mA", 1e-3, 0.0)),
+    (&[], unit(Quantity::Current, "cA", 1e-2, 0.0)),
+    // Voltage, reference V
+    (&["volt"], unit(Quantity::Voltage, "V", 1.0, 0.0)),
+    (&[], unit(Quantity::Voltage, "mV", 1e-3, 0.0)),
+    (&[], unit(Quantity::Voltage, "kV", 1e3, 0.0)),
+];
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(s: &str) -> UnitOfMeasure {
+        UnitOfMeasure::from(Some(s))
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_parsing() {
+        assert_eq!(unit("ms"), UnitOfMeasure::Time(TimeUnits::Millisecond));
+        assert_eq!(unit("Bar"), unit("bar"));
+        assert_eq!(unit("degC").to_string(), "°C");
+        assert_eq!(unit(""), UnitOfMeasure::Adimensional);
+        assert_eq!(unit("furlong"), UnitOfMeasure::Other("furlong".to_string()));
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_close(unit("Pa").convert(1e5, &unit("bar")).unwrap(), 1.0);
+        assert_close(unit("K").convert(273.15, &unit("degC")).unwrap(), 0.0);
+        assert_close(unit("degC").convert(100.0, &unit("degF")).unwrap(), 212.0);
+        assert_close(unit("m/s").convert(10.0, &unit("km/h")).unwrap(), 36.0);
+        assert_close(
+            unit("deg").convert(180.0, &unit("rad")).unwrap(),
+            std::f64::consts::PI,
+        );
+        assert_close(unit("s").convert(1.5, &unit("ms")).unwrap(), 1500.0);
+        // centidegrees, as in the MAVLink temperatures
+        assert_close(unit("cdegC").convert(2550.0, &unit("degC")).unwrap(), 25.5);
+        assert_close(unit("cdegC").convert(0.0, &unit("K")).unwrap(), 273.15);
+    }
+
+    #[test]
+    fn test_incompatible_units() {
+        assert_eq!(unit("Pa").convert(1.0, &unit("m")), None);
+        assert_eq!(unit("furlong").convert(1.0, &unit("m")), None);
+        assert_eq!(unit("furlong").convert(2.0, &unit("furlong")), Some(2.0));
+        assert_eq!(unit("Pa").display_unit(Some("m")), unit("Pa"));
+        assert_eq!(unit("Pa").display_unit(Some("bar")), unit("bar"));
+        // ambiguous symbols, as gauss on magnetometers, are left unknown
+        for symbol in ["C", "F", "G", "mG"] {
+            assert_eq!(unit(symbol), UnitOfMeasure::Other(symbol.to_string()));
+        }
+    }
+}